@@ -0,0 +1,10 @@
+CREATE TABLE bookmark (
+    id INTEGER PRIMARY KEY,
+    track_id INTEGER NOT NULL,
+    name TEXT NOT NULL,
+    position_ms INTEGER NOT NULL,
+    created_at DATETIME NOT NULL DEFAULT CURRENT_TIMESTAMP,
+    FOREIGN KEY (track_id) REFERENCES track (id)
+);
+
+CREATE INDEX IF NOT EXISTS bookmark_track_id ON bookmark(track_id);
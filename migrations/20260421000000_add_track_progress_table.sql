@@ -0,0 +1,7 @@
+CREATE TABLE track_progress (
+    track_id INTEGER PRIMARY KEY,
+    position_ms INTEGER NOT NULL,
+    finished BOOLEAN NOT NULL DEFAULT FALSE,
+    updated_at DATETIME NOT NULL DEFAULT CURRENT_TIMESTAMP,
+    FOREIGN KEY (track_id) REFERENCES track (id)
+);
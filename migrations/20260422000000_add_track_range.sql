@@ -0,0 +1,113 @@
+-- no-transaction
+-- Tracks split from a file by a CUE sheet share the file's location, so the track table is rebuilt
+-- to make tracks unique by their location and start time instead of their location alone.
+PRAGMA foreign_keys = OFF;
+
+BEGIN;
+
+CREATE TABLE track_new (
+    id INTEGER PRIMARY KEY,
+    title TEXT NOT NULL,
+    title_sortable TEXT NOT NULL,
+    album_id INTEGER,
+    track_number INTEGER,
+    disc_number INTEGER,
+    duration INTEGER NOT NULL,
+    created_at DATETIME NOT NULL DEFAULT CURRENT_TIMESTAMP,
+    genres TEXT,
+    tags TEXT,
+    location TEXT NOT NULL,
+    artist_names TEXT,
+    folder TEXT,
+    rg_track_gain REAL,
+    rg_track_peak REAL,
+    rg_album_gain REAL,
+    rg_album_peak REAL,
+    disc_subtitle TEXT,
+    start_ms INTEGER NOT NULL DEFAULT 0,
+    end_ms INTEGER,
+    UNIQUE (location, start_ms),
+    FOREIGN KEY (album_id) REFERENCES album (id)
+);
+
+INSERT INTO track_new (id, title, title_sortable, album_id, track_number, disc_number, duration,
+    created_at, genres, tags, location, artist_names, folder, rg_track_gain, rg_track_peak,
+    rg_album_gain, rg_album_peak, disc_subtitle)
+SELECT id, title, title_sortable, album_id, track_number, disc_number, duration, created_at,
+    genres, tags, location, artist_names, folder, rg_track_gain, rg_track_peak, rg_album_gain,
+    rg_album_peak, disc_subtitle
+FROM track;
+
+DROP TABLE track;
+
+ALTER TABLE track_new RENAME TO track;
+
+CREATE INDEX IF NOT EXISTS idx_track_album_id ON track (album_id, id);
+
+CREATE TRIGGER IF NOT EXISTS delete_album_trigger AFTER DELETE ON track
+BEGIN
+    DELETE FROM album
+    WHERE album.id = OLD.album_id
+    AND NOT EXISTS (
+        SELECT 1
+        FROM track
+        WHERE track.album_id = OLD.album_id
+    );
+END;
+
+CREATE TRIGGER IF NOT EXISTS delete_album_path_trigger AFTER DELETE ON track BEGIN
+DELETE FROM album_path
+WHERE
+    album_path.path = OLD.folder
+    AND album_path.disc_num = IFNULL (OLD.disc_number, -1)
+    AND album_path.album_id = OLD.album_id
+    AND NOT EXISTS (
+        SELECT
+            1
+        FROM
+            track
+        WHERE
+            track.folder = OLD.folder
+            AND track.disc_number = OLD.disc_number
+            AND track.album_id = OLD.album_id
+    );
+
+END;
+
+CREATE TRIGGER IF NOT EXISTS update_track_album_cleanup AFTER UPDATE OF album_id ON track
+WHEN OLD.album_id IS NOT NULL AND (NEW.album_id IS NULL OR OLD.album_id != NEW.album_id)
+BEGIN
+    DELETE FROM album_path
+    WHERE
+        album_path.path = OLD.folder
+        AND album_path.disc_num = IFNULL(OLD.disc_number, -1)
+        AND album_path.album_id = OLD.album_id
+        AND NOT EXISTS (
+            SELECT 1
+            FROM track
+            WHERE track.folder = OLD.folder
+              AND IFNULL(track.disc_number, -1) = IFNULL(OLD.disc_number, -1)
+              AND track.album_id = OLD.album_id
+        );
+
+    DELETE FROM album
+    WHERE album.id = OLD.album_id
+    AND NOT EXISTS (
+        SELECT 1
+        FROM track
+        WHERE track.album_id = OLD.album_id
+    );
+
+    DELETE FROM artist
+    WHERE NOT EXISTS (
+        SELECT 1
+        FROM album
+        WHERE album.artist_id = artist.id
+    );
+END;
+
+PRAGMA foreign_key_check;
+
+COMMIT;
+
+PRAGMA foreign_keys = ON;
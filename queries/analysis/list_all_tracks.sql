@@ -0,0 +1,2 @@
+SELECT id, album_id, location, start_ms, end_ms FROM track
+ORDER BY album_id, disc_number, track_number;
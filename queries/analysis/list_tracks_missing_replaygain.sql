@@ -0,0 +1,8 @@
+SELECT id, album_id, location, start_ms, end_ms FROM track
+WHERE (album_id IN (
+        SELECT album_id FROM track
+        WHERE album_id IS NOT NULL
+            AND (rg_track_gain IS NULL OR rg_album_gain IS NULL)
+    )
+    OR (album_id IS NULL AND rg_track_gain IS NULL))
+ORDER BY album_id, disc_number, track_number;
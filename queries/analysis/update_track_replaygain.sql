@@ -0,0 +1,6 @@
+UPDATE track
+SET rg_track_gain = $1,
+    rg_track_peak = $2,
+    rg_album_gain = $3,
+    rg_album_peak = $4
+WHERE id = $5;
@@ -0,0 +1,2 @@
+INSERT INTO bookmark (track_id, name, position_ms)
+    VALUES ($1, $2, $3);
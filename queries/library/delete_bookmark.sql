@@ -0,0 +1 @@
+DELETE FROM bookmark WHERE id = $1;
@@ -1,3 +1,3 @@
 SELECT * FROM track
-WHERE location = $1
+WHERE location = $1 AND start_ms = $2
 LIMIT 1;
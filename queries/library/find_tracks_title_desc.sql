@@ -2,7 +2,9 @@ SELECT
     t.id,
     t.title_sortable,
     t.album_id,
-    t.location
+    t.location,
+    t.start_ms,
+    t.end_ms
 FROM
     track t
 ORDER BY
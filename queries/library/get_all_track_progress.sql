@@ -0,0 +1 @@
+SELECT track_id, position_ms, finished FROM track_progress;
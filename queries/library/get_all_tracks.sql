@@ -1 +1 @@
-SELECT location, id, album_id FROM track;
+SELECT location, start_ms, end_ms, id, album_id FROM track;
@@ -0,0 +1,4 @@
+SELECT id, track_id, name, position_ms, created_at
+FROM bookmark
+WHERE track_id = $1
+ORDER BY position_ms, id;
@@ -0,0 +1,6 @@
+SELECT track.id AS track_id, track.duration, track.genres, track_progress.position_ms,
+       track_progress.finished
+    FROM track
+    LEFT JOIN track_progress ON track_progress.track_id = track.id
+    WHERE track.location = $1 AND track.start_ms = $2
+    LIMIT 1;
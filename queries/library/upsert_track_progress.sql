@@ -0,0 +1,6 @@
+INSERT INTO track_progress (track_id, position_ms, finished, updated_at)
+    VALUES ($1, $2, $3, CURRENT_TIMESTAMP)
+    ON CONFLICT (track_id) DO UPDATE SET
+        position_ms = EXCLUDED.position_ms,
+        finished = EXCLUDED.finished,
+        updated_at = EXCLUDED.updated_at;
@@ -1,4 +1,4 @@
-SELECT t.location FROM playlist_item AS pi
+SELECT t.location, t.start_ms, t.end_ms FROM playlist_item AS pi
     JOIN track AS t ON pi.track_id = t.id
     WHERE pi.playlist_id = $1
     ORDER BY pi.position ASC;
@@ -1,6 +1,6 @@
-INSERT INTO track (title, title_sortable, album_id, track_number, disc_number, duration, location, genres, artist_names, folder, rg_track_gain, rg_track_peak, rg_album_gain, rg_album_peak, disc_subtitle)
-    VALUES ($1, $2, $3, $4, $5, $6, $7, $8, $9, $10, $11, $12, $13, $14, $15)
-    ON CONFLICT (location) DO UPDATE SET
+INSERT INTO track (title, title_sortable, album_id, track_number, disc_number, duration, location, genres, artist_names, folder, rg_track_gain, rg_track_peak, rg_album_gain, rg_album_peak, disc_subtitle, start_ms, end_ms)
+    VALUES ($1, $2, $3, $4, $5, $6, $7, $8, $9, $10, $11, $12, $13, $14, $15, $16, $17)
+    ON CONFLICT (location, start_ms) DO UPDATE SET
         title = EXCLUDED.title,
         title_sortable = EXCLUDED.title_sortable,
         album_id = EXCLUDED.album_id,
@@ -11,9 +11,10 @@ INSERT INTO track (title, title_sortable, album_id, track_number, disc_number, d
         genres = EXCLUDED.genres,
         artist_names = EXCLUDED.artist_names,
         folder = EXCLUDED.folder,
-        rg_track_gain = EXCLUDED.rg_track_gain,
-        rg_track_peak = EXCLUDED.rg_track_peak,
-        rg_album_gain = EXCLUDED.rg_album_gain,
-        rg_album_peak = EXCLUDED.rg_album_peak,
-        disc_subtitle = EXCLUDED.disc_subtitle
+        rg_track_gain = COALESCE(EXCLUDED.rg_track_gain, track.rg_track_gain),
+        rg_track_peak = COALESCE(EXCLUDED.rg_track_peak, track.rg_track_peak),
+        rg_album_gain = COALESCE(EXCLUDED.rg_album_gain, track.rg_album_gain),
+        rg_album_peak = COALESCE(EXCLUDED.rg_album_peak, track.rg_album_peak),
+        disc_subtitle = EXCLUDED.disc_subtitle,
+        end_ms = EXCLUDED.end_ms
     RETURNING id;
@@ -0,0 +1,2 @@
+DELETE FROM bookmark
+WHERE track_id = $1;
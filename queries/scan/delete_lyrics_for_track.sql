@@ -1,4 +1,2 @@
 DELETE FROM lyrics
-WHERE track_id IN (
-    SELECT id FROM track WHERE location = $1
-);
+WHERE track_id = $1;
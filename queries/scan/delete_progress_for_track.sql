@@ -0,0 +1,2 @@
+DELETE FROM track_progress
+WHERE track_id = $1;
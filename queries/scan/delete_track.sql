@@ -1 +1 @@
-DELETE FROM track WHERE location = $1;
+DELETE FROM track WHERE id = $1;
@@ -1,5 +1,3 @@
 SELECT DISTINCT playlist_id
 FROM playlist_item
-WHERE track_id IN (
-    SELECT id FROM track WHERE location = $1
-);
+WHERE track_id = $1;
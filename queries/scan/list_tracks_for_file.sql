@@ -0,0 +1,2 @@
+SELECT id, start_ms FROM track
+WHERE location = $1;
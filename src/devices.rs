@@ -1,6 +1,11 @@
 pub mod builtin;
+pub mod dither;
+pub mod dsp;
 pub mod errors;
 pub mod format;
+pub mod mix;
 pub mod resample;
+pub mod stretch;
 pub mod traits;
 pub mod util;
+pub mod visualizer;
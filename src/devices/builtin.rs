@@ -1,4 +1,10 @@
+#[cfg(target_os = "linux")]
+pub mod alsa;
 pub mod cpal;
 pub mod dummy;
+pub mod file;
+pub mod network;
+#[cfg(all(target_os = "linux", feature = "pipewire"))]
+pub mod pipewire;
 #[cfg(target_os = "windows")]
 pub mod win_audiograph;
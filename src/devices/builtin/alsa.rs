@@ -0,0 +1,632 @@
+//! Direct ALSA device provider.
+//!
+//! Unlike the CPAL provider, which plays to ALSA's `default` PCM (usually a sound server), this
+//! provider lists the cards' `hw:` and `plughw:` PCMs and plays to them directly. `hw:` devices
+//! are used without any conversion, so they are opened in exactly the format the device reports
+//! as supported. Any other PCM (e.g. `null`, or a `file` PCM defined in `~/.asoundrc`) can be
+//! selected by its name.
+//!
+//! Samples are written to the PCM from the playback thread, which blocks until the device has
+//! room for them. The period and buffer sizes can be chosen with the format's `buffer_size`:
+//! `BufferSize::Fixed` sets the buffer size, and `BufferSize::Range(period, buffer)` sets both.
+
+use std::cell::OnceCell;
+
+use alsa::{
+    Direction, ValueOr,
+    device_name::HintIter,
+    pcm::{Access, Format, Frames, HwParams, IoFormat, PCM, State},
+};
+use intx::{I24, U24};
+use tracing::debug;
+
+use crate::{
+    devices::{
+        errors::{
+            CloseError, FindError, InfoError, InitializationError, ListError, OpenError,
+            ResetError, StateError, SubmissionError,
+        },
+        format::{BufferSize, ChannelSpec, FormatInfo, SampleFormat, SupportedFormat},
+        resample::SampleFrom,
+        traits::{Device, DeviceProvider, OutputStream},
+        util::{GainRamp, Scale},
+    },
+    media::pipeline::ChannelConsumers,
+    util::make_unknown_error,
+};
+
+const PROVIDER: &str = "alsa";
+
+/// The buffer length used when no buffer size is requested.
+const DEFAULT_BUFFER_MS: u32 = 100;
+
+/// The number of periods in the buffer when only the buffer size is requested.
+const DEFAULT_PERIODS: u32 = 4;
+
+/// Devices accepting more channels than this (usually plugins that accept anything) only report
+/// this many.
+const MAX_CHANNELS: u32 = 8;
+
+/// Sample formats to probe devices for, from most to least preferred as a default.
+const SAMPLE_FORMATS: &[SampleFormat] = &[
+    SampleFormat::Signed32,
+    SampleFormat::Signed24,
+    SampleFormat::Signed16,
+    SampleFormat::Float32,
+    SampleFormat::Float64,
+    SampleFormat::Unsigned32,
+    SampleFormat::Unsigned24,
+    SampleFormat::Unsigned16,
+    SampleFormat::Signed8,
+    SampleFormat::Unsigned8,
+];
+
+/// Sample rates to prefer as a default, if the device supports them.
+const PREFERRED_RATES: &[u32] = &[48000, 44100];
+
+fn alsa_format(format: SampleFormat) -> Format {
+    let little_endian = cfg!(target_endian = "little");
+
+    match format {
+        SampleFormat::Float64 => <f64 as IoFormat>::FORMAT,
+        SampleFormat::Float32 => <f32 as IoFormat>::FORMAT,
+        SampleFormat::Signed32 => <i32 as IoFormat>::FORMAT,
+        SampleFormat::Unsigned32 => <u32 as IoFormat>::FORMAT,
+        SampleFormat::Signed24 if little_endian => Format::S24LE,
+        SampleFormat::Signed24 => Format::S24BE,
+        SampleFormat::Unsigned24 if little_endian => Format::U24LE,
+        SampleFormat::Unsigned24 => Format::U24BE,
+        SampleFormat::Signed16 => <i16 as IoFormat>::FORMAT,
+        SampleFormat::Unsigned16 => <u16 as IoFormat>::FORMAT,
+        SampleFormat::Signed8 => <i8 as IoFormat>::FORMAT,
+        SampleFormat::Unsigned8 => <u8 as IoFormat>::FORMAT,
+    }
+}
+
+/// Whether a PCM from the device hints should be listed. Only the cards' own PCMs are: the
+/// others (`default`, `pulse`, `dmix`, ...) are better played to through the CPAL provider.
+fn is_listed(name: &str) -> bool {
+    name.starts_with("hw:") || name.starts_with("plughw:")
+}
+
+/// The name to show for a PCM: the first line of its description, followed by the kind of PCM.
+fn display_name(pcm: &str, description: Option<&str>) -> String {
+    let kind = pcm.split(':').next().unwrap_or(pcm);
+
+    match description.and_then(|desc| desc.lines().next()) {
+        Some(desc) if kind != pcm => format!("{desc} ({kind})"),
+        Some(desc) => desc.to_string(),
+        None => pcm.to_string(),
+    }
+}
+
+/// The period and buffer size (in frames) to request for the given buffer size.
+fn buffer_request(buffer_size: BufferSize, sample_rate: u32) -> (u32, u32) {
+    match buffer_size {
+        BufferSize::Range(period, buffer) => (period.min(buffer / 2).max(1), buffer),
+        BufferSize::Fixed(buffer) => ((buffer / DEFAULT_PERIODS).max(1), buffer),
+        BufferSize::Unknown => {
+            let buffer = sample_rate * DEFAULT_BUFFER_MS / 1000;
+            ((buffer / DEFAULT_PERIODS).max(1), buffer)
+        }
+    }
+}
+
+/// Choose the format to open a device with by default: the preferred sample format at 48 or
+/// 44.1 kHz in stereo, or whatever comes closest.
+fn default_format(supported: &[SupportedFormat]) -> Option<FormatInfo> {
+    let format = supported
+        .iter()
+        .find(|f| f.channels == ChannelSpec::Count(2))
+        .or_else(|| supported.first())?;
+
+    let sample_rate = PREFERRED_RATES
+        .iter()
+        .copied()
+        .find(|rate| (format.sample_rates.0..=format.sample_rates.1).contains(rate))
+        .unwrap_or(format.sample_rates.1);
+
+    Some(FormatInfo {
+        originating_provider: PROVIDER,
+        sample_type: format.sample_type,
+        sample_rate,
+        buffer_size: BufferSize::Unknown,
+        channels: format.channels,
+    })
+}
+
+/// Describes a PCM as found in ALSA's device hints.
+struct PcmHint {
+    name: String,
+    description: Option<String>,
+}
+
+fn playback_hints() -> Result<Vec<PcmHint>, alsa::Error> {
+    Ok(HintIter::new_str(None, "pcm")?
+        .filter(|hint| hint.direction.is_none_or(|dir| dir == Direction::Playback))
+        .filter_map(|hint| {
+            Some(PcmHint {
+                name: hint.name?,
+                description: hint.desc,
+            })
+        })
+        .collect())
+}
+
+#[derive(Default)]
+pub struct AlsaProvider {}
+
+impl DeviceProvider for AlsaProvider {
+    fn initialize(&mut self) -> Result<(), InitializationError> {
+        Ok(())
+    }
+
+    fn get_devices(&mut self) -> Result<Vec<Box<dyn Device>>, ListError> {
+        Ok(playback_hints()?
+            .into_iter()
+            .filter(|hint| is_listed(&hint.name))
+            .map(|hint| Box::new(AlsaDevice::from(hint)) as Box<dyn Device>)
+            .collect())
+    }
+
+    fn get_default_device(&mut self) -> Result<Box<dyn Device>, FindError> {
+        // there's no default card without a sound server, so use the first one
+        playback_hints()?
+            .into_iter()
+            .find(|hint| hint.name.starts_with("hw:"))
+            .ok_or(FindError::DeviceDoesNotExist)
+            .map(|hint| Box::new(AlsaDevice::from(hint)) as Box<dyn Device>)
+    }
+
+    fn get_device_by_uid(&mut self, id: &str) -> Result<Box<dyn Device>, FindError> {
+        if let Some(hint) = playback_hints()?.into_iter().find(|hint| hint.name == id) {
+            return Ok(Box::new(AlsaDevice::from(hint)));
+        }
+
+        // PCMs defined without a hint don't show up in the list, but can still be played to
+        match PCM::new(id, Direction::Playback, true) {
+            Ok(_) => Ok(Box::new(AlsaDevice::from(PcmHint {
+                name: id.to_string(),
+                description: None,
+            }))),
+            Err(e) => {
+                debug!("Failed to open PCM '{}': {}", id, e);
+                Err(FindError::DeviceDoesNotExist)
+            }
+        }
+    }
+}
+
+struct AlsaDevice {
+    hint: PcmHint,
+    /// The formats the device supports. Probing a device requires opening it, which fails while
+    /// a stream is playing to it, so the result is kept.
+    supported: OnceCell<Vec<SupportedFormat>>,
+}
+
+impl From<PcmHint> for AlsaDevice {
+    fn from(hint: PcmHint) -> Self {
+        Self {
+            hint,
+            supported: OnceCell::new(),
+        }
+    }
+}
+
+impl AlsaDevice {
+    fn probe(&self) -> Result<Vec<SupportedFormat>, alsa::Error> {
+        let pcm = PCM::new(&self.hint.name, Direction::Playback, true)?;
+        let mut supported = Vec::new();
+
+        for &sample_type in SAMPLE_FORMATS {
+            let hwp = HwParams::any(&pcm)?;
+            if hwp.set_access(Access::RWInterleaved).is_err()
+                || hwp.set_format(alsa_format(sample_type)).is_err()
+            {
+                continue;
+            }
+
+            let sample_rates = (hwp.get_rate_min()?, hwp.get_rate_max()?);
+            let buffer_size = BufferSize::Range(
+                hwp.get_buffer_size_min()? as u32,
+                hwp.get_buffer_size_max()? as u32,
+            );
+            let channels = hwp.get_channels_min()?..=hwp.get_channels_max()?.min(MAX_CHANNELS);
+
+            supported.extend(channels.map(|channels| SupportedFormat {
+                originating_provider: PROVIDER,
+                sample_type,
+                sample_rates,
+                buffer_size,
+                channels: ChannelSpec::Count(channels as u16),
+            }));
+        }
+
+        Ok(supported)
+    }
+
+    fn supported_formats(&self) -> Result<&[SupportedFormat], InfoError> {
+        if let Some(supported) = self.supported.get() {
+            return Ok(supported);
+        }
+
+        let supported = self.probe()?;
+        Ok(self.supported.get_or_init(|| supported))
+    }
+
+    fn create_stream<T: AlsaSample>(
+        &mut self,
+        format: FormatInfo,
+    ) -> Result<Box<dyn OutputStream>, OpenError> {
+        let (pcm, can_pause) = open_pcm(&self.hint.name, &format)?;
+
+        Ok(Box::new(AlsaStream::<T> {
+            pcm,
+            format,
+            channels: format.channels.count() as usize,
+            can_pause,
+            ramp: GainRamp::new(format.sample_rate),
+            volume: 1.0,
+            replaygain: 1.0,
+            interleave_buffer: Vec::new(),
+            io_buffer: Vec::new(),
+        }))
+    }
+}
+
+impl Device for AlsaDevice {
+    fn open_device(&mut self, format: FormatInfo) -> Result<Box<dyn OutputStream>, OpenError> {
+        if format.originating_provider != PROVIDER {
+            return Err(OpenError::InvalidConfigProvider);
+        }
+
+        match format.sample_type {
+            SampleFormat::Float64 => self.create_stream::<f64>(format),
+            SampleFormat::Float32 => self.create_stream::<f32>(format),
+            SampleFormat::Signed32 => self.create_stream::<i32>(format),
+            SampleFormat::Unsigned32 => self.create_stream::<u32>(format),
+            SampleFormat::Signed24 => self.create_stream::<I24>(format),
+            SampleFormat::Unsigned24 => self.create_stream::<U24>(format),
+            SampleFormat::Signed16 => self.create_stream::<i16>(format),
+            SampleFormat::Unsigned16 => self.create_stream::<u16>(format),
+            SampleFormat::Signed8 => self.create_stream::<i8>(format),
+            SampleFormat::Unsigned8 => self.create_stream::<u8>(format),
+        }
+    }
+
+    fn get_supported_formats(&self) -> Result<Vec<SupportedFormat>, InfoError> {
+        Ok(self.supported_formats()?.to_vec())
+    }
+
+    fn get_default_format(&self) -> Result<FormatInfo, InfoError> {
+        default_format(self.supported_formats()?).ok_or(InfoError::None)
+    }
+
+    fn get_name(&self) -> Result<String, InfoError> {
+        Ok(display_name(
+            &self.hint.name,
+            self.hint.description.as_deref(),
+        ))
+    }
+
+    fn get_uid(&self) -> Result<String, InfoError> {
+        Ok(self.hint.name.clone())
+    }
+
+    fn requires_matching_format(&self) -> bool {
+        true
+    }
+}
+
+/// Open a PCM for playback in the given format, returning it and whether it can be paused.
+fn open_pcm(name: &str, format: &FormatInfo) -> Result<(PCM, bool), alsa::Error> {
+    let pcm = PCM::new(name, Direction::Playback, false)?;
+    let (period, buffer) = buffer_request(format.buffer_size, format.sample_rate);
+
+    let can_pause = {
+        let hwp = HwParams::any(&pcm)?;
+        hwp.set_access(Access::RWInterleaved)?;
+        hwp.set_format(alsa_format(format.sample_type))?;
+        hwp.set_channels(format.channels.count() as u32)?;
+        hwp.set_rate(format.sample_rate, ValueOr::Nearest)?;
+        hwp.set_buffer_size_near(buffer as Frames)?;
+        hwp.set_period_size_near(period as Frames, ValueOr::Nearest)?;
+        pcm.hw_params(&hwp)?;
+        hwp.can_pause()
+    };
+
+    let hwp = pcm.hw_params_current()?;
+    let buffer_size = hwp.get_buffer_size()?;
+    debug!(
+        "Opened ALSA PCM '{}' with a buffer of {} frames and periods of {} frames",
+        name,
+        buffer_size,
+        hwp.get_period_size()?
+    );
+
+    // start once the buffer is half full, so playback doesn't run dry right away
+    let swp = pcm.sw_params_current()?;
+    swp.set_start_threshold(buffer_size / 2)?;
+    pcm.sw_params(&swp)?;
+
+    Ok((pcm, can_pause))
+}
+
+/// A sample type that can be written to a PCM. 24-bit samples are stored in 32 bits.
+trait AlsaSample: SampleFrom<f64> + SampleFrom<f32> + Scale + Copy + 'static {
+    type Io: IoFormat + Copy;
+
+    fn to_io(self) -> Self::Io;
+}
+
+macro_rules! impl_alsa_sample {
+    ($t:ty) => {
+        impl AlsaSample for $t {
+            type Io = $t;
+
+            fn to_io(self) -> Self::Io {
+                self
+            }
+        }
+    };
+    ($t:ty, $io:ty) => {
+        impl AlsaSample for $t {
+            type Io = $io;
+
+            fn to_io(self) -> Self::Io {
+                <$io>::from(self)
+            }
+        }
+    };
+}
+
+impl_alsa_sample!(f64);
+impl_alsa_sample!(f32);
+impl_alsa_sample!(i32);
+impl_alsa_sample!(u32);
+impl_alsa_sample!(I24, i32);
+impl_alsa_sample!(U24, u32);
+impl_alsa_sample!(i16);
+impl_alsa_sample!(u16);
+impl_alsa_sample!(i8);
+impl_alsa_sample!(u8);
+
+struct AlsaStream<T: AlsaSample> {
+    pcm: PCM,
+    format: FormatInfo,
+    channels: usize,
+    /// Whether the hardware supports pausing. Without it, pausing is a no-op and the device runs
+    /// dry instead.
+    can_pause: bool,
+    ramp: GainRamp,
+    volume: f64,
+    replaygain: f64,
+    interleave_buffer: Vec<T>,
+    io_buffer: Vec<T::Io>,
+}
+
+impl<T: AlsaSample> AlsaStream<T> {
+    /// Write the interleaved samples to the PCM, blocking until they all fit. Underruns are
+    /// recovered from, and reported once all samples have been written.
+    fn write(&mut self) -> Result<(), SubmissionError> {
+        self.ramp
+            .apply(&mut self.interleave_buffer, self.channels, self.volume);
+        self.io_buffer.clear();
+        self.io_buffer
+            .extend(self.interleave_buffer.iter().map(|sample| sample.to_io()));
+
+        let io = self.pcm.io_checked::<T::Io>()?;
+        let mut samples = &self.io_buffer[..];
+        let mut underrun = false;
+
+        while !samples.is_empty() {
+            match io.writei(samples) {
+                Ok(frames) => samples = &samples[frames * self.channels..],
+                Err(e) => match self.pcm.state() {
+                    State::Disconnected => return Err(SubmissionError::DeviceLost),
+                    state => {
+                        underrun |= state == State::XRun;
+                        self.pcm.try_recover(e, true)?;
+                    }
+                },
+            }
+        }
+
+        if underrun {
+            Err(SubmissionError::Underrun)
+        } else {
+            Ok(())
+        }
+    }
+
+    fn consume<S>(&mut self, input: &mut ChannelConsumers<S>, gain: S) -> usize
+    where
+        S: Copy + std::ops::Mul<Output = S>,
+        T: SampleFrom<S>,
+    {
+        let available = input.potentially_available();
+        if available == 0 {
+            return 0;
+        }
+
+        let read = input.try_read_to_staging(available);
+        if read == 0 {
+            return 0;
+        }
+
+        let staging = input.staging();
+
+        self.interleave_buffer.clear();
+        self.interleave_buffer.reserve(read * staging.len());
+
+        for i in 0..read {
+            for channel in staging {
+                self.interleave_buffer
+                    .push(T::sample_from(channel[i] * gain));
+            }
+        }
+
+        read
+    }
+}
+
+impl<T: AlsaSample> OutputStream for AlsaStream<T> {
+    fn close_stream(&mut self) -> Result<(), CloseError> {
+        Ok(())
+    }
+
+    fn needs_input(&self) -> bool {
+        true
+    }
+
+    fn play(&mut self) -> Result<(), StateError> {
+        match self.pcm.state() {
+            State::Paused => self.pcm.pause(false)?,
+            // the device ran dry while nothing was being played, which isn't worth reporting
+            State::XRun => self.pcm.prepare()?,
+            _ => {}
+        }
+
+        Ok(())
+    }
+
+    fn pause(&mut self) -> Result<(), StateError> {
+        if self.can_pause && self.pcm.state() == State::Running {
+            self.pcm.pause(true)?;
+        }
+
+        Ok(())
+    }
+
+    fn reset(&mut self) -> Result<(), ResetError> {
+        self.pcm.drop()?;
+        self.pcm.prepare()?;
+        self.ramp = GainRamp::new(self.format.sample_rate);
+
+        Ok(())
+    }
+
+    fn set_volume(&mut self, volume: f64) -> Result<(), StateError> {
+        self.volume = volume;
+        Ok(())
+    }
+
+    fn set_replaygain(&mut self, gain: f64) -> Result<(), StateError> {
+        self.replaygain = gain;
+        Ok(())
+    }
+
+    fn buffered_frames(&self) -> usize {
+        self.pcm.delay().map_or(0, |frames| frames.max(0) as usize)
+    }
+
+    fn consume_from(
+        &mut self,
+        input: &mut ChannelConsumers<f64>,
+    ) -> Result<usize, SubmissionError> {
+        let read = self.consume(input, self.replaygain);
+        if read > 0 {
+            self.write()?;
+        }
+
+        Ok(read)
+    }
+
+    fn consume_from_f32(
+        &mut self,
+        input: &mut ChannelConsumers<f32>,
+    ) -> Option<Result<usize, SubmissionError>> {
+        if self.format.sample_type != SampleFormat::Float32 {
+            return None;
+        }
+
+        let read = self.consume(input, self.replaygain as f32);
+        if read == 0 {
+            return Some(Ok(0));
+        }
+
+        Some(self.write().map(|_| read))
+    }
+}
+
+make_unknown_error!(alsa::Error, ListError);
+make_unknown_error!(alsa::Error, FindError);
+make_unknown_error!(alsa::Error, InfoError);
+make_unknown_error!(alsa::Error, OpenError);
+make_unknown_error!(alsa::Error, StateError);
+make_unknown_error!(alsa::Error, ResetError);
+make_unknown_error!(alsa::Error, SubmissionError);
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn supported(sample_type: SampleFormat, rates: (u32, u32), channels: u16) -> SupportedFormat {
+        SupportedFormat {
+            originating_provider: PROVIDER,
+            sample_type,
+            sample_rates: rates,
+            buffer_size: BufferSize::Range(64, 65536),
+            channels: ChannelSpec::Count(channels),
+        }
+    }
+
+    #[test]
+    fn lists_card_pcms_only() {
+        assert!(is_listed("hw:CARD=PCH,DEV=0"));
+        assert!(is_listed("plughw:CARD=DAC,DEV=0"));
+        assert!(!is_listed("default"));
+        assert!(!is_listed("null"));
+        assert!(!is_listed("dmix:CARD=PCH,DEV=0"));
+
+        assert_eq!(
+            display_name(
+                "hw:CARD=DAC,DEV=0",
+                Some("USB DAC, USB Audio\nDirect hardware device without any conversions")
+            ),
+            "USB DAC, USB Audio (hw)"
+        );
+        assert_eq!(
+            display_name(
+                "null",
+                Some("Discard all samples (playback) or generate zero samples")
+            ),
+            "Discard all samples (playback) or generate zero samples"
+        );
+        assert_eq!(display_name("file_out", None), "file_out");
+    }
+
+    #[test]
+    fn requests_period_and_buffer_sizes() {
+        assert_eq!(
+            buffer_request(BufferSize::Range(512, 4096), 48000),
+            (512, 4096)
+        );
+        // a period can't be larger than half the buffer
+        assert_eq!(
+            buffer_request(BufferSize::Range(4096, 4096), 48000),
+            (2048, 4096)
+        );
+        assert_eq!(buffer_request(BufferSize::Fixed(8192), 48000), (2048, 8192));
+        assert_eq!(buffer_request(BufferSize::Unknown, 48000), (1200, 4800));
+    }
+
+    #[test]
+    fn chooses_stereo_at_a_common_rate_by_default() {
+        let formats = [
+            supported(SampleFormat::Signed32, (44100, 192000), 1),
+            supported(SampleFormat::Signed32, (44100, 192000), 2),
+            supported(SampleFormat::Signed16, (8000, 48000), 2),
+        ];
+
+        let format = default_format(&formats).unwrap();
+        assert_eq!(format.sample_type, SampleFormat::Signed32);
+        assert_eq!(format.sample_rate, 48000);
+        assert_eq!(format.channels, ChannelSpec::Count(2));
+
+        // devices that only do odd rates are opened at their highest
+        let format = default_format(&[supported(SampleFormat::Signed16, (8000, 22050), 1)]);
+        assert_eq!(format.map(|f| f.sample_rate), Some(22050));
+        assert!(default_format(&[]).is_none());
+    }
+}
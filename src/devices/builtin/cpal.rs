@@ -6,8 +6,8 @@ use crate::{
         },
         format::{BufferSize, ChannelSpec, FormatInfo, SampleFormat, SupportedFormat},
         resample::SampleFrom,
-        traits::{Device, DeviceProvider, OutputStream},
-        util::{AtomicF64, GainRamp, Scale},
+        traits::{Device, DeviceChange, DeviceProvider, OutputStream},
+        util::{AtomicF64, GainRamp, Scale, write_to_ring},
     },
     media::{pipeline::ChannelConsumers, playback::Mute},
     util::make_unknown_error,
@@ -16,24 +16,31 @@ use cpal::{
     Host, SizedSample,
     traits::{DeviceTrait, HostTrait, StreamTrait},
 };
-use rb::{Producer, RB, RbConsumer, RbProducer, SpscRb};
+use rb::{Producer, RB, RbConsumer, RbInspector, RbProducer, SpscRb};
 use std::sync::Arc;
-use std::sync::atomic::Ordering;
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::mpsc::{Receiver, channel};
 use std::time::Duration;
+use tracing::warn;
 
 /// Delay between requesting a fade-out and pausing the stream. Must exceed
 /// the gain ramp length (15 ms) plus a few callback periods to ensure the
 /// audio thread drains a buffer at zero gain.
 const PAUSE_FADE_WAIT: Duration = Duration::from_millis(50);
 
+/// How often the device watcher lists the host's output devices.
+const DEVICE_POLL_INTERVAL: Duration = Duration::from_secs(1);
+
 pub struct CpalProvider {
     host: Host,
+    watcher: Option<DeviceWatcher>,
 }
 
 impl Default for CpalProvider {
     fn default() -> Self {
         Self {
             host: cpal::default_host(),
+            watcher: DeviceWatcher::spawn(),
         }
     }
 }
@@ -47,7 +54,7 @@ impl DeviceProvider for CpalProvider {
     fn get_devices(&mut self) -> Result<Vec<Box<dyn Device>>, ListError> {
         Ok(self
             .host
-            .devices()?
+            .output_devices()?
             .map(|dev| Box::new(CpalDevice::from(dev)) as Box<dyn Device>)
             .collect())
     }
@@ -61,7 +68,7 @@ impl DeviceProvider for CpalProvider {
 
     fn get_device_by_uid(&mut self, id: &str) -> Result<Box<dyn Device>, FindError> {
         self.host
-            .devices()?
+            .output_devices()?
             .find(|dev| {
                 id == dev
                     .description()
@@ -72,6 +79,111 @@ impl DeviceProvider for CpalProvider {
             .ok_or(FindError::DeviceDoesNotExist)
             .map(|dev| Box::new(CpalDevice::from(dev)) as Box<dyn Device>)
     }
+
+    fn poll_device_changes(&mut self) -> Vec<DeviceChange> {
+        self.watcher
+            .as_ref()
+            .map(|watcher| watcher.changes.try_iter().collect())
+            .unwrap_or_default()
+    }
+}
+
+/// The output devices of a host at one point in time, identified by their UIDs.
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+struct DeviceSnapshot {
+    devices: Vec<String>,
+    default: Option<String>,
+}
+
+impl DeviceSnapshot {
+    /// List the host's output devices. Returns `None` if they couldn't be listed.
+    fn take(host: &Host) -> Option<Self> {
+        let devices = host
+            .output_devices()
+            .ok()?
+            .filter_map(|dev| dev.description().ok().map(|v| v.name().to_string()))
+            .collect();
+        let default = host
+            .default_output_device()
+            .and_then(|dev| dev.description().ok().map(|v| v.name().to_string()));
+
+        Some(Self { devices, default })
+    }
+
+    /// The changes that lead from this snapshot to the next one.
+    fn changes_to(&self, next: &DeviceSnapshot) -> Vec<DeviceChange> {
+        let removed = self
+            .devices
+            .iter()
+            .filter(|uid| !next.devices.contains(uid))
+            .map(|uid| DeviceChange::Removed(uid.clone()));
+        let added = next
+            .devices
+            .iter()
+            .filter(|uid| !self.devices.contains(uid))
+            .map(|uid| DeviceChange::Added(uid.clone()));
+
+        let mut changes: Vec<_> = removed.chain(added).collect();
+
+        if next.default != self.default
+            && let Some(default) = &next.default
+        {
+            changes.push(DeviceChange::DefaultChanged(default.clone()));
+        }
+
+        changes
+    }
+}
+
+/// Lists the host's output devices on a background thread, since cpal has no device change
+/// notifications and listing devices can take long enough to interrupt playback on some hosts.
+struct DeviceWatcher {
+    changes: Receiver<DeviceChange>,
+    stop: Arc<AtomicBool>,
+}
+
+impl DeviceWatcher {
+    fn spawn() -> Option<Self> {
+        let (tx, rx) = channel();
+        let stop = Arc::new(AtomicBool::new(false));
+        let stop_thread = stop.clone();
+
+        let result = std::thread::Builder::new()
+            .name("cpal-device-watcher".to_string())
+            .spawn(move || {
+                let host = cpal::default_host();
+                let mut snapshot = DeviceSnapshot::take(&host).unwrap_or_default();
+
+                while !stop_thread.load(Ordering::Relaxed) {
+                    std::thread::sleep(DEVICE_POLL_INTERVAL);
+
+                    let Some(next) = DeviceSnapshot::take(&host) else {
+                        continue;
+                    };
+
+                    for change in snapshot.changes_to(&next) {
+                        if tx.send(change).is_err() {
+                            return;
+                        }
+                    }
+
+                    snapshot = next;
+                }
+            });
+
+        if let Err(e) = result {
+            warn!("Failed to start the output device watcher: {}", e);
+            return None;
+        }
+
+        Some(Self { changes: rx, stop })
+    }
+}
+
+impl Drop for DeviceWatcher {
+    fn drop(&mut self) {
+        self.stop.store(true, Ordering::Relaxed);
+    }
 }
 
 struct CpalDevice {
@@ -123,10 +235,10 @@ fn create_stream_internal<T: CpalSample>(
     config: &cpal::StreamConfig,
     buffer_size: usize,
     target_gain: Arc<AtomicF64>,
-) -> Result<(cpal::Stream, Producer<T>), OpenError> {
+    device_lost: Arc<AtomicBool>,
+) -> Result<(cpal::Stream, SpscRb<T>), OpenError> {
     let rb: SpscRb<T> = SpscRb::new(buffer_size);
     let cons = rb.consumer();
-    let prod = rb.producer();
     let channels = config.channels as usize;
     let mut ramp = GainRamp::new(config.sample_rate);
 
@@ -139,11 +251,15 @@ fn create_stream_internal<T: CpalSample>(
             let target = target_gain.load(Ordering::Relaxed);
             ramp.apply(data, channels, target);
         },
-        move |_| {},
+        move |err| {
+            if matches!(err, cpal::StreamError::DeviceNotAvailable) {
+                device_lost.store(true, Ordering::Relaxed);
+            }
+        },
         None,
     )?;
 
-    Ok((stream, prod))
+    Ok((stream, rb))
 }
 
 impl CpalDevice {
@@ -153,20 +269,28 @@ impl CpalDevice {
     {
         let config =
             cpal_config_from_info(&format).map_err(|_| OpenError::InvalidConfigProvider)?;
-        let ChannelSpec::Count(channels) = format.channels;
+        let channels = format.channels.count();
         let buffer_size = ((200 * config.sample_rate as usize) / 1000) * channels as usize;
         let target_gain = Arc::new(AtomicF64::new(1.0));
-        let (stream, prod) =
-            create_stream_internal::<T>(&self.device, &config, buffer_size, target_gain.clone())?;
+        let device_lost = Arc::new(AtomicBool::new(false));
+        let (stream, ring) = create_stream_internal::<T>(
+            &self.device,
+            &config,
+            buffer_size,
+            target_gain.clone(),
+            device_lost.clone(),
+        )?;
 
         Ok(Box::new(CpalStream {
-            ring_buf: prod,
+            ring_buf: ring.producer(),
+            ring,
             stream,
             format,
             config,
             buffer_size,
             device: self.device.clone(),
             target_gain,
+            device_lost,
             last_user_volume: 1.0,
             replaygain: 1.0,
             interleave_buffer: Vec::with_capacity(buffer_size),
@@ -253,12 +377,16 @@ where
     T: SizedSample + Default,
 {
     pub ring_buf: Producer<T>,
+    /// The ring buffer `ring_buf` writes to, kept to see how much of it hasn't been played yet.
+    pub ring: SpscRb<T>,
     pub stream: cpal::Stream,
     pub config: cpal::StreamConfig,
     pub device: cpal::Device,
     pub format: FormatInfo,
     pub buffer_size: usize,
     pub target_gain: Arc<AtomicF64>,
+    /// Set by the stream's error callback when the device goes away.
+    pub device_lost: Arc<AtomicBool>,
     /// most recent volume the user asked for. This is tracked separately
     /// from `target_gain` because pause-fades temporarily overwrite the
     /// shared atomic with 0.0. `play()` restores from this field.
@@ -292,15 +420,17 @@ where
     }
 
     fn reset(&mut self) -> Result<(), ResetError> {
-        let (stream, prod) = create_stream_internal::<T>(
+        let (stream, ring) = create_stream_internal::<T>(
             &self.device,
             &self.config,
             self.buffer_size,
             self.target_gain.clone(),
+            self.device_lost.clone(),
         )?;
 
         self.stream = stream;
-        self.ring_buf = prod;
+        self.ring_buf = ring.producer();
+        self.ring = ring;
         self.interleave_buffer.clear();
 
         Ok(())
@@ -317,6 +447,10 @@ where
         Ok(())
     }
 
+    fn buffered_frames(&self) -> usize {
+        self.ring.count() / self.config.channels as usize
+    }
+
     #[allow(clippy::needless_range_loop)]
     fn consume_from(
         &mut self,
@@ -347,13 +481,7 @@ where
             }
         }
 
-        // Write to device ring buffer
-        let mut slice: &[T] = &self.interleave_buffer;
-        while !slice.is_empty() {
-            if let Some(written) = self.ring_buf.write_blocking(slice) {
-                slice = &slice[written..];
-            }
-        }
+        write_to_ring(&self.ring_buf, &self.interleave_buffer, &self.device_lost)?;
 
         Ok(read)
     }
@@ -392,14 +520,9 @@ where
             }
         }
 
-        let mut slice: &[T] = &self.interleave_buffer;
-        while !slice.is_empty() {
-            if let Some(written) = self.ring_buf.write_blocking(slice) {
-                slice = &slice[written..];
-            }
-        }
-
-        Some(Ok(read))
+        Some(
+            write_to_ring(&self.ring_buf, &self.interleave_buffer, &self.device_lost).map(|_| read),
+        )
     }
 }
 
@@ -412,3 +535,38 @@ make_unknown_error!(cpal::SupportedStreamConfigsError, InfoError);
 make_unknown_error!(cpal::BuildStreamError, OpenError);
 make_unknown_error!(cpal::DevicesError, ListError);
 make_unknown_error!(cpal::DevicesError, FindError);
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn snapshot(devices: &[&str], default: Option<&str>) -> DeviceSnapshot {
+        DeviceSnapshot {
+            devices: devices.iter().map(|uid| uid.to_string()).collect(),
+            default: default.map(str::to_string),
+        }
+    }
+
+    #[test]
+    fn snapshot_changes() {
+        let before = snapshot(&["Speakers", "USB DAC"], Some("USB DAC"));
+        let after = snapshot(&["Speakers", "Headphones"], Some("Speakers"));
+
+        assert_eq!(
+            before.changes_to(&after),
+            vec![
+                DeviceChange::Removed("USB DAC".to_string()),
+                DeviceChange::Added("Headphones".to_string()),
+                DeviceChange::DefaultChanged("Speakers".to_string()),
+            ]
+        );
+        assert_eq!(after.changes_to(&after), vec![]);
+
+        // losing the default device entirely isn't a default change by itself
+        let no_default = snapshot(&["Speakers"], None);
+        assert_eq!(
+            snapshot(&["Speakers"], Some("Speakers")).changes_to(&no_default),
+            vec![]
+        );
+    }
+}
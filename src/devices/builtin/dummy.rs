@@ -68,23 +68,13 @@ impl DummyDevice {
 
     pub fn get_bit_format() -> Result<SampleFormat, InfoError> {
         let var = env::var("HB_DUMMY_BIT_FORMAT");
-        Ok(match var.as_deref() {
-            Ok("F64") => SampleFormat::Float64,
-            Ok("F32") => SampleFormat::Float32,
-            Ok("S32") => SampleFormat::Signed32,
-            Ok("U32") => SampleFormat::Unsigned32,
-            Ok("S24") => SampleFormat::Signed24,
-            Ok("U24") => SampleFormat::Unsigned24,
-            Ok("S16") => SampleFormat::Signed16,
-            Ok("U16") => SampleFormat::Unsigned16,
-            Ok("S8") => SampleFormat::Signed8,
-            Ok("U8") => SampleFormat::Unsigned8,
-            Err(std::env::VarError::NotPresent) => SampleFormat::Signed16,
-            Ok(_) => Err(InfoError::SampleFmt(var.unwrap()))?,
+        match var {
+            Ok(name) => name.parse(),
+            Err(std::env::VarError::NotPresent) => Ok(SampleFormat::Signed16),
             Err(std::env::VarError::NotUnicode(os)) => {
-                Err(InfoError::SampleFmt(os.to_string_lossy().into_owned()))?
+                Err(InfoError::SampleFmt(os.to_string_lossy().into_owned()))
             }
-        })
+        }
     }
 
     pub fn get_channels() -> u16 {
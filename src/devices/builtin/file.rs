@@ -0,0 +1,512 @@
+//! File sink device provider.
+//!
+//! Instead of playing audio, this provider writes everything submitted to its device to a WAV
+//! file, exactly as a real device would receive it (after resampling, gain and any other
+//! processing). This is useful for golden tests of the playback pipeline, and for rendering
+//! playback to a file.
+//!
+//! The sink is configured from the environment, like the dummy provider:
+//! - `HB_FILE_PATH`: The file to write. Defaults to `hummingbird-output.wav`.
+//! - `HB_FILE_SAMPLE_RATE`: The sample rate of the file. Defaults to 44100.
+//! - `HB_FILE_BIT_FORMAT`: The sample format of the file, one of `S8`, `S16`, `S24`, `S32` and
+//!   `F32`. Defaults to `S16`.
+//! - `HB_FILE_CHANNELS`: The number of channels of the file. Defaults to 2.
+//!
+//! The file is created when the device is first opened, and stays open for as long as the
+//! provider exists, so streams being recreated (e.g. when the channel count of the playing track
+//! changes) don't truncate it. The file is flushed whenever playback is paused or the stream is
+//! closed, and completed when the provider is dropped. Samples are written as soon as they are
+//! submitted, so rendering isn't limited to real time.
+
+use std::{
+    env,
+    fs::File,
+    io::{self, BufWriter},
+    path::PathBuf,
+    sync::{Arc, Mutex},
+};
+
+use hound::{WavSpec, WavWriter};
+use intx::I24;
+use thiserror::Error;
+use tracing::{debug, info, warn};
+
+use crate::{
+    devices::{
+        errors::{
+            CloseError, FindError, InfoError, InitializationError, ListError, OpenError,
+            ResetError, StateError, SubmissionError,
+        },
+        format::{BufferSize, ChannelSpec, FormatInfo, SampleFormat, SupportedFormat},
+        resample::SampleFrom,
+        traits::{Device, DeviceProvider, OutputStream},
+    },
+    media::pipeline::ChannelConsumers,
+    util::make_unknown_error,
+};
+
+const PROVIDER: &str = "file";
+
+/// The number of frames the playback thread is told to submit at once.
+const BUFFER_SIZE: u32 = 4096;
+
+/// The file written by the file sink, and the format it is written in.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct FileSinkConfig {
+    pub path: PathBuf,
+    pub sample_rate: u32,
+    pub sample_type: SampleFormat,
+    pub channels: u16,
+}
+
+impl FileSinkConfig {
+    pub fn new(path: impl Into<PathBuf>, sample_rate: u32, sample_type: SampleFormat) -> Self {
+        Self {
+            path: path.into(),
+            sample_rate,
+            sample_type,
+            channels: 2,
+        }
+    }
+
+    pub fn with_channels(mut self, channels: u16) -> Self {
+        self.channels = channels;
+        self
+    }
+
+    /// Reads the configuration from the `HB_FILE_*` environment variables.
+    pub fn from_env() -> Result<Self, InfoError> {
+        let path = env::var_os("HB_FILE_PATH")
+            .map(PathBuf::from)
+            .unwrap_or_else(|| PathBuf::from("hummingbird-output.wav"));
+        let sample_rate = env::var("HB_FILE_SAMPLE_RATE")
+            .ok()
+            .and_then(|s| s.parse().ok())
+            .unwrap_or(44100);
+        let sample_type = match env::var("HB_FILE_BIT_FORMAT") {
+            Ok(name) => name.parse()?,
+            Err(env::VarError::NotPresent) => SampleFormat::Signed16,
+            Err(env::VarError::NotUnicode(os)) => {
+                return Err(InfoError::SampleFmt(os.to_string_lossy().into_owned()));
+            }
+        };
+        let channels = env::var("HB_FILE_CHANNELS")
+            .ok()
+            .and_then(|s| s.parse().ok())
+            .unwrap_or(2);
+
+        let config = Self::new(path, sample_rate, sample_type).with_channels(channels);
+        config.validate()?;
+
+        Ok(config)
+    }
+
+    /// The number of bits each sample is stored with.
+    fn bits_per_sample(&self) -> u16 {
+        match self.sample_type {
+            SampleFormat::Signed8 => 8,
+            SampleFormat::Signed16 => 16,
+            SampleFormat::Signed24 => 24,
+            _ => 32,
+        }
+    }
+
+    fn validate(&self) -> Result<(), InfoError> {
+        match self.sample_type {
+            SampleFormat::Signed8
+            | SampleFormat::Signed16
+            | SampleFormat::Signed24
+            | SampleFormat::Signed32
+            | SampleFormat::Float32 => Ok(()),
+            _ => Err(InfoError::SampleFmt(format!(
+                "{:?} in a WAV file",
+                self.sample_type
+            ))),
+        }
+    }
+
+    fn format(&self) -> FormatInfo {
+        FormatInfo {
+            originating_provider: PROVIDER,
+            sample_type: self.sample_type,
+            sample_rate: self.sample_rate,
+            buffer_size: BufferSize::Fixed(BUFFER_SIZE),
+            channels: ChannelSpec::Count(self.channels),
+        }
+    }
+}
+
+type Writer = WavWriter<BufWriter<File>>;
+
+fn create_writer(config: &FileSinkConfig) -> Result<Writer, SinkError> {
+    let spec = WavSpec {
+        channels: config.channels,
+        sample_rate: config.sample_rate,
+        bits_per_sample: config.bits_per_sample(),
+        sample_format: if config.sample_type == SampleFormat::Float32 {
+            hound::SampleFormat::Float
+        } else {
+            hound::SampleFormat::Int
+        },
+    };
+    let file = BufWriter::new(File::create(&config.path)?);
+
+    Ok(WavWriter::new(file, spec)?)
+}
+
+#[derive(Debug, Error)]
+enum SinkError {
+    #[error("{0}")]
+    Io(#[from] io::Error),
+    #[error("{0}")]
+    Wav(#[from] hound::Error),
+}
+
+/// The file being written to, shared by the provider, its device and the device's streams.
+type SharedWriter = Arc<Mutex<Option<Writer>>>;
+
+fn flush_shared(writer: &SharedWriter) -> Result<(), SinkError> {
+    match writer.lock().expect("poisoned").as_mut() {
+        Some(writer) => Ok(writer.flush()?),
+        None => Ok(()),
+    }
+}
+
+/// A device provider writing audio to a file instead of playing it. See the module documentation
+/// for how it is configured.
+pub struct FileProvider {
+    config: Option<FileSinkConfig>,
+    writer: SharedWriter,
+}
+
+impl FileProvider {
+    /// Creates a provider configured from the environment when it is initialized.
+    pub fn new() -> Self {
+        Self::with_config(None)
+    }
+
+    /// Creates a provider writing with the given configuration, or configured from the
+    /// environment if there is none.
+    pub fn with_config(config: Option<FileSinkConfig>) -> Self {
+        Self {
+            config,
+            writer: Arc::new(Mutex::new(None)),
+        }
+    }
+
+    fn device(&self) -> Result<FileDevice, FindError> {
+        let config = self
+            .config
+            .clone()
+            .ok_or_else(|| FindError::Unknown("provider not initialized".to_string()))?;
+
+        Ok(FileDevice {
+            config,
+            writer: self.writer.clone(),
+        })
+    }
+}
+
+impl DeviceProvider for FileProvider {
+    fn initialize(&mut self) -> Result<(), InitializationError> {
+        let config = match self.config.take() {
+            Some(config) => config,
+            None => FileSinkConfig::from_env()
+                .map_err(|e| InitializationError::Unknown(e.to_string()))?,
+        };
+        config
+            .validate()
+            .map_err(|e| InitializationError::Unknown(e.to_string()))?;
+
+        info!(
+            "FileProvider initialized, writing {:?} {}Hz {}ch to {}",
+            config.sample_type,
+            config.sample_rate,
+            config.channels,
+            config.path.display()
+        );
+        warn!("This device provider WILL not play any actual audio.");
+
+        self.config = Some(config);
+        Ok(())
+    }
+
+    fn get_devices(&mut self) -> Result<Vec<Box<dyn Device>>, ListError> {
+        let device = self
+            .device()
+            .map_err(|e| ListError::Unknown(e.to_string()))?;
+        Ok(vec![Box::new(device) as Box<dyn Device>])
+    }
+
+    fn get_default_device(&mut self) -> Result<Box<dyn Device>, FindError> {
+        Ok(Box::new(self.device()?) as Box<dyn Device>)
+    }
+
+    fn get_device_by_uid(&mut self, id: &str) -> Result<Box<dyn Device>, FindError> {
+        if id == PROVIDER {
+            self.get_default_device()
+        } else {
+            Err(FindError::DeviceDoesNotExist)
+        }
+    }
+}
+
+impl Drop for FileProvider {
+    fn drop(&mut self) {
+        if let Some(writer) = self.writer.lock().expect("poisoned").take()
+            && let Err(e) = writer.finalize()
+        {
+            warn!("Failed to complete output file: {e}");
+        }
+    }
+}
+
+pub struct FileDevice {
+    config: FileSinkConfig,
+    writer: SharedWriter,
+}
+
+impl Device for FileDevice {
+    fn open_device(&mut self, format: FormatInfo) -> Result<Box<dyn OutputStream>, OpenError> {
+        if format.originating_provider != PROVIDER {
+            return Err(OpenError::InvalidConfigProvider);
+        }
+        if format.sample_type != self.config.sample_type
+            || format.sample_rate != self.config.sample_rate
+            || format.channels.count() != self.config.channels
+        {
+            return Err(OpenError::InvalidSampleFormat);
+        }
+
+        let mut writer = self.writer.lock().expect("poisoned");
+        if writer.is_none() {
+            debug!("Creating output file {}", self.config.path.display());
+            *writer = Some(create_writer(&self.config)?);
+        }
+
+        Ok(Box::new(FileStream {
+            sample_type: self.config.sample_type,
+            writer: self.writer.clone(),
+            volume: 1.0,
+            replaygain: 1.0,
+            ints: Vec::new(),
+            floats: Vec::new(),
+        }) as Box<dyn OutputStream>)
+    }
+
+    fn get_supported_formats(&self) -> Result<Vec<SupportedFormat>, InfoError> {
+        Ok(vec![SupportedFormat {
+            originating_provider: PROVIDER,
+            sample_type: self.config.sample_type,
+            sample_rates: (self.config.sample_rate, self.config.sample_rate),
+            buffer_size: BufferSize::Fixed(BUFFER_SIZE),
+            channels: ChannelSpec::Count(self.config.channels),
+        }])
+    }
+
+    fn get_default_format(&self) -> Result<FormatInfo, InfoError> {
+        Ok(self.config.format())
+    }
+
+    fn get_name(&self) -> Result<String, InfoError> {
+        Ok(format!("File ({})", self.config.path.display()))
+    }
+
+    fn get_uid(&self) -> Result<String, InfoError> {
+        Ok(PROVIDER.to_string())
+    }
+
+    fn requires_matching_format(&self) -> bool {
+        true
+    }
+}
+
+/// Appends the staged samples to `out`, interleaved and converted with `convert`.
+fn interleave<T>(staging: &[Vec<f64>], read: usize, out: &mut Vec<T>, convert: impl Fn(f64) -> T) {
+    out.clear();
+    out.reserve(read * staging.len());
+
+    for i in 0..read {
+        for channel in staging {
+            out.push(convert(channel[i]));
+        }
+    }
+}
+
+pub struct FileStream {
+    sample_type: SampleFormat,
+    writer: SharedWriter,
+    volume: f64,
+    replaygain: f64,
+    ints: Vec<i32>,
+    floats: Vec<f32>,
+}
+
+impl OutputStream for FileStream {
+    fn close_stream(&mut self) -> Result<(), CloseError> {
+        flush_shared(&self.writer)?;
+        Ok(())
+    }
+
+    fn needs_input(&self) -> bool {
+        true
+    }
+
+    fn play(&mut self) -> Result<(), StateError> {
+        Ok(())
+    }
+
+    fn pause(&mut self) -> Result<(), StateError> {
+        flush_shared(&self.writer)?;
+        Ok(())
+    }
+
+    fn reset(&mut self) -> Result<(), ResetError> {
+        Ok(())
+    }
+
+    fn set_volume(&mut self, volume: f64) -> Result<(), StateError> {
+        self.volume = volume;
+        Ok(())
+    }
+
+    fn set_replaygain(&mut self, gain: f64) -> Result<(), StateError> {
+        self.replaygain = gain;
+        Ok(())
+    }
+
+    fn consume_from(
+        &mut self,
+        input: &mut ChannelConsumers<f64>,
+    ) -> Result<usize, SubmissionError> {
+        let available = input.potentially_available();
+        if available == 0 {
+            return Ok(0);
+        }
+
+        let read = input.try_read_to_staging(available);
+        if read == 0 {
+            return Ok(0);
+        }
+
+        // the gain is applied without ramping, so the same input always produces the same file
+        let gain = self.volume * self.replaygain;
+        let staging = input.staging();
+        let int = |sample: f64| (sample * gain).clamp(-1.0, 1.0);
+
+        match self.sample_type {
+            SampleFormat::Float32 => {
+                interleave(staging, read, &mut self.floats, |sample| {
+                    f32::sample_from(sample * gain)
+                });
+            }
+            SampleFormat::Signed8 => interleave(staging, read, &mut self.ints, |sample| {
+                i8::sample_from(int(sample)).into()
+            }),
+            SampleFormat::Signed16 => interleave(staging, read, &mut self.ints, |sample| {
+                i16::sample_from(int(sample)).into()
+            }),
+            SampleFormat::Signed24 => interleave(staging, read, &mut self.ints, |sample| {
+                I24::sample_from(int(sample)).into()
+            }),
+            _ => interleave(staging, read, &mut self.ints, |sample| {
+                i32::sample_from(int(sample))
+            }),
+        }
+
+        let mut writer = self.writer.lock().expect("poisoned");
+        let writer = writer.as_mut().ok_or(SubmissionError::DeviceLost)?;
+        let written = if self.sample_type == SampleFormat::Float32 {
+            self.floats
+                .iter()
+                .try_for_each(|&sample| writer.write_sample(sample))
+        } else {
+            self.ints
+                .iter()
+                .try_for_each(|&sample| writer.write_sample(sample))
+        };
+        written.map_err(SinkError::from)?;
+
+        Ok(read)
+    }
+}
+
+impl Drop for FileStream {
+    fn drop(&mut self) {
+        if let Err(e) = flush_shared(&self.writer) {
+            warn!("Failed to flush output file: {e}");
+        }
+    }
+}
+
+make_unknown_error!(SinkError, OpenError);
+make_unknown_error!(SinkError, CloseError);
+make_unknown_error!(SinkError, StateError);
+make_unknown_error!(SinkError, SubmissionError);
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{media::pipeline::ChannelBuffers, test_support::TestDir};
+
+    fn write_file(config: FileSinkConfig, channels: &[Vec<f64>]) {
+        let mut provider = FileProvider::with_config(Some(config));
+        provider.initialize().unwrap();
+        let mut device = provider.get_default_device().unwrap();
+        let format = device.get_default_format().unwrap();
+        let mut stream = device.open_device(format).unwrap();
+
+        let (producers, mut consumers) = ChannelBuffers::<f64>::new(channels.len(), 1024).split();
+        for chunk in 0..channels[0].len().div_ceil(512) {
+            let range = chunk * 512..((chunk + 1) * 512).min(channels[0].len());
+            let slices: Vec<&[f64]> = channels.iter().map(|c| &c[range.clone()]).collect();
+            producers.write_slices(&slices);
+            while stream.consume_from(&mut consumers).unwrap() > 0 {}
+        }
+
+        stream.close_stream().unwrap();
+    }
+
+    fn sine(frames: usize, freq: f64) -> Vec<f64> {
+        (0..frames)
+            .map(|i| (i as f64 * freq / 44100.0 * std::f64::consts::TAU).sin() * 0.5)
+            .collect()
+    }
+
+    #[test]
+    fn writes_wav() {
+        let dir = TestDir::new("hummingbird-file-sink");
+        let path = dir.join("out.wav");
+        let channels = vec![sine(3000, 440.0), sine(3000, 660.0)];
+        write_file(
+            FileSinkConfig::new(&path, 44100, SampleFormat::Signed16),
+            &channels,
+        );
+
+        let mut reader = hound::WavReader::open(&path).unwrap();
+        let spec = reader.spec();
+        assert_eq!(spec.channels, 2);
+        assert_eq!(spec.sample_rate, 44100);
+        assert_eq!(spec.bits_per_sample, 16);
+
+        let samples: Vec<i16> = reader.samples().map(Result::unwrap).collect();
+        assert_eq!(samples.len(), 6000);
+        for (i, pair) in samples.chunks(2).enumerate() {
+            assert_eq!(pair[0], i16::sample_from(channels[0][i]));
+            assert_eq!(pair[1], i16::sample_from(channels[1][i]));
+        }
+    }
+
+    #[test]
+    fn rejects_unsigned_samples() {
+        let config = FileSinkConfig::new("out.wav", 44100, SampleFormat::Unsigned16);
+        assert!(
+            FileProvider::with_config(Some(config))
+                .initialize()
+                .is_err()
+        );
+
+        let config = FileSinkConfig::new("out.wav", 44100, SampleFormat::Float32);
+        assert!(FileProvider::with_config(Some(config)).initialize().is_ok());
+    }
+}
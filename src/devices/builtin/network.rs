@@ -0,0 +1,977 @@
+//! Network device provider.
+//!
+//! This provider streams the final PCM to other devices on the network, for whole-house audio. It
+//! has one device for each way of sending audio:
+//! - RTP: L16 or L24 RTP packets are sent to a multicast group (or a single receiver). RTP has no
+//!   way for receivers to report back, so packets are paced by the local clock alone. Receivers
+//!   whose clocks run slightly faster or slower than it slowly drift, and have to absorb that in
+//!   their jitter buffers. The SDP description receivers need is logged when the stream is opened.
+//! - Snapcast (TCP): raw PCM is written to a Snapcast server's TCP source, e.g.
+//!   `source = tcp://127.0.0.1:4953?name=Hummingbird&mode=server`.
+//! - Snapcast (pipe): raw PCM is written to a Snapcast server's pipe source, e.g.
+//!   `source = pipe:///tmp/snapfifo?name=Hummingbird`.
+//!
+//! Snapcast reads from its sources at the rate of its own clock, so writes wait once its buffer
+//! is full, and the server's clock paces playback. If the server stops reading for longer than
+//! [`WRITE_TIMEOUT`], the stream reports the device as lost. The sample format of the Snapcast source has
+//! to match the one configured here (`sampleformat=48000:16:2` by default).
+//!
+//! The provider is configured from the environment, like the dummy provider:
+//! - `HB_RTP_ADDRESS`: Where RTP packets are sent. Defaults to `239.255.77.77:5004`.
+//! - `HB_SNAPCAST_ADDRESS`: The Snapcast TCP source to connect to. Defaults to `127.0.0.1:4953`.
+//! - `HB_SNAPCAST_PIPE`: The Snapcast pipe source to write to. Defaults to `/tmp/snapfifo`.
+//! - `HB_NETWORK_SAMPLE_RATE`: The sample rate of the stream. Defaults to 48000.
+//! - `HB_NETWORK_BIT_FORMAT`: The sample format of the stream, `S16` or `S24`. Defaults to `S16`.
+//! - `HB_NETWORK_CHANNELS`: The number of channels of the stream. Defaults to 2.
+
+use std::{
+    env,
+    fs::{File, OpenOptions},
+    io::{self, Write},
+    net::{Shutdown, SocketAddr, TcpStream, ToSocketAddrs, UdpSocket},
+    path::{Path, PathBuf},
+    sync::{
+        Arc,
+        atomic::{AtomicBool, Ordering},
+        mpsc::{self, Receiver, Sender},
+    },
+    thread::{self, JoinHandle},
+    time::{Duration, Instant},
+};
+
+use intx::I24;
+use rb::{Consumer, Producer, RB, RbConsumer, SpscRb};
+use tracing::{debug, info, warn};
+
+use crate::{
+    devices::{
+        errors::{
+            CloseError, FindError, InfoError, InitializationError, ListError, OpenError,
+            ResetError, StateError, SubmissionError,
+        },
+        format::{BufferSize, ChannelSpec, FormatInfo, SampleFormat, SupportedFormat},
+        resample::SampleFrom,
+        traits::{Device, DeviceProvider, OutputStream},
+        util::{GainRamp, write_to_ring},
+    },
+    media::pipeline::ChannelConsumers,
+    util::make_unknown_error,
+};
+
+const PROVIDER: &str = "network";
+
+/// The number of frames the playback thread is told to submit at once.
+const BUFFER_SIZE: u32 = 4096;
+
+/// How much audio is buffered between the playback thread and the sending thread, in
+/// milliseconds.
+const BUFFER_MS: usize = 200;
+
+/// The largest RTP payload sent, which keeps packets within a standard Ethernet MTU.
+const MAX_RTP_PAYLOAD: usize = 1200;
+
+/// The dynamic RTP payload type used for formats without a static one.
+const DYNAMIC_PAYLOAD_TYPE: u8 = 96;
+
+/// How far ahead of the local clock RTP packets may be sent.
+const RTP_LEAD: Duration = Duration::from_millis(20);
+
+const CONNECT_TIMEOUT: Duration = Duration::from_secs(2);
+
+/// How long a write to a Snapcast source may wait for the server to read before the device is
+/// considered lost.
+const WRITE_TIMEOUT: Duration = Duration::from_secs(2);
+
+/// How long the sending thread waits before checking for more audio when there is none.
+const IDLE_INTERVAL: Duration = Duration::from_millis(2);
+
+/// The ways audio can be sent over the network.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Sink {
+    Rtp,
+    SnapcastTcp,
+    SnapcastPipe,
+}
+
+impl Sink {
+    const ALL: [Sink; 3] = [Sink::Rtp, Sink::SnapcastTcp, Sink::SnapcastPipe];
+
+    fn uid(self) -> &'static str {
+        match self {
+            Sink::Rtp => "rtp",
+            Sink::SnapcastTcp => "snapcast-tcp",
+            Sink::SnapcastPipe => "snapcast-pipe",
+        }
+    }
+
+    fn from_uid(uid: &str) -> Option<Self> {
+        Self::ALL.into_iter().find(|sink| sink.uid() == uid)
+    }
+}
+
+/// Where the network provider sends audio to, and the format it is sent in.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct NetworkConfig {
+    pub rtp_address: String,
+    pub snapcast_address: String,
+    pub snapcast_pipe: PathBuf,
+    pub sample_rate: u32,
+    pub sample_type: SampleFormat,
+    pub channels: u16,
+}
+
+impl Default for NetworkConfig {
+    fn default() -> Self {
+        Self {
+            rtp_address: "239.255.77.77:5004".to_string(),
+            snapcast_address: "127.0.0.1:4953".to_string(),
+            snapcast_pipe: PathBuf::from("/tmp/snapfifo"),
+            sample_rate: 48000,
+            sample_type: SampleFormat::Signed16,
+            channels: 2,
+        }
+    }
+}
+
+impl NetworkConfig {
+    /// Reads the configuration from the `HB_RTP_*`, `HB_SNAPCAST_*` and `HB_NETWORK_*`
+    /// environment variables.
+    pub fn from_env() -> Result<Self, InfoError> {
+        let defaults = Self::default();
+        let sample_type = match env::var("HB_NETWORK_BIT_FORMAT") {
+            Ok(name) => name.parse()?,
+            Err(env::VarError::NotPresent) => defaults.sample_type,
+            Err(env::VarError::NotUnicode(os)) => {
+                return Err(InfoError::SampleFmt(os.to_string_lossy().into_owned()));
+            }
+        };
+
+        let config = Self {
+            rtp_address: env::var("HB_RTP_ADDRESS").unwrap_or(defaults.rtp_address),
+            snapcast_address: env::var("HB_SNAPCAST_ADDRESS").unwrap_or(defaults.snapcast_address),
+            snapcast_pipe: env::var_os("HB_SNAPCAST_PIPE")
+                .map(PathBuf::from)
+                .unwrap_or(defaults.snapcast_pipe),
+            sample_rate: env::var("HB_NETWORK_SAMPLE_RATE")
+                .ok()
+                .and_then(|s| s.parse().ok())
+                .unwrap_or(defaults.sample_rate),
+            sample_type,
+            channels: env::var("HB_NETWORK_CHANNELS")
+                .ok()
+                .and_then(|s| s.parse().ok())
+                .unwrap_or(defaults.channels),
+        };
+        config.validate()?;
+
+        Ok(config)
+    }
+
+    fn validate(&self) -> Result<(), InfoError> {
+        match self.sample_type {
+            SampleFormat::Signed16 | SampleFormat::Signed24 => Ok(()),
+            other => Err(InfoError::SampleFmt(format!(
+                "{:?} over the network, only S16 and S24 are supported",
+                other
+            ))),
+        }
+    }
+
+    /// The number of bits each sample is sent with.
+    fn bits_per_sample(&self) -> u16 {
+        if self.sample_type == SampleFormat::Signed24 {
+            24
+        } else {
+            16
+        }
+    }
+
+    fn format(&self) -> FormatInfo {
+        FormatInfo {
+            originating_provider: PROVIDER,
+            sample_type: self.sample_type,
+            sample_rate: self.sample_rate,
+            buffer_size: BufferSize::Fixed(BUFFER_SIZE),
+            channels: ChannelSpec::Count(self.channels),
+        }
+    }
+
+    fn target(&self, sink: Sink) -> String {
+        match sink {
+            Sink::Rtp => self.rtp_address.clone(),
+            Sink::SnapcastTcp => self.snapcast_address.clone(),
+            Sink::SnapcastPipe => self.snapcast_pipe.display().to_string(),
+        }
+    }
+}
+
+fn resolve(address: &str) -> io::Result<SocketAddr> {
+    address.to_socket_addrs()?.next().ok_or_else(|| {
+        io::Error::new(
+            io::ErrorKind::NotFound,
+            format!("{address} doesn't resolve to an address"),
+        )
+    })
+}
+
+/// Sends RTP packets with raw PCM payloads (RFC 3551 L16, RFC 3190 L24).
+struct RtpSender {
+    socket: UdpSocket,
+    destination: SocketAddr,
+    payload_type: u8,
+    sequence: u16,
+    timestamp: u32,
+    ssrc: u32,
+    packet: Vec<u8>,
+}
+
+impl RtpSender {
+    fn connect(destination: SocketAddr, config: &NetworkConfig) -> io::Result<Self> {
+        let bind: SocketAddr = if destination.is_ipv4() {
+            "0.0.0.0:0".parse().unwrap()
+        } else {
+            "[::]:0".parse().unwrap()
+        };
+        let socket = UdpSocket::bind(bind)?;
+        if destination.ip().is_multicast() && destination.is_ipv4() {
+            socket.set_multicast_ttl_v4(1)?;
+        }
+
+        // L16 at 44.1 kHz has static payload types, everything else uses a dynamic one
+        let payload_type = match (
+            config.bits_per_sample(),
+            config.sample_rate,
+            config.channels,
+        ) {
+            (16, 44100, 2) => 10,
+            (16, 44100, 1) => 11,
+            _ => DYNAMIC_PAYLOAD_TYPE,
+        };
+
+        Ok(Self {
+            socket,
+            destination,
+            payload_type,
+            sequence: rand::random(),
+            timestamp: rand::random(),
+            ssrc: rand::random(),
+            packet: Vec::with_capacity(12 + MAX_RTP_PAYLOAD),
+        })
+    }
+
+    /// An SDP description of the stream, which receivers need to play it.
+    fn sdp(&self, config: &NetworkConfig) -> String {
+        let family = if self.destination.is_ipv4() {
+            "IP4"
+        } else {
+            "IP6"
+        };
+        let ttl = if self.destination.ip().is_multicast() && self.destination.is_ipv4() {
+            "/1"
+        } else {
+            ""
+        };
+
+        format!(
+            "v=0\r\no=- {ssrc} 0 IN {family} {ip}\r\ns=Hummingbird\r\nc=IN {family} {ip}{ttl}\r\n\
+            t=0 0\r\nm=audio {port} RTP/AVP {pt}\r\na=rtpmap:{pt} L{bits}/{rate}/{channels}\r\n",
+            ssrc = self.ssrc,
+            ip = self.destination.ip(),
+            port = self.destination.port(),
+            pt = self.payload_type,
+            bits = config.bits_per_sample(),
+            rate = config.sample_rate,
+            channels = config.channels,
+        )
+    }
+
+    fn send(&mut self, payload: &[u8], frames: usize) -> io::Result<()> {
+        self.packet.clear();
+        // version 2, no padding, no extension, no CSRCs, no marker
+        self.packet.push(0x80);
+        self.packet.push(self.payload_type);
+        self.packet.extend_from_slice(&self.sequence.to_be_bytes());
+        self.packet.extend_from_slice(&self.timestamp.to_be_bytes());
+        self.packet.extend_from_slice(&self.ssrc.to_be_bytes());
+        self.packet.extend_from_slice(payload);
+
+        self.socket.send_to(&self.packet, self.destination)?;
+
+        self.sequence = self.sequence.wrapping_add(1);
+        self.timestamp = self.timestamp.wrapping_add(frames as u32);
+        Ok(())
+    }
+}
+
+/// A Snapcast pipe source, opened without blocking so that writes to a full pipe can give up
+/// once the server hasn't read from it for [`WRITE_TIMEOUT`], or the stream is closed.
+struct PipeWriter {
+    file: File,
+    shared: Arc<Shared>,
+}
+
+impl PipeWriter {
+    fn open(path: &Path, shared: Arc<Shared>) -> io::Result<Self> {
+        let mut options = OpenOptions::new();
+        // Opening a FIFO for reading as well doesn't block until the server opens it
+        options.read(true).write(true);
+        #[cfg(unix)]
+        {
+            use std::os::unix::fs::OpenOptionsExt;
+            options.custom_flags(libc::O_NONBLOCK);
+        }
+
+        Ok(Self {
+            file: options.open(path)?,
+            shared,
+        })
+    }
+}
+
+impl Write for PipeWriter {
+    fn write(&mut self, buf: &[u8]) -> io::Result<usize> {
+        let deadline = Instant::now() + WRITE_TIMEOUT;
+
+        loop {
+            match self.file.write(buf) {
+                Err(e) if e.kind() == io::ErrorKind::WouldBlock => {
+                    if self.shared.stop.load(Ordering::Relaxed) {
+                        return Err(io::Error::other("stream closed"));
+                    }
+                    if Instant::now() >= deadline {
+                        return Err(io::Error::new(
+                            io::ErrorKind::TimedOut,
+                            "Snapcast stopped reading from the pipe",
+                        ));
+                    }
+                    thread::sleep(IDLE_INTERVAL);
+                }
+                result => return result,
+            }
+        }
+    }
+
+    fn flush(&mut self) -> io::Result<()> {
+        self.file.flush()
+    }
+}
+
+enum Transport {
+    Rtp(RtpSender),
+    /// A Snapcast TCP connection or pipe.
+    Stream(Box<dyn Write + Send>),
+}
+
+impl Transport {
+    /// Connect to the sink. For a Snapcast TCP source, a handle to the connection is returned as
+    /// well, so that it can be shut down while the sending thread is waiting on a write.
+    fn connect(
+        sink: Sink,
+        config: &NetworkConfig,
+        shared: &Arc<Shared>,
+    ) -> io::Result<(Self, Option<TcpStream>)> {
+        Ok(match sink {
+            Sink::Rtp => {
+                let sender = RtpSender::connect(resolve(&config.rtp_address)?, config)?;
+                info!("Streaming RTP, SDP:\n{}", sender.sdp(config));
+                (Transport::Rtp(sender), None)
+            }
+            Sink::SnapcastTcp => {
+                let stream = TcpStream::connect_timeout(
+                    &resolve(&config.snapcast_address)?,
+                    CONNECT_TIMEOUT,
+                )?;
+                stream.set_nodelay(true)?;
+                stream.set_write_timeout(Some(WRITE_TIMEOUT))?;
+                let handle = stream.try_clone()?;
+                (Transport::Stream(Box::new(stream)), Some(handle))
+            }
+            Sink::SnapcastPipe => {
+                let pipe = PipeWriter::open(&config.snapcast_pipe, shared.clone())?;
+                (Transport::Stream(Box::new(pipe)), None)
+            }
+        })
+    }
+
+    /// The number of frames sent at once.
+    fn packet_frames(&self, config: &NetworkConfig) -> usize {
+        let rate = config.sample_rate as usize;
+        match self {
+            // 1 ms packets, like AES67, as long as they fit within the MTU
+            Transport::Rtp(_) => {
+                let frame_bytes = config.channels as usize * config.bits_per_sample() as usize / 8;
+                (rate / 1000).min(MAX_RTP_PAYLOAD / frame_bytes).max(1)
+            }
+            Transport::Stream(_) => (rate / 100).max(1),
+        }
+    }
+
+    /// Packs interleaved samples into the byte order and sample size the transport expects.
+    fn encode(&self, samples: &[i32], bits: u16, out: &mut Vec<u8>) {
+        out.clear();
+
+        match (self, bits) {
+            // network byte order
+            (Transport::Rtp(_), 16) => {
+                out.extend(samples.iter().flat_map(|&s| (s as i16).to_be_bytes()));
+            }
+            (Transport::Rtp(_), _) => {
+                out.extend(samples.iter().flat_map(|&s| {
+                    let [_, a, b, c] = s.to_be_bytes();
+                    [a, b, c]
+                }));
+            }
+            (Transport::Stream(_), 16) => {
+                out.extend(samples.iter().flat_map(|&s| (s as i16).to_le_bytes()));
+            }
+            // Snapcast stores 24 bit samples in 4 bytes
+            (Transport::Stream(_), _) => {
+                out.extend(samples.iter().flat_map(|&s| s.to_le_bytes()));
+            }
+        }
+    }
+
+    fn send(&mut self, payload: &[u8], frames: usize) -> io::Result<()> {
+        match self {
+            Transport::Rtp(sender) => sender.send(payload, frames),
+            Transport::Stream(stream) => stream.write_all(payload),
+        }
+    }
+}
+
+/// Keeps RTP packets from being sent faster than real time, as measured by the local clock.
+struct Pacer {
+    start: Instant,
+    sample_rate: u32,
+    frames_sent: u64,
+}
+
+impl Pacer {
+    fn new(sample_rate: u32) -> Self {
+        Self {
+            start: Instant::now(),
+            sample_rate,
+            frames_sent: 0,
+        }
+    }
+
+    fn restart(&mut self) {
+        self.start = Instant::now();
+        self.frames_sent = 0;
+    }
+
+    /// Wait until `frames` more frames can be sent.
+    fn wait(&mut self, frames: usize) {
+        let due = Duration::from_secs_f64(self.frames_sent as f64 / self.sample_rate as f64);
+        let elapsed = self.start.elapsed() + RTP_LEAD;
+        if due > elapsed {
+            thread::sleep(due - elapsed);
+        }
+
+        self.frames_sent += frames as u64;
+    }
+}
+
+/// State shared between a stream and its sending thread.
+#[derive(Default)]
+struct Shared {
+    paused: AtomicBool,
+    stop: AtomicBool,
+    device_lost: AtomicBool,
+}
+
+/// Sends audio from the ring buffer until the stream is closed. A new ring buffer is sent through
+/// `swaps` when the stream is reset.
+fn run_sender(
+    mut transport: Transport,
+    config: NetworkConfig,
+    mut consumer: Consumer<i32>,
+    swaps: Receiver<Consumer<i32>>,
+    shared: Arc<Shared>,
+) {
+    let channels = config.channels as usize;
+    let bits = config.bits_per_sample();
+    let mut samples = vec![0; transport.packet_frames(&config) * channels];
+    let mut filled = 0;
+    let mut bytes = Vec::new();
+    let mut pacer = Pacer::new(config.sample_rate);
+
+    while !shared.stop.load(Ordering::Relaxed) {
+        if let Ok(new) = swaps.try_recv() {
+            consumer = new;
+            filled = 0;
+            pacer.restart();
+        }
+
+        if shared.paused.load(Ordering::Relaxed) {
+            thread::sleep(IDLE_INTERVAL);
+            pacer.restart();
+            continue;
+        }
+
+        match consumer.read(&mut samples[filled..]) {
+            Ok(read) => filled += read,
+            Err(_) => {
+                // Ran out of audio, so the clock starts over once there is more
+                thread::sleep(IDLE_INTERVAL);
+                pacer.restart();
+                continue;
+            }
+        }
+
+        if filled < samples.len() {
+            continue;
+        }
+        filled = 0;
+
+        let frames = samples.len() / channels;
+        if matches!(transport, Transport::Rtp(_)) {
+            pacer.wait(frames);
+        }
+
+        transport.encode(&samples, bits, &mut bytes);
+        if let Err(e) = transport.send(&bytes, frames) {
+            // writes are cut short on purpose when the stream is closed
+            if !shared.stop.load(Ordering::Relaxed) {
+                warn!("Failed to send audio: {e}");
+                shared.device_lost.store(true, Ordering::Relaxed);
+            }
+            break;
+        }
+    }
+
+    debug!("Network sender stopped");
+}
+
+/// A device provider streaming audio over the network. See the module documentation for how it
+/// is configured.
+pub struct NetworkProvider {
+    config: Option<NetworkConfig>,
+}
+
+impl NetworkProvider {
+    /// Creates a provider configured from the environment when it is initialized.
+    pub fn new() -> Self {
+        Self::with_config(None)
+    }
+
+    /// Creates a provider with the given configuration, or configured from the environment if
+    /// there is none.
+    pub fn with_config(config: Option<NetworkConfig>) -> Self {
+        Self { config }
+    }
+
+    fn device(&self, sink: Sink) -> Result<NetworkDevice, FindError> {
+        let config = self
+            .config
+            .clone()
+            .ok_or_else(|| FindError::Unknown("provider not initialized".to_string()))?;
+
+        Ok(NetworkDevice { sink, config })
+    }
+
+    fn sinks() -> impl Iterator<Item = Sink> {
+        // pipes are only available on Unix-like systems
+        Sink::ALL
+            .into_iter()
+            .filter(|&sink| cfg!(unix) || sink != Sink::SnapcastPipe)
+    }
+}
+
+impl DeviceProvider for NetworkProvider {
+    fn initialize(&mut self) -> Result<(), InitializationError> {
+        let config = match self.config.take() {
+            Some(config) => config,
+            None => NetworkConfig::from_env()
+                .map_err(|e| InitializationError::Unknown(e.to_string()))?,
+        };
+        config
+            .validate()
+            .map_err(|e| InitializationError::Unknown(e.to_string()))?;
+
+        info!(
+            "NetworkProvider initialized, streaming {:?} {}Hz {}ch",
+            config.sample_type, config.sample_rate, config.channels
+        );
+
+        self.config = Some(config);
+        Ok(())
+    }
+
+    fn get_devices(&mut self) -> Result<Vec<Box<dyn Device>>, ListError> {
+        Self::sinks()
+            .map(|sink| {
+                self.device(sink)
+                    .map(|device| Box::new(device) as Box<dyn Device>)
+                    .map_err(|e| ListError::Unknown(e.to_string()))
+            })
+            .collect()
+    }
+
+    fn get_default_device(&mut self) -> Result<Box<dyn Device>, FindError> {
+        Ok(Box::new(self.device(Sink::Rtp)?) as Box<dyn Device>)
+    }
+
+    fn get_device_by_uid(&mut self, id: &str) -> Result<Box<dyn Device>, FindError> {
+        match Sink::from_uid(id) {
+            Some(sink) if Self::sinks().any(|s| s == sink) => {
+                Ok(Box::new(self.device(sink)?) as Box<dyn Device>)
+            }
+            _ => Err(FindError::DeviceDoesNotExist),
+        }
+    }
+}
+
+pub struct NetworkDevice {
+    sink: Sink,
+    config: NetworkConfig,
+}
+
+impl Device for NetworkDevice {
+    fn open_device(&mut self, format: FormatInfo) -> Result<Box<dyn OutputStream>, OpenError> {
+        if format.originating_provider != PROVIDER {
+            return Err(OpenError::InvalidConfigProvider);
+        }
+        if format.sample_type != self.config.sample_type
+            || format.sample_rate != self.config.sample_rate
+            || format.channels.count() != self.config.channels
+        {
+            return Err(OpenError::InvalidSampleFormat);
+        }
+
+        debug!(
+            "Connecting to {:?} at {}",
+            self.sink,
+            self.config.target(self.sink)
+        );
+        let shared = Arc::new(Shared::default());
+        let (transport, connection) = Transport::connect(self.sink, &self.config, &shared)?;
+
+        let buffer_size =
+            BUFFER_MS * self.config.sample_rate as usize / 1000 * self.config.channels as usize;
+        let ring: SpscRb<i32> = SpscRb::new(buffer_size);
+        let (swap_sender, swaps) = mpsc::channel();
+
+        let sender = {
+            let config = self.config.clone();
+            let consumer = ring.consumer();
+            let shared = shared.clone();
+            thread::Builder::new()
+                .name("network-sender".to_string())
+                .spawn(move || run_sender(transport, config, consumer, swaps, shared))?
+        };
+
+        Ok(Box::new(NetworkStream {
+            sample_type: self.config.sample_type,
+            buffer_size,
+            ring_buf: ring.producer(),
+            swap_sender,
+            shared,
+            sender: Some(sender),
+            connection,
+            ramp: GainRamp::new(self.config.sample_rate),
+            volume: 1.0,
+            replaygain: 1.0,
+            samples: Vec::new(),
+        }) as Box<dyn OutputStream>)
+    }
+
+    fn get_supported_formats(&self) -> Result<Vec<SupportedFormat>, InfoError> {
+        Ok(vec![SupportedFormat {
+            originating_provider: PROVIDER,
+            sample_type: self.config.sample_type,
+            sample_rates: (self.config.sample_rate, self.config.sample_rate),
+            buffer_size: BufferSize::Fixed(BUFFER_SIZE),
+            channels: ChannelSpec::Count(self.config.channels),
+        }])
+    }
+
+    fn get_default_format(&self) -> Result<FormatInfo, InfoError> {
+        Ok(self.config.format())
+    }
+
+    fn get_name(&self) -> Result<String, InfoError> {
+        let kind = match self.sink {
+            Sink::Rtp => "RTP",
+            Sink::SnapcastTcp | Sink::SnapcastPipe => "Snapcast",
+        };
+        Ok(format!("{kind} ({})", self.config.target(self.sink)))
+    }
+
+    fn get_uid(&self) -> Result<String, InfoError> {
+        Ok(self.sink.uid().to_string())
+    }
+
+    fn requires_matching_format(&self) -> bool {
+        true
+    }
+}
+
+pub struct NetworkStream {
+    sample_type: SampleFormat,
+    buffer_size: usize,
+    ring_buf: Producer<i32>,
+    /// Hands a new ring buffer to the sending thread when the stream is reset.
+    swap_sender: Sender<Consumer<i32>>,
+    shared: Arc<Shared>,
+    sender: Option<JoinHandle<()>>,
+    /// The Snapcast TCP connection, shut down when the stream is closed so that the sending
+    /// thread doesn't have to wait for a write to time out.
+    connection: Option<TcpStream>,
+    ramp: GainRamp,
+    volume: f64,
+    replaygain: f64,
+    samples: Vec<i32>,
+}
+
+impl NetworkStream {
+    fn stop_sender(&mut self) {
+        self.shared.stop.store(true, Ordering::Relaxed);
+        if let Some(connection) = self.connection.take() {
+            connection.shutdown(Shutdown::Both).ok();
+        }
+        if let Some(sender) = self.sender.take()
+            && sender.join().is_err()
+        {
+            warn!("Network sender panicked");
+        }
+    }
+}
+
+impl OutputStream for NetworkStream {
+    fn close_stream(&mut self) -> Result<(), CloseError> {
+        self.stop_sender();
+        Ok(())
+    }
+
+    fn needs_input(&self) -> bool {
+        true
+    }
+
+    fn play(&mut self) -> Result<(), StateError> {
+        self.shared.paused.store(false, Ordering::Relaxed);
+        Ok(())
+    }
+
+    fn pause(&mut self) -> Result<(), StateError> {
+        self.shared.paused.store(true, Ordering::Relaxed);
+        Ok(())
+    }
+
+    fn reset(&mut self) -> Result<(), ResetError> {
+        // Audio that hasn't been sent yet is dropped by giving the sender a new ring buffer
+        let ring: SpscRb<i32> = SpscRb::new(self.buffer_size);
+        self.swap_sender
+            .send(ring.consumer())
+            .map_err(|_| ResetError::Unknown("network sender stopped".to_string()))?;
+        self.ring_buf = ring.producer();
+
+        Ok(())
+    }
+
+    fn set_volume(&mut self, volume: f64) -> Result<(), StateError> {
+        self.volume = volume;
+        Ok(())
+    }
+
+    fn set_replaygain(&mut self, gain: f64) -> Result<(), StateError> {
+        self.replaygain = gain;
+        Ok(())
+    }
+
+    fn consume_from(
+        &mut self,
+        input: &mut ChannelConsumers<f64>,
+    ) -> Result<usize, SubmissionError> {
+        if self.shared.device_lost.load(Ordering::Relaxed) {
+            return Err(SubmissionError::DeviceLost);
+        }
+
+        let available = input.potentially_available();
+        if available == 0 {
+            return Ok(0);
+        }
+
+        let read = input.try_read_to_staging(available);
+        if read == 0 {
+            return Ok(0);
+        }
+
+        let staging = input.staging();
+        self.samples.clear();
+        self.samples.reserve(read * staging.len());
+
+        for i in 0..read {
+            let gain = self.ramp.next_frame(self.volume) * self.replaygain;
+            for channel in staging {
+                let sample = (channel[i] * gain).clamp(-1.0, 1.0);
+                self.samples.push(match self.sample_type {
+                    SampleFormat::Signed24 => I24::sample_from(sample).into(),
+                    _ => i16::sample_from(sample).into(),
+                });
+            }
+        }
+
+        write_to_ring(&self.ring_buf, &self.samples, &self.shared.device_lost)?;
+
+        Ok(read)
+    }
+}
+
+impl Drop for NetworkStream {
+    fn drop(&mut self) {
+        self.stop_sender();
+    }
+}
+
+make_unknown_error!(io::Error, OpenError);
+
+#[cfg(test)]
+mod tests {
+    use std::{io::Read, net::TcpListener};
+
+    use super::*;
+    use crate::media::pipeline::ChannelBuffers;
+
+    fn open(sink: Sink, config: NetworkConfig) -> Box<dyn OutputStream> {
+        let mut provider = NetworkProvider::with_config(Some(config));
+        provider.initialize().unwrap();
+        let mut device = provider.get_device_by_uid(sink.uid()).unwrap();
+        let format = device.get_default_format().unwrap();
+        let mut stream = device.open_device(format).unwrap();
+        stream.play().unwrap();
+        stream
+    }
+
+    /// Submit `frames` frames of a ramp through the stream, with the volume ramp already settled.
+    fn submit(stream: &mut Box<dyn OutputStream>, frames: usize) -> Vec<f64> {
+        let samples: Vec<f64> = (0..frames).map(|i| i as f64 / 32767.0).collect();
+        let (producers, mut consumers) = ChannelBuffers::<f64>::new(2, frames).split();
+        producers.write_slices(&[&samples, &samples]);
+        while stream.consume_from(&mut consumers).unwrap() > 0 {}
+        samples
+    }
+
+    #[test]
+    fn sends_rtp_packets() {
+        let receiver = UdpSocket::bind("127.0.0.1:0").unwrap();
+        receiver
+            .set_read_timeout(Some(Duration::from_secs(5)))
+            .unwrap();
+        let config = NetworkConfig {
+            rtp_address: receiver.local_addr().unwrap().to_string(),
+            ..NetworkConfig::default()
+        };
+
+        let mut stream = open(Sink::Rtp, config);
+        submit(&mut stream, 4800);
+
+        let mut packet = [0; 2048];
+        let mut previous: Option<(u16, u32)> = None;
+        let mut last_sample = None;
+        for _ in 0..20 {
+            let len = receiver.recv(&mut packet).unwrap();
+            assert_eq!(len, 12 + 48 * 4);
+            assert_eq!(packet[0], 0x80);
+            assert_eq!(packet[1], DYNAMIC_PAYLOAD_TYPE);
+
+            let sequence = u16::from_be_bytes([packet[2], packet[3]]);
+            let timestamp = u32::from_be_bytes([packet[4], packet[5], packet[6], packet[7]]);
+            if let Some((prev_sequence, prev_timestamp)) = previous {
+                assert_eq!(sequence, prev_sequence.wrapping_add(1));
+                assert_eq!(timestamp, prev_timestamp.wrapping_add(48));
+            }
+            previous = Some((sequence, timestamp));
+
+            // both channels carry the same big endian sample
+            let left = i16::from_be_bytes([packet[12], packet[13]]);
+            let right = i16::from_be_bytes([packet[14], packet[15]]);
+            assert_eq!(left, right);
+            last_sample = Some(left);
+        }
+
+        // the volume ramp has settled and the samples count up by one per frame
+        let last = last_sample.unwrap();
+        let len = receiver.recv(&mut packet).unwrap();
+        let next = i16::from_be_bytes([packet[12], packet[13]]);
+        assert_eq!(len, 12 + 48 * 4);
+        assert_eq!(next, last + 48);
+
+        stream.close_stream().unwrap();
+    }
+
+    #[test]
+    fn writes_raw_pcm_to_snapcast() {
+        let listener = TcpListener::bind("127.0.0.1:0").unwrap();
+        let config = NetworkConfig {
+            snapcast_address: listener.local_addr().unwrap().to_string(),
+            sample_type: SampleFormat::Signed24,
+            ..NetworkConfig::default()
+        };
+
+        let mut stream = open(Sink::SnapcastTcp, config);
+        let (mut connection, _) = listener.accept().unwrap();
+        let samples = submit(&mut stream, 4800);
+
+        let mut bytes = vec![0; 4800 * 2 * 4];
+        connection.read_exact(&mut bytes).unwrap();
+        stream.close_stream().unwrap();
+
+        // once the volume ramp has settled, samples arrive as 24 bit values in 4 bytes
+        for (frame, sample) in bytes.chunks(8).zip(&samples).skip(1000) {
+            let left = i32::from_le_bytes(frame[..4].try_into().unwrap());
+            let right = i32::from_le_bytes(frame[4..].try_into().unwrap());
+            assert_eq!(left, right);
+            assert_eq!(left, i32::from(I24::sample_from(*sample)));
+        }
+    }
+
+    #[cfg(unix)]
+    #[test]
+    fn reports_a_stalled_pipe_as_lost() {
+        use std::{ffi::CString, os::unix::ffi::OsStrExt};
+
+        let dir = crate::test_support::TestDir::new("hummingbird-snapfifo");
+        let path = dir.join("snapfifo");
+        let c_path = CString::new(path.as_os_str().as_bytes()).unwrap();
+        assert_eq!(unsafe { libc::mkfifo(c_path.as_ptr(), 0o600) }, 0);
+
+        let config = NetworkConfig {
+            snapcast_pipe: path,
+            ..NetworkConfig::default()
+        };
+        let mut stream = open(Sink::SnapcastPipe, config);
+
+        // nothing reads from the pipe, so it fills up and the writes time out
+        let samples = vec![0.0; 4800];
+        let deadline = Instant::now() + WRITE_TIMEOUT * 5;
+        let result = loop {
+            let (producers, mut consumers) = ChannelBuffers::<f64>::new(2, 4800).split();
+            producers.write_slices(&[&samples, &samples]);
+            match stream.consume_from(&mut consumers) {
+                Ok(_) if Instant::now() < deadline => {}
+                result => break result,
+            }
+        };
+        assert!(matches!(result, Err(SubmissionError::DeviceLost)));
+
+        let closing = Instant::now();
+        stream.close_stream().unwrap();
+        assert!(closing.elapsed() < WRITE_TIMEOUT);
+    }
+
+    #[test]
+    fn describes_the_stream_for_receivers() {
+        let config = NetworkConfig {
+            rtp_address: "239.255.77.77:5004".to_string(),
+            sample_rate: 44100,
+            ..NetworkConfig::default()
+        };
+        let sender = RtpSender::connect(resolve(&config.rtp_address).unwrap(), &config).unwrap();
+        let sdp = sender.sdp(&config);
+
+        assert!(sdp.contains("c=IN IP4 239.255.77.77/1\r\n"));
+        assert!(sdp.contains("m=audio 5004 RTP/AVP 10\r\n"));
+        assert!(sdp.contains("a=rtpmap:10 L16/44100/2\r\n"));
+    }
+}
@@ -0,0 +1,920 @@
+//! Native PipeWire device provider.
+//!
+//! PipeWire objects can only be used from the thread running their main loop, so a dedicated
+//! thread owns the connection, the registry and every stream. The provider, its devices and its
+//! streams talk to that thread over channels, and audio is passed through a ring buffer that the
+//! stream's process callback reads from.
+
+use std::{
+    cell::RefCell,
+    collections::HashMap,
+    io::Cursor,
+    rc::Rc,
+    sync::{
+        Arc, Mutex,
+        atomic::{AtomicBool, AtomicU64, Ordering},
+        mpsc,
+    },
+    thread::JoinHandle,
+    time::Duration,
+};
+
+use pipewire as pw;
+use pw::{
+    metadata::{Metadata, MetadataListener},
+    properties::properties,
+    spa,
+    stream::{Stream, StreamFlags, StreamListener, StreamState},
+    types::ObjectType,
+};
+use rb::{Consumer, Producer, RB, RbConsumer, RbInspector, SpscRb};
+use tracing::{debug, warn};
+
+use crate::{
+    devices::{
+        errors::{
+            CloseError, FindError, InfoError, InitializationError, ListError, OpenError,
+            ResetError, StateError, SubmissionError,
+        },
+        format::{BufferSize, ChannelSpec, FormatInfo, SampleFormat, SupportedFormat},
+        traits::{Device, DeviceChange, DeviceProvider, OutputStream},
+        util::{AtomicF64, GainRamp, write_to_ring},
+    },
+    media::pipeline::ChannelConsumers,
+};
+
+/// Delay between requesting a fade-out and pausing the stream, see the CPAL provider.
+const PAUSE_FADE_WAIT: Duration = Duration::from_millis(50);
+
+/// How long to wait for the PipeWire thread to connect, or to open a stream.
+const REPLY_TIMEOUT: Duration = Duration::from_secs(5);
+
+/// PipeWire converts to the sink's rate itself, so streams are opened at a common rate unless a
+/// track asks for something else.
+const DEFAULT_SAMPLE_RATE: u32 = 48000;
+
+/// The metadata key holding the default sink, as JSON (`{"name":"<node.name>"}`).
+const DEFAULT_SINK_KEY: &str = "default.audio.sink";
+
+const APP_NAME: &str = "Hummingbird";
+
+/// An audio sink known to PipeWire. Sinks are identified by their `node.name`, which stays the
+/// same across reconnects, unlike their object ID.
+#[derive(Debug, Clone)]
+struct Sink {
+    id: u32,
+    name: String,
+    description: String,
+}
+
+#[derive(Debug, Default)]
+struct SinkState {
+    sinks: Vec<Sink>,
+    default: Option<String>,
+}
+
+impl SinkState {
+    fn description_of(&self, name: &str) -> Option<&str> {
+        self.sinks
+            .iter()
+            .find(|sink| sink.name == name)
+            .map(|sink| sink.description.as_str())
+    }
+}
+
+/// Requests sent to the PipeWire thread.
+enum Command {
+    Open {
+        key: u64,
+        target: Option<String>,
+        format: FormatInfo,
+        consumer: Consumer<f32>,
+        target_gain: Arc<AtomicF64>,
+        device_lost: Arc<AtomicBool>,
+        reply: mpsc::Sender<Result<(), String>>,
+    },
+    SetActive {
+        key: u64,
+        active: bool,
+    },
+    SetMediaName {
+        key: u64,
+        name: String,
+    },
+    Close {
+        key: u64,
+    },
+    Quit,
+}
+
+/// The state owned by a stream's process callback.
+struct ProcessData {
+    consumer: Consumer<f32>,
+    channels: usize,
+    scratch: Vec<f32>,
+    ramp: GainRamp,
+    target_gain: Arc<AtomicF64>,
+}
+
+struct OpenStream {
+    stream: Stream,
+    _listener: StreamListener<ProcessData>,
+}
+
+/// The handle to the PipeWire thread shared by the provider, its devices and its streams.
+#[derive(Clone)]
+struct Connection {
+    commands: pw::channel::Sender<Command>,
+    sinks: Arc<Mutex<SinkState>>,
+    next_key: Arc<AtomicU64>,
+}
+
+impl Connection {
+    fn send(&self, command: Command) {
+        if self.commands.send(command).is_err() {
+            warn!("PipeWire thread is no longer running");
+        }
+    }
+}
+
+pub struct PipeWireProvider {
+    connection: Connection,
+    changes: mpsc::Receiver<DeviceChange>,
+    thread: Option<JoinHandle<()>>,
+}
+
+impl PipeWireProvider {
+    /// Connect to the PipeWire daemon. Fails if it isn't running.
+    pub fn connect() -> Result<Self, InitializationError> {
+        let (commands, command_rx) = pw::channel::channel();
+        let (changes_tx, changes) = mpsc::channel();
+        let (ready_tx, ready_rx) = mpsc::channel();
+        let sinks = Arc::new(Mutex::new(SinkState::default()));
+
+        let thread_sinks = sinks.clone();
+        let thread = std::thread::Builder::new()
+            .name("pipewire".to_string())
+            .spawn(move || run(command_rx, thread_sinks, changes_tx, ready_tx))
+            .map_err(|e| InitializationError::Unknown(e.to_string()))?;
+
+        match ready_rx.recv_timeout(REPLY_TIMEOUT) {
+            Ok(Ok(())) => {}
+            Ok(Err(e)) => return Err(InitializationError::Unknown(e)),
+            Err(_) => {
+                return Err(InitializationError::Unknown(
+                    "timed out connecting to PipeWire".to_string(),
+                ));
+            }
+        }
+
+        Ok(Self {
+            connection: Connection {
+                commands,
+                sinks,
+                next_key: Arc::new(AtomicU64::new(0)),
+            },
+            changes,
+            thread: Some(thread),
+        })
+    }
+
+    fn device(&self, target: Option<String>) -> Box<dyn Device> {
+        Box::new(PipeWireDevice {
+            connection: self.connection.clone(),
+            target,
+        })
+    }
+}
+
+impl Drop for PipeWireProvider {
+    fn drop(&mut self) {
+        self.connection.send(Command::Quit);
+
+        if let Some(thread) = self.thread.take()
+            && thread.join().is_err()
+        {
+            warn!("PipeWire thread panicked");
+        }
+    }
+}
+
+impl DeviceProvider for PipeWireProvider {
+    fn initialize(&mut self) -> Result<(), InitializationError> {
+        Ok(())
+    }
+
+    fn get_devices(&mut self) -> Result<Vec<Box<dyn Device>>, ListError> {
+        let names: Vec<String> = {
+            let state = self.connection.sinks.lock().unwrap();
+            state.sinks.iter().map(|sink| sink.name.clone()).collect()
+        };
+
+        Ok(names
+            .into_iter()
+            .map(|name| self.device(Some(name)))
+            .collect())
+    }
+
+    fn get_default_device(&mut self) -> Result<Box<dyn Device>, FindError> {
+        Ok(self.device(None))
+    }
+
+    fn get_device_by_uid(&mut self, id: &str) -> Result<Box<dyn Device>, FindError> {
+        let exists = {
+            let state = self.connection.sinks.lock().unwrap();
+            state.sinks.iter().any(|sink| sink.name == id)
+        };
+
+        if exists {
+            Ok(self.device(Some(id.to_string())))
+        } else {
+            Err(FindError::DeviceDoesNotExist)
+        }
+    }
+
+    fn poll_device_changes(&mut self) -> Vec<DeviceChange> {
+        self.changes.try_iter().collect()
+    }
+}
+
+/// A PipeWire sink, or the default sink if `target` is `None`. Streams on the default sink are
+/// moved by the session manager when the default changes.
+struct PipeWireDevice {
+    connection: Connection,
+    target: Option<String>,
+}
+
+impl PipeWireDevice {
+    /// The `node.name` of the sink, resolving the default sink.
+    fn sink_name(&self) -> Option<String> {
+        self.target.clone().or_else(|| {
+            let state = self.connection.sinks.lock().unwrap();
+            state.default.clone()
+        })
+    }
+}
+
+impl Device for PipeWireDevice {
+    fn open_device(&mut self, format: FormatInfo) -> Result<Box<dyn OutputStream>, OpenError> {
+        if format.originating_provider != "pipewire" {
+            return Err(OpenError::InvalidConfigProvider);
+        }
+        if format.sample_type != SampleFormat::Float32 {
+            return Err(OpenError::InvalidSampleFormat);
+        }
+
+        let channels = format.channels.count();
+        let buffer_size = ((200 * format.sample_rate as usize) / 1000) * channels as usize;
+        let target_gain = Arc::new(AtomicF64::new(1.0));
+        let device_lost = Arc::new(AtomicBool::new(false));
+
+        let (key, ring) = open_stream(
+            &self.connection,
+            self.target.clone(),
+            format,
+            buffer_size,
+            target_gain.clone(),
+            device_lost.clone(),
+        )?;
+
+        Ok(Box::new(PipeWireStream {
+            connection: self.connection.clone(),
+            key,
+            target: self.target.clone(),
+            ring_buf: ring.producer(),
+            ring,
+            format,
+            buffer_size,
+            target_gain,
+            device_lost,
+            last_user_volume: 1.0,
+            replaygain: 1.0,
+            interleave_buffer: Vec::with_capacity(buffer_size),
+        }))
+    }
+
+    fn get_supported_formats(&self) -> Result<Vec<SupportedFormat>, InfoError> {
+        Ok((1..=8)
+            .map(|channels| SupportedFormat {
+                originating_provider: "pipewire",
+                sample_type: SampleFormat::Float32,
+                sample_rates: (8000, 384000),
+                buffer_size: BufferSize::Unknown,
+                channels: ChannelSpec::Count(channels),
+            })
+            .collect())
+    }
+
+    fn get_default_format(&self) -> Result<FormatInfo, InfoError> {
+        Ok(FormatInfo {
+            originating_provider: "pipewire",
+            sample_type: SampleFormat::Float32,
+            sample_rate: DEFAULT_SAMPLE_RATE,
+            buffer_size: BufferSize::Unknown,
+            channels: ChannelSpec::Count(2),
+        })
+    }
+
+    fn get_name(&self) -> Result<String, InfoError> {
+        let name = self.sink_name().ok_or(InfoError::None)?;
+        let state = self.connection.sinks.lock().unwrap();
+
+        Ok(state.description_of(&name).unwrap_or(&name).to_string())
+    }
+
+    fn get_uid(&self) -> Result<String, InfoError> {
+        self.sink_name().ok_or(InfoError::None)
+    }
+
+    fn requires_matching_format(&self) -> bool {
+        false
+    }
+}
+
+/// Ask the PipeWire thread to open a stream, returning its key and the ring buffer feeding it.
+fn open_stream(
+    connection: &Connection,
+    target: Option<String>,
+    format: FormatInfo,
+    buffer_size: usize,
+    target_gain: Arc<AtomicF64>,
+    device_lost: Arc<AtomicBool>,
+) -> Result<(u64, SpscRb<f32>), OpenError> {
+    let rb: SpscRb<f32> = SpscRb::new(buffer_size);
+    let key = connection.next_key.fetch_add(1, Ordering::Relaxed);
+    let (reply, reply_rx) = mpsc::channel();
+
+    connection.send(Command::Open {
+        key,
+        target,
+        format,
+        consumer: rb.consumer(),
+        target_gain,
+        device_lost,
+        reply,
+    });
+
+    match reply_rx.recv_timeout(REPLY_TIMEOUT) {
+        Ok(Ok(())) => Ok((key, rb)),
+        Ok(Err(e)) => Err(OpenError::Unknown(e)),
+        Err(_) => Err(OpenError::Unknown(
+            "timed out opening PipeWire stream".to_string(),
+        )),
+    }
+}
+
+struct PipeWireStream {
+    connection: Connection,
+    key: u64,
+    target: Option<String>,
+    ring_buf: Producer<f32>,
+    /// The ring buffer `ring_buf` writes to, kept to see how much of it hasn't been played yet.
+    ring: SpscRb<f32>,
+    format: FormatInfo,
+    buffer_size: usize,
+    target_gain: Arc<AtomicF64>,
+    /// Set when the stream errors or is disconnected from its sink.
+    device_lost: Arc<AtomicBool>,
+    /// See `CpalStream::last_user_volume`.
+    last_user_volume: f64,
+    replaygain: f64,
+    interleave_buffer: Vec<f32>,
+}
+
+impl Drop for PipeWireStream {
+    fn drop(&mut self) {
+        self.connection.send(Command::Close { key: self.key });
+    }
+}
+
+impl OutputStream for PipeWireStream {
+    fn close_stream(&mut self) -> Result<(), CloseError> {
+        // the stream is destroyed on the PipeWire thread when this is dropped
+        Ok(())
+    }
+
+    fn needs_input(&self) -> bool {
+        true
+    }
+
+    fn play(&mut self) -> Result<(), StateError> {
+        self.target_gain
+            .store(self.last_user_volume, Ordering::Relaxed);
+        self.connection.send(Command::SetActive {
+            key: self.key,
+            active: true,
+        });
+        Ok(())
+    }
+
+    fn pause(&mut self) -> Result<(), StateError> {
+        self.target_gain.store(0.0, Ordering::Relaxed);
+        std::thread::sleep(PAUSE_FADE_WAIT);
+        self.connection.send(Command::SetActive {
+            key: self.key,
+            active: false,
+        });
+        Ok(())
+    }
+
+    fn reset(&mut self) -> Result<(), ResetError> {
+        // the ring buffer is read on the PipeWire thread, so the only safe way to drop what's in
+        // it is to start over with a new stream
+        let (key, ring) = open_stream(
+            &self.connection,
+            self.target.clone(),
+            self.format,
+            self.buffer_size,
+            self.target_gain.clone(),
+            self.device_lost.clone(),
+        )?;
+
+        self.connection.send(Command::Close { key: self.key });
+        self.key = key;
+        self.ring_buf = ring.producer();
+        self.ring = ring;
+        self.interleave_buffer.clear();
+
+        Ok(())
+    }
+
+    fn set_volume(&mut self, volume: f64) -> Result<(), StateError> {
+        self.last_user_volume = volume;
+        self.target_gain.store(volume, Ordering::Relaxed);
+        Ok(())
+    }
+
+    fn set_replaygain(&mut self, gain: f64) -> Result<(), StateError> {
+        self.replaygain = gain;
+        Ok(())
+    }
+
+    fn set_media_name(&mut self, name: &str) -> Result<(), StateError> {
+        self.connection.send(Command::SetMediaName {
+            key: self.key,
+            name: name.to_string(),
+        });
+        Ok(())
+    }
+
+    fn buffered_frames(&self) -> usize {
+        self.ring.count() / self.format.channels.count() as usize
+    }
+
+    #[allow(clippy::needless_range_loop)]
+    fn consume_from(
+        &mut self,
+        input: &mut ChannelConsumers<f64>,
+    ) -> Result<usize, SubmissionError> {
+        let available = input.potentially_available();
+        if available == 0 {
+            return Ok(0);
+        }
+
+        let read = input.try_read_to_staging(available);
+        if read == 0 {
+            return Ok(0);
+        }
+
+        let staging = input.staging();
+        let channel_count = staging.len();
+        let rg = self.replaygain;
+
+        self.interleave_buffer.clear();
+        self.interleave_buffer.reserve(read * channel_count);
+
+        for i in 0..read {
+            for ch in 0..channel_count {
+                self.interleave_buffer.push((staging[ch][i] * rg) as f32);
+            }
+        }
+
+        write_to_ring(&self.ring_buf, &self.interleave_buffer, &self.device_lost)?;
+
+        Ok(read)
+    }
+
+    #[allow(clippy::needless_range_loop)]
+    fn consume_from_f32(
+        &mut self,
+        input: &mut ChannelConsumers<f32>,
+    ) -> Option<Result<usize, SubmissionError>> {
+        let available = input.potentially_available();
+        if available == 0 {
+            return Some(Ok(0));
+        }
+
+        let read = input.try_read_to_staging(available);
+        if read == 0 {
+            return Some(Ok(0));
+        }
+
+        let staging = input.staging();
+        let channel_count = staging.len();
+        let rg = self.replaygain as f32;
+
+        self.interleave_buffer.clear();
+        self.interleave_buffer.reserve(read * channel_count);
+
+        for i in 0..read {
+            for ch in 0..channel_count {
+                self.interleave_buffer.push(staging[ch][i] * rg);
+            }
+        }
+
+        Some(
+            write_to_ring(&self.ring_buf, &self.interleave_buffer, &self.device_lost).map(|_| read),
+        )
+    }
+}
+
+/// Parse the sink name out of the default sink metadata value.
+fn parse_default_sink(value: &str) -> Option<String> {
+    serde_json::from_str::<serde_json::Value>(value)
+        .ok()?
+        .get("name")?
+        .as_str()
+        .map(str::to_string)
+}
+
+/// The body of the PipeWire thread.
+fn run(
+    commands: pw::channel::Receiver<Command>,
+    sinks: Arc<Mutex<SinkState>>,
+    changes: mpsc::Sender<DeviceChange>,
+    ready: mpsc::Sender<Result<(), String>>,
+) {
+    pw::init();
+
+    let connected = (|| {
+        let mainloop = pw::main_loop::MainLoop::new(None)?;
+        let context = pw::context::Context::new(&mainloop)?;
+        let core = context.connect(None)?;
+        let registry = Rc::new(core.get_registry()?);
+        Ok::<_, pw::Error>((mainloop, context, core, registry))
+    })();
+
+    let (mainloop, _context, core, registry) = match connected {
+        Ok(connected) => connected,
+        Err(e) => {
+            ready.send(Err(e.to_string())).ok();
+            return;
+        }
+    };
+
+    // changes are only reported once the initial list of sinks is known
+    let synced = Rc::new(RefCell::new(false));
+    let default_metadata: Rc<RefCell<Option<(Metadata, MetadataListener)>>> =
+        Rc::new(RefCell::new(None));
+
+    let _registry_listener = registry
+        .add_listener_local()
+        .global({
+            let registry = Rc::downgrade(&registry);
+            let sinks = sinks.clone();
+            let changes = changes.clone();
+            let synced = synced.clone();
+            let default_metadata = default_metadata.clone();
+
+            move |global| {
+                let Some(props) = global.props else {
+                    return;
+                };
+
+                match &global.type_ {
+                    ObjectType::Node if props.get("media.class") == Some("Audio/Sink") => {
+                        let Some(name) = props.get("node.name") else {
+                            return;
+                        };
+                        let description = props
+                            .get("node.description")
+                            .or_else(|| props.get("node.nick"))
+                            .unwrap_or(name);
+
+                        sinks.lock().unwrap().sinks.push(Sink {
+                            id: global.id,
+                            name: name.to_string(),
+                            description: description.to_string(),
+                        });
+
+                        if *synced.borrow() {
+                            changes.send(DeviceChange::Added(name.to_string())).ok();
+                        }
+                    }
+                    ObjectType::Metadata if props.get("metadata.name") == Some("default") => {
+                        let Some(registry) = registry.upgrade() else {
+                            return;
+                        };
+                        let metadata: Metadata = match registry.bind(global) {
+                            Ok(metadata) => metadata,
+                            Err(e) => {
+                                warn!("Failed to bind PipeWire default metadata: {}", e);
+                                return;
+                            }
+                        };
+
+                        let sinks = sinks.clone();
+                        let changes = changes.clone();
+                        let listener = metadata
+                            .add_listener_local()
+                            .property(move |_, key, _, value| {
+                                if key != Some(DEFAULT_SINK_KEY) {
+                                    return 0;
+                                }
+
+                                let default = value.and_then(parse_default_sink);
+                                let mut state = sinks.lock().unwrap();
+                                // the first value is the initial default, not a change
+                                if state.default.is_some()
+                                    && default != state.default
+                                    && let Some(default) = &default
+                                {
+                                    changes
+                                        .send(DeviceChange::DefaultChanged(default.clone()))
+                                        .ok();
+                                }
+                                state.default = default;
+
+                                0
+                            })
+                            .register();
+
+                        *default_metadata.borrow_mut() = Some((metadata, listener));
+                    }
+                    _ => {}
+                }
+            }
+        })
+        .global_remove({
+            let sinks = sinks.clone();
+            let changes = changes.clone();
+
+            move |id| {
+                let mut state = sinks.lock().unwrap();
+                if let Some(idx) = state.sinks.iter().position(|sink| sink.id == id) {
+                    let sink = state.sinks.remove(idx);
+                    changes.send(DeviceChange::Removed(sink.name)).ok();
+                }
+            }
+        })
+        .register();
+
+    let pending = match core.sync(0) {
+        Ok(pending) => pending,
+        Err(e) => {
+            ready.send(Err(e.to_string())).ok();
+            return;
+        }
+    };
+
+    let _core_listener = core
+        .add_listener_local()
+        .done({
+            let synced = synced.clone();
+            move |id, seq| {
+                if id == pw::core::PW_ID_CORE && seq == pending && !*synced.borrow() {
+                    *synced.borrow_mut() = true;
+                    ready.send(Ok(())).ok();
+                }
+            }
+        })
+        .error(|id, seq, res, message| {
+            warn!(id, seq, res, "PipeWire error: {}", message);
+        })
+        .register();
+
+    let streams: Rc<RefCell<HashMap<u64, OpenStream>>> = Rc::new(RefCell::new(HashMap::new()));
+
+    let _command_receiver = commands.attach(mainloop.loop_(), {
+        let mainloop = mainloop.clone();
+        let core = core.clone();
+
+        move |command| match command {
+            Command::Open {
+                key,
+                target,
+                format,
+                consumer,
+                target_gain,
+                device_lost,
+                reply,
+            } => {
+                let result =
+                    create_stream(&core, target, format, consumer, target_gain, device_lost)
+                        .map(|stream| {
+                            streams.borrow_mut().insert(key, stream);
+                        })
+                        .map_err(|e| e.to_string());
+                reply.send(result).ok();
+            }
+            Command::SetActive { key, active } => {
+                if let Some(open) = streams.borrow().get(&key)
+                    && let Err(e) = open.stream.set_active(active)
+                {
+                    warn!("Failed to set PipeWire stream state: {}", e);
+                }
+            }
+            Command::SetMediaName { key, name } => {
+                if let Some(open) = streams.borrow().get(&key) {
+                    let props = properties! {
+                        *pw::keys::MEDIA_NAME => name,
+                    };
+                    // SAFETY: both pointers are valid for the duration of the call, and PipeWire
+                    // copies the properties
+                    unsafe {
+                        pw::sys::pw_stream_update_properties(
+                            open.stream.as_raw_ptr(),
+                            props.dict().as_raw_ptr(),
+                        );
+                    }
+                }
+            }
+            Command::Close { key } => {
+                if let Some(open) = streams.borrow_mut().remove(&key)
+                    && let Err(e) = open.stream.disconnect()
+                {
+                    debug!("Failed to disconnect PipeWire stream: {}", e);
+                }
+            }
+            Command::Quit => mainloop.quit(),
+        }
+    });
+
+    mainloop.run();
+}
+
+/// The SPA channel positions (`enum spa_audio_channel`) of each channel, so that PipeWire can
+/// route them to the right speakers. Without positions, PipeWire maps channels to the sink's
+/// ports in order.
+fn spa_positions(channels: ChannelSpec) -> Option<[u32; 64]> {
+    const MONO: u32 = 2;
+    // positions in the order of the `ChannelLayout` flags
+    const POSITIONS: [u32; 18] = [
+        3,  // FL
+        4,  // FR
+        5,  // FC
+        6,  // LFE
+        12, // RL
+        13, // RR
+        9,  // FLC
+        10, // FRC
+        11, // RC
+        7,  // SL
+        8,  // SR
+        14, // TC
+        15, // TFL
+        16, // TFC
+        17, // TFR
+        18, // TRL
+        19, // TRC
+        20, // TRR
+    ];
+
+    let layout = channels.layout()?;
+    let mut position = [0; 64];
+
+    if layout.count() == 1 {
+        position[0] = MONO;
+        return Some(position);
+    }
+
+    for (channel, flag) in layout.positions().enumerate() {
+        position[channel] = POSITIONS
+            .get(flag.bits().trailing_zeros() as usize)
+            .copied()
+            .unwrap_or(0);
+    }
+
+    Some(position)
+}
+
+/// Create a stream on the PipeWire thread. Streams start inactive, and are activated by
+/// `OutputStream::play`.
+fn create_stream(
+    core: &pw::core::Core,
+    target: Option<String>,
+    format: FormatInfo,
+    consumer: Consumer<f32>,
+    target_gain: Arc<AtomicF64>,
+    device_lost: Arc<AtomicBool>,
+) -> Result<OpenStream, pw::Error> {
+    let channels = format.channels.count() as usize;
+    let quantum = format.sample_rate / 50; // 20 ms
+
+    let mut props = properties! {
+        *pw::keys::MEDIA_TYPE => "Audio",
+        *pw::keys::MEDIA_CATEGORY => "Playback",
+        *pw::keys::MEDIA_ROLE => "Music",
+        *pw::keys::MEDIA_NAME => APP_NAME,
+        *pw::keys::APP_NAME => APP_NAME,
+        *pw::keys::APP_ID => "org.mailliw.hummingbird",
+        *pw::keys::APP_ICON_NAME => "hummingbird",
+        *pw::keys::NODE_NAME => "hummingbird",
+        *pw::keys::NODE_LATENCY => format!("{}/{}", quantum, format.sample_rate),
+    };
+
+    if let Some(target) = target {
+        props.insert("target.object", target);
+        // a disconnected sink is handled by the playback thread, which may want to pause
+        props.insert("node.dont-reconnect", "true");
+    }
+
+    let stream = Stream::new(core, APP_NAME, props)?;
+
+    let data = ProcessData {
+        consumer,
+        channels,
+        scratch: vec![0.0; quantum as usize * channels * 4],
+        ramp: GainRamp::new(format.sample_rate),
+        target_gain,
+    };
+
+    let listener = stream
+        .add_local_listener_with_user_data(data)
+        .state_changed(move |_, _, _, new| {
+            if matches!(new, StreamState::Error(_) | StreamState::Unconnected) {
+                device_lost.store(true, Ordering::Relaxed);
+            }
+        })
+        .process(|stream, data| {
+            let Some(mut buffer) = stream.dequeue_buffer() else {
+                return;
+            };
+
+            let stride = size_of::<f32>() * data.channels;
+            let datas = buffer.datas_mut();
+            let Some(out) = datas[0].data() else {
+                return;
+            };
+
+            let frames = (out.len() / stride).min(data.scratch.len() / data.channels);
+            let samples = &mut data.scratch[..frames * data.channels];
+            samples.fill(0.0);
+            let _ = data.consumer.read(samples);
+
+            let target = data.target_gain.load(Ordering::Relaxed);
+            data.ramp.apply(samples, data.channels, target);
+
+            for (bytes, sample) in out.chunks_exact_mut(size_of::<f32>()).zip(samples.iter()) {
+                bytes.copy_from_slice(&sample.to_le_bytes());
+            }
+
+            let chunk = datas[0].chunk_mut();
+            *chunk.offset_mut() = 0;
+            *chunk.stride_mut() = stride as i32;
+            *chunk.size_mut() = (stride * frames) as u32;
+        })
+        .register()?;
+
+    let mut audio_info = spa::param::audio::AudioInfoRaw::new();
+    audio_info.set_format(spa::param::audio::AudioFormat::F32LE);
+    audio_info.set_rate(format.sample_rate);
+    audio_info.set_channels(channels as u32);
+    if let Some(position) = spa_positions(format.channels) {
+        audio_info.set_position(position);
+    }
+
+    let values: Vec<u8> = spa::pod::serialize::PodSerializer::serialize(
+        Cursor::new(Vec::new()),
+        &spa::pod::Value::Object(spa::pod::Object {
+            type_: spa::utils::SpaTypes::ObjectParamFormat.as_raw(),
+            id: spa::param::ParamType::EnumFormat.as_raw(),
+            properties: audio_info.into(),
+        }),
+    )
+    .map_err(|_| pw::Error::CreationFailed)?
+    .0
+    .into_inner();
+
+    let mut params = [spa::pod::Pod::from_bytes(&values).ok_or(pw::Error::CreationFailed)?];
+
+    stream.connect(
+        spa::utils::Direction::Output,
+        None,
+        StreamFlags::AUTOCONNECT
+            | StreamFlags::MAP_BUFFERS
+            | StreamFlags::RT_PROCESS
+            | StreamFlags::INACTIVE,
+        &mut params,
+    )?;
+
+    Ok(OpenStream {
+        stream,
+        _listener: listener,
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn parses_default_sink_metadata() {
+        assert_eq!(
+            parse_default_sink(r#"{"name":"alsa_output.usb-dac.analog-stereo"}"#).as_deref(),
+            Some("alsa_output.usb-dac.analog-stereo")
+        );
+        assert_eq!(parse_default_sink("not json"), None);
+        assert_eq!(parse_default_sink(r#"{"other":1}"#), None);
+    }
+}
@@ -0,0 +1,185 @@
+use intx::I24;
+use rand::{RngExt, SeedableRng, rngs::SmallRng};
+
+use super::format::SampleFormat;
+
+/// Error feedback coefficients for noise shaping, which move quantization noise away from the
+/// frequencies hearing is most sensitive to and towards the top of the spectrum (Wannamaker's
+/// 3-tap filter).
+const SHAPING: [f64; 3] = [1.623, -0.982, 0.109];
+
+/// Adds TPDF dither to audio that is about to be quantized to 16 or 24 bit integers, optionally
+/// with noise shaping.
+///
+/// Devices quantize by scaling samples to the integer range and rounding, after applying any
+/// gain they handle themselves. The dither stage quantizes the audio the same way, so that the
+/// samples it outputs are already on the device's integer grid and the device's rounding is a
+/// no-op.
+pub struct Dither {
+    /// The value of a full scale sample, in LSBs.
+    scale: f64,
+    noise_shaping: bool,
+    /// The most recent quantization errors of each channel, newest first.
+    errors: Vec<[f64; 3]>,
+    rng: SmallRng,
+}
+
+impl Dither {
+    /// Create a dither stage for the given device format, if the format needs one.
+    pub fn new(sample_type: SampleFormat, channels: usize, noise_shaping: bool) -> Option<Self> {
+        let scale = match sample_type {
+            SampleFormat::Signed16 => f64::from(i16::MAX),
+            SampleFormat::Signed24 => f64::from(i32::from(I24::MAX)),
+            _ => return None,
+        };
+
+        Some(Self {
+            scale,
+            noise_shaping,
+            errors: vec![[0.0; 3]; channels],
+            rng: SmallRng::seed_from_u64(rand::rng().random()),
+        })
+    }
+
+    /// Dither and quantize `frames` frames in place. `gain` is the gain the device applies
+    /// before quantizing.
+    pub fn process(&mut self, buffers: &mut [Vec<f64>], frames: usize, gain: f64) {
+        if gain <= 0.0 {
+            return;
+        }
+
+        let step = gain * self.scale;
+
+        for (buffer, errors) in buffers.iter_mut().zip(&mut self.errors) {
+            for sample in &mut buffer[..frames] {
+                let mut target = *sample * step;
+                if self.noise_shaping {
+                    target -= SHAPING
+                        .iter()
+                        .zip(errors.iter())
+                        .map(|(c, e)| c * e)
+                        .sum::<f64>();
+                }
+
+                // The difference of two uniform values has a triangular distribution between -1
+                // and 1 LSB
+                let noise = self.rng.random::<f64>() - self.rng.random::<f64>();
+                let quantized = (target + noise).round().clamp(-self.scale, self.scale);
+
+                if self.noise_shaping {
+                    // Clipped samples would feed back huge errors, so the error is limited to
+                    // what rounding dithered audio can produce
+                    let error = (quantized - target).clamp(-1.5, 1.5);
+                    *errors = [error, errors[0], errors[1]];
+                }
+
+                *sample = quantized / step;
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::f64::consts::PI;
+
+    use super::*;
+    use crate::devices::resample::SampleFrom;
+
+    fn sine(frames: usize) -> Vec<f64> {
+        (0..frames)
+            .map(|i| (2.0 * PI * 1000.0 * i as f64 / 44100.0).sin() * 0.25)
+            .collect()
+    }
+
+    /// Power of the first difference of the error signal, relative to the power of the error
+    /// itself. White noise gives 2.0, and noise shaped towards high frequencies gives more.
+    fn high_frequency_ratio(error: &[f64]) -> f64 {
+        let power: f64 = error.iter().map(|e| e * e).sum();
+        let diff: f64 = error.windows(2).map(|w| (w[1] - w[0]).powi(2)).sum();
+        diff / power
+    }
+
+    #[test]
+    fn ignores_formats_that_are_not_dithered() {
+        assert!(Dither::new(SampleFormat::Float32, 2, false).is_none());
+        assert!(Dither::new(SampleFormat::Signed32, 2, false).is_none());
+        assert!(Dither::new(SampleFormat::Signed16, 2, false).is_some());
+        assert!(Dither::new(SampleFormat::Signed24, 2, false).is_some());
+    }
+
+    #[test]
+    fn output_lands_on_the_device_grid() {
+        for gain in [1.0, 0.5, 0.317] {
+            let mut dither = Dither::new(SampleFormat::Signed16, 1, true).unwrap();
+            let mut buffers = vec![sine(4096)];
+            dither.process(&mut buffers, 4096, gain);
+
+            for sample in &buffers[0] {
+                let scaled = sample * gain * f64::from(i16::MAX);
+                assert!((scaled - scaled.round()).abs() < 1e-6);
+                assert_eq!(i16::sample_from(sample * gain) as f64, scaled.round());
+            }
+        }
+    }
+
+    #[test]
+    fn error_is_small_and_decorrelated() {
+        let mut dither = Dither::new(SampleFormat::Signed16, 1, false).unwrap();
+        let original = sine(44100);
+        let mut buffers = vec![original.clone()];
+        dither.process(&mut buffers, 44100, 1.0);
+
+        let error: Vec<f64> = buffers[0]
+            .iter()
+            .zip(&original)
+            .map(|(d, o)| (d - o) * f64::from(i16::MAX))
+            .collect();
+
+        assert!(error.iter().all(|e| e.abs() <= 1.5));
+        // TPDF dither plus rounding gives an error power of 1/4 LSB²
+        let power = error.iter().map(|e| e * e).sum::<f64>() / error.len() as f64;
+        assert!((power - 0.25).abs() < 0.03, "power = {power}");
+        assert!((high_frequency_ratio(&error) - 2.0).abs() < 0.1);
+    }
+
+    #[test]
+    fn noise_shaping_moves_noise_up() {
+        let mut dither = Dither::new(SampleFormat::Signed16, 1, true).unwrap();
+        let original = sine(44100);
+        let mut buffers = vec![original.clone()];
+        dither.process(&mut buffers, 44100, 1.0);
+
+        let error: Vec<f64> = buffers[0]
+            .iter()
+            .zip(&original)
+            .map(|(d, o)| (d - o) * f64::from(i16::MAX))
+            .collect();
+
+        assert!(high_frequency_ratio(&error) > 3.0);
+    }
+
+    #[test]
+    fn noise_stays_shaped_below_full_volume() {
+        let volume = 0.3;
+        let original = sine(44100);
+
+        // The device scales by its volume and rounds, which should leave the dithered samples
+        // unchanged, so the error it outputs is still the shaped one
+        let device_error = |gain: f64| {
+            let mut dither = Dither::new(SampleFormat::Signed16, 1, true).unwrap();
+            let mut buffers = vec![original.clone()];
+            dither.process(&mut buffers, 44100, gain);
+
+            buffers[0]
+                .iter()
+                .zip(&original)
+                .map(|(d, o)| f64::from(i16::sample_from(d * volume)) - o * volume * 32767.0)
+                .collect::<Vec<_>>()
+        };
+
+        assert!(high_frequency_ratio(&device_error(volume)) > 3.0);
+        // Dithering for full volume gets requantized by the device, which adds white noise
+        assert!(high_frequency_ratio(&device_error(1.0)) < 3.0);
+    }
+}
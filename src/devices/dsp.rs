@@ -0,0 +1,5 @@
+pub mod biquad;
+pub mod chain;
+pub mod effects;
+pub mod equalizer;
+pub mod limiter;
@@ -0,0 +1,170 @@
+use std::f64::consts::PI;
+
+use crate::settings::equalizer::FilterKind;
+
+/// Normalized coefficients for a second-order IIR filter, following the formulas from the RBJ
+/// Audio EQ Cookbook.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct Coefficients {
+    b0: f64,
+    b1: f64,
+    b2: f64,
+    a1: f64,
+    a2: f64,
+}
+
+impl Coefficients {
+    pub fn new(
+        kind: FilterKind,
+        frequency_hz: f64,
+        gain_db: f64,
+        q: f64,
+        sample_rate: u32,
+    ) -> Self {
+        let nyquist = sample_rate as f64 / 2.0;
+        // filters at or above nyquist are unstable, and a Q of zero divides by zero
+        let frequency = frequency_hz.clamp(1.0, nyquist * 0.99);
+        let q = q.max(0.01);
+
+        let a = 10.0_f64.powf(gain_db / 40.0);
+        let w0 = 2.0 * PI * frequency / sample_rate as f64;
+        let (sin, cos) = w0.sin_cos();
+        let alpha = sin / (2.0 * q);
+        let sqrt_a_alpha = 2.0 * a.sqrt() * alpha;
+
+        let (b0, b1, b2, a0, a1, a2) = match kind {
+            FilterKind::Peaking => (
+                1.0 + alpha * a,
+                -2.0 * cos,
+                1.0 - alpha * a,
+                1.0 + alpha / a,
+                -2.0 * cos,
+                1.0 - alpha / a,
+            ),
+            FilterKind::LowShelf => (
+                a * ((a + 1.0) - (a - 1.0) * cos + sqrt_a_alpha),
+                2.0 * a * ((a - 1.0) - (a + 1.0) * cos),
+                a * ((a + 1.0) - (a - 1.0) * cos - sqrt_a_alpha),
+                (a + 1.0) + (a - 1.0) * cos + sqrt_a_alpha,
+                -2.0 * ((a - 1.0) + (a + 1.0) * cos),
+                (a + 1.0) + (a - 1.0) * cos - sqrt_a_alpha,
+            ),
+            FilterKind::HighShelf => (
+                a * ((a + 1.0) + (a - 1.0) * cos + sqrt_a_alpha),
+                -2.0 * a * ((a - 1.0) + (a + 1.0) * cos),
+                a * ((a + 1.0) + (a - 1.0) * cos - sqrt_a_alpha),
+                (a + 1.0) - (a - 1.0) * cos + sqrt_a_alpha,
+                2.0 * ((a - 1.0) - (a + 1.0) * cos),
+                (a + 1.0) - (a - 1.0) * cos - sqrt_a_alpha,
+            ),
+            FilterKind::LowPass => (
+                (1.0 - cos) / 2.0,
+                1.0 - cos,
+                (1.0 - cos) / 2.0,
+                1.0 + alpha,
+                -2.0 * cos,
+                1.0 - alpha,
+            ),
+            FilterKind::HighPass => (
+                (1.0 + cos) / 2.0,
+                -(1.0 + cos),
+                (1.0 + cos) / 2.0,
+                1.0 + alpha,
+                -2.0 * cos,
+                1.0 - alpha,
+            ),
+        };
+
+        Self {
+            b0: b0 / a0,
+            b1: b1 / a0,
+            b2: b2 / a0,
+            a1: a1 / a0,
+            a2: a2 / a0,
+        }
+    }
+
+    /// Create coefficients from an already designed filter, given as `[b0, b1, b2]` and
+    /// `[a0, a1, a2]`.
+    pub fn from_raw(b: [f64; 3], a: [f64; 3]) -> Self {
+        Self {
+            b0: b[0] / a[0],
+            b1: b[1] / a[0],
+            b2: b[2] / a[0],
+            a1: a[1] / a[0],
+            a2: a[2] / a[0],
+        }
+    }
+}
+
+/// Per-channel filter state, in transposed direct form II.
+///
+/// The state is kept separate from the coefficients so that the coefficients can be swapped
+/// while audio is playing without resetting the filter, which would cause an audible click.
+#[derive(Debug, Clone, Copy, Default)]
+pub struct BiquadState {
+    z1: f64,
+    z2: f64,
+}
+
+impl BiquadState {
+    #[inline]
+    pub fn process(&mut self, coefficients: &Coefficients, input: f64) -> f64 {
+        let output = coefficients.b0 * input + self.z1;
+        self.z1 = coefficients.b1 * input - coefficients.a1 * output + self.z2;
+        self.z2 = coefficients.b2 * input - coefficients.a2 * output;
+        output
+    }
+
+    pub fn reset(&mut self) {
+        *self = Self::default();
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::f64::consts::PI;
+
+    use super::{BiquadState, Coefficients};
+    use crate::settings::equalizer::FilterKind;
+
+    /// Run a sine wave through the filter and return the steady-state gain in dB.
+    fn measure_gain_db(coefficients: &Coefficients, frequency_hz: f64, sample_rate: u32) -> f64 {
+        let mut state = BiquadState::default();
+        let mut peak: f64 = 0.0;
+
+        for n in 0..sample_rate as usize {
+            let input = (2.0 * PI * frequency_hz * n as f64 / sample_rate as f64).sin();
+            let output = state.process(coefficients, input);
+
+            // skip the first half second so the filter can settle
+            if n > sample_rate as usize / 2 {
+                peak = peak.max(output.abs());
+            }
+        }
+
+        20.0 * peak.log10()
+    }
+
+    #[test]
+    fn peaking_filter_applies_gain_at_center_frequency() {
+        let coefficients = Coefficients::new(FilterKind::Peaking, 1000.0, 6.0, 1.41, 48000);
+
+        assert!((measure_gain_db(&coefficients, 1000.0, 48000) - 6.0).abs() < 0.1);
+        assert!(measure_gain_db(&coefficients, 50.0, 48000).abs() < 0.1);
+    }
+
+    #[test]
+    fn high_pass_filter_attenuates_low_frequencies() {
+        let coefficients = Coefficients::new(
+            FilterKind::HighPass,
+            1000.0,
+            0.0,
+            std::f64::consts::FRAC_1_SQRT_2,
+            48000,
+        );
+
+        assert!(measure_gain_db(&coefficients, 100.0, 48000) < -35.0);
+        assert!(measure_gain_db(&coefficients, 10000.0, 48000).abs() < 0.1);
+    }
+}
@@ -0,0 +1,176 @@
+use std::mem::discriminant;
+
+use tracing::debug;
+
+use crate::{
+    devices::traits::DspEffect,
+    settings::dsp::{DspSettings, EffectConfig},
+};
+
+use super::{
+    effects::{Balance, ChannelSwap, MonoDownmix},
+    limiter::Limiter,
+};
+
+/// An ordered list of effects, run one after the other on the same block of audio.
+#[derive(Default)]
+pub struct DspChain {
+    /// Each effect, along with the configuration it was created or last updated with.
+    effects: Vec<(EffectConfig, Box<dyn DspEffect>)>,
+}
+
+impl DspChain {
+    /// Change the chain to contain the enabled effects from the given settings, in order.
+    ///
+    /// Effects that are already in the chain are kept, with their parameters updated, so that
+    /// their state (e.g. a limiter's delay line) carries over. Only effects that weren't in the
+    /// chain before are created.
+    pub fn update(&mut self, settings: &DspSettings) {
+        let mut previous = std::mem::take(&mut self.effects);
+
+        for entry in settings.effects.iter().filter(|entry| entry.enabled) {
+            let config = entry.effect;
+            let existing = previous
+                .iter()
+                .position(|(old, _)| discriminant(old) == discriminant(&config));
+
+            let effect = match existing {
+                Some(idx) => {
+                    let (old, mut effect) = previous.remove(idx);
+                    if old != config {
+                        effect.set_config(&config);
+                    }
+                    effect
+                }
+                None => create_effect(config),
+            };
+
+            self.effects.push((config, effect));
+        }
+
+        debug!(
+            "DSP chain: {:?}",
+            self.effects
+                .iter()
+                .map(|(_, e)| e.name())
+                .collect::<Vec<_>>()
+        );
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.effects.is_empty()
+    }
+
+    /// Run every effect on `frames` frames of planar audio, in place.
+    pub fn process(
+        &mut self,
+        buffers: &mut [Vec<f64>],
+        frames: usize,
+        sample_rate: u32,
+        channels: usize,
+    ) {
+        for (_, effect) in &mut self.effects {
+            effect.process(buffers, frames, sample_rate, channels);
+        }
+    }
+
+    /// The total latency of the chain, in frames.
+    pub fn latency_frames(&self, sample_rate: u32) -> usize {
+        self.effects
+            .iter()
+            .map(|(_, effect)| effect.latency_frames(sample_rate))
+            .sum()
+    }
+
+    pub fn reset(&mut self) {
+        for (_, effect) in &mut self.effects {
+            effect.reset();
+        }
+    }
+
+    pub fn set_output_gain(&mut self, gain: f64) {
+        for (_, effect) in &mut self.effects {
+            effect.set_output_gain(gain);
+        }
+    }
+}
+
+fn create_effect(config: EffectConfig) -> Box<dyn DspEffect> {
+    match config {
+        EffectConfig::Balance { balance } => Box::new(Balance::new(balance)),
+        EffectConfig::MonoDownmix => Box::new(MonoDownmix),
+        EffectConfig::ChannelSwap => Box::new(ChannelSwap),
+        EffectConfig::Limiter {
+            threshold_db,
+            release_ms,
+        } => Box::new(Limiter::new(threshold_db, release_ms)),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::settings::dsp::EffectEntry;
+
+    fn settings(effects: &[EffectConfig]) -> DspSettings {
+        DspSettings {
+            effects: effects
+                .iter()
+                .map(|&effect| EffectEntry {
+                    enabled: true,
+                    effect,
+                })
+                .collect(),
+        }
+    }
+
+    fn limiter(threshold_db: f64) -> EffectConfig {
+        EffectConfig::Limiter {
+            threshold_db,
+            release_ms: 50.0,
+        }
+    }
+
+    /// Run a block of constant audio through the chain, returning the first output sample.
+    fn first_output(chain: &mut DspChain, value: f64) -> f64 {
+        let frames = 480;
+        let mut buffers = vec![vec![value; frames]; 2];
+        chain.process(&mut buffers, frames, 48000, 2);
+        buffers[0][0]
+    }
+
+    #[test]
+    fn updating_parameters_keeps_effect_state() {
+        let mut chain = DspChain::default();
+        chain.update(&settings(&[limiter(0.0)]));
+
+        // a new limiter starts with silence for the length of its look-ahead
+        assert_eq!(first_output(&mut chain, 0.5), 0.0);
+
+        chain.update(&settings(&[limiter(-0.1)]));
+        assert_eq!(first_output(&mut chain, 0.5), 0.5);
+    }
+
+    #[test]
+    fn reordering_keeps_effect_state() {
+        let mut chain = DspChain::default();
+        chain.update(&settings(&[
+            limiter(0.0),
+            EffectConfig::Balance { balance: 0.0 },
+        ]));
+        first_output(&mut chain, 0.5);
+
+        chain.update(&settings(&[
+            EffectConfig::Balance { balance: 0.0 },
+            limiter(0.0),
+        ]));
+        assert_eq!(first_output(&mut chain, 0.5), 0.5);
+
+        chain.update(&settings(&[EffectConfig::Balance { balance: 0.0 }]));
+        chain.update(&settings(&[
+            EffectConfig::Balance { balance: 0.0 },
+            limiter(0.0),
+        ]));
+        assert_eq!(first_output(&mut chain, 0.5), 0.0);
+    }
+}
@@ -0,0 +1,115 @@
+use crate::{devices::traits::DspEffect, settings::dsp::EffectConfig};
+
+/// Stereo balance. Attenuates one of the first two channels, leaving the other untouched.
+pub struct Balance {
+    /// -1.0 (left only) to 1.0 (right only)
+    balance: f64,
+}
+
+impl Balance {
+    pub fn new(balance: f64) -> Self {
+        Self {
+            balance: balance.clamp(-1.0, 1.0),
+        }
+    }
+
+    fn gains(&self) -> (f64, f64) {
+        ((1.0 - self.balance).min(1.0), (1.0 + self.balance).min(1.0))
+    }
+}
+
+impl DspEffect for Balance {
+    fn name(&self) -> &'static str {
+        "balance"
+    }
+
+    fn process(&mut self, buffers: &mut [Vec<f64>], frames: usize, _: u32, channels: usize) {
+        if channels < 2 || self.balance == 0.0 {
+            return;
+        }
+
+        let (left_gain, right_gain) = self.gains();
+        let [left, right, ..] = buffers else {
+            return;
+        };
+
+        left.iter_mut().take(frames).for_each(|s| *s *= left_gain);
+        right.iter_mut().take(frames).for_each(|s| *s *= right_gain);
+    }
+
+    fn set_config(&mut self, config: &EffectConfig) {
+        if let EffectConfig::Balance { balance } = *config {
+            self.balance = balance.clamp(-1.0, 1.0);
+        }
+    }
+}
+
+/// Mixes all channels down to mono, and writes the result back to every channel.
+pub struct MonoDownmix;
+
+impl DspEffect for MonoDownmix {
+    fn name(&self) -> &'static str {
+        "mono downmix"
+    }
+
+    fn process(&mut self, buffers: &mut [Vec<f64>], frames: usize, _: u32, channels: usize) {
+        if channels < 2 {
+            return;
+        }
+
+        let scale = 1.0 / channels as f64;
+
+        for frame in 0..frames {
+            let mixed = buffers.iter().map(|channel| channel[frame]).sum::<f64>() * scale;
+
+            for channel in buffers.iter_mut() {
+                channel[frame] = mixed;
+            }
+        }
+    }
+}
+
+/// Swaps the first two channels.
+pub struct ChannelSwap;
+
+impl DspEffect for ChannelSwap {
+    fn name(&self) -> &'static str {
+        "channel swap"
+    }
+
+    fn process(&mut self, buffers: &mut [Vec<f64>], _: usize, _: u32, channels: usize) {
+        if channels >= 2 {
+            buffers.swap(0, 1);
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn balance_attenuates_only_the_opposite_channel() {
+        let mut buffers = vec![vec![1.0; 4], vec![1.0; 4]];
+        Balance::new(0.25).process(&mut buffers, 4, 44100, 2);
+
+        assert_eq!(buffers[0], vec![0.75; 4]);
+        assert_eq!(buffers[1], vec![1.0; 4]);
+    }
+
+    #[test]
+    fn mono_downmix_averages_channels() {
+        let mut buffers = vec![vec![1.0, 0.5], vec![0.0, -0.5]];
+        MonoDownmix.process(&mut buffers, 2, 44100, 2);
+
+        assert_eq!(buffers, vec![vec![0.5, 0.0], vec![0.5, 0.0]]);
+    }
+
+    #[test]
+    fn channel_swap_ignores_mono() {
+        let mut buffers = vec![vec![1.0]];
+        ChannelSwap.process(&mut buffers, 1, 44100, 1);
+
+        assert_eq!(buffers, vec![vec![1.0]]);
+    }
+}
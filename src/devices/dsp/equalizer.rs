@@ -0,0 +1,124 @@
+use crate::{
+    devices::util::GainRamp,
+    settings::equalizer::{EqualizerBand, EqualizerSettings},
+};
+
+use super::biquad::{BiquadState, Coefficients};
+
+/// Parametric equalizer operating on planar f64 audio.
+///
+/// Settings can be changed while audio is playing: filter state is preserved when the
+/// coefficients change, the pre-amp is interpolated across a block, and enabling or disabling
+/// the equalizer blends between the dry and filtered signal with a short [`GainRamp`].
+pub struct Equalizer {
+    sample_rate: u32,
+    channels: usize,
+    bands: Vec<EqualizerBand>,
+    coefficients: Vec<Coefficients>,
+    /// Filter state, indexed by channel and then by band.
+    states: Vec<Vec<BiquadState>>,
+    preamp: f64,
+    preamp_target: f64,
+    enabled: bool,
+    wet: GainRamp,
+    wet_gains: Vec<f64>,
+}
+
+impl Equalizer {
+    pub fn new(settings: &EqualizerSettings, sample_rate: u32, channels: usize) -> Self {
+        let preamp = db_to_linear(settings.preamp_db);
+        let mut equalizer = Self {
+            sample_rate,
+            channels,
+            bands: Vec::new(),
+            coefficients: Vec::new(),
+            states: vec![Vec::new(); channels],
+            preamp,
+            preamp_target: preamp,
+            enabled: false,
+            wet: GainRamp::new(sample_rate),
+            wet_gains: Vec::new(),
+        };
+
+        equalizer.update(settings);
+        equalizer
+    }
+
+    /// Returns true if the equalizer was created for the given format.
+    pub fn matches_format(&self, sample_rate: u32, channels: usize) -> bool {
+        self.sample_rate == sample_rate && self.channels == channels
+    }
+
+    /// Apply new settings. Takes effect from the next call to `process`.
+    pub fn update(&mut self, settings: &EqualizerSettings) {
+        self.enabled = settings.enabled;
+        self.preamp_target = db_to_linear(settings.preamp_db);
+
+        if self.bands == settings.bands {
+            return;
+        }
+
+        self.coefficients = settings
+            .bands
+            .iter()
+            .map(|band| {
+                Coefficients::new(
+                    band.kind,
+                    band.frequency_hz,
+                    band.gain_db,
+                    band.q,
+                    self.sample_rate,
+                )
+            })
+            .collect();
+
+        // the state of existing bands is kept, so that adjusting a band doesn't click
+        for states in &mut self.states {
+            states.resize(settings.bands.len(), BiquadState::default());
+        }
+
+        self.bands = settings.bands.clone();
+    }
+
+    /// Filter `frames` frames of planar audio in place.
+    pub fn process(&mut self, buffers: &mut [Vec<f64>], frames: usize) {
+        let target = if self.enabled { 1.0 } else { 0.0 };
+
+        // fully bypassed, nothing to do
+        if target == 0.0 && self.wet.current() == 0.0 {
+            self.preamp = self.preamp_target;
+            for state in self.states.iter_mut().flatten() {
+                state.reset();
+            }
+            return;
+        }
+
+        self.wet_gains.clear();
+        self.wet_gains
+            .extend((0..frames).map(|_| self.wet.next_frame(target)));
+
+        let preamp_start = self.preamp;
+        let preamp_step = (self.preamp_target - self.preamp) / frames.max(1) as f64;
+
+        for (channel, states) in buffers.iter_mut().zip(&mut self.states) {
+            for (frame, sample) in channel.iter_mut().take(frames).enumerate() {
+                let dry = *sample;
+                let preamp = preamp_start + preamp_step * (frame + 1) as f64;
+
+                let mut wet = dry * preamp;
+                for (state, coefficients) in states.iter_mut().zip(&self.coefficients) {
+                    wet = state.process(coefficients, wet);
+                }
+
+                let mix = self.wet_gains[frame];
+                *sample = dry * (1.0 - mix) + wet * mix;
+            }
+        }
+
+        self.preamp = self.preamp_target;
+    }
+}
+
+fn db_to_linear(db: f64) -> f64 {
+    10.0_f64.powf(db / 20.0)
+}
@@ -0,0 +1,217 @@
+use std::collections::VecDeque;
+
+use crate::{devices::traits::DspEffect, settings::dsp::EffectConfig};
+
+/// How far ahead the limiter looks for peaks.
+const LOOKAHEAD_MS: f64 = 1.5;
+
+/// Look-ahead brick-wall limiter.
+///
+/// The limiter delays its output by [`LOOKAHEAD_MS`] so that the gain can already be reduced by
+/// the time a peak reaches the output, which means the gain only ever changes gradually on
+/// release. The ceiling takes the output gain (ReplayGain) into account, so a positive ReplayGain
+/// pre-amp can't push the signal past the threshold once it is applied by the device.
+pub struct Limiter {
+    ceiling: f64,
+    release_ms: f64,
+    output_gain: f64,
+    format: Option<(u32, usize)>,
+    lookahead: usize,
+    release_coefficient: f64,
+    /// Delayed samples, one queue per channel.
+    delay: Vec<VecDeque<f64>>,
+    /// Monotonic queue of (frame index, required gain), used to find the minimum required gain
+    /// across the look-ahead window in constant time.
+    window: VecDeque<(usize, f64)>,
+    frame_index: usize,
+    gain: f64,
+    input: Vec<f64>,
+}
+
+impl Limiter {
+    pub fn new(threshold_db: f64, release_ms: f64) -> Self {
+        Self {
+            ceiling: Self::ceiling(threshold_db),
+            release_ms: release_ms.max(1.0),
+            output_gain: 1.0,
+            format: None,
+            lookahead: 0,
+            release_coefficient: 0.0,
+            delay: Vec::new(),
+            window: VecDeque::new(),
+            frame_index: 0,
+            gain: 1.0,
+            input: Vec::new(),
+        }
+    }
+
+    fn ceiling(threshold_db: f64) -> f64 {
+        10.0_f64.powf(threshold_db.min(0.0) / 20.0)
+    }
+
+    fn release_coefficient(release_ms: f64, sample_rate: u32) -> f64 {
+        (-1000.0 / (release_ms * sample_rate as f64)).exp()
+    }
+
+    fn lookahead_frames(sample_rate: u32) -> usize {
+        (sample_rate as f64 * LOOKAHEAD_MS / 1000.0).round() as usize
+    }
+
+    fn configure(&mut self, sample_rate: u32, channels: usize) {
+        self.format = Some((sample_rate, channels));
+        self.lookahead = Self::lookahead_frames(sample_rate);
+        self.release_coefficient = Self::release_coefficient(self.release_ms, sample_rate);
+        self.delay = vec![VecDeque::with_capacity(self.lookahead + 1); channels];
+        self.input = vec![0.0; channels];
+        self.prime();
+    }
+
+    /// Fill the delay line with silence, so that output starts `lookahead` frames late.
+    fn prime(&mut self) {
+        self.window.clear();
+        self.gain = 1.0;
+
+        for delay in &mut self.delay {
+            delay.clear();
+            delay.extend(std::iter::repeat_n(0.0, self.lookahead));
+        }
+
+        for idx in 0..self.lookahead {
+            self.window.push_back((idx, 1.0));
+        }
+
+        self.frame_index = self.lookahead;
+    }
+}
+
+impl DspEffect for Limiter {
+    fn name(&self) -> &'static str {
+        "limiter"
+    }
+
+    fn process(
+        &mut self,
+        buffers: &mut [Vec<f64>],
+        frames: usize,
+        sample_rate: u32,
+        channels: usize,
+    ) {
+        if self.format != Some((sample_rate, channels)) {
+            self.configure(sample_rate, channels);
+        }
+
+        let ceiling = self.ceiling / self.output_gain.max(f64::EPSILON);
+
+        for frame in 0..frames {
+            let mut peak: f64 = 0.0;
+            for (input, channel) in self.input.iter_mut().zip(buffers.iter()) {
+                *input = channel[frame];
+                peak = peak.max(input.abs());
+            }
+
+            let required = if peak > ceiling { ceiling / peak } else { 1.0 };
+
+            while self
+                .window
+                .back()
+                .is_some_and(|&(_, gain)| gain >= required)
+            {
+                self.window.pop_back();
+            }
+            self.window.push_back((self.frame_index, required));
+
+            // the window covers the frame being output and the `lookahead` frames after it
+            let output_index = self.frame_index - self.lookahead;
+            while self
+                .window
+                .front()
+                .is_some_and(|&(idx, _)| idx < output_index)
+            {
+                self.window.pop_front();
+            }
+            self.frame_index += 1;
+
+            let target = self.window.front().map(|&(_, gain)| gain).unwrap_or(1.0);
+            self.gain = if target < self.gain {
+                target
+            } else {
+                target + (self.gain - target) * self.release_coefficient
+            };
+
+            for ((delay, input), channel) in self
+                .delay
+                .iter_mut()
+                .zip(&self.input)
+                .zip(buffers.iter_mut())
+            {
+                delay.push_back(*input);
+                let delayed = delay.pop_front().unwrap_or(0.0);
+                channel[frame] = (delayed * self.gain).clamp(-ceiling, ceiling);
+            }
+        }
+    }
+
+    fn latency_frames(&self, sample_rate: u32) -> usize {
+        Self::lookahead_frames(sample_rate)
+    }
+
+    fn reset(&mut self) {
+        self.prime();
+    }
+
+    fn set_output_gain(&mut self, gain: f64) {
+        self.output_gain = gain;
+    }
+
+    fn set_config(&mut self, config: &EffectConfig) {
+        let EffectConfig::Limiter {
+            threshold_db,
+            release_ms,
+        } = *config
+        else {
+            return;
+        };
+
+        self.ceiling = Self::ceiling(threshold_db);
+        self.release_ms = release_ms.max(1.0);
+        if let Some((sample_rate, _)) = self.format {
+            self.release_coefficient = Self::release_coefficient(self.release_ms, sample_rate);
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn limiter_keeps_output_below_ceiling() {
+        let mut limiter = Limiter::new(-6.0, 50.0);
+        limiter.set_output_gain(2.0);
+
+        let frames = 4800;
+        let mut buffers = vec![
+            (0..frames)
+                .map(|i| (i as f64 * 0.05).sin())
+                .collect::<Vec<_>>();
+            2
+        ];
+        limiter.process(&mut buffers, frames, 48000, 2);
+
+        let ceiling = 10.0_f64.powf(-6.0 / 20.0) / 2.0;
+        assert!(buffers.iter().flatten().all(|s| s.abs() <= ceiling + 1e-12));
+    }
+
+    #[test]
+    fn limiter_delays_by_its_latency() {
+        let mut limiter = Limiter::new(0.0, 50.0);
+        let latency = limiter.latency_frames(48000);
+
+        let mut buffers = vec![vec![0.0; latency + 1]];
+        buffers[0][0] = 0.5;
+        limiter.process(&mut buffers, latency + 1, 48000, 1);
+
+        assert_eq!(buffers[0][latency], 0.5);
+        assert!(buffers[0][..latency].iter().all(|&s| s == 0.0));
+    }
+}
@@ -10,6 +10,10 @@ pub enum InitializationError {
 
 #[derive(PartialEq, Eq, Debug, Clone, Error)]
 pub enum SubmissionError {
+    #[error("The device is no longer available")]
+    DeviceLost,
+    #[error("The device ran out of audio to play")]
+    Underrun,
     #[error("Unknown stream error: `{0}`")]
     Unknown(String),
 }
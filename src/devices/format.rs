@@ -1,3 +1,9 @@
+use std::str::FromStr;
+
+use bitflags::bitflags;
+
+use super::errors::InfoError;
+
 #[derive(Clone, Copy, Debug, PartialEq, Eq)]
 pub enum SampleFormat {
     Float64,
@@ -12,22 +18,132 @@ pub enum SampleFormat {
     Unsigned8,
 }
 
+impl FromStr for SampleFormat {
+    type Err = InfoError;
+
+    /// Parses the short names used to configure devices from the environment, e.g. `S16` or
+    /// `F32`.
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        Ok(match s {
+            "F64" => SampleFormat::Float64,
+            "F32" => SampleFormat::Float32,
+            "S32" => SampleFormat::Signed32,
+            "U32" => SampleFormat::Unsigned32,
+            "S24" => SampleFormat::Signed24,
+            "U24" => SampleFormat::Unsigned24,
+            "S16" => SampleFormat::Signed16,
+            "U16" => SampleFormat::Unsigned16,
+            "S8" => SampleFormat::Signed8,
+            "U8" => SampleFormat::Unsigned8,
+            _ => return Err(InfoError::SampleFmt(s.to_string())),
+        })
+    }
+}
+
+bitflags! {
+    /// Speaker positions of a set of channels. Channels are always ordered by position, in the
+    /// order of these flags, which is the order used by WAVE_FORMAT_EXTENSIBLE and FLAC.
+    #[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+    pub struct ChannelLayout: u32 {
+        const FRONT_LEFT            = 1 << 0;
+        const FRONT_RIGHT           = 1 << 1;
+        const FRONT_CENTER          = 1 << 2;
+        const LOW_FREQUENCY         = 1 << 3;
+        const BACK_LEFT             = 1 << 4;
+        const BACK_RIGHT            = 1 << 5;
+        const FRONT_LEFT_OF_CENTER  = 1 << 6;
+        const FRONT_RIGHT_OF_CENTER = 1 << 7;
+        const BACK_CENTER           = 1 << 8;
+        const SIDE_LEFT             = 1 << 9;
+        const SIDE_RIGHT            = 1 << 10;
+        const TOP_CENTER            = 1 << 11;
+        const TOP_FRONT_LEFT        = 1 << 12;
+        const TOP_FRONT_CENTER      = 1 << 13;
+        const TOP_FRONT_RIGHT       = 1 << 14;
+        const TOP_BACK_LEFT         = 1 << 15;
+        const TOP_BACK_CENTER       = 1 << 16;
+        const TOP_BACK_RIGHT        = 1 << 17;
+    }
+}
+
+impl ChannelLayout {
+    pub const MONO: Self = Self::FRONT_CENTER;
+    pub const STEREO: Self = Self::FRONT_LEFT.union(Self::FRONT_RIGHT);
+    pub const SURROUND_5_1: Self = Self::STEREO
+        .union(Self::FRONT_CENTER)
+        .union(Self::LOW_FREQUENCY)
+        .union(Self::BACK_LEFT)
+        .union(Self::BACK_RIGHT);
+    pub const SURROUND_7_1: Self = Self::SURROUND_5_1
+        .union(Self::SIDE_LEFT)
+        .union(Self::SIDE_RIGHT);
+
+    /// The layout usually meant by a plain channel count, as defined by FLAC.
+    pub fn for_count(count: u16) -> Option<Self> {
+        Some(match count {
+            1 => Self::MONO,
+            2 => Self::STEREO,
+            3 => Self::STEREO | Self::FRONT_CENTER,
+            4 => Self::STEREO | Self::BACK_LEFT | Self::BACK_RIGHT,
+            5 => Self::SURROUND_5_1 - Self::LOW_FREQUENCY,
+            6 => Self::SURROUND_5_1,
+            7 => {
+                (Self::SURROUND_5_1 - Self::BACK_LEFT - Self::BACK_RIGHT)
+                    | Self::BACK_CENTER
+                    | Self::SIDE_LEFT
+                    | Self::SIDE_RIGHT
+            }
+            8 => Self::SURROUND_7_1,
+            _ => return None,
+        })
+    }
+
+    pub fn count(self) -> u16 {
+        self.bits().count_ones() as u16
+    }
+
+    /// The position of each channel, in channel order.
+    pub fn positions(self) -> impl Iterator<Item = ChannelLayout> {
+        (0..u32::BITS)
+            .map(|bit| ChannelLayout::from_bits_retain(1 << bit))
+            .filter(move |position| self.contains(*position))
+    }
+
+    /// The index of the channel at the given position, if there is one.
+    pub fn index_of(self, position: ChannelLayout) -> Option<usize> {
+        self.positions().position(|p| p == position)
+    }
+}
+
 #[derive(Debug, Clone, Copy, PartialEq, Eq)]
 pub enum ChannelSpec {
     Count(u16),
+    /// Channels at known speaker positions.
+    Layout(ChannelLayout),
 }
 
 impl ChannelSpec {
     pub fn count(self) -> u16 {
         match self {
             ChannelSpec::Count(count) => count,
+            ChannelSpec::Layout(layout) => layout.count(),
+        }
+    }
+
+    /// The speaker positions of the channels. Plain channel counts are assumed to use the usual
+    /// layout for that many channels, if there is one.
+    pub fn layout(self) -> Option<ChannelLayout> {
+        match self {
+            ChannelSpec::Count(count) => ChannelLayout::for_count(count),
+            ChannelSpec::Layout(layout) => Some(layout),
         }
     }
 }
 
 #[derive(Debug, Clone, Copy, PartialEq, Eq)]
 pub enum BufferSize {
-    /// Inclusive range of supported buffer sizes.
+    /// Inclusive range of supported buffer sizes. When opening a stream, providers that split
+    /// their buffer into periods (like ALSA) take this as the period size and the buffer size.
     Range(u32, u32),
     Fixed(u32),
     Unknown,
@@ -42,7 +158,9 @@ pub struct FormatInfo {
     pub channels: ChannelSpec,
 }
 
-/// TODO: this will be used in the future
+/// A range of formats a device can be opened with, as reported by
+/// `Device::get_supported_formats`.
+#[derive(Debug, Clone)]
 #[allow(dead_code)]
 pub struct SupportedFormat {
     pub originating_provider: &'static str,
@@ -52,3 +170,18 @@ pub struct SupportedFormat {
     pub buffer_size: BufferSize,
     pub channels: ChannelSpec,
 }
+
+impl SupportedFormat {
+    /// Whether a stream with the given sample type, rate and channels can be opened in this
+    /// format.
+    pub fn supports(
+        &self,
+        sample_type: SampleFormat,
+        sample_rate: u32,
+        channels: ChannelSpec,
+    ) -> bool {
+        self.sample_type == sample_type
+            && (self.sample_rates.0..=self.sample_rates.1).contains(&sample_rate)
+            && self.channels.count() == channels.count()
+    }
+}
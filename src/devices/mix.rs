@@ -0,0 +1,332 @@
+use crate::settings::channels::ChannelSettings;
+
+use super::format::{ChannelLayout, ChannelSpec};
+
+/// How many times a channel may be redirected to a neighbouring position before it is dropped.
+const MAX_REDIRECTS: u32 = 4;
+
+/// Mixes audio from one set of channels to another, e.g. to play a 5.1 track on stereo
+/// headphones.
+///
+/// Each output channel is a weighted sum of the input channels. When both sides have known
+/// speaker positions, channels that exist on both sides are passed through, and the others are
+/// folded into their nearest neighbours: the center into the front left and right, surrounds into
+/// the front or the other pair of surrounds, and so on. Mono tracks are copied to both front
+/// channels at full level, like most devices do. Without positions, channels are matched up in
+/// order.
+pub struct ChannelMixer {
+    input: ChannelSpec,
+    output: ChannelSpec,
+    /// Coefficients, indexed by output channel and then by input channel.
+    matrix: Vec<Vec<f64>>,
+    buffer: Vec<Vec<f64>>,
+}
+
+impl ChannelMixer {
+    /// Create a mixer from `input` to `output`, or `None` if the channels are the same and don't
+    /// need mixing.
+    pub fn new(
+        input: ChannelSpec,
+        output: ChannelSpec,
+        settings: &ChannelSettings,
+    ) -> Option<ChannelMixer> {
+        let matrix = match (input.layout(), output.layout()) {
+            (Some(from), Some(to)) if from == to => return None,
+            (Some(from), Some(to)) => positional_matrix(from, to, settings),
+            _ if input.count() == output.count() => return None,
+            _ => ordered_matrix(input.count() as usize, output.count() as usize),
+        };
+
+        Some(Self::from_matrix(input, output, matrix))
+    }
+
+    /// Create a mixer with the given coefficients, indexed by output channel and then by input
+    /// channel.
+    pub fn from_matrix(input: ChannelSpec, output: ChannelSpec, matrix: Vec<Vec<f64>>) -> Self {
+        assert_eq!(matrix.len(), output.count() as usize);
+        assert!(matrix.iter().all(|row| row.len() == input.count() as usize));
+
+        Self {
+            input,
+            output,
+            buffer: vec![Vec::new(); matrix.len()],
+            matrix,
+        }
+    }
+
+    pub fn input(&self) -> ChannelSpec {
+        self.input
+    }
+
+    pub fn output(&self) -> ChannelSpec {
+        self.output
+    }
+
+    /// Mix `frames` frames of planar input, returning the mixed channels.
+    pub fn process(&mut self, input: &[Vec<f64>], frames: usize) -> &mut [Vec<f64>] {
+        for (row, output) in self.matrix.iter().zip(&mut self.buffer) {
+            output.clear();
+            output.resize(frames, 0.0);
+
+            for (&gain, channel) in row.iter().zip(input) {
+                if gain == 0.0 {
+                    continue;
+                }
+
+                for (out, &sample) in output.iter_mut().zip(&channel[..frames]) {
+                    *out += sample * gain;
+                }
+            }
+        }
+
+        &mut self.buffer
+    }
+}
+
+/// Match up channels in order, for channels without known positions. Mono is copied to the first
+/// two channels, and channels without a counterpart are dropped or left silent.
+fn ordered_matrix(inputs: usize, outputs: usize) -> Vec<Vec<f64>> {
+    let mut matrix = vec![vec![0.0; inputs]; outputs];
+
+    if inputs == 1 {
+        for row in matrix.iter_mut().take(2) {
+            row[0] = 1.0;
+        }
+    } else {
+        for (channel, row) in matrix.iter_mut().enumerate().take(inputs) {
+            row[channel] = 1.0;
+        }
+    }
+
+    matrix
+}
+
+fn positional_matrix(
+    from: ChannelLayout,
+    to: ChannelLayout,
+    settings: &ChannelSettings,
+) -> Vec<Vec<f64>> {
+    // a single channel is mono, whatever position it is labelled with
+    let from_mono = from.count() == 1;
+    let from = if from_mono { ChannelLayout::MONO } else { from };
+    let to = if to.count() == 1 {
+        ChannelLayout::MONO
+    } else {
+        to
+    };
+
+    let mut matrix = vec![vec![0.0; from.count() as usize]; to.count() as usize];
+
+    for (input, position) in from.positions().enumerate() {
+        let mut route = |position: ChannelLayout, gain: f64| {
+            if let Some(output) = to.index_of(position) {
+                matrix[output][input] += gain;
+            }
+        };
+
+        if from_mono && !to.contains(ChannelLayout::MONO) {
+            fold(ChannelLayout::FRONT_LEFT, 1.0, to, settings, 0, &mut route);
+            fold(ChannelLayout::FRONT_RIGHT, 1.0, to, settings, 0, &mut route);
+        } else {
+            fold(position, 1.0, to, settings, 0, &mut route);
+        }
+    }
+
+    if settings.normalize {
+        let loudest = matrix
+            .iter()
+            .map(|row| row.iter().map(|gain| gain.abs()).sum::<f64>())
+            .fold(0.0, f64::max);
+
+        if loudest > 1.0 {
+            for gain in matrix.iter_mut().flatten() {
+                *gain /= loudest;
+            }
+        }
+    }
+
+    matrix
+}
+
+/// Route a channel at `position` into the layout `to`, calling `route` with each output position
+/// it ends up in and the gain it is mixed in with.
+fn fold(
+    position: ChannelLayout,
+    gain: f64,
+    to: ChannelLayout,
+    settings: &ChannelSettings,
+    depth: u32,
+    route: &mut impl FnMut(ChannelLayout, f64),
+) {
+    use ChannelLayout as C;
+
+    if to.contains(position) {
+        route(position, gain);
+        return;
+    }
+
+    if depth >= MAX_REDIRECTS {
+        return;
+    }
+
+    let mut fold_into = |position: ChannelLayout, factor: f64| {
+        fold(position, gain * factor, to, settings, depth + 1, route);
+    };
+
+    let has = |positions: ChannelLayout| to.contains(positions);
+    let pair_gain = std::f64::consts::FRAC_1_SQRT_2;
+
+    match position {
+        C::FRONT_CENTER => {
+            fold_into(C::FRONT_LEFT, settings.center_gain());
+            fold_into(C::FRONT_RIGHT, settings.center_gain());
+        }
+        C::FRONT_LEFT | C::FRONT_RIGHT => fold_into(C::FRONT_CENTER, 0.5),
+        C::FRONT_LEFT_OF_CENTER => fold_into(C::FRONT_LEFT, 1.0),
+        C::FRONT_RIGHT_OF_CENTER => fold_into(C::FRONT_RIGHT, 1.0),
+        C::LOW_FREQUENCY => {
+            if let Some(lfe) = settings.lfe_gain() {
+                fold_into(C::FRONT_LEFT, lfe);
+                fold_into(C::FRONT_RIGHT, lfe);
+            }
+        }
+        C::BACK_LEFT if has(C::SIDE_LEFT) => fold_into(C::SIDE_LEFT, 1.0),
+        C::BACK_RIGHT if has(C::SIDE_RIGHT) => fold_into(C::SIDE_RIGHT, 1.0),
+        C::SIDE_LEFT if has(C::BACK_LEFT) => fold_into(C::BACK_LEFT, 1.0),
+        C::SIDE_RIGHT if has(C::BACK_RIGHT) => fold_into(C::BACK_RIGHT, 1.0),
+        C::BACK_LEFT | C::SIDE_LEFT => fold_into(C::FRONT_LEFT, settings.surround_gain()),
+        C::BACK_RIGHT | C::SIDE_RIGHT => fold_into(C::FRONT_RIGHT, settings.surround_gain()),
+        C::BACK_CENTER if has(C::BACK_LEFT | C::BACK_RIGHT) => {
+            fold_into(C::BACK_LEFT, pair_gain);
+            fold_into(C::BACK_RIGHT, pair_gain);
+        }
+        C::BACK_CENTER => {
+            fold_into(C::SIDE_LEFT, pair_gain);
+            fold_into(C::SIDE_RIGHT, pair_gain);
+        }
+        // height channels are played from the speaker below them
+        C::TOP_CENTER | C::TOP_FRONT_CENTER => fold_into(C::FRONT_CENTER, 1.0),
+        C::TOP_FRONT_LEFT => fold_into(C::FRONT_LEFT, 1.0),
+        C::TOP_FRONT_RIGHT => fold_into(C::FRONT_RIGHT, 1.0),
+        C::TOP_BACK_LEFT => fold_into(C::BACK_LEFT, 1.0),
+        C::TOP_BACK_RIGHT => fold_into(C::BACK_RIGHT, 1.0),
+        C::TOP_BACK_CENTER => fold_into(C::BACK_CENTER, 1.0),
+        _ => {}
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn mixer(from: ChannelSpec, to: ChannelSpec, settings: &ChannelSettings) -> ChannelMixer {
+        ChannelMixer::new(from, to, settings).expect("channels should need mixing")
+    }
+
+    fn unnormalized() -> ChannelSettings {
+        ChannelSettings {
+            normalize: false,
+            ..ChannelSettings::default()
+        }
+    }
+
+    fn assert_close(actual: &[Vec<f64>], expected: &[&[f64]]) {
+        assert_eq!(actual.len(), expected.len());
+        for (a, e) in actual.iter().zip(expected) {
+            assert_eq!(a.len(), e.len());
+            for (a, e) in a.iter().zip(e.iter()) {
+                assert!((a - e).abs() < 1e-3, "{actual:?} != {expected:?}");
+            }
+        }
+    }
+
+    #[test]
+    fn same_layout_needs_no_mixing() {
+        let settings = ChannelSettings::default();
+        let stereo = ChannelSpec::Layout(ChannelLayout::STEREO);
+        assert!(ChannelMixer::new(stereo, ChannelSpec::Count(2), &settings).is_none());
+        assert!(
+            ChannelMixer::new(ChannelSpec::Count(12), ChannelSpec::Count(12), &settings).is_none()
+        );
+    }
+
+    #[test]
+    fn downmixes_5_1_to_stereo() {
+        let from = ChannelSpec::Layout(ChannelLayout::SURROUND_5_1);
+        let to = ChannelSpec::Count(2);
+
+        // FL FR FC LFE BL BR, with the LFE dropped
+        let m = mixer(from, to, &unnormalized());
+        assert_close(
+            &m.matrix,
+            &[
+                &[1.0, 0.0, 0.708, 0.0, 0.708, 0.0],
+                &[0.0, 1.0, 0.708, 0.0, 0.0, 0.708],
+            ],
+        );
+
+        let m = mixer(from, to, &ChannelSettings::default());
+        let loudest: f64 = m.matrix[0].iter().sum();
+        assert!((loudest - 1.0).abs() < 1e-9);
+    }
+
+    #[test]
+    fn upmixes_mono() {
+        let settings = ChannelSettings::default();
+        let mut m = mixer(ChannelSpec::Count(1), ChannelSpec::Count(2), &settings);
+        let out = m.process(&[vec![0.5, -0.25]], 2);
+        assert_eq!(out, &[vec![0.5, -0.25], vec![0.5, -0.25]]);
+
+        // a device with a center speaker plays mono from it
+        let m = mixer(ChannelSpec::Count(1), ChannelSpec::Count(6), &settings);
+        assert_close(&m.matrix, &[&[0.0], &[0.0], &[1.0], &[0.0], &[0.0], &[0.0]]);
+    }
+
+    #[test]
+    fn downmixes_stereo_to_mono() {
+        let m = mixer(
+            ChannelSpec::Count(2),
+            ChannelSpec::Count(1),
+            &ChannelSettings::default(),
+        );
+        assert_close(&m.matrix, &[&[0.5, 0.5]]);
+    }
+
+    #[test]
+    fn moves_back_channels_to_sides() {
+        // 7.1 with side channels only, fed with 5.1 using back channels
+        let to =
+            (ChannelLayout::SURROUND_5_1 - ChannelLayout::BACK_LEFT - ChannelLayout::BACK_RIGHT)
+                | ChannelLayout::SIDE_LEFT
+                | ChannelLayout::SIDE_RIGHT;
+        let m = mixer(
+            ChannelSpec::Layout(ChannelLayout::SURROUND_5_1),
+            ChannelSpec::Layout(to),
+            &ChannelSettings::default(),
+        );
+
+        // FL FR FC LFE SL SR <- FL FR FC LFE BL BR
+        assert_close(
+            &m.matrix,
+            &[
+                &[1.0, 0.0, 0.0, 0.0, 0.0, 0.0],
+                &[0.0, 1.0, 0.0, 0.0, 0.0, 0.0],
+                &[0.0, 0.0, 1.0, 0.0, 0.0, 0.0],
+                &[0.0, 0.0, 0.0, 1.0, 0.0, 0.0],
+                &[0.0, 0.0, 0.0, 0.0, 1.0, 0.0],
+                &[0.0, 0.0, 0.0, 0.0, 0.0, 1.0],
+            ],
+        );
+    }
+
+    #[test]
+    fn matches_unknown_channels_in_order() {
+        let mut m = mixer(
+            ChannelSpec::Count(10),
+            ChannelSpec::Count(2),
+            &ChannelSettings::default(),
+        );
+        let input: Vec<Vec<f64>> = (0..10).map(|c| vec![c as f64]).collect();
+        assert_eq!(m.process(&input, 1), &[vec![0.0], vec![1.0]]);
+    }
+}
@@ -1,11 +1,21 @@
-use std::collections::VecDeque;
+mod integer;
+
+use std::{collections::VecDeque, time::Duration};
 
 use audioadapter_buffers::direct::SequentialSliceOfVecs;
 use intx::{I24, U24};
-use rubato::{Fft, FixedSync, Resampler as RubatoResampler};
+use rubato::{
+    Async, Fft, FixedAsync, FixedSync, Resampler as RubatoResampler, SincInterpolationParameters,
+    SincInterpolationType, WindowFunction, calculate_cutoff,
+};
 use tracing::info;
 
-use crate::media::pipeline::{ChannelConsumers, ChannelProducers};
+use crate::{
+    media::pipeline::{ChannelConsumers, ChannelProducers},
+    settings::resampler::ResamplerProfile,
+};
+
+use integer::IntegerUpsampler;
 
 pub trait SampleInto<T> {
     fn sample_into(self) -> T;
@@ -57,16 +67,20 @@ pub trait SampleFrom<T> {
     fn sample_from(value: T) -> Self;
 }
 
+// Conversions to integers round to the nearest value, so that decoding an integer sample to f64
+// and converting it back gives exactly the original sample. Bit-perfect playback relies on this.
+
 impl SampleFrom<f64> for U24 {
     fn sample_from(value: f64) -> Self {
-        U24::try_from(((value + 1.0) * f64::from(i32::from(I24::MAX))) as u32)
+        U24::try_from(((value + 1.0) * f64::from(i32::from(I24::MAX))).round() as u32)
             .expect("out of U24 bounds")
     }
 }
 
 impl SampleFrom<f64> for I24 {
     fn sample_from(value: f64) -> Self {
-        I24::try_from((value * f64::from(i32::from(I24::MAX))) as i32).expect("out of I24 bounds")
+        I24::try_from((value * f64::from(i32::from(I24::MAX))).round() as i32)
+            .expect("out of I24 bounds")
     }
 }
 
@@ -86,7 +100,7 @@ macro_rules! impl_sample_from_f64 {
     ($t:ty, $max_type:ty, $offset:expr) => {
         impl SampleFrom<f64> for $t {
             fn sample_from(value: f64) -> $t {
-                ((value - $offset) * f64::from(<$max_type>::MAX)) as $t
+                ((value - $offset) * f64::from(<$max_type>::MAX)).round() as $t
             }
         }
     };
@@ -162,8 +176,105 @@ impl SampleFrom<f32> for U24 {
     }
 }
 
+/// The resampling algorithm behind a [`Resampler`].
+enum Kernel {
+    Rubato(Box<dyn RubatoResampler<f64>>),
+    Integer(IntegerUpsampler),
+}
+
+/// The sinc filter used by a profile, or `None` if the profile doesn't use one.
+fn sinc_parameters(profile: ResamplerProfile) -> Option<SincInterpolationParameters> {
+    let (sinc_len, oversampling_factor, interpolation) = match profile {
+        ResamplerProfile::Fast => (64, 64, SincInterpolationType::Linear),
+        ResamplerProfile::Balanced => return None,
+        ResamplerProfile::VeryHigh | ResamplerProfile::BitExact => {
+            (256, 256, SincInterpolationType::Cubic)
+        }
+    };
+
+    let window = WindowFunction::BlackmanHarris2;
+    Some(SincInterpolationParameters {
+        sinc_len,
+        f_cutoff: calculate_cutoff(sinc_len, window),
+        interpolation,
+        oversampling_factor,
+        window,
+    })
+}
+
+impl Kernel {
+    fn new(
+        profile: ResamplerProfile,
+        orig_rate: u32,
+        target_rate: u32,
+        duration: usize,
+        channels: usize,
+    ) -> Self {
+        if profile == ResamplerProfile::BitExact
+            && let Some(upsampler) = IntegerUpsampler::new(orig_rate, target_rate, channels)
+        {
+            return Kernel::Integer(upsampler);
+        }
+
+        match sinc_parameters(profile) {
+            Some(parameters) => Kernel::Rubato(Box::new(
+                Async::<f64>::new_sinc(
+                    target_rate as f64 / orig_rate as f64,
+                    1.0,
+                    &parameters,
+                    duration,
+                    channels,
+                    FixedAsync::Input,
+                )
+                .unwrap(),
+            )),
+            None => Kernel::Rubato(Box::new(
+                Fft::<f64>::new(
+                    orig_rate as usize,
+                    target_rate as usize,
+                    duration,
+                    2,
+                    channels,
+                    FixedSync::Input,
+                )
+                .unwrap(),
+            )),
+        }
+    }
+
+    /// The most frames a single chunk of input can produce.
+    fn output_frames_max(&self, duration: usize, orig_rate: u32, target_rate: u32) -> usize {
+        match self {
+            Kernel::Rubato(resampler) => resampler.output_frames_max(),
+            Kernel::Integer(_) => {
+                (duration + integer::HALF_TAPS) * (target_rate / orig_rate) as usize
+            }
+        }
+    }
+
+    /// The delay between a source frame going in and the resampled frame coming out.
+    fn latency(&self, orig_rate: u32, target_rate: u32) -> Duration {
+        match self {
+            Kernel::Rubato(resampler) => {
+                Duration::from_secs_f64(resampler.output_delay() as f64 / target_rate as f64)
+            }
+            Kernel::Integer(_) => {
+                Duration::from_secs_f64(integer::HALF_TAPS as f64 / orig_rate as f64)
+            }
+        }
+    }
+
+    fn reset(&mut self) {
+        match self {
+            Kernel::Rubato(resampler) => resampler.reset(),
+            Kernel::Integer(upsampler) => upsampler.reset(),
+        }
+    }
+}
+
 pub struct Resampler {
-    resampler: Fft<f64>,
+    kernel: Kernel,
+    profile: ResamplerProfile,
     duration: u64,
     input_buffer: Vec<VecDeque<f64>>,
     output_buffer: Vec<Vec<f64>>,
@@ -176,29 +287,39 @@ pub struct Resampler {
 }
 
 impl Resampler {
-    pub fn new(orig_rate: u32, target_rate: u32, duration: u64, channels: u16) -> Self {
+    pub fn new(
+        profile: ResamplerProfile,
+        orig_rate: u32,
+        target_rate: u32,
+        duration: u64,
+        channels: u16,
+    ) -> Self {
+        let channels_usize = channels as usize;
+        let kernel = Kernel::new(
+            profile,
+            orig_rate,
+            target_rate,
+            duration as usize,
+            channels_usize,
+        );
+
         if orig_rate != target_rate {
             info!(
-                "Resampling required, resampling from {:?} to {:?} (duration {:?})",
-                orig_rate, target_rate, duration
+                "Resampling required, resampling from {:?} to {:?} (duration {:?}, profile {:?}, \
+                latency {:?})",
+                orig_rate,
+                target_rate,
+                duration,
+                profile,
+                kernel.latency(orig_rate, target_rate)
             );
         }
 
-        let resampler = Fft::<f64>::new(
-            orig_rate as usize,
-            target_rate as usize,
-            duration as usize,
-            2,
-            channels as usize,
-            FixedSync::Input,
-        )
-        .unwrap();
-
-        let channels_usize = channels as usize;
-        let output_frames_max = resampler.output_frames_max();
+        let output_frames_max = kernel.output_frames_max(duration as usize, orig_rate, target_rate);
 
         Resampler {
-            resampler,
+            kernel,
+            profile,
             duration,
             input_buffer: (0..channels)
                 .map(|_| VecDeque::with_capacity(duration as usize * 2))
@@ -223,14 +344,21 @@ impl Resampler {
         self.source_rate != self.target_rate
     }
 
+    /// The delay a resampler with the given profile adds when converting between two rates.
+    pub fn latency(profile: ResamplerProfile, source_rate: u32, target_rate: u32) -> Duration {
+        Kernel::new(profile, source_rate, target_rate, 1024, 1).latency(source_rate, target_rate)
+    }
+
     pub fn matches_params(
         &self,
+        profile: ResamplerProfile,
         source_rate: u32,
         target_rate: u32,
         duration: u64,
         channels: usize,
     ) -> bool {
-        self.source_rate == source_rate
+        self.profile == profile
+            && self.source_rate == source_rate
             && self.target_rate == target_rate
             && self.duration == duration
             && self.channels == channels
@@ -253,7 +381,7 @@ impl Resampler {
         for buf in &mut self.temp_output {
             buf.fill(0.0);
         }
-        self.resampler.reset();
+        self.kernel.reset();
         self.eof = false;
     }
 
@@ -278,6 +406,33 @@ impl Resampler {
             return 0; // not enough input yet
         }
 
+        self.process_buffered(output)
+    }
+
+    /// Resample whatever is still held in the internal buffers, including a trailing partial
+    /// chunk, and write it to the output. The resampler is reset afterwards.
+    ///
+    /// This is used when the resampler is about to be replaced while input is still pending,
+    /// for example when a gapless track transition changes the source sample rate.
+    pub fn flush(&mut self, output: &ChannelProducers<f64>) -> usize {
+        if !self.needs_resampling() {
+            return 0;
+        }
+
+        self.eof = true;
+        let written = self.process_buffered(output);
+        self.reset();
+
+        written
+    }
+
+    /// Run the resampler over the internal input buffers. If `eof` is set, any remaining
+    /// partial chunk is processed as well.
+    fn process_buffered(&mut self, output: &ChannelProducers<f64>) -> usize {
+        if matches!(self.kernel, Kernel::Integer(_)) {
+            return self.process_integer(output);
+        }
+
         let mut total_output = 0;
         let duration = self.duration as usize;
 
@@ -299,8 +454,10 @@ impl Resampler {
             )
             .unwrap();
 
-            let (_, frames_written) = self
-                .resampler
+            let Kernel::Rubato(resampler) = &mut self.kernel else {
+                unreachable!("integer kernels are processed separately");
+            };
+            let (_, frames_written) = resampler
                 .process_into_buffer(&input_adapter, &mut output_adapter, None)
                 .expect("resampler error");
 
@@ -342,7 +499,10 @@ impl Resampler {
                     partial_len: Some(input_frames),
                 };
 
-                if let Ok((_, frames_written)) = self.resampler.process_into_buffer(
+                let Kernel::Rubato(resampler) = &mut self.kernel else {
+                    unreachable!("integer kernels are processed separately");
+                };
+                if let Ok((_, frames_written)) = resampler.process_into_buffer(
                     &input_adapter,
                     &mut output_adapter,
                     Some(&indexing),
@@ -362,6 +522,45 @@ impl Resampler {
         total_output
     }
 
+    /// Run the integer upsampler over the internal input buffers. It accepts any number of
+    /// frames, so there are no partial chunks to handle at EOF, only the interpolated samples
+    /// that are still waiting for later input.
+    fn process_integer(&mut self, output: &ChannelProducers<f64>) -> usize {
+        let Kernel::Integer(upsampler) = &mut self.kernel else {
+            return 0;
+        };
+
+        let mut total_output = 0;
+        loop {
+            let available = self
+                .input_buffer
+                .iter()
+                .map(VecDeque::len)
+                .min()
+                .unwrap_or(0);
+            let frames = available.min(self.duration as usize);
+            if frames == 0 {
+                break;
+            }
+
+            for (temp, buffer) in self.temp_input.iter_mut().zip(&mut self.input_buffer) {
+                temp.clear();
+                temp.extend(buffer.drain(..frames));
+            }
+
+            let written = upsampler.process(&self.temp_input, frames, &mut self.temp_output);
+            output.write_vecs(&self.temp_output);
+            total_output += written;
+        }
+
+        if self.eof {
+            total_output += upsampler.flush(&mut self.temp_output);
+            output.write_vecs(&self.temp_output);
+        }
+
+        total_output
+    }
+
     fn passthrough_direct(
         input: &mut ChannelConsumers<f64>,
         output: &ChannelProducers<f64>,
@@ -397,3 +596,27 @@ impl Resampler {
         read
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn integer_samples_survive_f64_round_trip() {
+        for sample in i16::MIN + 1..=i16::MAX {
+            let value: f64 = sample.sample_into();
+            assert_eq!(i16::sample_from(value), sample);
+        }
+
+        for sample in (-(1i32 << 23) + 1..1 << 23).step_by(97) {
+            let sample = I24::try_from(sample).unwrap();
+            let value: f64 = sample.sample_into();
+            assert_eq!(I24::sample_from(value), sample);
+        }
+
+        for sample in [i32::MIN + 1, -1, 0, 1, 123_456_789, i32::MAX] {
+            let value: f64 = sample.sample_into();
+            assert_eq!(i32::sample_from(value), sample);
+        }
+    }
+}
@@ -0,0 +1,152 @@
+use std::f64::consts::PI;
+
+/// The number of source frames on each side of an interpolated sample that contribute to it.
+/// This is also the latency of the upsampler, in source frames.
+pub const HALF_TAPS: usize = 32;
+
+/// Upsampler for integer ratios that keeps every source sample as it is.
+///
+/// Every `factor`th output sample is a copy of a source sample, and the samples in between are
+/// interpolated with a windowed sinc filter. The filter is zero at every source sample except
+/// its own, so the source samples pass through bit-for-bit.
+pub struct IntegerUpsampler {
+    factor: usize,
+    /// The filter for each output phase. Phase 0 is the source sample itself, so its filter is
+    /// never used.
+    phases: Vec<Vec<f64>>,
+    /// The last source frames of each channel, which the next interpolated samples depend on.
+    history: Vec<Vec<f64>>,
+}
+
+fn sinc(t: f64) -> f64 {
+    if t == 0.0 {
+        1.0
+    } else {
+        (PI * t).sin() / (PI * t)
+    }
+}
+
+/// Blackman-Harris window, centered on 0 and reaching 0 at `-half_width` and `half_width`.
+fn window(t: f64, half_width: f64) -> f64 {
+    let x = PI * t / half_width;
+    0.35875 + 0.48829 * x.cos() + 0.14128 * (2.0 * x).cos() + 0.01168 * (3.0 * x).cos()
+}
+
+impl IntegerUpsampler {
+    /// Create an upsampler, if `target_rate` is a multiple of `source_rate` other than itself.
+    pub fn new(source_rate: u32, target_rate: u32, channels: usize) -> Option<Self> {
+        if source_rate == 0
+            || target_rate <= source_rate
+            || !target_rate.is_multiple_of(source_rate)
+        {
+            return None;
+        }
+
+        let factor = (target_rate / source_rate) as usize;
+        let phases = (0..factor)
+            .map(|phase| {
+                let offset = phase as f64 / factor as f64;
+                let mut taps: Vec<f64> = (0..2 * HALF_TAPS)
+                    .map(|i| {
+                        let t = offset + (HALF_TAPS - 1) as f64 - i as f64;
+                        sinc(t) * window(t, HALF_TAPS as f64)
+                    })
+                    .collect();
+
+                // Normalize so that every phase has unity gain at DC
+                let sum: f64 = taps.iter().sum();
+                taps.iter_mut().for_each(|tap| *tap /= sum);
+                taps
+            })
+            .collect();
+
+        Some(Self {
+            factor,
+            phases,
+            history: (0..channels).map(|_| vec![0.0; HALF_TAPS - 1]).collect(),
+        })
+    }
+
+    /// Upsample `frames` frames of `input`, replacing the contents of `output`. Returns the
+    /// number of frames written.
+    pub fn process(&mut self, input: &[Vec<f64>], frames: usize, output: &mut [Vec<f64>]) -> usize {
+        let mut written = 0;
+
+        for ((history, input), output) in self.history.iter_mut().zip(input).zip(output) {
+            history.extend_from_slice(&input[..frames]);
+            output.clear();
+
+            let windows = history.len().saturating_sub(2 * HALF_TAPS - 1);
+            for start in 0..windows {
+                let taps = &history[start..start + 2 * HALF_TAPS];
+                output.push(taps[HALF_TAPS - 1]);
+                for phase in &self.phases[1..] {
+                    output.push(taps.iter().zip(phase).map(|(x, h)| x * h).sum());
+                }
+            }
+
+            history.drain(..windows);
+            written = windows * self.factor;
+        }
+
+        written
+    }
+
+    /// Write out the interpolated samples that are still waiting for later source frames, as if
+    /// the source was followed by silence. The upsampler is reset afterwards.
+    pub fn flush(&mut self, output: &mut [Vec<f64>]) -> usize {
+        let silence = vec![vec![0.0; HALF_TAPS]; self.history.len()];
+        let written = self.process(&silence, HALF_TAPS, output);
+        self.reset();
+        written
+    }
+
+    pub fn reset(&mut self) {
+        for history in &mut self.history {
+            history.clear();
+            history.resize(HALF_TAPS - 1, 0.0);
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn only_accepts_integer_ratios() {
+        assert!(IntegerUpsampler::new(44100, 88200, 2).is_some());
+        assert!(IntegerUpsampler::new(48000, 192000, 2).is_some());
+        assert!(IntegerUpsampler::new(44100, 48000, 2).is_none());
+        assert!(IntegerUpsampler::new(48000, 48000, 2).is_none());
+        assert!(IntegerUpsampler::new(96000, 48000, 2).is_none());
+    }
+
+    #[test]
+    fn keeps_source_samples_and_interpolates_between_them() {
+        let mut upsampler = IntegerUpsampler::new(44100, 88200, 1).unwrap();
+        let signal = |t: f64| (2.0 * PI * 1000.0 * t / 44100.0).sin() * 0.5;
+
+        let input: Vec<f64> = (0..4096).map(|i| signal(i as f64)).collect();
+        let mut output = vec![Vec::new()];
+        let mut result = Vec::new();
+        for chunk in 0..4 {
+            let chunk_input = vec![input[chunk * 1024..(chunk + 1) * 1024].to_vec()];
+            let written = upsampler.process(&chunk_input, 1024, &mut output);
+            result.extend_from_slice(&output[0][..written]);
+        }
+        let written = upsampler.flush(&mut output);
+        result.extend_from_slice(&output[0][..written]);
+
+        assert_eq!(result.len(), 4096 * 2);
+        for (i, sample) in input.iter().enumerate() {
+            assert_eq!(result[i * 2].to_bits(), sample.to_bits());
+        }
+
+        // Away from the edges, the interpolated samples follow the original signal
+        for i in HALF_TAPS..4096 - HALF_TAPS {
+            let expected = signal(i as f64 + 0.5);
+            assert!((result[i * 2 + 1] - expected).abs() < 1e-3);
+        }
+    }
+}
@@ -0,0 +1,295 @@
+use std::f64::consts::PI;
+
+use crate::media::pipeline::{ChannelConsumers, ChannelProducers};
+
+/// Length of each analysis window.
+const WINDOW_MS: f64 = 40.0;
+/// How far from its ideal position a window may be moved to line up with the previous one.
+const SEEK_MS: f64 = 12.0;
+
+pub const MIN_SPEED: f64 = 0.5;
+pub const MAX_SPEED: f64 = 3.0;
+
+/// Time stretcher, changing the speed of audio without changing its pitch.
+///
+/// This uses WSOLA (waveform similarity overlap-add): windows are taken from the input at
+/// intervals scaled by the speed and overlap-added at a fixed interval, with each window shifted
+/// slightly so that it lines up with the audio it is overlapping. At 1x, every window is taken
+/// exactly where the previous one left off, so the input is reproduced unchanged.
+pub struct TimeStretcher {
+    sample_rate: u32,
+    channels: usize,
+    speed: f64,
+    window: Vec<f64>,
+    /// Half the window length, the distance between consecutive output windows.
+    hop: usize,
+    seek: usize,
+    input: Vec<Vec<f64>>,
+    /// The second half of the previous window, waiting to be overlapped with the next one.
+    tail: Vec<Vec<f64>>,
+    /// Where the input continues from the end of the previous window.
+    natural: usize,
+    /// Where the next window would ideally start. Moves by `hop * speed` for every output hop.
+    analysis: f64,
+    fresh: bool,
+    output: Vec<Vec<f64>>,
+    mono: Vec<f64>,
+}
+
+impl TimeStretcher {
+    pub fn new(sample_rate: u32, channels: usize, speed: f64) -> Self {
+        let hop = ((sample_rate as f64 * WINDOW_MS / 2000.0).round() as usize).max(1);
+        let length = hop * 2;
+
+        // periodic Hann window, two of these overlapping by half always sum to 1
+        let window = (0..length)
+            .map(|i| 0.5 - 0.5 * (2.0 * PI * i as f64 / length as f64).cos())
+            .collect();
+
+        Self {
+            sample_rate,
+            channels,
+            speed: speed.clamp(MIN_SPEED, MAX_SPEED),
+            window,
+            hop,
+            seek: (sample_rate as f64 * SEEK_MS / 1000.0).round() as usize,
+            input: vec![Vec::new(); channels],
+            tail: vec![vec![0.0; hop]; channels],
+            natural: 0,
+            analysis: 0.0,
+            fresh: true,
+            output: vec![Vec::with_capacity(hop); channels],
+            mono: Vec::new(),
+        }
+    }
+
+    pub fn matches_params(&self, sample_rate: u32, channels: usize) -> bool {
+        self.sample_rate == sample_rate && self.channels == channels
+    }
+
+    pub fn set_speed(&mut self, speed: f64) {
+        self.speed = speed.clamp(MIN_SPEED, MAX_SPEED);
+    }
+
+    /// The number of input frames held internally.
+    pub fn backlog(&self) -> usize {
+        self.input.first().map(Vec::len).unwrap_or(0)
+    }
+
+    /// Discard all buffered audio, e.g. after seeking.
+    pub fn reset(&mut self) {
+        for channel in &mut self.input {
+            channel.clear();
+        }
+        self.natural = 0;
+        self.analysis = 0.0;
+        self.fresh = true;
+    }
+
+    /// Read all available input and write roughly `max_output` stretched frames to the output.
+    /// Input that can't be processed yet is kept for the next call.
+    pub fn process_ring_buffers(
+        &mut self,
+        input: &mut ChannelConsumers<f64>,
+        output: &ChannelProducers<f64>,
+        max_output: usize,
+    ) -> usize {
+        let capacity = input.potentially_available();
+        if capacity > 0 {
+            let read = input.try_read_to_staging(capacity);
+            for (buffer, staging) in self.input.iter_mut().zip(input.staging()) {
+                buffer.extend_from_slice(&staging[..read]);
+            }
+        }
+
+        let mut written = 0;
+        while written < max_output && self.process_hop() {
+            output.write_vecs(&self.output);
+            written += self.hop;
+        }
+
+        self.drain_consumed();
+        written
+    }
+
+    /// Write out everything that is still buffered, at its original speed. Called when the input
+    /// is about to change format, so that the end of the previous track isn't lost.
+    pub fn flush(&mut self, output: &ChannelProducers<f64>) -> usize {
+        let available = self.backlog().saturating_sub(self.natural);
+        if self.fresh || available == 0 {
+            self.reset();
+            return 0;
+        }
+
+        // overlapping the tail with its own continuation reconstructs the input exactly
+        for ((out, tail), input) in self.output.iter_mut().zip(&self.tail).zip(&self.input) {
+            out.clear();
+            out.extend(input[self.natural..].iter().enumerate().map(|(i, sample)| {
+                if i < self.hop {
+                    tail[i] + sample * self.window[i]
+                } else {
+                    *sample
+                }
+            }));
+        }
+
+        output.write_vecs(&self.output);
+        self.reset();
+        available
+    }
+
+    /// Produce one hop of output into `self.output`. Returns false if more input is needed.
+    fn process_hop(&mut self) -> bool {
+        let length = self.hop * 2;
+        let available = self.backlog();
+
+        let position = if self.fresh {
+            self.analysis.round() as usize
+        } else if self.speed == 1.0 {
+            self.analysis = self.natural as f64;
+            self.natural
+        } else {
+            let ideal = self.analysis.round() as usize;
+            let start = ideal.saturating_sub(self.seek);
+            let end = ideal + self.seek;
+
+            if end + length > available || self.natural + self.hop > available {
+                return false;
+            }
+
+            self.best_offset(start, end)
+        };
+
+        if position + length > available {
+            return false;
+        }
+
+        for ((out, tail), input) in self.output.iter_mut().zip(&mut self.tail).zip(&self.input) {
+            let frame = &input[position..position + length];
+            out.clear();
+
+            if self.fresh {
+                out.extend_from_slice(&frame[..self.hop]);
+            } else {
+                out.extend((0..self.hop).map(|i| tail[i] + frame[i] * self.window[i]));
+            }
+
+            for (i, tail) in tail.iter_mut().enumerate() {
+                *tail = frame[self.hop + i] * self.window[self.hop + i];
+            }
+        }
+
+        self.fresh = false;
+        self.natural = position + self.hop;
+        self.analysis += self.hop as f64 * self.speed;
+
+        true
+    }
+
+    /// Find the window start between `start` and `end` whose first half best matches the
+    /// audio following the previous window.
+    fn best_offset(&mut self, start: usize, end: usize) -> usize {
+        let hop = self.hop;
+
+        self.mono.clear();
+        self.mono.extend((0..end - start + hop).map(|i| {
+            self.input
+                .iter()
+                .map(|channel| channel[start + i])
+                .sum::<f64>()
+        }));
+        let reference_offset = self.mono.len();
+        self.mono.extend((0..hop).map(|i| {
+            self.input
+                .iter()
+                .map(|channel| channel[self.natural + i])
+                .sum::<f64>()
+        }));
+
+        let (candidates, reference) = self.mono.split_at(reference_offset);
+
+        let mut best = start;
+        let mut best_score = f64::MIN;
+
+        // every other offset and sample is enough to line up the waveforms, at a quarter of the
+        // cost
+        for offset in (0..=end - start).step_by(2) {
+            let candidate = &candidates[offset..offset + hop];
+            let (mut correlation, mut energy) = (0.0, 0.0);
+
+            for i in (0..hop).step_by(2) {
+                correlation += candidate[i] * reference[i];
+                energy += candidate[i] * candidate[i];
+            }
+
+            let score = correlation / energy.sqrt().max(f64::EPSILON);
+            if score > best_score {
+                best_score = score;
+                best = start + offset;
+            }
+        }
+
+        best
+    }
+
+    /// Drop input that no future window can use.
+    fn drain_consumed(&mut self) {
+        let lowest = (self.analysis.round() as usize)
+            .saturating_sub(self.seek)
+            .min(self.natural);
+
+        if lowest == 0 {
+            return;
+        }
+
+        for channel in &mut self.input {
+            channel.drain(..lowest);
+        }
+        self.natural -= lowest;
+        self.analysis -= lowest as f64;
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::media::pipeline::ChannelBuffers;
+
+    use super::*;
+
+    fn run(stretcher: &mut TimeStretcher, input: &[f64]) -> Vec<f64> {
+        let (in_producers, mut in_consumers) = ChannelBuffers::<f64>::new(1, 1 << 16).split();
+        let (out_producers, mut out_consumers) = ChannelBuffers::<f64>::new(1, 1 << 17).split();
+
+        in_producers.write_slices(&[input]);
+        while stretcher.process_ring_buffers(&mut in_consumers, &out_producers, 4096) > 0 {}
+
+        let read = out_consumers.try_read_to_staging(1 << 17);
+        out_consumers.staging()[0][..read].to_vec()
+    }
+
+    #[test]
+    fn unity_speed_reproduces_input() {
+        let input: Vec<f64> = (0..20_000).map(|i| (i as f64 * 0.013).sin()).collect();
+        let mut stretcher = TimeStretcher::new(48000, 1, 1.0);
+
+        let output = run(&mut stretcher, &input);
+
+        assert!(output.len() > 10_000);
+        for (a, b) in output.iter().zip(&input) {
+            assert!((a - b).abs() < 1e-9);
+        }
+    }
+
+    #[test]
+    fn output_length_follows_speed() {
+        let input: Vec<f64> = (0..48_000).map(|i| (i as f64 * 0.05).sin()).collect();
+
+        for speed in [0.5, 2.0] {
+            let mut stretcher = TimeStretcher::new(48000, 1, speed);
+            let output = run(&mut stretcher, &input);
+
+            let expected = input.len() as f64 / speed;
+            assert!((output.len() as f64 - expected).abs() < expected * 0.1);
+        }
+    }
+}
@@ -1,6 +1,6 @@
 #![allow(dead_code)]
 
-use crate::media::pipeline::ChannelConsumers;
+use crate::{media::pipeline::ChannelConsumers, settings::dsp::EffectConfig};
 
 use super::{
     errors::{
@@ -24,6 +24,24 @@ pub trait DeviceProvider {
     fn get_default_device(&mut self) -> Result<Box<dyn Device>, FindError>;
     /// Requests the device provider find and return a device by its UID.
     fn get_device_by_uid(&mut self, id: &str) -> Result<Box<dyn Device>, FindError>;
+    /// Returns the device changes that happened since the last call, oldest first. This is
+    /// called regularly by the playback thread, so it must not block. Providers that can't detect
+    /// device changes should return an empty list.
+    fn poll_device_changes(&mut self) -> Vec<DeviceChange> {
+        Vec::new()
+    }
+}
+
+/// A change to the devices available to a device provider, as reported by
+/// `DeviceProvider::poll_device_changes`. Devices are identified by their UID.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum DeviceChange {
+    /// A device was connected.
+    Added(String),
+    /// A device was disconnected, or stopped working.
+    Removed(String),
+    /// The system default device changed to the given device.
+    DefaultChanged(String),
 }
 
 pub trait Device {
@@ -77,6 +95,19 @@ pub trait OutputStream {
         Ok(())
     }
 
+    /// Sets the name of the media being played, usually the current track's title. Providers
+    /// that publish stream properties to the system (e.g. for desktop mixers) should show it
+    /// there; others can ignore it.
+    fn set_media_name(&mut self, _name: &str) -> Result<(), StateError> {
+        Ok(())
+    }
+
+    /// Returns the number of frames that have been submitted but not played yet. Providers that
+    /// can't tell should return 0.
+    fn buffered_frames(&self) -> usize {
+        0
+    }
+
     /// Consume samples from ring buffer consumers and submit them to the device.
     fn consume_from(&mut self, input: &mut ChannelConsumers<f64>)
     -> Result<usize, SubmissionError>;
@@ -91,3 +122,37 @@ pub trait OutputStream {
         None
     }
 }
+
+/// The DspEffect trait defines an audio effect that can be inserted into the playback thread's
+/// effect chain. Effects run after resampling and the equalizer, so they always see audio at the
+/// device's sample rate and channel count.
+///
+/// Audio is passed as planar f64 blocks: one buffer per channel, each holding at least `frames`
+/// samples. Effects process the block in place and must always produce exactly as many frames as
+/// they were given - effects that need to look ahead should delay their output instead, and report
+/// that delay through `latency_frames`.
+pub trait DspEffect: Send {
+    /// Returns the name of the effect, for logging.
+    fn name(&self) -> &'static str;
+    /// Process `frames` frames of planar audio in place. The sample rate and channel count may
+    /// change between calls, in which case the effect should reset any internal state.
+    fn process(
+        &mut self,
+        buffers: &mut [Vec<f64>],
+        frames: usize,
+        sample_rate: u32,
+        channels: usize,
+    );
+    /// Returns the number of frames the effect delays its output by at the given sample rate.
+    fn latency_frames(&self, _sample_rate: u32) -> usize {
+        0
+    }
+    /// Clears any internal state, e.g. after a seek.
+    fn reset(&mut self) {}
+    /// Informs the effect of the gain that will be applied after the chain (currently the
+    /// ReplayGain multiplier), for effects that need to account for it.
+    fn set_output_gain(&mut self, _gain: f64) {}
+    /// Applies new parameters from the effect's settings, keeping any internal state. Only
+    /// called with the configuration of the same kind of effect.
+    fn set_config(&mut self, _config: &EffectConfig) {}
+}
@@ -1,7 +1,15 @@
 use intx::{I24, U24};
-use std::sync::atomic::AtomicU64;
-
-use super::resample::{SampleFrom, SampleInto};
+use rb::{Producer, RbProducer};
+use std::{
+    f64::consts::FRAC_PI_2,
+    sync::atomic::{AtomicBool, AtomicU64, Ordering},
+    time::{Duration, Instant},
+};
+
+use super::{
+    errors::SubmissionError,
+    resample::{SampleFrom, SampleInto},
+};
 
 // Code is dead on non-Linux platforms only
 #[allow(dead_code)]
@@ -75,6 +83,45 @@ impl AtomicF64 {
     }
 }
 
+/// How long to wait between attempts to write to a full ring buffer.
+const WRITE_RETRY_INTERVAL: Duration = Duration::from_millis(2);
+
+/// If a device ring buffer hasn't been read from in this long, the device has stopped pulling
+/// audio without reporting an error, which some hosts do when a device is unplugged.
+const STALL_TIMEOUT: Duration = Duration::from_secs(2);
+
+/// Write samples to a device ring buffer, waiting for space as needed. Fails instead of waiting
+/// forever if `device_lost` is set (usually by the stream's error callback), or if the device
+/// stops reading from the buffer.
+pub fn write_to_ring<T: Copy>(
+    ring_buf: &Producer<T>,
+    samples: &[T],
+    device_lost: &AtomicBool,
+) -> Result<(), SubmissionError> {
+    let mut slice = samples;
+    let mut last_progress = Instant::now();
+
+    while !slice.is_empty() {
+        if device_lost.load(Ordering::Relaxed) {
+            return Err(SubmissionError::DeviceLost);
+        }
+
+        match ring_buf.write(slice) {
+            Ok(written) => {
+                slice = &slice[written..];
+                last_progress = Instant::now();
+            }
+            Err(_) if last_progress.elapsed() > STALL_TIMEOUT => {
+                device_lost.store(true, Ordering::Relaxed);
+                return Err(SubmissionError::DeviceLost);
+            }
+            Err(_) => std::thread::sleep(WRITE_RETRY_INTERVAL),
+        }
+    }
+
+    Ok(())
+}
+
 pub const GAIN_RAMP_MS: f64 = 15.0;
 
 /// Linear gain ramp.
@@ -91,7 +138,12 @@ pub struct GainRamp {
 
 impl GainRamp {
     pub fn new(sample_rate_hz: u32) -> Self {
-        let ramp_frames = (sample_rate_hz as f64 * GAIN_RAMP_MS / 1000.0).max(1.0);
+        Self::with_duration(sample_rate_hz, GAIN_RAMP_MS)
+    }
+
+    /// Create a ramp where a full-scale transition takes `ramp_ms` instead of [`GAIN_RAMP_MS`].
+    pub fn with_duration(sample_rate_hz: u32, ramp_ms: f64) -> Self {
+        let ramp_frames = (sample_rate_hz as f64 * ramp_ms / 1000.0).max(1.0);
         Self {
             current: 0.0,
             step: 1.0 / ramp_frames,
@@ -107,6 +159,19 @@ impl GainRamp {
         }
     }
 
+    /// The gain applied to the most recent frame.
+    pub fn current(&self) -> f64 {
+        self.current
+    }
+
+    /// Step the ramp forward by a single frame and return the gain for that frame.
+    ///
+    /// This is intended for planar buffers, where `apply` can't be used.
+    pub fn next_frame(&mut self, target: f64) -> f64 {
+        self.advance_toward_target(target);
+        self.current
+    }
+
     fn advance_frame_pos(&mut self, samples: usize, channels: usize) {
         self.frame_pos = (self.frame_pos + samples) % channels;
     }
@@ -149,9 +214,37 @@ impl GainRamp {
     }
 }
 
+/// Equal-power crossfade between two signals.
+///
+/// A [`GainRamp`] tracks the linear progress of the fade, which is mapped onto a quarter sine
+/// wave so that the combined power of both signals stays constant for the whole fade.
+pub struct EqualPowerFade {
+    progress: GainRamp,
+}
+
+impl EqualPowerFade {
+    pub fn new(sample_rate_hz: u32, duration: Duration) -> Self {
+        Self {
+            progress: GainRamp::with_duration(sample_rate_hz, duration.as_secs_f64() * 1000.0),
+        }
+    }
+
+    /// Advance the fade by one frame, returning the gains for the outgoing and incoming signal.
+    pub fn next_gains(&mut self) -> (f64, f64) {
+        let angle = self.progress.next_frame(1.0) * FRAC_PI_2;
+        (angle.cos(), angle.sin())
+    }
+
+    pub fn is_finished(&self) -> bool {
+        self.progress.current >= 1.0
+    }
+}
+
 #[cfg(test)]
 mod tests {
-    use super::GainRamp;
+    use std::time::Duration;
+
+    use super::{EqualPowerFade, GainRamp};
 
     fn assert_approx_eq(lhs: f32, rhs: f32) {
         assert!((lhs - rhs).abs() < 1e-6, "left={lhs}, right={rhs}");
@@ -193,4 +286,20 @@ mod tests {
         assert_approx_eq(faded[0], 14.0 / 15.0);
         assert_eq!(ramp.frame_pos, 1);
     }
+
+    #[test]
+    fn equal_power_fade_keeps_constant_power() {
+        let mut fade = EqualPowerFade::new(1000, Duration::from_millis(8));
+
+        for _ in 0..7 {
+            let (outgoing, incoming) = fade.next_gains();
+            assert!((outgoing.powi(2) + incoming.powi(2) - 1.0).abs() < 1e-9);
+            assert!(!fade.is_finished());
+        }
+
+        let (outgoing, incoming) = fade.next_gains();
+        assert!(outgoing.abs() < 1e-9);
+        assert!((incoming - 1.0).abs() < 1e-9);
+        assert!(fade.is_finished());
+    }
 }
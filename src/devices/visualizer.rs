@@ -0,0 +1,265 @@
+use std::{f64::consts::PI, sync::Arc};
+
+use realfft::{RealFftPlanner, RealToComplex, num_complex::Complex};
+
+/// Number of samples in each spectrum analysis window.
+pub const FFT_SIZE: usize = 2048;
+/// Number of logarithmically spaced bands the spectrum is reduced to.
+pub const SPECTRUM_BANDS: usize = 64;
+/// The lowest level reported for a spectrum band, in dBFS.
+pub const SPECTRUM_FLOOR_DB: f32 = -90.0;
+
+const MIN_FREQUENCY: f64 = 20.0;
+const MAX_FREQUENCY: f64 = 20000.0;
+/// The longest the device can hold on to audio before it is heard, in milliseconds. Analysis
+/// can be delayed by up to this much to line up with what's audible.
+const MAX_DELAY_MS: usize = 1000;
+
+/// The level of a single channel since the previous frame, as linear amplitudes.
+#[derive(Debug, Clone, Copy, Default, PartialEq)]
+pub struct ChannelLevel {
+    pub rms: f32,
+    pub peak: f32,
+}
+
+/// A snapshot of the audio being played, for spectrum analyzers and level meters.
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct VisualizerFrame {
+    /// The level of each band in dBFS, from low to high frequencies. A full scale sine reads
+    /// 0 dB, and anything below [`SPECTRUM_FLOOR_DB`] reads as the floor.
+    pub spectrum: Vec<f32>,
+    /// The level of each output channel.
+    pub levels: Vec<ChannelLevel>,
+}
+
+/// Read-only tap on the processed audio, measuring its spectrum and levels.
+///
+/// Audio is pushed in whatever chunks the pipeline produces, which only copies it into a history
+/// of each channel. The analysis is done when a frame is requested, so the cost depends on how
+/// often the UI is updated rather than on the amount of audio played. Since audio is pushed
+/// before the device has played it, frames are analyzed as of some number of frames ago.
+pub struct Visualizer {
+    sample_rate: u32,
+    /// The most recent audio of each channel, as ring buffers indexed by frame number.
+    history: Vec<Vec<f64>>,
+    history_frames: u64,
+    /// The total number of frames pushed.
+    written: u64,
+    /// The frame the previous analysis ended at, where level measurement continues from.
+    measured: u64,
+    window: Vec<f64>,
+    /// The scale that turns FFT magnitudes into amplitudes relative to full scale.
+    normalization: f64,
+    fft: Arc<dyn RealToComplex<f64>>,
+    input: Vec<f64>,
+    output: Vec<Complex<f64>>,
+    scratch: Vec<Complex<f64>>,
+    /// The range of FFT bins covered by each band.
+    bands: Vec<(usize, usize)>,
+}
+
+impl Visualizer {
+    pub fn new(channels: usize, sample_rate: u32) -> Self {
+        // Hann window
+        let window: Vec<f64> = (0..FFT_SIZE)
+            .map(|i| 0.5 - 0.5 * (2.0 * PI * i as f64 / FFT_SIZE as f64).cos())
+            .collect();
+        let normalization = 2.0 / window.iter().sum::<f64>();
+
+        let bin_width = sample_rate as f64 / FFT_SIZE as f64;
+        let max_frequency = MAX_FREQUENCY.min(sample_rate as f64 / 2.0);
+        let ratio = (max_frequency / MIN_FREQUENCY).powf(1.0 / SPECTRUM_BANDS as f64);
+        let bands = (0..SPECTRUM_BANDS)
+            .map(|band| {
+                let low = MIN_FREQUENCY * ratio.powi(band as i32);
+                let high = low * ratio;
+                let first = ((low / bin_width).round() as usize).clamp(1, FFT_SIZE / 2 - 1);
+                let last = ((high / bin_width).round() as usize).clamp(first + 1, FFT_SIZE / 2);
+                (first, last)
+            })
+            .collect();
+
+        let fft = RealFftPlanner::<f64>::new().plan_fft_forward(FFT_SIZE);
+        let history_frames = FFT_SIZE + sample_rate as usize * MAX_DELAY_MS / 1000;
+
+        Self {
+            sample_rate,
+            history: vec![vec![0.0; history_frames]; channels],
+            history_frames: history_frames as u64,
+            written: 0,
+            measured: 0,
+            window,
+            normalization,
+            input: fft.make_input_vec(),
+            output: fft.make_output_vec(),
+            scratch: fft.make_scratch_vec(),
+            fft,
+            bands,
+        }
+    }
+
+    /// Whether the visualizer was created for the given output format.
+    pub fn matches_format(&self, channels: usize, sample_rate: u32) -> bool {
+        self.history.len() == channels && self.sample_rate == sample_rate
+    }
+
+    /// Add `frames` frames of audio. Never allocates.
+    pub fn push(&mut self, buffers: &[Vec<f64>], frames: usize) {
+        for (buffer, history) in buffers.iter().zip(&mut self.history) {
+            for (i, &sample) in buffer[..frames].iter().enumerate() {
+                history[((self.written + i as u64) % self.history_frames) as usize] = sample;
+            }
+        }
+        self.written += frames as u64;
+    }
+
+    /// Analyze the audio as it was `delay` frames ago, and start measuring levels anew from
+    /// there.
+    pub fn frame(&mut self, delay: usize) -> VisualizerFrame {
+        let history_frames = self.history_frames;
+        let delay = (delay as u64).min(history_frames - FFT_SIZE as u64);
+        let end = self.written.saturating_sub(delay);
+
+        let scale = 1.0 / self.history.len().max(1) as f64;
+        for (i, input) in self.input.iter_mut().enumerate() {
+            // frames from before the first push are silent
+            *input = match (end + i as u64).checked_sub(FFT_SIZE as u64) {
+                Some(frame) => {
+                    let idx = (frame % history_frames) as usize;
+                    self.history.iter().map(|h| h[idx]).sum::<f64>() * scale * self.window[i]
+                }
+                None => 0.0,
+            };
+        }
+        // only fails if the buffers have the wrong length
+        let _ = self
+            .fft
+            .process_with_scratch(&mut self.input, &mut self.output, &mut self.scratch);
+
+        let spectrum = self
+            .bands
+            .iter()
+            .map(|&(first, last)| {
+                let magnitude = self.output[first..last]
+                    .iter()
+                    .map(|bin| bin.norm())
+                    .fold(0.0, f64::max);
+                let db = 20.0 * (magnitude * self.normalization).log10();
+                (db as f32).max(SPECTRUM_FLOOR_DB)
+            })
+            .collect();
+
+        let start = self.measured.max(end.saturating_sub(history_frames));
+        let counted = end.saturating_sub(start);
+        let levels = self
+            .history
+            .iter()
+            .map(|history| {
+                let (sum_squares, peak) = (start..end)
+                    .map(|frame| history[(frame % history_frames) as usize])
+                    .fold((0.0, 0.0_f64), |(sum_squares, peak), sample| {
+                        (sum_squares + sample * sample, peak.max(sample.abs()))
+                    });
+
+                ChannelLevel {
+                    rms: if counted == 0 {
+                        0.0
+                    } else {
+                        (sum_squares / counted as f64).sqrt() as f32
+                    },
+                    peak: peak as f32,
+                }
+            })
+            .collect();
+
+        self.measured = self.measured.max(end);
+
+        VisualizerFrame { spectrum, levels }
+    }
+
+    /// Forget all audio, for example after seeking.
+    pub fn reset(&mut self) {
+        for history in &mut self.history {
+            history.fill(0.0);
+        }
+        self.written = 0;
+        self.measured = 0;
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn sine(frequency: f64, amplitude: f64, frames: usize) -> Vec<f64> {
+        (0..frames)
+            .map(|i| (2.0 * PI * frequency * i as f64 / 48000.0).sin() * amplitude)
+            .collect()
+    }
+
+    #[test]
+    fn measures_levels_per_channel() {
+        let mut visualizer = Visualizer::new(2, 48000);
+        visualizer.push(&[sine(1000.0, 0.5, 4800), vec![0.0; 4800]], 4800);
+
+        let frame = visualizer.frame(0);
+        assert!((frame.levels[0].peak - 0.5).abs() < 1e-3);
+        assert!((frame.levels[0].rms - 0.5 / 2f32.sqrt()).abs() < 1e-3);
+        assert_eq!(frame.levels[1], ChannelLevel::default());
+
+        // levels are measured from the previous frame onwards
+        let frame = visualizer.frame(0);
+        assert_eq!(frame.levels[0], ChannelLevel::default());
+    }
+
+    #[test]
+    fn spectrum_peaks_at_the_signal_frequency() {
+        let mut visualizer = Visualizer::new(1, 48000);
+        visualizer.push(&[sine(1000.0, 1.0, FFT_SIZE)], FFT_SIZE);
+
+        let frame = visualizer.frame(0);
+        assert_eq!(frame.spectrum.len(), SPECTRUM_BANDS);
+
+        let (loudest, level) = frame
+            .spectrum
+            .iter()
+            .enumerate()
+            .max_by(|a, b| a.1.total_cmp(b.1))
+            .unwrap();
+        let (first, last) = visualizer.bands[loudest];
+        let bin = 1000.0 / (48000.0 / FFT_SIZE as f64);
+        assert!((first as f64 - 1.0..=last as f64).contains(&bin));
+        assert!(level.abs() < 1.0, "level = {level}");
+
+        // bands far away from the signal only see the window's sidelobes
+        assert!(frame.spectrum[0] < -60.0);
+        assert!(frame.spectrum[SPECTRUM_BANDS - 1] < -60.0);
+    }
+
+    #[test]
+    fn silence_reads_as_the_floor() {
+        let mut visualizer = Visualizer::new(2, 44100);
+        visualizer.push(&[vec![0.0; 1024], vec![0.0; 1024]], 1024);
+
+        let frame = visualizer.frame(0);
+        assert!(frame.spectrum.iter().all(|&db| db == SPECTRUM_FLOOR_DB));
+    }
+
+    #[test]
+    fn analysis_is_delayed_by_the_unplayed_audio() {
+        let mut visualizer = Visualizer::new(1, 48000);
+        visualizer.push(&[sine(1000.0, 0.5, 4800)], 4800);
+        // audio after the sine hasn't been heard yet
+        visualizer.push(&[vec![0.0; 2400]], 2400);
+
+        let frame = visualizer.frame(2400);
+        assert!((frame.levels[0].peak - 0.5).abs() < 1e-3);
+        assert!(frame.spectrum.iter().any(|&db| db > -10.0));
+
+        // the next frame continues where this one ended
+        visualizer.push(&[vec![0.0; 2400]], 2400);
+        let frame = visualizer.frame(2400);
+        assert_eq!(frame.levels[0], ChannelLevel::default());
+        assert!(frame.spectrum.iter().all(|&db| db == SPECTRUM_FLOOR_DB));
+    }
+}
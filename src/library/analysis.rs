@@ -0,0 +1,454 @@
+mod loudness;
+
+use std::{
+    path::{Path, PathBuf},
+    sync::{
+        Arc,
+        atomic::{AtomicBool, Ordering},
+    },
+    time::SystemTime,
+};
+
+use anyhow::anyhow;
+use camino::Utf8PathBuf;
+use gpui::{App, Global};
+use sqlx::SqlitePool;
+use tokio::{
+    sync::mpsc::{
+        Receiver, Sender, UnboundedReceiver, UnboundedSender, channel, unbounded_channel,
+    },
+    task::spawn_blocking,
+};
+use tracing::{error, info, warn};
+
+use crate::{
+    library::{
+        analysis::loudness::{Loudness, LoudnessMeter},
+        scan::{ScanRecordUpdater, file_scan_timestamp},
+    },
+    media::{
+        cue::TrackRange,
+        errors::PlaybackReadError,
+        lookup_table::{try_open_media, try_write_tags},
+        metadata::{ReplayGainValues, TagUpdate},
+        pipeline::{ChannelBuffers, DecodeResult},
+        ranged::RangedStream,
+        traits::MediaProviderFeatures,
+    },
+    ui::models::Models,
+};
+
+/// Large enough to hold any single decoded packet.
+const DECODE_BUFFER_FRAMES: usize = 1 << 16;
+
+#[derive(Debug, PartialEq, Clone)]
+pub enum AnalysisEvent {
+    AnalysisProgress { current: u64, total: u64 },
+    AnalysisComplete,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+enum AnalysisCommand {
+    /// Analyze every album containing a track without ReplayGain values, and every track outside
+    /// of an album without them.
+    AnalyzeMissing {
+        write_tags: bool,
+    },
+    /// Analyze the entire library, replacing existing values.
+    AnalyzeAll {
+        write_tags: bool,
+    },
+    Stop,
+}
+
+#[derive(Debug, Clone, Copy)]
+struct AnalysisRun {
+    all: bool,
+    /// Whether the results are also written to the tags of the analyzed files.
+    write_tags: bool,
+}
+
+impl AnalysisRun {
+    /// Combine a newly requested run with one that is already waiting to start.
+    fn merge(pending: Option<AnalysisRun>, all: bool, write_tags: bool) -> AnalysisRun {
+        AnalysisRun {
+            all: all || pending.is_some_and(|run| run.all),
+            write_tags,
+        }
+    }
+}
+
+/// A track waiting to be analyzed.
+#[derive(sqlx::FromRow)]
+struct PendingTrack {
+    id: i64,
+    album_id: Option<i64>,
+    location: String,
+    start_ms: i64,
+    end_ms: Option<i64>,
+}
+
+impl PendingTrack {
+    fn range(&self) -> Option<TrackRange> {
+        TrackRange::from_columns(self.start_ms, self.end_ms)
+    }
+}
+
+/// Interface to the loudness analyzer, which measures tracks in the background and stores their
+/// ReplayGain values in the library.
+pub struct AnalysisInterface {
+    events_rx: Option<UnboundedReceiver<AnalysisEvent>>,
+    cmd_tx: Sender<AnalysisCommand>,
+}
+
+impl AnalysisInterface {
+    pub(self) fn new(
+        events_rx: Option<UnboundedReceiver<AnalysisEvent>>,
+        cmd_tx: Sender<AnalysisCommand>,
+    ) -> Self {
+        AnalysisInterface { events_rx, cmd_tx }
+    }
+
+    pub fn analyze_missing(&self, write_tags: bool) {
+        self.cmd_tx
+            .blocking_send(AnalysisCommand::AnalyzeMissing { write_tags })
+            .expect("could not send analysis start command");
+    }
+
+    pub fn analyze_all(&self, write_tags: bool) {
+        self.cmd_tx
+            .blocking_send(AnalysisCommand::AnalyzeAll { write_tags })
+            .expect("could not send full analysis start command");
+    }
+
+    pub fn stop(&self) {
+        self.cmd_tx
+            .blocking_send(AnalysisCommand::Stop)
+            .expect("could not send analysis stop command");
+    }
+
+    pub fn start_broadcast(&mut self, cx: &mut App) {
+        let mut events_rx = None;
+        std::mem::swap(&mut self.events_rx, &mut events_rx);
+
+        let state_model = cx.global::<Models>().analysis_state.clone();
+
+        let Some(mut events_rx) = events_rx else {
+            return;
+        };
+        cx.spawn(async move |cx| {
+            while let Some(event) = events_rx.recv().await {
+                state_model.update(cx, |m, cx| {
+                    *m = event;
+                    cx.notify()
+                });
+            }
+        })
+        .detach();
+    }
+}
+
+impl Global for AnalysisInterface {}
+
+/// Decode a file, or the section of it played by a CUE sheet track, and measure its loudness.
+/// Returns `None` if the analysis was cancelled.
+fn measure_track(
+    path: &Path,
+    range: Option<TrackRange>,
+    cancel: &AtomicBool,
+) -> anyhow::Result<Option<Loudness>> {
+    let mut stream = try_open_media(path, MediaProviderFeatures::PROVIDES_DECODER)?
+        .ok_or_else(|| anyhow!("no media provider can decode this file"))?;
+    stream.start_playback()?;
+
+    if let Some(range) = range {
+        stream = Box::new(RangedStream::new(stream, path, range)?);
+    }
+
+    let channels = stream.channels()?.count() as usize;
+    let sample_rate = stream.sample_rate()?;
+
+    let (producers, mut consumers) =
+        ChannelBuffers::<f64>::new(channels, DECODE_BUFFER_FRAMES).split();
+    let mut meter = LoudnessMeter::new(sample_rate, channels);
+
+    loop {
+        if cancel.load(Ordering::Relaxed) {
+            stream.close()?;
+            return Ok(None);
+        }
+
+        match stream.decode_into(&producers) {
+            Ok(DecodeResult::Decoded { .. }) => {
+                let read = consumers.try_read_to_staging(DECODE_BUFFER_FRAMES);
+                meter.process(consumers.staging(), read);
+            }
+            Ok(DecodeResult::Eof) | Err(PlaybackReadError::Eof) => break,
+            // a corrupt packet only loses a few milliseconds of audio
+            Err(PlaybackReadError::Unknown(_)) => {}
+            Err(e) => return Err(e.into()),
+        }
+    }
+
+    stream.close()?;
+    Ok(Some(meter.finish()))
+}
+
+async fn list_tracks(pool: &SqlitePool, all: bool) -> sqlx::Result<Vec<PendingTrack>> {
+    let query = if all {
+        include_str!("../../queries/analysis/list_all_tracks.sql")
+    } else {
+        include_str!("../../queries/analysis/list_tracks_missing_replaygain.sql")
+    };
+
+    sqlx::query_as(query).fetch_all(pool).await
+}
+
+/// Turn the measurements of an album (or a single track outside of an album) into ReplayGain
+/// values for each track.
+fn replaygain_values(
+    results: &[(i64, PathBuf, Loudness)],
+    is_album: bool,
+) -> Vec<(i64, PathBuf, ReplayGainValues)> {
+    let album =
+        is_album.then(|| Loudness::combine(results.iter().map(|(_, _, loudness)| loudness)));
+    let album_gain = album.as_ref().and_then(Loudness::gain_db);
+    let album_peak = album.as_ref().map(|album| album.peak);
+
+    results
+        .iter()
+        .filter_map(|(id, path, loudness)| {
+            // silent tracks have no meaningful gain
+            let track_gain = loudness.gain_db()?;
+
+            Some((
+                *id,
+                path.clone(),
+                ReplayGainValues {
+                    track_gain,
+                    track_peak: loudness.peak,
+                    album_gain,
+                    album_peak,
+                },
+            ))
+        })
+        .collect()
+}
+
+async fn store_results(
+    pool: &SqlitePool,
+    values: &[(i64, PathBuf, ReplayGainValues)],
+) -> sqlx::Result<()> {
+    let query = include_str!("../../queries/analysis/update_track_replaygain.sql");
+
+    let mut tx = pool.begin().await?;
+
+    for (id, _, values) in values {
+        sqlx::query(query)
+            .bind(values.track_gain)
+            .bind(values.track_peak)
+            .bind(values.album_gain)
+            .bind(values.album_peak)
+            .bind(id)
+            .execute(&mut *tx)
+            .await?;
+    }
+
+    tx.commit().await
+}
+
+/// Write ReplayGain tags to the analyzed files. Returns the files that were changed, with their
+/// scan timestamps from before and after writing.
+fn write_tags(
+    values: Vec<(i64, PathBuf, ReplayGainValues)>,
+) -> Vec<(Utf8PathBuf, SystemTime, SystemTime)> {
+    let mut rewritten = Vec::with_capacity(values.len());
+
+    for (_, path, values) in values {
+        let Ok(path) = Utf8PathBuf::from_path_buf(path) else {
+            continue;
+        };
+        let before = file_scan_timestamp(&path);
+
+        let update = TagUpdate {
+            replaygain: Some(values),
+            ..Default::default()
+        };
+
+        match try_write_tags(path.as_std_path(), &update) {
+            Ok(true) => {}
+            Ok(false) => {
+                warn!("Writing tags is not supported for {:?}", path);
+                continue;
+            }
+            Err(e) => {
+                warn!("Could not write ReplayGain tags to {:?}: {}", path, e);
+                continue;
+            }
+        }
+
+        if let Some(before) = before
+            && let Some(after) = file_scan_timestamp(&path)
+        {
+            rewritten.push((path, before, after));
+        }
+    }
+
+    rewritten
+}
+
+async fn run_analyzer(
+    pool: SqlitePool,
+    record_updater: ScanRecordUpdater,
+    mut command_rx: Receiver<AnalysisCommand>,
+    event_tx: UnboundedSender<AnalysisEvent>,
+) {
+    let num_workers = std::thread::available_parallelism()
+        .map(|n| n.get())
+        .unwrap_or(4)
+        .clamp(2, 8)
+        - 1;
+
+    let mut pending: Option<AnalysisRun> = None;
+
+    loop {
+        let AnalysisRun { all, write_tags } = match pending.take() {
+            Some(run) => run,
+            None => match command_rx.recv().await {
+                Some(AnalysisCommand::AnalyzeMissing { write_tags }) => {
+                    AnalysisRun::merge(None, false, write_tags)
+                }
+                Some(AnalysisCommand::AnalyzeAll { write_tags }) => {
+                    AnalysisRun::merge(None, true, write_tags)
+                }
+                Some(AnalysisCommand::Stop) => continue,
+                None => return, // channel closed, shut down
+            },
+        };
+
+        let tracks = match list_tracks(&pool, all).await {
+            Ok(tracks) => tracks,
+            Err(e) => {
+                error!("Could not list tracks for loudness analysis: {:?}", e);
+                let _ = event_tx.send(AnalysisEvent::AnalysisComplete);
+                continue;
+            }
+        };
+
+        info!(
+            "Starting loudness analysis of {} tracks (all: {})",
+            tracks.len(),
+            all
+        );
+
+        let time_start = std::time::Instant::now();
+        let total = tracks.len() as u64;
+        let mut analyzed: u64 = 0;
+        let cancel_flag = Arc::new(AtomicBool::new(false));
+
+        let _ = event_tx.send(AnalysisEvent::AnalysisProgress { current: 0, total });
+
+        // tracks are sorted by album, so every album is a contiguous run
+        for group in tracks.chunk_by(|a, b| a.album_id.is_some() && a.album_id == b.album_id) {
+            let mut results: Vec<(i64, PathBuf, Loudness)> = Vec::with_capacity(group.len());
+
+            for chunk in group.chunks(num_workers) {
+                let handles: Vec<_> = chunk
+                    .iter()
+                    .map(|track| {
+                        let path = PathBuf::from(&track.location);
+                        let range = track.range();
+                        let id = track.id;
+                        let cancel_flag = Arc::clone(&cancel_flag);
+                        spawn_blocking(move || {
+                            let result = measure_track(&path, range, &cancel_flag);
+                            (id, path, result)
+                        })
+                    })
+                    .collect();
+
+                for handle in handles {
+                    match handle.await.expect("analysis task panicked") {
+                        (id, path, Ok(Some(loudness))) => results.push((id, path, loudness)),
+                        (_, _, Ok(None)) => {}
+                        (_, path, Err(e)) => {
+                            warn!("Could not analyze {:?}: {}", path, e);
+                        }
+                    }
+                }
+
+                analyzed += chunk.len() as u64;
+                let _ = event_tx.send(AnalysisEvent::AnalysisProgress {
+                    current: analyzed,
+                    total,
+                });
+
+                while let Ok(command) = command_rx.try_recv() {
+                    match command {
+                        AnalysisCommand::Stop => cancel_flag.store(true, Ordering::Relaxed),
+                        AnalysisCommand::AnalyzeMissing { write_tags } => {
+                            pending = Some(AnalysisRun::merge(pending, false, write_tags));
+                        }
+                        AnalysisCommand::AnalyzeAll { write_tags } => {
+                            pending = Some(AnalysisRun::merge(pending, true, write_tags));
+                        }
+                    }
+                }
+
+                if cancel_flag.load(Ordering::Relaxed) {
+                    break;
+                }
+            }
+
+            // a partially analyzed album would get the wrong album gain
+            if cancel_flag.load(Ordering::Relaxed) {
+                break;
+            }
+
+            let values = replaygain_values(&results, group[0].album_id.is_some());
+            if let Err(e) = store_results(&pool, &values).await {
+                error!("Failed to store loudness analysis results: {:?}", e);
+                continue;
+            }
+
+            if write_tags {
+                // tracks split from a file by a CUE sheet share the file's tags, so their values
+                // are only kept in the library
+                let values: Vec<_> = values
+                    .into_iter()
+                    .filter(|(id, _, _)| {
+                        group
+                            .iter()
+                            .any(|track| track.id == *id && track.range().is_none())
+                    })
+                    .collect();
+                let rewritten = spawn_blocking(move || self::write_tags(values))
+                    .await
+                    .expect("tag writing task panicked");
+                record_updater.files_rewritten(rewritten).await;
+            }
+        }
+
+        info!(
+            "Loudness analysis {} after {} tracks in {} seconds",
+            if cancel_flag.load(Ordering::Relaxed) {
+                "cancelled"
+            } else {
+                "complete"
+            },
+            analyzed,
+            time_start.elapsed().as_secs_f32()
+        );
+
+        let _ = event_tx.send(AnalysisEvent::AnalysisComplete);
+    }
+}
+
+pub fn start_analyzer(pool: SqlitePool, record_updater: ScanRecordUpdater) -> AnalysisInterface {
+    let (cmd_tx, command_rx) = channel(10);
+    let (event_tx, events_rx) = unbounded_channel();
+
+    crate::RUNTIME.spawn(run_analyzer(pool, record_updater, command_rx, event_tx));
+
+    AnalysisInterface::new(Some(events_rx), cmd_tx)
+}
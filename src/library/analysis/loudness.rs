@@ -0,0 +1,343 @@
+use std::{collections::VecDeque, f64::consts::PI};
+
+use crate::devices::dsp::biquad::{BiquadState, Coefficients};
+
+/// The loudness ReplayGain 2.0 normalizes to, in LUFS.
+pub const REFERENCE_LUFS: f64 = -18.0;
+
+/// Blocks quieter than this are never counted towards the integrated loudness.
+const ABSOLUTE_GATE_LUFS: f64 = -70.0;
+/// Blocks this much quieter than the ungated loudness are not counted either.
+const RELATIVE_GATE_LU: f64 = -10.0;
+
+/// Gating blocks are 400ms long and start every 100ms, so each one is made of four 100ms
+/// sub-blocks.
+const SUB_BLOCKS_PER_BLOCK: usize = 4;
+
+const OVERSAMPLING: usize = 4;
+const TAPS_PER_PHASE: usize = 12;
+
+/// EBU R128 loudness meter, measuring integrated loudness (ITU-R BS.1770-4) and true peak.
+///
+/// Instead of the loudness itself, the meter keeps the mean square of every gating block, so that
+/// the blocks of several tracks can be pooled to measure the loudness of a whole album.
+pub struct LoudnessMeter {
+    shelf: Coefficients,
+    high_pass: Coefficients,
+    filters: Vec<(BiquadState, BiquadState)>,
+    weights: Vec<f64>,
+    sub_block_frames: usize,
+    sub_block_position: usize,
+    sub_block_energy: f64,
+    recent: VecDeque<f64>,
+    blocks: Vec<f64>,
+    true_peak: TruePeak,
+}
+
+impl LoudnessMeter {
+    pub fn new(sample_rate: u32, channels: usize) -> Self {
+        let (shelf, high_pass) = k_weighting(sample_rate);
+
+        Self {
+            shelf,
+            high_pass,
+            filters: vec![Default::default(); channels],
+            weights: (0..channels)
+                .map(|channel| channel_weight(channel, channels))
+                .collect(),
+            sub_block_frames: (sample_rate as usize / 10).max(1),
+            sub_block_position: 0,
+            sub_block_energy: 0.0,
+            recent: VecDeque::with_capacity(SUB_BLOCKS_PER_BLOCK + 1),
+            blocks: Vec::new(),
+            true_peak: TruePeak::new(channels),
+        }
+    }
+
+    /// Measure `frames` frames of planar audio.
+    pub fn process(&mut self, buffers: &[Vec<f64>], frames: usize) {
+        self.true_peak.process(buffers, frames);
+
+        for frame in 0..frames {
+            let mut energy = 0.0;
+
+            for ((channel, (shelf, high_pass)), weight) in
+                buffers.iter().zip(&mut self.filters).zip(&self.weights)
+            {
+                let weighted =
+                    high_pass.process(&self.high_pass, shelf.process(&self.shelf, channel[frame]));
+                energy += weight * weighted * weighted;
+            }
+
+            self.sub_block_energy += energy;
+            self.sub_block_position += 1;
+
+            if self.sub_block_position == self.sub_block_frames {
+                self.recent
+                    .push_back(self.sub_block_energy / self.sub_block_frames as f64);
+                if self.recent.len() > SUB_BLOCKS_PER_BLOCK {
+                    self.recent.pop_front();
+                }
+                if self.recent.len() == SUB_BLOCKS_PER_BLOCK {
+                    self.blocks
+                        .push(self.recent.iter().sum::<f64>() / SUB_BLOCKS_PER_BLOCK as f64);
+                }
+
+                self.sub_block_position = 0;
+                self.sub_block_energy = 0.0;
+            }
+        }
+    }
+
+    pub fn finish(self) -> Loudness {
+        Loudness {
+            blocks: self.blocks,
+            peak: self.true_peak.peak,
+        }
+    }
+}
+
+/// The result of measuring a track, or a group of tracks.
+#[derive(Debug, Clone, Default)]
+pub struct Loudness {
+    blocks: Vec<f64>,
+    /// The true peak, as a linear amplitude.
+    pub peak: f64,
+}
+
+impl Loudness {
+    /// Pool the measurements of several tracks, as if they were played back to back.
+    pub fn combine<'a>(tracks: impl IntoIterator<Item = &'a Loudness>) -> Self {
+        let mut combined = Loudness::default();
+
+        for track in tracks {
+            combined.blocks.extend_from_slice(&track.blocks);
+            combined.peak = combined.peak.max(track.peak);
+        }
+
+        combined
+    }
+
+    /// The gated integrated loudness in LUFS, or `None` if the audio is silent or shorter than a
+    /// single gating block.
+    pub fn integrated(&self) -> Option<f64> {
+        let absolute_gate = power(ABSOLUTE_GATE_LUFS);
+        let (sum, count) = mean_above(&self.blocks, absolute_gate)?;
+
+        let relative_gate = sum / count as f64 * 10.0_f64.powf(RELATIVE_GATE_LU / 10.0);
+        let (sum, count) = mean_above(&self.blocks, relative_gate.max(absolute_gate))?;
+
+        Some(lufs(sum / count as f64))
+    }
+
+    /// The ReplayGain adjustment needed to bring the audio to [`REFERENCE_LUFS`], in dB.
+    pub fn gain_db(&self) -> Option<f64> {
+        self.integrated().map(|loudness| REFERENCE_LUFS - loudness)
+    }
+}
+
+fn mean_above(blocks: &[f64], threshold: f64) -> Option<(f64, usize)> {
+    let (sum, count) = blocks
+        .iter()
+        .filter(|&&block| block > threshold)
+        .fold((0.0, 0), |(sum, count), block| (sum + block, count + 1));
+
+    (count > 0).then_some((sum, count))
+}
+
+fn lufs(power: f64) -> f64 {
+    -0.691 + 10.0 * power.log10()
+}
+
+fn power(lufs: f64) -> f64 {
+    10.0_f64.powf((lufs + 0.691) / 10.0)
+}
+
+/// Surround channels are weighted higher, and the LFE channel is ignored. Only 5.1 audio is
+/// known to carry surround channels in the usual order.
+fn channel_weight(channel: usize, channels: usize) -> f64 {
+    match (channels, channel) {
+        (6, 3) => 0.0,
+        (6, 4 | 5) => 1.41,
+        _ => 1.0,
+    }
+}
+
+/// The two stages of the K-weighting filter, designed for the given sample rate. These reproduce
+/// the coefficients listed in BS.1770 at 48kHz.
+fn k_weighting(sample_rate: u32) -> (Coefficients, Coefficients) {
+    let rate = sample_rate as f64;
+
+    // high shelf modelling the acoustic effect of the head
+    let frequency = 1681.974450955533;
+    let gain_db = 3.999843853973347;
+    let q = 0.7071752369554196;
+
+    let k = (PI * frequency / rate).tan();
+    let vh = 10.0_f64.powf(gain_db / 20.0);
+    let vb = vh.powf(0.4996667741545416);
+
+    let shelf = Coefficients::from_raw(
+        [
+            vh + vb * k / q + k * k,
+            2.0 * (k * k - vh),
+            vh - vb * k / q + k * k,
+        ],
+        [
+            1.0 + k / q + k * k,
+            2.0 * (k * k - 1.0),
+            1.0 - k / q + k * k,
+        ],
+    );
+
+    // revised low-frequency B-curve high pass
+    let frequency = 38.13547087602444;
+    let q = 0.5003270373238773;
+
+    let k = (PI * frequency / rate).tan();
+
+    let high_pass = Coefficients::from_raw(
+        [1.0, -2.0, 1.0],
+        [
+            1.0 + k / q + k * k,
+            2.0 * (k * k - 1.0),
+            1.0 - k / q + k * k,
+        ],
+    );
+
+    (shelf, high_pass)
+}
+
+/// True peak measurement, finding peaks between samples by oversampling 4x with a polyphase
+/// windowed-sinc interpolator.
+struct TruePeak {
+    phases: Vec<[f64; TAPS_PER_PHASE]>,
+    history: Vec<[f64; TAPS_PER_PHASE]>,
+    position: usize,
+    peak: f64,
+}
+
+impl TruePeak {
+    fn new(channels: usize) -> Self {
+        let length = OVERSAMPLING * TAPS_PER_PHASE;
+        let center = (length - 1) as f64 / 2.0;
+
+        let mut phases = vec![[0.0; TAPS_PER_PHASE]; OVERSAMPLING];
+        for n in 0..length {
+            let x = (n as f64 - center) / OVERSAMPLING as f64;
+            let sinc = if x == 0.0 {
+                1.0
+            } else {
+                (PI * x).sin() / (PI * x)
+            };
+            let t = 2.0 * PI * n as f64 / (length - 1) as f64;
+            let blackman = 0.42 - 0.5 * t.cos() + 0.08 * (2.0 * t).cos();
+
+            phases[n % OVERSAMPLING][n / OVERSAMPLING] = sinc * blackman;
+        }
+
+        // every phase should pass DC unchanged
+        for phase in &mut phases {
+            let sum: f64 = phase.iter().sum();
+            for tap in phase.iter_mut() {
+                *tap /= sum;
+            }
+        }
+
+        Self {
+            phases,
+            history: vec![[0.0; TAPS_PER_PHASE]; channels],
+            position: 0,
+            peak: 0.0,
+        }
+    }
+
+    fn process(&mut self, buffers: &[Vec<f64>], frames: usize) {
+        for frame in 0..frames {
+            for (history, channel) in self.history.iter_mut().zip(buffers) {
+                let sample = channel[frame];
+                history[self.position] = sample;
+                self.peak = self.peak.max(sample.abs());
+
+                for phase in &self.phases {
+                    let interpolated: f64 = phase
+                        .iter()
+                        .enumerate()
+                        .map(|(k, tap)| {
+                            tap * history[(self.position + TAPS_PER_PHASE - k) % TAPS_PER_PHASE]
+                        })
+                        .sum();
+                    self.peak = self.peak.max(interpolated.abs());
+                }
+            }
+
+            self.position = (self.position + 1) % TAPS_PER_PHASE;
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn measure(buffers: &[Vec<f64>], sample_rate: u32) -> Loudness {
+        let mut meter = LoudnessMeter::new(sample_rate, buffers.len());
+        meter.process(buffers, buffers[0].len());
+        meter.finish()
+    }
+
+    fn sine(frequency: f64, amplitude_db: f64, seconds: f64, sample_rate: u32) -> Vec<f64> {
+        let amplitude = 10.0_f64.powf(amplitude_db / 20.0);
+        (0..(seconds * sample_rate as f64) as usize)
+            .map(|n| amplitude * (2.0 * PI * frequency * n as f64 / sample_rate as f64).sin())
+            .collect()
+    }
+
+    #[test]
+    fn stereo_sine_matches_reference_level() {
+        // EBU Tech 3341 case 1: a 1kHz sine at -23dBFS in both channels measures -23 LUFS
+        for sample_rate in [44100, 48000] {
+            let channel = sine(1000.0, -23.0, 20.0, sample_rate);
+            let loudness = measure(&[channel.clone(), channel], sample_rate);
+
+            assert!((loudness.integrated().unwrap() + 23.0).abs() < 0.1);
+            assert!((loudness.gain_db().unwrap() - 5.0).abs() < 0.1);
+        }
+    }
+
+    #[test]
+    fn silence_is_gated() {
+        let tone = sine(1000.0, -23.0, 10.0, 48000);
+        let mut channel = tone.clone();
+        channel.extend(std::iter::repeat_n(0.0, 48000 * 10));
+
+        // without gating, the silence would make this 3dB quieter
+        let expected = measure(&[tone], 48000).integrated().unwrap();
+        let loudness = measure(&[channel], 48000).integrated().unwrap();
+        assert!((loudness - expected).abs() < 0.1);
+        assert!(measure(&[vec![0.0; 48000]], 48000).integrated().is_none());
+    }
+
+    #[test]
+    fn album_loudness_pools_blocks() {
+        let quiet = measure(&[sine(1000.0, -26.0, 10.0, 48000)], 48000);
+        let loud = measure(&[sine(1000.0, -20.0, 10.0, 48000)], 48000);
+        let album = Loudness::combine([&quiet, &loud]);
+
+        let integrated = album.integrated().unwrap();
+        assert!(integrated > quiet.integrated().unwrap());
+        assert!(integrated < loud.integrated().unwrap());
+        assert_eq!(album.peak, loud.peak);
+    }
+
+    #[test]
+    fn true_peak_finds_inter_sample_peaks() {
+        // a quarter of the sample rate, offset so that every sample lands at 0.707
+        let channel: Vec<f64> = (0..48000)
+            .map(|n| (PI / 2.0 * n as f64 + PI / 4.0).sin())
+            .collect();
+        let loudness = measure(&[channel], 48000);
+
+        assert!((loudness.peak - 1.0).abs() < 0.05);
+    }
+}
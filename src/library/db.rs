@@ -9,7 +9,10 @@ use sqlx::{
 use tracing::debug;
 
 use crate::{
-    library::types::{ArtistWithCounts, Playlist, PlaylistItem, TrackStats},
+    library::types::{
+        ArtistWithCounts, Bookmark, Playlist, PlaylistItem, ResumeInfo, TrackProgress, TrackStats,
+    },
+    media::cue::TrackRange,
     ui::app::Pool,
 };
 
@@ -174,10 +177,11 @@ pub async fn list_albums(
     Ok(albums)
 }
 
+#[allow(clippy::type_complexity)]
 pub async fn list_tracks(
     pool: &SqlitePool,
     sort_method: TrackSortMethod,
-) -> sqlx::Result<Vec<(i64, String, Option<i64>, String)>> {
+) -> sqlx::Result<Vec<(i64, String, Option<i64>, String, Option<TrackRange>)>> {
     let query = match sort_method {
         TrackSortMethod::TitleAsc => {
             include_str!("../../queries/library/find_tracks_title_asc.sql")
@@ -211,11 +215,22 @@ pub async fn list_tracks(
         }
     };
 
-    let tracks = sqlx::query_as::<_, (i64, String, Option<i64>, String)>(query)
+    let tracks = sqlx::query_as::<_, (i64, String, Option<i64>, String, i64, Option<i64>)>(query)
         .fetch_all(pool)
         .await?;
 
-    Ok(tracks)
+    Ok(tracks
+        .into_iter()
+        .map(|(id, title, album_id, location, start_ms, end_ms)| {
+            (
+                id,
+                title,
+                album_id,
+                location,
+                TrackRange::from_columns(start_ms, end_ms),
+            )
+        })
+        .collect())
 }
 
 pub async fn list_tracks_in_album(
@@ -400,11 +415,16 @@ pub async fn get_track_by_id(pool: &SqlitePool, track_id: i64) -> sqlx::Result<A
     Ok(track)
 }
 
-pub async fn get_track_by_path(pool: &SqlitePool, path: &Path) -> sqlx::Result<Option<Arc<Track>>> {
+pub async fn get_track_by_path(
+    pool: &SqlitePool,
+    path: &Path,
+    range: Option<TrackRange>,
+) -> sqlx::Result<Option<Arc<Track>>> {
     let query = include_str!("../../queries/library/find_track_by_path.sql");
 
     let track = sqlx::query_as(query)
         .bind(path.to_string_lossy().as_ref())
+        .bind(TrackRange::start_column(range))
         .fetch_optional(pool)
         .await?
         .map(Arc::new);
@@ -516,18 +536,26 @@ pub async fn get_playlist(pool: &SqlitePool, playlist_id: i64) -> sqlx::Result<A
     Ok(Arc::new(playlist))
 }
 
+/// Returns (location, range)
 pub async fn get_playlist_track_files(
     pool: &SqlitePool,
     playlist_id: i64,
-) -> sqlx::Result<Arc<Vec<String>>> {
+) -> sqlx::Result<Arc<Vec<(String, Option<TrackRange>)>>> {
     let query = include_str!("../../queries/playlist/get_track_files.sql");
 
-    let track_files: Vec<(String,)> = sqlx::query_as(query)
+    let track_files: Vec<(String, i64, Option<i64>)> = sqlx::query_as(query)
         .bind(playlist_id)
         .fetch_all(pool)
         .await?;
 
-    Ok(Arc::new(track_files.into_iter().map(|v| v.0).collect()))
+    Ok(Arc::new(
+        track_files
+            .into_iter()
+            .map(|(location, start_ms, end_ms)| {
+                (location, TrackRange::from_columns(start_ms, end_ms))
+            })
+            .collect(),
+    ))
 }
 
 /// Returns (playlist_item_id, track_id, album_id)
@@ -769,12 +797,26 @@ pub async fn artist_id_for_album(pool: &SqlitePool, album_id: i64) -> sqlx::Resu
     Ok(artist_id)
 }
 
-pub async fn get_all_tracks(pool: &SqlitePool) -> sqlx::Result<Vec<(String, i64, i64)>> {
+/// Returns (location, range, track_id, album_id)
+pub async fn get_all_tracks(
+    pool: &SqlitePool,
+) -> sqlx::Result<Vec<(String, Option<TrackRange>, i64, i64)>> {
     let query = include_str!("../../queries/library/get_all_tracks.sql");
 
-    let tracks: Vec<(String, i64, i64)> = sqlx::query_as(query).fetch_all(pool).await?;
-
-    Ok(tracks)
+    let tracks: Vec<(String, i64, Option<i64>, i64, i64)> =
+        sqlx::query_as(query).fetch_all(pool).await?;
+
+    Ok(tracks
+        .into_iter()
+        .map(|(location, start_ms, end_ms, id, album_id)| {
+            (
+                location,
+                TrackRange::from_columns(start_ms, end_ms),
+                id,
+                album_id,
+            )
+        })
+        .collect())
 }
 
 pub async fn list_album_paths(pool: &SqlitePool, album_id: i64) -> sqlx::Result<Vec<String>> {
@@ -796,6 +838,84 @@ pub async fn lyrics_for_track(pool: &SqlitePool, track_id: i64) -> sqlx::Result<
     Ok(row.map(|(content,)| content))
 }
 
+pub async fn bookmarks_for_track(
+    pool: &SqlitePool,
+    track_id: i64,
+) -> sqlx::Result<Arc<Vec<Bookmark>>> {
+    let query = include_str!("../../queries/library/get_bookmarks_by_track_id.sql");
+
+    let bookmarks = sqlx::query_as(query).bind(track_id).fetch_all(pool).await?;
+
+    Ok(Arc::new(bookmarks))
+}
+
+pub async fn create_bookmark(
+    pool: &SqlitePool,
+    track_id: i64,
+    name: &str,
+    position_ms: i64,
+) -> sqlx::Result<i64> {
+    let query = include_str!("../../queries/library/create_bookmark.sql");
+
+    let id = sqlx::query(query)
+        .bind(track_id)
+        .bind(name)
+        .bind(position_ms)
+        .execute(pool)
+        .await?
+        .last_insert_rowid();
+
+    Ok(id)
+}
+
+pub async fn delete_bookmark(pool: &SqlitePool, bookmark_id: i64) -> sqlx::Result<()> {
+    let query = include_str!("../../queries/library/delete_bookmark.sql");
+
+    sqlx::query(query).bind(bookmark_id).execute(pool).await?;
+
+    Ok(())
+}
+
+pub async fn resume_info_by_path(
+    pool: &SqlitePool,
+    path: &Path,
+    range: Option<TrackRange>,
+) -> sqlx::Result<Option<ResumeInfo>> {
+    let query = include_str!("../../queries/library/get_resume_info_by_path.sql");
+
+    sqlx::query_as(query)
+        .bind(path.to_string_lossy().as_ref())
+        .bind(TrackRange::start_column(range))
+        .fetch_optional(pool)
+        .await
+}
+
+pub async fn all_track_progress(pool: &SqlitePool) -> sqlx::Result<Arc<Vec<TrackProgress>>> {
+    let query = include_str!("../../queries/library/get_all_track_progress.sql");
+
+    let progress = sqlx::query_as(query).fetch_all(pool).await?;
+
+    Ok(Arc::new(progress))
+}
+
+pub async fn save_track_progress(
+    pool: &SqlitePool,
+    track_id: i64,
+    position_ms: i64,
+    finished: bool,
+) -> sqlx::Result<()> {
+    let query = include_str!("../../queries/library/upsert_track_progress.sql");
+
+    sqlx::query(query)
+        .bind(track_id)
+        .bind(position_ms)
+        .bind(finished)
+        .execute(pool)
+        .await?;
+
+    Ok(())
+}
+
 pub trait LibraryAccess {
     fn list_albums(&self, sort_method: AlbumSortMethod) -> sqlx::Result<Vec<(u32, String)>>;
     // TODO: handle this better
@@ -803,13 +923,17 @@ pub trait LibraryAccess {
     fn list_tracks(
         &self,
         sort_method: TrackSortMethod,
-    ) -> sqlx::Result<Vec<(i64, String, Option<i64>, String)>>;
+    ) -> sqlx::Result<Vec<(i64, String, Option<i64>, String, Option<TrackRange>)>>;
     fn list_tracks_in_album(&self, album_id: i64) -> sqlx::Result<Arc<Vec<Track>>>;
     fn get_album_by_id(&self, album_id: i64, method: AlbumMethod) -> sqlx::Result<Arc<Album>>;
     fn get_artist_name_by_id(&self, artist_id: i64) -> sqlx::Result<Arc<String>>;
     fn get_artist_by_id(&self, artist_id: i64) -> sqlx::Result<Arc<Artist>>;
     fn get_track_by_id(&self, track_id: i64) -> sqlx::Result<Arc<Track>>;
-    fn get_track_by_path(&self, path: &Path) -> sqlx::Result<Option<Arc<Track>>>;
+    fn get_track_by_path(
+        &self,
+        path: &Path,
+        range: Option<TrackRange>,
+    ) -> sqlx::Result<Option<Arc<Track>>>;
     fn list_albums_search(&self) -> sqlx::Result<Vec<(u32, String, String)>>;
     #[allow(clippy::type_complexity)]
     fn list_tracks_search(&self) -> sqlx::Result<Vec<(i64, String, String, Option<i64>)>>;
@@ -819,7 +943,10 @@ pub trait LibraryAccess {
     fn rename_playlist(&self, playlist_id: i64, name: &str) -> sqlx::Result<()>;
     fn get_all_playlists(&self) -> sqlx::Result<Arc<Vec<Playlist>>>;
     fn get_playlist(&self, playlist_id: i64) -> sqlx::Result<Arc<Playlist>>;
-    fn get_playlist_track_files(&self, playlist_id: i64) -> sqlx::Result<Arc<Vec<String>>>;
+    fn get_playlist_track_files(
+        &self,
+        playlist_id: i64,
+    ) -> sqlx::Result<Arc<Vec<(String, Option<TrackRange>)>>>;
     fn get_playlist_tracks(&self, playlist_id: i64) -> sqlx::Result<Arc<Vec<(i64, i64, i64)>>>;
     fn get_playlist_tracks_sorted(
         &self,
@@ -846,9 +973,24 @@ pub trait LibraryAccess {
     ) -> sqlx::Result<Arc<Vec<Track>>>;
     fn get_all_tracks_by_artist(&self, artist_id: i64) -> sqlx::Result<Arc<Vec<Track>>>;
     fn artist_id_for_album(&self, album_id: i64) -> sqlx::Result<i64>;
-    fn get_all_tracks(&self) -> sqlx::Result<Vec<(String, i64, i64)>>;
+    fn get_all_tracks(&self) -> sqlx::Result<Vec<(String, Option<TrackRange>, i64, i64)>>;
     fn list_album_paths(&self, album_id: i64) -> sqlx::Result<Vec<String>>;
     fn lyrics_for_track(&self, track_id: i64) -> sqlx::Result<Option<String>>;
+    fn bookmarks_for_track(&self, track_id: i64) -> sqlx::Result<Arc<Vec<Bookmark>>>;
+    fn create_bookmark(&self, track_id: i64, name: &str, position_ms: i64) -> sqlx::Result<i64>;
+    fn delete_bookmark(&self, bookmark_id: i64) -> sqlx::Result<()>;
+    fn resume_info_by_path(
+        &self,
+        path: &Path,
+        range: Option<TrackRange>,
+    ) -> sqlx::Result<Option<ResumeInfo>>;
+    fn all_track_progress(&self) -> sqlx::Result<Arc<Vec<TrackProgress>>>;
+    fn save_track_progress(
+        &self,
+        track_id: i64,
+        position_ms: i64,
+        finished: bool,
+    ) -> sqlx::Result<()>;
 }
 
 impl LibraryAccess for App {
@@ -860,7 +1002,7 @@ impl LibraryAccess for App {
     fn list_tracks(
         &self,
         sort_method: TrackSortMethod,
-    ) -> sqlx::Result<Vec<(i64, String, Option<i64>, String)>> {
+    ) -> sqlx::Result<Vec<(i64, String, Option<i64>, String, Option<TrackRange>)>> {
         let pool: &Pool = self.global();
         crate::RUNTIME.block_on(list_tracks(&pool.0, sort_method))
     }
@@ -890,9 +1032,13 @@ impl LibraryAccess for App {
         crate::RUNTIME.block_on(get_track_by_id(&pool.0, track_id))
     }
 
-    fn get_track_by_path(&self, path: &Path) -> sqlx::Result<Option<Arc<Track>>> {
+    fn get_track_by_path(
+        &self,
+        path: &Path,
+        range: Option<TrackRange>,
+    ) -> sqlx::Result<Option<Arc<Track>>> {
         let pool: &Pool = self.global();
-        crate::RUNTIME.block_on(get_track_by_path(&pool.0, path))
+        crate::RUNTIME.block_on(get_track_by_path(&pool.0, path, range))
     }
 
     /// Lists all albums for searching. Returns a vector of tuples containing the id, name, and artist
@@ -937,7 +1083,10 @@ impl LibraryAccess for App {
         crate::RUNTIME.block_on(get_playlist(&pool.0, playlist_id))
     }
 
-    fn get_playlist_track_files(&self, playlist_id: i64) -> sqlx::Result<Arc<Vec<String>>> {
+    fn get_playlist_track_files(
+        &self,
+        playlist_id: i64,
+    ) -> sqlx::Result<Arc<Vec<(String, Option<TrackRange>)>>> {
         let pool: &Pool = self.global();
         crate::RUNTIME.block_on(get_playlist_track_files(&pool.0, playlist_id))
     }
@@ -1032,7 +1181,7 @@ impl LibraryAccess for App {
         crate::RUNTIME.block_on(artist_id_for_album(&pool.0, album_id))
     }
 
-    fn get_all_tracks(&self) -> sqlx::Result<Vec<(String, i64, i64)>> {
+    fn get_all_tracks(&self) -> sqlx::Result<Vec<(String, Option<TrackRange>, i64, i64)>> {
         let pool: &Pool = self.global();
         crate::RUNTIME.block_on(get_all_tracks(&pool.0))
     }
@@ -1046,4 +1195,48 @@ impl LibraryAccess for App {
         let pool: &Pool = self.global();
         crate::RUNTIME.block_on(lyrics_for_track(&pool.0, track_id))
     }
+
+    fn bookmarks_for_track(&self, track_id: i64) -> sqlx::Result<Arc<Vec<Bookmark>>> {
+        let pool: &Pool = self.global();
+        crate::RUNTIME.block_on(bookmarks_for_track(&pool.0, track_id))
+    }
+
+    fn create_bookmark(&self, track_id: i64, name: &str, position_ms: i64) -> sqlx::Result<i64> {
+        let pool: &Pool = self.global();
+        crate::RUNTIME.block_on(create_bookmark(&pool.0, track_id, name, position_ms))
+    }
+
+    fn delete_bookmark(&self, bookmark_id: i64) -> sqlx::Result<()> {
+        let pool: &Pool = self.global();
+        crate::RUNTIME.block_on(delete_bookmark(&pool.0, bookmark_id))
+    }
+
+    fn resume_info_by_path(
+        &self,
+        path: &Path,
+        range: Option<TrackRange>,
+    ) -> sqlx::Result<Option<ResumeInfo>> {
+        let pool: &Pool = self.global();
+        crate::RUNTIME.block_on(resume_info_by_path(&pool.0, path, range))
+    }
+
+    fn all_track_progress(&self) -> sqlx::Result<Arc<Vec<TrackProgress>>> {
+        let pool: &Pool = self.global();
+        crate::RUNTIME.block_on(all_track_progress(&pool.0))
+    }
+
+    fn save_track_progress(
+        &self,
+        track_id: i64,
+        position_ms: i64,
+        finished: bool,
+    ) -> sqlx::Result<()> {
+        let pool: &Pool = self.global();
+        crate::RUNTIME.block_on(save_track_progress(
+            &pool.0,
+            track_id,
+            position_ms,
+            finished,
+        ))
+    }
 }
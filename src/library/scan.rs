@@ -3,6 +3,8 @@ mod decode;
 mod discover;
 mod record;
 
+pub use discover::file_scan_timestamp;
+
 use std::{
     sync::{
         Arc,
@@ -29,12 +31,14 @@ use tracing::{error, info, warn};
 use crate::{
     library::scan::{
         database::{AlbumCacheKey, AlbumPathCacheKey, update_metadata},
-        decode::{FileInformation, read_metadata_for_path},
+        decode::{TrackInformation, read_metadata_for_path},
         discover::{
-            cleanup_removed_directories, cleanup_with_exclusions, discover, rescan_discover,
+            cleanup_removed_directories, cleanup_stale_tracks, cleanup_with_exclusions,
+            content_probe, discover, rescan_discover,
         },
         record::{SCAN_VERSION, ScanRecord, load_scan_record, write_checkpoint, write_scan_record},
     },
+    media::cue::TrackRange,
     paths,
     settings::scan::{MissingFolderPolicy, ScanSettings},
     ui::models::{Models, PlaylistEvent},
@@ -72,6 +76,9 @@ enum ScanCommand {
     RescanPaths(Vec<Utf8PathBuf>),
     ResolveMissingFolders(MissingFolderAction),
     UpdateSettings(ScanSettings),
+    /// Files were modified by Hummingbird itself (e.g. when writing tags), given as the path and
+    /// the scan timestamps from before and after the change.
+    FilesRewritten(Vec<(Utf8PathBuf, SystemTime, SystemTime)>),
     Stop,
 }
 
@@ -157,6 +164,12 @@ impl ScanInterface {
             .expect("could not send missing folder resolution");
     }
 
+    pub fn record_updater(&self) -> ScanRecordUpdater {
+        ScanRecordUpdater {
+            cmd_tx: self.cmd_tx.clone(),
+        }
+    }
+
     pub fn start_broadcast(&mut self, cx: &mut App) {
         let mut events_rx = None;
         std::mem::swap(&mut self.events_rx, &mut events_rx);
@@ -194,6 +207,24 @@ impl ScanInterface {
 
 impl Global for ScanInterface {}
 
+/// Handle for telling the scanner about files changed by Hummingbird, so that they aren't
+/// treated as changed on the next scan.
+#[derive(Clone)]
+pub struct ScanRecordUpdater {
+    cmd_tx: Sender<ScanCommand>,
+}
+
+impl ScanRecordUpdater {
+    pub async fn files_rewritten(&self, files: Vec<(Utf8PathBuf, SystemTime, SystemTime)>) {
+        if files.is_empty() {
+            return;
+        }
+        if let Err(e) = self.cmd_tx.send(ScanCommand::FilesRewritten(files)).await {
+            warn!("Could not update scan record for rewritten files: {:?}", e);
+        }
+    }
+}
+
 async fn resolve_missing_folder_action(
     command_rx: &mut Receiver<ScanCommand>,
     event_tx: &UnboundedSender<ScanEvent>,
@@ -226,7 +257,8 @@ async fn resolve_missing_folder_action(
                     Some(ScanCommand::Stop) => break MissingFolderAction::KeepInLibrary,
                     Some(ScanCommand::Scan)
                     | Some(ScanCommand::ForceScan)
-                    | Some(ScanCommand::RescanPaths(_)) => {}
+                    | Some(ScanCommand::RescanPaths(_))
+                    | Some(ScanCommand::FilesRewritten(_)) => {}
                     None => break MissingFolderAction::KeepInLibrary,
                 }
             }
@@ -354,6 +386,11 @@ async fn run_scanner(
                     Some(ScanCommand::UpdateSettings(s)) => {
                         scan_settings = s;
                     }
+                    Some(ScanCommand::FilesRewritten(files)) => {
+                        if scan_record.update_rewritten(&files) {
+                            write_scan_record(&scan_record, &scan_record_path).await;
+                        }
+                    }
                     Some(ScanCommand::Stop) => continue,
                     None => return, // channel closed, shut down
                 }
@@ -449,7 +486,7 @@ async fn run_scanner(
         // run concurrently and no step in the scanning process blocks the other
         let (path_tx, path_rx) = tokio::sync::mpsc::channel::<(Utf8PathBuf, SystemTime)>(64);
         let (meta_tx, mut meta_rx) =
-            tokio::sync::mpsc::channel::<(Utf8PathBuf, SystemTime, FileInformation)>(
+            tokio::sync::mpsc::channel::<(Utf8PathBuf, SystemTime, Vec<TrackInformation>)>(
                 num_workers * 8,
             );
         // Channel for files that failed metadata decoding - these should be added to scan_record
@@ -477,7 +514,8 @@ async fn run_scanner(
             }
             ScanMode::Targeted { paths } => {
                 let paths = paths.clone();
-                spawn_blocking(move || rescan_discover(paths, path_tx, cancel_for_discover))
+                let probe = content_probe(&scan_settings);
+                spawn_blocking(move || rescan_discover(paths, probe, path_tx, cancel_for_discover))
             }
         };
 
@@ -507,12 +545,12 @@ async fn run_scanner(
                         break;
                     }
 
-                    if let Some(info) = read_metadata_for_path(&path, &mut art_cache) {
+                    if let Some(tracks) = read_metadata_for_path(&path, &mut art_cache) {
                         if cancel_flag.load(Ordering::Relaxed) {
                             break;
                         }
 
-                        if meta_tx.blocking_send((path, timestamp, info)).is_err() {
+                        if meta_tx.blocking_send((path, timestamp, tracks)).is_err() {
                             break;
                         }
                     } else {
@@ -535,6 +573,7 @@ async fn run_scanner(
         let mut artist_cache: FxHashMap<String, i64> = FxHashMap::default();
         let mut album_cache: FxHashMap<AlbumCacheKey, i64> = FxHashMap::default();
         let mut album_path_cache: FxHashMap<AlbumPathCacheKey, Utf8PathBuf> = FxHashMap::default();
+        let mut updated_playlists: FxHashSet<i64> = FxHashSet::default();
         let mut tx = Some(
             pool.begin()
                 .await
@@ -578,6 +617,9 @@ async fn run_scanner(
                         Some(ScanCommand::UpdateSettings(s)) => {
                             scan_settings = s;
                         }
+                        Some(ScanCommand::FilesRewritten(files)) => {
+                            scan_record_shared.lock().await.update_rewritten(&files);
+                        }
                         Some(ScanCommand::ResolveMissingFolders(_)) => {}
                         None => return,
                     }
@@ -603,7 +645,7 @@ async fn run_scanner(
                 }
 
                 item = meta_rx.recv() => {
-                    let Some((path, timestamp, (metadata, length, image))) = item else {
+                    let Some((path, timestamp, tracks)) = item else {
                         if items_in_tx > 0 {
                             if let Err(e) = tx
                                 .take()
@@ -623,20 +665,37 @@ async fn run_scanner(
                         break;
                     };
 
-                    let result = update_metadata(
-                        tx.as_mut()
-                            .expect("scan transaction should be active"),
-                        &metadata,
-                        &path,
-                        length,
-                        &image,
-                        mode.force_albums(),
-                        &mut force_encountered_albums,
-                        &mut artist_cache,
-                        &mut album_cache,
-                        &mut album_path_cache,
-                    )
-                    .await;
+                    let conn = tx.as_mut().expect("scan transaction should be active");
+                    let mut result = Ok(());
+                    let mut starts = Vec::with_capacity(tracks.len());
+
+                    for (range, (metadata, length, image)) in &tracks {
+                        result = update_metadata(
+                            conn,
+                            metadata,
+                            &path,
+                            *range,
+                            *length,
+                            image,
+                            mode.force_albums(),
+                            &mut force_encountered_albums,
+                            &mut artist_cache,
+                            &mut album_cache,
+                            &mut album_path_cache,
+                        )
+                        .await;
+
+                        if result.is_err() {
+                            break;
+                        }
+                        starts.push(TrackRange::start_column(*range));
+                    }
+
+                    // the file may have been split into different tracks when it was last scanned
+                    if result.is_ok() {
+                        cleanup_stale_tracks(conn, &path, &starts, &mut updated_playlists)
+                            .await;
+                    }
 
                     match result {
                         Ok(_) => {
@@ -746,6 +805,12 @@ async fn run_scanner(
                 }
             }
 
+            if !updated_playlists.is_empty() {
+                let _ = event_tx.send(ScanEvent::PlaylistsUpdated(
+                    updated_playlists.into_iter().collect(),
+                ));
+            }
+
             info!(
                 "Scan cancelled after {} files in {} seconds, writing checkpoint only.",
                 scanned,
@@ -803,6 +868,12 @@ async fn run_scanner(
             warn!("Failed to delete scan record checkpoint: {:?}", e);
         }
 
+        if !updated_playlists.is_empty() {
+            let _ = event_tx.send(ScanEvent::PlaylistsUpdated(
+                updated_playlists.into_iter().collect(),
+            ));
+        }
+
         let _ = event_tx.send(mode.completion_event());
     }
 }
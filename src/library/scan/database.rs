@@ -8,7 +8,7 @@ use crate::{
         scan::decode::process_album_art,
         types::{DATE_PRECISION_FULL_DATE, DATE_PRECISION_YEAR, DATE_PRECISION_YEAR_MONTH},
     },
-    media::metadata::Metadata,
+    media::{cue::TrackRange, metadata::Metadata},
 };
 
 async fn insert_artist(
@@ -198,6 +198,7 @@ async fn insert_track(
     metadata: &Metadata,
     album_id: Option<i64>,
     path: &Utf8Path,
+    range: Option<TrackRange>,
     length: u64,
     album_path_cache: &mut FxHashMap<AlbumPathCacheKey, Utf8PathBuf>,
 ) -> anyhow::Result<Option<i64>> {
@@ -267,6 +268,12 @@ async fn insert_track(
             .bind(metadata.replaygain_album_gain)
             .bind(metadata.replaygain_album_peak)
             .bind(&metadata.disc_subtitle)
+            .bind(TrackRange::start_column(range))
+            .bind(
+                range
+                    .and_then(|range| range.end_ms)
+                    .map(|end_ms| end_ms as i64),
+            )
             .fetch_one(&mut *conn)
             .await;
 
@@ -282,6 +289,7 @@ pub async fn update_metadata(
     conn: &mut SqliteConnection,
     metadata: &Metadata,
     path: &Utf8Path,
+    range: Option<TrackRange>,
     length: u64,
     image: &Option<Box<[u8]>>,
     is_force: bool,
@@ -319,7 +327,16 @@ pub async fn update_metadata(
         album_cache,
     )
     .await?;
-    let track_id = insert_track(conn, metadata, album_id, path, length, album_path_cache).await?;
+    let track_id = insert_track(
+        conn,
+        metadata,
+        album_id,
+        path,
+        range,
+        length,
+        album_path_cache,
+    )
+    .await?;
 
     if let Some(track_id) = track_id {
         if let Some(lyrics) = &metadata.lyrics {
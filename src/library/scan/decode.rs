@@ -7,7 +7,10 @@ use image::{DynamicImage, EncodableLayout, codecs::jpeg::JpegEncoder, imageops};
 use rustc_hash::FxHashMap;
 
 use crate::media::{
-    lookup_table::try_open_media, metadata::Metadata, traits::MediaProviderFeatures,
+    cue::{self, TrackRange},
+    lookup_table::try_open_media,
+    metadata::Metadata,
+    traits::MediaProviderFeatures,
 };
 
 /// Information extracted from a media file during the metadata reading stage.
@@ -15,6 +18,9 @@ use crate::media::{
 /// happens in `insert_album` when a new album is actually created.
 pub type FileInformation = (Metadata, u64, Option<Box<[u8]>>);
 
+/// A track found in a file: the whole file, or one section of it listed by a CUE sheet.
+pub type TrackInformation = (Option<TrackRange>, FileInformation);
+
 /// Read metadata, duration, and embedded image from a file using the global provider lookup table.
 /// Returns raw (unprocessed) image bytes.
 fn scan_path(path: &Utf8Path) -> Result<FileInformation, ()> {
@@ -122,14 +128,34 @@ pub fn process_album_art(image: &[u8]) -> anyhow::Result<(Vec<u8>, Vec<u8>)> {
     Ok((resized, thumb_buf))
 }
 
+/// Splits a file into the tracks listed by its CUE sheet, if it has one that lists any.
+fn split_cue_tracks(path: &Utf8Path, info: &FileInformation) -> Vec<TrackInformation> {
+    let (metadata, len, image) = info;
+    let Some(sheet) = cue::read_sheet(path, metadata.cuesheet.as_deref()) else {
+        return Vec::new();
+    };
+
+    sheet
+        .tracks_for(path, metadata)
+        .into_iter()
+        .map(|(range, metadata)| {
+            let len = range.duration_ms(len * 1000) / 1000;
+            (Some(range), (metadata, len, image.clone()))
+        })
+        .collect()
+}
+
 /// Read metadata from a file, resolve album art (embedded or from directory).
 ///
+/// Returns a single track for most files, and one track per CUE sheet entry for files that are
+/// split by a CUE sheet.
+///
 /// Each metadata reader thread maintains its own `art_cache` to avoid redundant directory scans
 /// for files in the same folder.
 pub fn read_metadata_for_path(
     path: &Utf8Path,
     art_cache: &mut FxHashMap<Utf8PathBuf, Option<Arc<[u8]>>>,
-) -> Option<FileInformation> {
+) -> Option<Vec<TrackInformation>> {
     if let Ok(mut metadata) = scan_path(path) {
         if metadata.2.is_none()
             && let Some(art) = scan_path_for_album_art(path, art_cache)
@@ -137,9 +163,15 @@ pub fn read_metadata_for_path(
             metadata.2 = Some(art.to_vec().into_boxed_slice());
         }
 
+        let tracks = split_cue_tracks(path, &metadata);
+        if !tracks.is_empty() {
+            return Some(tracks);
+        }
+
         metadata.0.lyrics = resolve_lyrics(path, metadata.0.lyrics.take());
+        metadata.0.cuesheet = None;
 
-        Some(metadata)
+        Some(vec![(None, metadata)])
     } else {
         None
     }
@@ -8,13 +8,17 @@ use std::{
 
 use camino::{Utf8Path, Utf8PathBuf};
 use rustc_hash::{FxHashMap, FxHashSet};
-use sqlx::SqlitePool;
+use sqlx::{SqliteConnection, SqlitePool};
 use tokio::sync::{Mutex, mpsc::Sender};
 use tracing::{debug, error, info};
 
 use crate::{
     library::scan::record::ScanRecord,
-    media::{lookup_table::can_be_read, traits::MediaProviderFeatures},
+    media::{
+        cue,
+        lookup_table::{ContentProbe, can_be_read},
+        traits::MediaProviderFeatures,
+    },
     settings::scan::ScanSettings,
 };
 
@@ -24,21 +28,31 @@ pub fn sidecar_lyrics_path(path: &Utf8Path) -> Option<Utf8PathBuf> {
     Some(parent.join(format!("{}.lrc", stem)))
 }
 
-fn file_scan_timestamp(path: &Utf8Path) -> Option<SystemTime> {
+/// The time a file was last changed, including its lyrics and CUE sheet sidecar files.
+///
+/// Each sidecar that exists also offsets the timestamp by a distinct number of nanoseconds, so
+/// adding or removing a sidecar that is older than the file still causes a rescan.
+pub fn file_scan_timestamp(path: &Utf8Path) -> Option<SystemTime> {
     let audio_timestamp = std::fs::metadata(path).ok()?.modified().ok()?;
-    let lyrics_timestamp = sidecar_lyrics_path(path)
-        .and_then(|lrc_path| std::fs::metadata(lrc_path).ok())
-        .and_then(|metadata| metadata.modified().ok());
-    let base_timestamp = match lyrics_timestamp {
-        Some(lyrics_timestamp) if lyrics_timestamp > audio_timestamp => lyrics_timestamp,
-        _ => audio_timestamp,
+    let modified = |sidecar: Utf8PathBuf| {
+        std::fs::metadata(sidecar)
+            .ok()
+            .and_then(|metadata| metadata.modified().ok())
     };
 
-    let presence_offset = if lyrics_timestamp.is_some() {
-        Duration::from_nanos(1)
-    } else {
-        Duration::ZERO
-    };
+    let lyrics_timestamp = sidecar_lyrics_path(path).and_then(modified);
+    let cue_timestamp = cue::sidecar_paths(path)
+        .into_iter()
+        .filter_map(modified)
+        .max();
+    let base_timestamp = [lyrics_timestamp, cue_timestamp]
+        .into_iter()
+        .flatten()
+        .fold(audio_timestamp, SystemTime::max);
+
+    let presence_offset = Duration::from_nanos(
+        u64::from(lyrics_timestamp.is_some()) + 2 * u64::from(cue_timestamp.is_some()),
+    );
     UNIX_EPOCH
         .checked_add(
             base_timestamp
@@ -49,13 +63,24 @@ fn file_scan_timestamp(path: &Utf8Path) -> Option<SystemTime> {
         .or(Some(base_timestamp))
 }
 
+/// Which files have their contents looked at during scans, if no provider claims their
+/// extension.
+pub fn content_probe(settings: &ScanSettings) -> ContentProbe {
+    if settings.probe_extensionless {
+        ContentProbe::Extensionless
+    } else {
+        ContentProbe::Never
+    }
+}
+
 /// Returns the file's scan timestamp if it exists on disk and is a supported media file,
 /// otherwise `None`.
-fn file_scan_timestamp_if_supported(path: &Utf8Path) -> Option<SystemTime> {
+fn file_scan_timestamp_if_supported(path: &Utf8Path, probe: ContentProbe) -> Option<SystemTime> {
     let timestamp = file_scan_timestamp(path)?;
     can_be_read(
         path.as_std_path(),
         MediaProviderFeatures::PROVIDES_METADATA | MediaProviderFeatures::ALLOWS_INDEXING,
+        probe,
     )
     .unwrap_or(false)
     .then_some(timestamp)
@@ -67,8 +92,9 @@ fn file_scan_timestamp_if_supported(path: &Utf8Path) -> Option<SystemTime> {
 fn file_is_scannable(
     path: &Utf8Path,
     scan_record: &FxHashMap<Utf8PathBuf, SystemTime>,
+    probe: ContentProbe,
 ) -> Option<SystemTime> {
-    let timestamp = file_scan_timestamp_if_supported(path)?;
+    let timestamp = file_scan_timestamp_if_supported(path, probe)?;
 
     if let Some(last_scan) = scan_record.get(path)
         && *last_scan == timestamp
@@ -148,16 +174,68 @@ pub async fn cleanup_removed_directories(
     updated_playlists
 }
 
+/// Lists the IDs and start times of all tracks in the library that are played from the file at
+/// `path`: the file itself, or the tracks its CUE sheet splits it into.
+async fn list_tracks_for_file(
+    conn: &mut SqliteConnection,
+    path: &Utf8Path,
+) -> Result<Vec<(i64, i64)>, sqlx::Error> {
+    sqlx::query_as::<_, (i64, i64)>(include_str!(
+        "../../../queries/scan/list_tracks_for_file.sql"
+    ))
+    .bind(path.as_str())
+    .fetch_all(&mut *conn)
+    .await
+}
+
+/// Removes every track played from the file at `path`.
 async fn cleanup_track(
     tx: &mut sqlx::Transaction<'_, sqlx::Sqlite>,
     path: &Utf8Path,
     updated_playlists: &mut FxHashSet<i64>,
+) -> bool {
+    cleanup_stale_tracks(&mut **tx, path, &[], updated_playlists).await
+}
+
+/// Removes the tracks played from the file at `path` that don't start at one of the times in
+/// `keep`, for example when a CUE sheet was added to or removed from the file since it was last
+/// scanned.
+pub async fn cleanup_stale_tracks(
+    conn: &mut SqliteConnection,
+    path: &Utf8Path,
+    keep: &[i64],
+    updated_playlists: &mut FxHashSet<i64>,
+) -> bool {
+    let tracks = match list_tracks_for_file(conn, path).await {
+        Ok(tracks) => tracks,
+        Err(e) => {
+            error!("Database error while listing tracks for file: {:?}", e);
+            return false;
+        }
+    };
+
+    for (track_id, start_ms) in tracks {
+        if !keep.contains(&start_ms) {
+            debug!("removing track {} of {:?}", track_id, path);
+            if !cleanup_track_id(conn, track_id, updated_playlists).await {
+                return false;
+            }
+        }
+    }
+
+    true
+}
+
+async fn cleanup_track_id(
+    conn: &mut SqliteConnection,
+    track_id: i64,
+    updated_playlists: &mut FxHashSet<i64>,
 ) -> bool {
     let affected_playlists = sqlx::query_scalar::<_, i64>(include_str!(
         "../../../queries/scan/list_playlist_ids_for_track.sql"
     ))
-    .bind(path.as_str())
-    .fetch_all(&mut **tx)
+    .bind(track_id)
+    .fetch_all(&mut *conn)
     .await;
 
     let affected_playlists = match affected_playlists {
@@ -174,8 +252,8 @@ async fn cleanup_track(
     let playlist_result = sqlx::query(include_str!(
         "../../../queries/scan/delete_playlist_items_for_track.sql"
     ))
-    .bind(path.as_str())
-    .execute(&mut **tx)
+    .bind(track_id)
+    .execute(&mut *conn)
     .await;
 
     if let Err(e) = playlist_result {
@@ -190,8 +268,8 @@ async fn cleanup_track(
     let lyrics_result = sqlx::query(include_str!(
         "../../../queries/scan/delete_lyrics_for_track.sql"
     ))
-    .bind(path.as_str())
-    .execute(&mut **tx)
+    .bind(track_id)
+    .execute(&mut *conn)
     .await;
 
     if let Err(e) = lyrics_result {
@@ -199,9 +277,33 @@ async fn cleanup_track(
         return false;
     }
 
+    let bookmarks_result = sqlx::query(include_str!(
+        "../../../queries/scan/delete_bookmarks_for_track.sql"
+    ))
+    .bind(track_id)
+    .execute(&mut *conn)
+    .await;
+
+    if let Err(e) = bookmarks_result {
+        error!("Database error while deleting bookmarks for track: {:?}", e);
+        return false;
+    }
+
+    let progress_result = sqlx::query(include_str!(
+        "../../../queries/scan/delete_progress_for_track.sql"
+    ))
+    .bind(track_id)
+    .execute(&mut *conn)
+    .await;
+
+    if let Err(e) = progress_result {
+        error!("Database error while deleting progress for track: {:?}", e);
+        return false;
+    }
+
     let track_result = sqlx::query(include_str!("../../../queries/scan/delete_track.sql"))
-        .bind(path.as_str())
-        .execute(&mut **tx)
+        .bind(track_id)
+        .execute(&mut *conn)
         .await;
 
     if let Err(e) = track_result {
@@ -274,6 +376,7 @@ pub async fn cleanup_with_exclusions(
 /// Returns the total number of discovered files once the walk is complete.
 pub fn rescan_discover(
     paths: Vec<Utf8PathBuf>,
+    probe: ContentProbe,
     path_tx: Sender<(Utf8PathBuf, SystemTime)>,
     cancel_flag: Arc<AtomicBool>,
 ) -> u64 {
@@ -323,14 +426,14 @@ pub fn rescan_discover(
                     continue;
                 }
 
-                if emit_rescan_file(&file_path, &path_tx, &cancel_flag).is_some() {
+                if emit_rescan_file(&file_path, probe, &path_tx, &cancel_flag).is_some() {
                     discovered_total += 1;
                 } else if cancel_flag.load(Ordering::Relaxed) {
                     return discovered_total;
                 }
             }
         } else if canonical.is_file() {
-            if emit_rescan_file(&canonical, &path_tx, &cancel_flag).is_some() {
+            if emit_rescan_file(&canonical, probe, &path_tx, &cancel_flag).is_some() {
                 discovered_total += 1;
             } else if cancel_flag.load(Ordering::Relaxed) {
                 return discovered_total;
@@ -370,10 +473,11 @@ fn canonicalize_dir_entry(entry: std::io::Result<std::fs::DirEntry>) -> Option<U
 /// emission; `None` if the file was skipped, cancelled, or the channel closed.
 fn emit_rescan_file(
     path: &Utf8Path,
+    probe: ContentProbe,
     path_tx: &Sender<(Utf8PathBuf, SystemTime)>,
     cancel_flag: &Arc<AtomicBool>,
 ) -> Option<SystemTime> {
-    let timestamp = file_scan_timestamp_if_supported(path)?;
+    let timestamp = file_scan_timestamp_if_supported(path, probe)?;
 
     if cancel_flag.load(Ordering::Relaxed) {
         return None;
@@ -397,6 +501,7 @@ pub fn discover(
 ) -> u64 {
     let mut visited: FxHashSet<Utf8PathBuf> = FxHashSet::default();
     let mut stack: Vec<Utf8PathBuf> = settings.paths.clone();
+    let probe = content_probe(&settings);
     let mut discovered_total: u64 = 0;
 
     while let Some(dir) = stack.pop() {
@@ -430,7 +535,7 @@ pub fn discover(
             } else {
                 let timestamp = {
                     let sr = scan_record.blocking_lock();
-                    file_is_scannable(&path, &sr.records)
+                    file_is_scannable(&path, &sr.records, probe)
                 };
 
                 if let Some(ts) = timestamp {
@@ -34,6 +34,25 @@ impl ScanRecord {
     pub fn is_version_mismatch(&self) -> bool {
         self.version != SCAN_VERSION
     }
+
+    /// Record the new timestamps of files changed by Hummingbird. Files whose recorded timestamp
+    /// doesn't match the one from before the change were also changed by something else (or never
+    /// scanned), and are left alone so the next scan picks them up. Returns whether anything
+    /// changed.
+    pub fn update_rewritten(&mut self, files: &[(Utf8PathBuf, SystemTime, SystemTime)]) -> bool {
+        let mut changed = false;
+
+        for (path, before, after) in files {
+            if let Some(timestamp) = self.records.get_mut(path)
+                && timestamp == before
+            {
+                *timestamp = *after;
+                changed = true;
+            }
+        }
+
+        changed
+    }
 }
 
 pub async fn load_scan_record(path: &Path) -> ScanRecord {
@@ -175,3 +194,36 @@ pub async fn write_scan_record(scan_record: &ScanRecord, path: &Path) {
         }
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use std::time::{Duration, UNIX_EPOCH};
+
+    use super::*;
+
+    #[test]
+    fn update_rewritten_skips_files_changed_elsewhere() {
+        let before = UNIX_EPOCH + Duration::from_secs(100);
+        let after = UNIX_EPOCH + Duration::from_secs(200);
+        let outside = UNIX_EPOCH + Duration::from_secs(150);
+
+        let mut record = ScanRecord::new_current();
+        record.records.insert("/music/a.flac".into(), before);
+        record.records.insert("/music/b.flac".into(), outside);
+
+        let changed = record.update_rewritten(&[
+            ("/music/a.flac".into(), before, after),
+            ("/music/b.flac".into(), before, after),
+            ("/music/c.flac".into(), before, after),
+        ]);
+
+        assert!(changed);
+        assert_eq!(record.records[&Utf8PathBuf::from("/music/a.flac")], after);
+        assert_eq!(record.records[&Utf8PathBuf::from("/music/b.flac")], outside);
+        assert!(
+            !record
+                .records
+                .contains_key(&Utf8PathBuf::from("/music/c.flac"))
+        );
+    }
+}
@@ -9,7 +9,7 @@ use image::{Frame, RgbaImage};
 use smallvec::SmallVec;
 use sqlx::{Database, Decode, Sqlite, Type, encode::IsNull, error::BoxDynError};
 
-use crate::util::rgb_to_bgr;
+use crate::{media::cue::TrackRange, util::rgb_to_bgr};
 
 #[derive(sqlx::FromRow)]
 pub struct Artist {
@@ -250,6 +250,19 @@ pub struct Track {
     pub rg_album_peak: Option<f64>,
     #[sqlx(default)]
     pub disc_subtitle: Option<DBString>,
+    /// Where the track starts in its file, for tracks split from a file by a CUE sheet.
+    #[sqlx(default)]
+    pub start_ms: i64,
+    /// Where the track ends in its file, if it ends before the end of the file.
+    #[sqlx(default)]
+    pub end_ms: Option<i64>,
+}
+
+impl Track {
+    /// The section of the file that is played, if the track is split from a file by a CUE sheet.
+    pub fn range(&self) -> Option<TrackRange> {
+        TrackRange::from_columns(self.start_ms, self.end_ms)
+    }
 }
 
 #[derive(sqlx::Type, Clone, Copy, Debug, PartialEq)]
@@ -286,6 +299,33 @@ pub struct PlaylistItem {
     pub position: i64,
 }
 
+#[derive(sqlx::FromRow, Clone, Debug, PartialEq)]
+pub struct Bookmark {
+    pub id: i64,
+    pub track_id: i64,
+    pub name: DBString,
+    pub position_ms: i64,
+    pub created_at: DateTime<Utc>,
+}
+
+/// How far into a resumable track playback got.
+#[derive(sqlx::FromRow, Clone, Copy, Debug, PartialEq)]
+pub struct TrackProgress {
+    pub track_id: i64,
+    pub position_ms: i64,
+    pub finished: bool,
+}
+
+/// What's needed to decide whether a track should resume, and where.
+#[derive(sqlx::FromRow, Clone, Debug, PartialEq)]
+pub struct ResumeInfo {
+    pub track_id: i64,
+    pub duration: i64,
+    pub genres: Option<DBString>,
+    pub position_ms: Option<i64>,
+    pub finished: Option<bool>,
+}
+
 #[derive(sqlx::FromRow, Clone)]
 pub struct TrackStats {
     pub track_count: i64,
@@ -12,6 +12,7 @@ use super::{
 };
 use crate::{
     library::db::{AlbumMethod, AlbumSortMethod, ArtistSortMethod, LibraryAccess, TrackSortMethod},
+    media::cue::TrackRange,
     ui::{
         availability::{
             album_has_available_tracks, artist_has_available_tracks, is_track_available,
@@ -323,7 +324,7 @@ impl Column for TrackColumn {
 }
 
 impl TableData<TrackColumn> for Track {
-    type Identifier = (i64, String, Option<i64>, String);
+    type Identifier = (i64, String, Option<i64>, String, Option<TrackRange>);
     type ContextMenuContext = TrackContextMenuContext;
 
     fn get_table_name() -> SharedString {
@@ -463,6 +464,7 @@ impl TableData<TrackColumn> for Track {
             self.title.0.clone().into(),
             self.album_id,
             self.location.to_string_lossy().to_string(),
+            self.range(),
         )
     }
 
@@ -482,6 +484,7 @@ impl TableData<TrackColumn> for Track {
             self.id,
             self.album_id,
             self.location.clone(),
+            self.range(),
             self.title.0.clone(),
         )))
     }
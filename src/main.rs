@@ -9,7 +9,10 @@ use cntp_i18n::{I18N_MANAGER, tr_load};
 use std::path::Path;
 use std::sync::LazyLock;
 
-use crate::media::{builtin::symphonia::SymphoniaProvider, lookup_table::add_provider};
+use crate::media::{
+    builtin::{lofty::LoftyProvider, symphonia::SymphoniaProvider},
+    lookup_table::add_provider,
+};
 
 mod devices;
 mod library;
@@ -54,6 +57,11 @@ fn main() -> anyhow::Result<()> {
     tracing::info!("version {VERSION_STRING}");
 
     add_provider(Box::new(SymphoniaProvider));
+    add_provider(Box::new(LoftyProvider));
+    #[cfg(feature = "ffmpeg")]
+    crate::media::builtin::ffmpeg::register_providers();
+    #[cfg(feature = "plugins")]
+    crate::media::plugin::load_plugins(&paths::data_dir().join("plugins"));
 
     crate::ui::app::run()
 }
@@ -1,7 +1,11 @@
 pub mod builtin;
+pub mod cue;
 pub mod errors;
 pub mod lookup_table;
 pub mod metadata;
 pub mod pipeline;
 pub mod playback;
+#[cfg(feature = "plugins")]
+pub mod plugin;
+pub mod ranged;
 pub mod traits;
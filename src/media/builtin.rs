@@ -1 +1,4 @@
+#[cfg(feature = "ffmpeg")]
+pub mod ffmpeg;
+pub mod lofty;
 pub mod symphonia;
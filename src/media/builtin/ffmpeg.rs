@@ -0,0 +1,877 @@
+//! Providers for formats Symphonia can't decode, backed by the system's FFmpeg libraries.
+//!
+//! FFmpeg reads the [`File`] handed to the provider through custom IO callbacks, so files are
+//! opened exactly like they are for every other provider. Each format family is registered as its
+//! own provider, which keeps the list of providers in the logs meaningful and keeps FFmpeg away
+//! from the formats Symphonia already handles.
+
+use std::{
+    ffi::{CStr, CString, OsStr, c_int, c_void},
+    fs::File,
+    io::{Read, Seek, SeekFrom},
+    mem::ManuallyDrop,
+    ptr,
+    sync::LazyLock,
+};
+
+use ffmpeg_next::{
+    self as ffmpeg, Error, Packet,
+    codec::{self, decoder},
+    ffi,
+    format::{self, context::Input, stream::Disposition},
+    frame, log, media,
+};
+use intx::I24;
+use regex::Regex;
+use smallvec::SmallVec;
+use tracing::{info, warn};
+
+use crate::{
+    devices::format::{ChannelSpec, SampleFormat},
+    devices::resample::SampleInto,
+    media::{
+        builtin::symphonia::{ParsedReleaseDate, parse_release_date},
+        errors::{
+            ChannelRetrievalError, CloseError, FrameDurationError, MetadataError, OpenError,
+            PlaybackReadError, PlaybackStartError, PlaybackStopError, SeekError,
+            TrackDurationError,
+        },
+        lookup_table::add_provider,
+        metadata::Metadata,
+        pipeline::{ChannelProducers, DecodeResult},
+        traits::{F32DecodeResult, MediaProvider, MediaProviderFeatures, MediaStream},
+    },
+};
+
+/// Size of the buffer FFmpeg reads the file through.
+const IO_BUFFER_SIZE: usize = 64 * 1024;
+
+/// A family of formats decoded by FFmpeg.
+pub struct FfmpegProvider {
+    name: &'static str,
+    /// Demuxer the formats need that FFmpeg may have been built without.
+    required_demuxer: Option<&'static CStr>,
+    extensions: &'static [&'static str],
+}
+
+const PROVIDERS: [FfmpegProvider; 6] = [
+    FfmpegProvider {
+        name: "WavPack",
+        required_demuxer: None,
+        extensions: &["wv"],
+    },
+    FfmpegProvider {
+        name: "Monkey's Audio",
+        required_demuxer: None,
+        extensions: &["ape"],
+    },
+    FfmpegProvider {
+        name: "Musepack",
+        required_demuxer: None,
+        extensions: &["mpc", "mp+", "mpp"],
+    },
+    FfmpegProvider {
+        name: "TTA",
+        required_demuxer: None,
+        extensions: &["tta"],
+    },
+    // DSD is converted to PCM by FFmpeg's decoder, at an eighth of the DSD rate
+    FfmpegProvider {
+        name: "DSD",
+        required_demuxer: None,
+        extensions: &["dsf", "dff"],
+    },
+    FfmpegProvider {
+        name: "Tracker modules",
+        required_demuxer: Some(c"libopenmpt"),
+        extensions: &["mod", "xm", "it", "s3m"],
+    },
+];
+
+/// Register a provider for each format family FFmpeg is used for.
+pub fn register_providers() {
+    if let Err(e) = ffmpeg::init() {
+        warn!("Could not initialize FFmpeg, not registering its providers: {e}");
+        return;
+    }
+    // decoding errors are reported through the stream, FFmpeg's own logging is just noise
+    log::set_level(log::Level::Fatal);
+
+    for provider in PROVIDERS {
+        if let Some(demuxer) = provider.required_demuxer
+            && !has_demuxer(demuxer)
+        {
+            info!(
+                "FFmpeg was built without {:?}, not registering the {} provider",
+                demuxer, provider.name
+            );
+            continue;
+        }
+
+        add_provider(Box::new(provider));
+    }
+}
+
+fn has_demuxer(name: &CStr) -> bool {
+    // SAFETY: name is a valid C string, and FFmpeg's demuxers are static
+    unsafe { !ffi::av_find_input_format(name.as_ptr()).is_null() }
+}
+
+impl MediaProvider for FfmpegProvider {
+    fn open(&self, file: File, ext: Option<&OsStr>) -> Result<Box<dyn MediaStream>, OpenError> {
+        let demuxer = Demuxer::open(file, ext)?;
+        Ok(Box::new(FfmpegStream::new(demuxer)?))
+    }
+
+    fn supported_extensions(&self) -> &[&str] {
+        self.extensions
+    }
+
+    fn supported_features(&self) -> MediaProviderFeatures {
+        MediaProviderFeatures::ALLOWS_INDEXING
+            | MediaProviderFeatures::PROVIDES_DECODER
+            | MediaProviderFeatures::PROVIDES_METADATA
+    }
+
+    fn name(&self) -> &str {
+        self.name
+    }
+}
+
+unsafe extern "C" fn read_file(opaque: *mut c_void, buf: *mut u8, buf_size: c_int) -> c_int {
+    // SAFETY: opaque is the file owned by the demuxer, and buf is valid for buf_size bytes
+    let file = unsafe { &mut *opaque.cast::<File>() };
+    let buf = unsafe { std::slice::from_raw_parts_mut(buf, buf_size as usize) };
+
+    match file.read(buf) {
+        Ok(0) => ffi::AVERROR_EOF,
+        Ok(read) => read as c_int,
+        Err(_) => ffi::AVERROR(ffmpeg::error::EIO),
+    }
+}
+
+unsafe extern "C" fn seek_file(opaque: *mut c_void, offset: i64, whence: c_int) -> i64 {
+    // SAFETY: opaque is the file owned by the demuxer
+    let file = unsafe { &mut *opaque.cast::<File>() };
+
+    let whence = whence & !(ffi::AVSEEK_FORCE as c_int);
+    if whence == ffi::AVSEEK_SIZE as c_int {
+        return file.metadata().map(|m| m.len() as i64).unwrap_or(-1);
+    }
+
+    // SEEK_SET, SEEK_CUR and SEEK_END
+    let position = match whence {
+        0 => SeekFrom::Start(offset as u64),
+        1 => SeekFrom::Current(offset),
+        2 => SeekFrom::End(offset),
+        _ => return -1,
+    };
+    file.seek(position).map(|p| p as i64).unwrap_or(-1)
+}
+
+/// An FFmpeg demuxer reading from a file through custom IO.
+struct Demuxer {
+    input: ManuallyDrop<Input>,
+    io: *mut ffi::AVIOContext,
+    file: *mut File,
+}
+
+// SAFETY: the IO context and the file are only used through the demuxer, which owns them
+unsafe impl Send for Demuxer {}
+
+impl Demuxer {
+    fn open(file: File, ext: Option<&OsStr>) -> Result<Self, OpenError> {
+        // the name is only used to let FFmpeg consider the extension when probing
+        let name = CString::new(format!(
+            "stream.{}",
+            ext.and_then(|e| e.to_str()).unwrap_or_default()
+        ))
+        .map_err(|_| OpenError::UnsupportedFormat)?;
+
+        unsafe {
+            let file = Box::into_raw(Box::new(file));
+            let buffer = ffi::av_malloc(IO_BUFFER_SIZE).cast::<u8>();
+            let io = ffi::avio_alloc_context(
+                buffer,
+                IO_BUFFER_SIZE as c_int,
+                0,
+                file.cast(),
+                Some(read_file),
+                None,
+                Some(seek_file),
+            );
+
+            // FFmpeg may have replaced the buffer, so it's freed through the IO context
+            let free_io = |mut io: *mut ffi::AVIOContext| {
+                ffi::av_freep(ptr::addr_of_mut!((*io).buffer).cast());
+                ffi::avio_context_free(&mut io);
+                drop(Box::from_raw(file));
+            };
+
+            let mut context = ffi::avformat_alloc_context();
+            (*context).pb = io;
+            (*context).flags |= ffi::AVFMT_FLAG_CUSTOM_IO as c_int;
+
+            // frees the context on failure
+            if ffi::avformat_open_input(&mut context, name.as_ptr(), ptr::null(), ptr::null_mut())
+                < 0
+            {
+                free_io(io);
+                return Err(OpenError::UnsupportedFormat);
+            }
+
+            if ffi::avformat_find_stream_info(context, ptr::null_mut()) < 0 {
+                ffi::avformat_close_input(&mut context);
+                free_io(io);
+                return Err(OpenError::FileCorrupt);
+            }
+
+            Ok(Self {
+                input: ManuallyDrop::new(Input::wrap(context)),
+                io,
+                file,
+            })
+        }
+    }
+}
+
+impl Drop for Demuxer {
+    fn drop(&mut self) {
+        unsafe {
+            // with custom IO, closing the input leaves the IO context alone
+            ManuallyDrop::drop(&mut self.input);
+            ffi::av_freep(ptr::addr_of_mut!((*self.io).buffer).cast());
+            ffi::avio_context_free(&mut self.io);
+            drop(Box::from_raw(self.file));
+        }
+    }
+}
+
+pub struct FfmpegStream {
+    demuxer: Option<Demuxer>,
+    stream_index: usize,
+    /// Seconds per tick of the audio stream's timestamps.
+    time_base: f64,
+    duration_secs: u64,
+    decoder: Option<decoder::Audio>,
+    /// Whether the end of the file was reached, and the decoder is being drained.
+    draining: bool,
+    packet: Packet,
+    frame: frame::Audio,
+    frame_duration: u64,
+    position_ms: u64,
+    metadata: Metadata,
+    pending_metadata_update: bool,
+    image: Option<Box<[u8]>>,
+    conversion_buffer: Vec<Vec<f64>>,
+}
+
+impl FfmpegStream {
+    fn new(demuxer: Demuxer) -> Result<Self, OpenError> {
+        let input = &demuxer.input;
+        let stream = input
+            .streams()
+            .best(media::Type::Audio)
+            .ok_or(OpenError::UnsupportedFormat)?;
+
+        let stream_index = stream.index();
+        let time_base = f64::from(stream.time_base());
+        let duration_secs = if input.duration() > 0 {
+            input.duration() as u64 / ffi::AV_TIME_BASE as u64
+        } else {
+            (stream.duration().max(0) as f64 * time_base) as u64
+        };
+
+        let mut metadata = Metadata::default();
+        read_tags(&mut metadata, input.metadata().iter());
+        read_tags(&mut metadata, stream.metadata().iter());
+
+        let image = input
+            .streams()
+            .find(|s| s.disposition().contains(Disposition::ATTACHED_PIC))
+            .and_then(|s| unsafe {
+                let picture = &(*s.as_ptr()).attached_pic;
+                (!picture.data.is_null() && picture.size > 0)
+                    .then(|| std::slice::from_raw_parts(picture.data, picture.size as usize).into())
+            });
+
+        Ok(Self {
+            demuxer: Some(demuxer),
+            stream_index,
+            time_base,
+            duration_secs,
+            decoder: None,
+            draining: false,
+            packet: Packet::empty(),
+            frame: frame::Audio::empty(),
+            frame_duration: 0,
+            position_ms: 0,
+            metadata,
+            pending_metadata_update: true,
+            image,
+            conversion_buffer: Vec::new(),
+        })
+    }
+
+    /// Decode the next frame into `self.frame`. Returns `false` at the end of the stream.
+    fn next_frame(&mut self) -> Result<bool, PlaybackReadError> {
+        let Some(demuxer) = &mut self.demuxer else {
+            return Err(PlaybackReadError::InvalidState);
+        };
+        let Some(decoder) = &mut self.decoder else {
+            return Err(PlaybackReadError::NeverStarted);
+        };
+
+        loop {
+            match decoder.receive_frame(&mut self.frame) {
+                Ok(()) => break,
+                Err(Error::Eof) => return Ok(false),
+                Err(Error::Other { errno }) if errno == ffmpeg::error::EAGAIN => {}
+                Err(e) => return Err(PlaybackReadError::DecodeFatal(e.to_string())),
+            }
+
+            if self.draining {
+                return Ok(false);
+            }
+
+            match self.packet.read(&mut demuxer.input) {
+                Ok(()) => {
+                    if self.packet.stream() != self.stream_index {
+                        continue;
+                    }
+
+                    match decoder.send_packet(&self.packet) {
+                        // skip over corrupt packets, like the Symphonia provider does
+                        Ok(()) | Err(Error::InvalidData) => {}
+                        Err(e) => return Err(PlaybackReadError::DecodeFatal(e.to_string())),
+                    }
+                }
+                // read errors are treated as the end of the file
+                Err(_) => {
+                    self.draining = true;
+                    let _ = decoder.send_eof();
+                }
+            }
+        }
+
+        let rate = self.frame.rate().max(1) as u64;
+        match self.frame.timestamp() {
+            Some(ts) => {
+                self.position_ms = (ts.max(0) as f64 * self.time_base * 1000.0) as u64;
+            }
+            None => self.position_ms += self.frame_duration * 1000 / rate,
+        }
+        self.frame_duration = self.frame.samples() as u64;
+
+        Ok(true)
+    }
+
+    /// The samples of one channel of the current frame, or all channels for packed formats.
+    fn plane<T: Copy>(&self, index: usize, len: usize) -> &[T] {
+        // SAFETY: FFmpeg allocates every plane with room for at least `samples` samples, and
+        // `extended_data` holds a plane for each channel even past the 8 in `data`
+        unsafe {
+            let plane = *(*self.frame.as_ptr()).extended_data.add(index);
+            std::slice::from_raw_parts(plane.cast::<T>(), len)
+        }
+    }
+
+    /// Convert the current frame into `self.conversion_buffer`, one buffer per channel.
+    fn convert<T: Copy>(&mut self, convert: impl Fn(T) -> f64) {
+        let channels = self.frame.channels() as usize;
+        let frames = self.frame.samples();
+        let mut buffers = std::mem::take(&mut self.conversion_buffer);
+        buffers.resize_with(channels, Vec::new);
+
+        for buffer in buffers.iter_mut() {
+            buffer.clear();
+        }
+
+        if self.frame.is_planar() {
+            for (ch, buffer) in buffers.iter_mut().enumerate() {
+                buffer.extend(self.plane::<T>(ch, frames).iter().map(|&s| convert(s)));
+            }
+        } else {
+            for (i, &sample) in self.plane::<T>(0, frames * channels).iter().enumerate() {
+                buffers[i % channels].push(convert(sample));
+            }
+        }
+
+        self.conversion_buffer = buffers;
+    }
+
+    fn bits_per_raw_sample(&self) -> i32 {
+        self.decoder
+            .as_ref()
+            .map(|d| unsafe { (*d.as_ptr()).bits_per_raw_sample })
+            .unwrap_or_default()
+    }
+}
+
+impl MediaStream for FfmpegStream {
+    fn close(&mut self) -> Result<(), CloseError> {
+        self.stop_playback()
+            .map_err(|e| CloseError::Unknown(e.to_string()))?;
+        self.metadata = Metadata::default();
+        self.demuxer = None;
+        Ok(())
+    }
+
+    fn start_playback(&mut self) -> Result<(), PlaybackStartError> {
+        let Some(demuxer) = &self.demuxer else {
+            return Err(PlaybackStartError::InvalidState);
+        };
+        let stream = demuxer
+            .input
+            .stream(self.stream_index)
+            .ok_or(PlaybackStartError::NothingToPlay)?;
+
+        let context = codec::Context::from_parameters(stream.parameters())
+            .map_err(|e| PlaybackStartError::MediaError(e.to_string()))?;
+        self.decoder = Some(
+            context
+                .decoder()
+                .audio()
+                .map_err(|_| PlaybackStartError::Undecodable)?,
+        );
+        self.draining = false;
+
+        Ok(())
+    }
+
+    fn stop_playback(&mut self) -> Result<(), PlaybackStopError> {
+        self.decoder = None;
+        Ok(())
+    }
+
+    fn seek(&mut self, time: f64) -> Result<(), SeekError> {
+        let Some(demuxer) = &mut self.demuxer else {
+            return Err(SeekError::InvalidState);
+        };
+
+        let target = (time.max(0.0) * ffi::AV_TIME_BASE as f64) as i64;
+        demuxer
+            .input
+            .seek(target, ..)
+            .map_err(|e| SeekError::Unknown(e.to_string()))?;
+
+        if let Some(decoder) = &mut self.decoder {
+            decoder.flush();
+        }
+        self.draining = false;
+        self.position_ms = (time.max(0.0) * 1000.0) as u64;
+
+        Ok(())
+    }
+
+    fn frame_duration(&self) -> Result<u64, FrameDurationError> {
+        if self.decoder.is_none() || self.frame_duration == 0 {
+            Err(FrameDurationError::NeverStarted)
+        } else {
+            Ok(self.frame_duration)
+        }
+    }
+
+    fn read_metadata(&mut self) -> Result<&Metadata, MetadataError> {
+        self.pending_metadata_update = false;
+
+        if self.demuxer.is_some() {
+            Ok(&self.metadata)
+        } else {
+            Err(MetadataError::InvalidState)
+        }
+    }
+
+    fn metadata_updated(&self) -> bool {
+        self.pending_metadata_update
+    }
+
+    fn read_image(&mut self) -> Result<Option<Box<[u8]>>, MetadataError> {
+        if self.demuxer.is_some() {
+            Ok(self.image.take())
+        } else {
+            Err(MetadataError::InvalidState)
+        }
+    }
+
+    fn duration_secs(&self) -> Result<u64, TrackDurationError> {
+        if self.decoder.is_none() {
+            Err(TrackDurationError::NeverStarted)
+        } else {
+            Ok(self.duration_secs)
+        }
+    }
+
+    fn position_ms(&self) -> Result<u64, TrackDurationError> {
+        if self.decoder.is_none() {
+            Err(TrackDurationError::NeverStarted)
+        } else {
+            Ok(self.position_ms)
+        }
+    }
+
+    fn channels(&self) -> Result<ChannelSpec, ChannelRetrievalError> {
+        let Some(demuxer) = &self.demuxer else {
+            return Err(ChannelRetrievalError::InvalidState);
+        };
+        let stream = demuxer
+            .input
+            .stream(self.stream_index)
+            .ok_or(ChannelRetrievalError::NothingToPlay)?;
+
+        let channels = unsafe { (*stream.parameters().as_ptr()).ch_layout.nb_channels };
+        if channels <= 0 {
+            return Err(ChannelRetrievalError::NothingToPlay);
+        }
+
+        Ok(ChannelSpec::Count(channels as u16))
+    }
+
+    fn sample_format(&self) -> Result<SampleFormat, ChannelRetrievalError> {
+        let Some(decoder) = &self.decoder else {
+            return Err(ChannelRetrievalError::NeverStarted);
+        };
+
+        match decoder.format() {
+            format::Sample::U8(_) => Ok(SampleFormat::Unsigned8),
+            format::Sample::I16(_) => Ok(SampleFormat::Signed16),
+            format::Sample::I32(_) if self.bits_per_raw_sample() == 24 => {
+                Ok(SampleFormat::Signed24)
+            }
+            format::Sample::I32(_) | format::Sample::I64(_) => Ok(SampleFormat::Signed32),
+            format::Sample::F32(_) => Ok(SampleFormat::Float32),
+            format::Sample::F64(_) => Ok(SampleFormat::Float64),
+            format::Sample::None => Err(ChannelRetrievalError::InvalidState),
+        }
+    }
+
+    fn sample_rate(&self) -> Result<u32, ChannelRetrievalError> {
+        let Some(demuxer) = &self.demuxer else {
+            return Err(ChannelRetrievalError::InvalidState);
+        };
+        let stream = demuxer
+            .input
+            .stream(self.stream_index)
+            .ok_or(ChannelRetrievalError::NothingToPlay)?;
+
+        let rate = unsafe { (*stream.parameters().as_ptr()).sample_rate };
+        if rate <= 0 {
+            return Err(ChannelRetrievalError::NothingToPlay);
+        }
+
+        Ok(rate as u32)
+    }
+
+    fn decode_into(
+        &mut self,
+        output: &ChannelProducers<f64>,
+    ) -> Result<DecodeResult, PlaybackReadError> {
+        if !self.next_frame()? {
+            return Ok(DecodeResult::Eof);
+        }
+
+        // convert - shouldn't lose any quality
+        match self.frame.format() {
+            format::Sample::U8(_) => self.convert(|s: u8| s.sample_into()),
+            format::Sample::I16(_) => self.convert(|s: i16| s.sample_into()),
+            // 24 bit samples are stored in the upper bits
+            format::Sample::I32(_) if self.bits_per_raw_sample() == 24 => {
+                self.convert(|s: i32| I24::try_from(s >> 8).expect("i24 overflow").sample_into())
+            }
+            format::Sample::I32(_) => self.convert(|s: i32| s.sample_into()),
+            format::Sample::I64(_) => self.convert(|s: i64| ((s >> 32) as i32).sample_into()),
+            format::Sample::F32(_) => self.convert(|s: f32| s.sample_into()),
+            format::Sample::F64(_) => self.convert(|s: f64| s),
+            format::Sample::None => {
+                return Err(PlaybackReadError::DecodeFatal(
+                    "decoder returned a frame without a sample format".to_string(),
+                ));
+            }
+        }
+
+        output.write_vecs(&self.conversion_buffer);
+
+        Ok(DecodeResult::Decoded {
+            frames: self.frame.samples(),
+            rate: self.frame.rate(),
+        })
+    }
+
+    fn decode_into_f32(
+        &mut self,
+        output: &ChannelProducers<f32>,
+    ) -> Result<F32DecodeResult, PlaybackReadError> {
+        // packed samples would have to be copied anyway, so they go through the f64 path
+        if self.decoder.as_ref().map(|d| d.format())
+            != Some(format::Sample::F32(format::sample::Type::Planar))
+        {
+            return Ok(F32DecodeResult::NotF32);
+        }
+
+        if !self.next_frame()? {
+            return Ok(F32DecodeResult::Decoded(DecodeResult::Eof));
+        }
+
+        let frames = self.frame.samples();
+        let channels = self.frame.channels() as usize;
+        let slices: SmallVec<[&[f32]; 8]> = (0..channels)
+            .map(|ch| self.plane::<f32>(ch, frames))
+            .collect();
+        output.write_slices(&slices);
+
+        Ok(F32DecodeResult::Decoded(DecodeResult::Decoded {
+            frames,
+            rate: self.frame.rate(),
+        }))
+    }
+}
+
+/// Parse a ReplayGain gain such as "-3.21 dB".
+fn parse_gain(value: &str) -> Option<f64> {
+    let value = value.trim();
+    let value = if value.len() >= 2 && value[value.len() - 2..].eq_ignore_ascii_case("db") {
+        value[..value.len() - 2].trim()
+    } else {
+        value
+    };
+    value.parse().ok()
+}
+
+/// Parse a "current/total" position, as used for track and disc numbers.
+fn parse_position(value: &str) -> (Option<u64>, Option<u64>) {
+    match value.split_once('/') {
+        Some((current, total)) => (current.trim().parse().ok(), total.trim().parse().ok()),
+        None => (value.trim().parse().ok(), None),
+    }
+}
+
+/// Matches disc numbers that carry the disc's subtitle, like `Disc 2 - Live`.
+static DISC_SUBTITLE_REGEX: LazyLock<Regex> =
+    LazyLock::new(|| Regex::new(r"(?:Disc )?(\d+) (?:-|—|-) (.+)").unwrap());
+
+/// Fill in metadata from FFmpeg's tags. FFmpeg maps ID3 frames to its own generic names, but
+/// passes APEv2 and Vorbis comment keys through unchanged, so several spellings are accepted for
+/// each field.
+fn read_tags<'a>(metadata: &mut Metadata, tags: impl Iterator<Item = (&'a str, &'a str)>) {
+    for (key, value) in tags {
+        let key = key.to_ascii_lowercase();
+        let text = || Some(value.to_string());
+
+        match key.as_str() {
+            "title" => metadata.name = text(),
+            "artist" => metadata.artist = text(),
+            "album_artist" | "album artist" | "albumartist" => metadata.album_artist = text(),
+            "original_artist" | "originalartist" => metadata.original_artist = text(),
+            "composer" => metadata.composer = text(),
+            "album" => metadata.album = text(),
+            "genre" => metadata.genre = text(),
+            "grouping" | "contentgroup" => metadata.grouping = text(),
+            "bpm" | "tbpm" => metadata.bpm = value.trim().parse().ok(),
+            "compilation" => metadata.compilation = matches!(value.trim(), "1" | "true"),
+            "date" | "year" => {
+                metadata.date = None;
+                metadata.year_month = None;
+                metadata.year = None;
+
+                match parse_release_date(value) {
+                    Some(ParsedReleaseDate::FullDate(date)) => metadata.date = Some(date),
+                    Some(ParsedReleaseDate::YearMonth(year, month)) => {
+                        metadata.year_month = Some((year, month))
+                    }
+                    Some(ParsedReleaseDate::Year(year)) => metadata.year = Some(year),
+                    None => {}
+                }
+            }
+            "track" | "tracknumber" => {
+                let (current, total) = parse_position(value);
+                metadata.track_current = current;
+                if total.is_some() {
+                    metadata.track_max = total;
+                }
+            }
+            "tracktotal" | "totaltracks" => metadata.track_max = value.trim().parse().ok(),
+            "disc" | "discnumber" => {
+                if let Some(captures) = DISC_SUBTITLE_REGEX.captures(value) {
+                    metadata.disc_current = captures[1].parse().ok();
+                    metadata.disc_subtitle = Some(captures[2].to_string());
+                } else {
+                    let (current, total) = parse_position(value);
+                    metadata.disc_current = current;
+                    if total.is_some() {
+                        metadata.disc_max = total;
+                    }
+                }
+            }
+            "disctotal" | "totaldiscs" => metadata.disc_max = value.trim().parse().ok(),
+            "discsubtitle" | "setsubtitle" => metadata.disc_subtitle = text(),
+            "label" | "publisher" => metadata.label = text(),
+            "catalog" | "catalognumber" => metadata.catalog = text(),
+            "isrc" => metadata.isrc = text(),
+            "albumsort" | "album-sort" | "sort_album" => metadata.sort_album = text(),
+            "albumartistsort" | "album_artist-sort" => metadata.artist_sort = text(),
+            "musicbrainz_albumid" | "musicbrainz album id" => metadata.mbid_album = text(),
+            "replaygain_track_gain" => metadata.replaygain_track_gain = parse_gain(value),
+            "replaygain_track_peak" => metadata.replaygain_track_peak = value.trim().parse().ok(),
+            "replaygain_album_gain" => metadata.replaygain_album_gain = parse_gain(value),
+            "replaygain_album_peak" => metadata.replaygain_album_peak = value.trim().parse().ok(),
+            "cuesheet" => metadata.cuesheet = text(),
+            // ID3 lyrics carry their language, e.g. `lyrics-eng`
+            key if key == "lyrics" || key == "unsyncedlyrics" || key.starts_with("lyrics-") => {
+                metadata.lyrics = text()
+            }
+            _ => {}
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::{ffi::OsStr, fs::File, path::Path};
+
+    use super::{FfmpegProvider, read_tags};
+    use crate::{
+        devices::resample::SampleFrom,
+        media::{
+            metadata::Metadata,
+            pipeline::{ChannelBuffers, DecodeResult},
+            traits::{MediaProvider, MediaStream},
+        },
+        test_support::TestDir,
+    };
+
+    const RATE: u32 = 44100;
+
+    /// Write a second of stereo audio whose left channel counts every other frame, so the
+    /// position of any decoded sample can be read back from its value.
+    fn write_counting_wav(path: &Path) {
+        let spec = hound::WavSpec {
+            channels: 2,
+            sample_rate: RATE,
+            bits_per_sample: 16,
+            sample_format: hound::SampleFormat::Int,
+        };
+        let mut writer = hound::WavWriter::create(path, spec).unwrap();
+        for frame in 0..RATE {
+            let value = (frame / 2) as i16;
+            writer.write_sample(value).unwrap();
+            writer.write_sample(-value).unwrap();
+        }
+        writer.finalize().unwrap();
+    }
+
+    /// Decode the next frame, returning its samples per channel.
+    fn decode(stream: &mut dyn MediaStream) -> Option<Vec<Vec<i16>>> {
+        let (producers, mut consumers) = ChannelBuffers::<f64>::new(2, 1 << 16).split();
+        match stream.decode_into(&producers).unwrap() {
+            DecodeResult::Decoded { frames, rate } => {
+                assert_eq!(rate, RATE);
+                assert_eq!(consumers.try_read_to_staging(1 << 16), frames);
+                Some(
+                    consumers
+                        .staging()
+                        .iter()
+                        .map(|channel| channel.iter().map(|&s| i16::sample_from(s)).collect())
+                        .collect(),
+                )
+            }
+            DecodeResult::Eof => None,
+        }
+    }
+
+    #[test]
+    fn decodes_and_seeks_through_custom_io() {
+        ffmpeg_next::init().unwrap();
+        let dir = TestDir::new("hummingbird-ffmpeg");
+        let path = dir.join("counting.wav");
+        write_counting_wav(&path);
+
+        let provider = FfmpegProvider {
+            name: "Test",
+            required_demuxer: None,
+            extensions: &["wav"],
+        };
+        let mut stream = provider
+            .open(File::open(&path).unwrap(), Some(OsStr::new("wav")))
+            .unwrap();
+        stream.start_playback().unwrap();
+
+        assert_eq!(stream.channels().unwrap().count(), 2);
+        assert_eq!(stream.sample_rate().unwrap(), RATE);
+        assert_eq!(stream.duration_secs().unwrap(), 1);
+
+        let mut frames = 0;
+        while let Some(channels) = decode(stream.as_mut()) {
+            for (left, right) in channels[0].iter().zip(&channels[1]) {
+                assert_eq!(*left, (frames / 2) as i16);
+                assert_eq!(*right, -left);
+                frames += 1;
+            }
+        }
+        assert_eq!(frames, RATE);
+
+        stream.seek(0.5).unwrap();
+        let channels = decode(stream.as_mut()).unwrap();
+        let frame = u64::try_from(channels[0][0]).unwrap() * 2;
+        let position_ms = stream.position_ms().unwrap();
+        assert!(
+            frame.abs_diff(u64::from(RATE / 2)) < 4096,
+            "frame = {frame}"
+        );
+        assert!(
+            (frame * 1000 / u64::from(RATE)).abs_diff(position_ms) <= 1,
+            "frame = {frame}, position = {position_ms} ms"
+        );
+    }
+
+    #[test]
+    fn reads_ape_style_tags() {
+        let mut metadata = Metadata::default();
+        read_tags(
+            &mut metadata,
+            [
+                ("Title", "Song"),
+                ("Album Artist", "Band"),
+                ("Track", "3/12"),
+                ("Disc", "2"),
+                ("Year", "1999"),
+                ("REPLAYGAIN_TRACK_GAIN", "-3.21 dB"),
+                ("REPLAYGAIN_TRACK_PEAK", "0.98"),
+            ]
+            .into_iter(),
+        );
+
+        assert_eq!(metadata.name.as_deref(), Some("Song"));
+        assert_eq!(metadata.album_artist.as_deref(), Some("Band"));
+        assert_eq!(metadata.track_current, Some(3));
+        assert_eq!(metadata.track_max, Some(12));
+        assert_eq!(metadata.disc_current, Some(2));
+        assert_eq!(metadata.year, Some(1999));
+        assert_eq!(metadata.replaygain_track_gain, Some(-3.21));
+        assert_eq!(metadata.replaygain_track_peak, Some(0.98));
+    }
+
+    #[test]
+    fn reads_ffmpeg_generic_tags() {
+        let mut metadata = Metadata::default();
+        read_tags(
+            &mut metadata,
+            [
+                ("album_artist", "Band"),
+                ("track", "7"),
+                ("TRACKTOTAL", "9"),
+                ("disc", "1 - Live"),
+                ("publisher", "Label"),
+                ("lyrics-eng", "la la la"),
+            ]
+            .into_iter(),
+        );
+
+        assert_eq!(metadata.album_artist.as_deref(), Some("Band"));
+        assert_eq!(metadata.track_current, Some(7));
+        assert_eq!(metadata.track_max, Some(9));
+        assert_eq!(metadata.disc_current, Some(1));
+        assert_eq!(metadata.disc_subtitle.as_deref(), Some("Live"));
+        assert_eq!(metadata.label.as_deref(), Some("Label"));
+        assert_eq!(metadata.lyrics.as_deref(), Some("la la la"));
+    }
+}
@@ -0,0 +1,298 @@
+use std::{ffi::OsStr, fs::File, path::Path};
+
+use lofty::{
+    ape::{ApeFile, ApeItem, ApeTag},
+    config::{ParseOptions, WriteOptions},
+    file::{AudioFile, FileType},
+    flac::FlacFile,
+    id3::v2::Id3v2Tag,
+    iff::{aiff::AiffFile, wav::WavFile},
+    mp4::{Atom, AtomData, AtomIdent, Ilst, Mp4File},
+    mpeg::MpegFile,
+    musepack::MpcFile,
+    ogg::{OpusFile, VorbisComments, VorbisFile},
+    picture::{Picture, PictureType},
+    tag::{ItemKey, ItemValue, MergeTag, SplitTag, TagExt},
+    wavpack::WavPackFile,
+};
+
+use crate::media::{
+    errors::{OpenError, TagWriteError},
+    metadata::{ReplayGainValues, TagField, TagUpdate},
+    traits::{MediaProvider, MediaProviderFeatures, MediaStream},
+};
+
+/// Opus files are normalized to -23 LUFS instead of ReplayGain's -18 LUFS.
+const R128_OFFSET_DB: f64 = -5.0;
+
+/// Tag writer backed by Lofty. Symphonia can only read tags, so this provider handles writing
+/// them, and doesn't open files for playback or metadata.
+pub struct LoftyProvider;
+
+impl MediaProvider for LoftyProvider {
+    fn open(&self, _: File, _: Option<&OsStr>) -> Result<Box<dyn MediaStream>, OpenError> {
+        Err(OpenError::UnsupportedFormat)
+    }
+
+    fn supported_extensions(&self) -> &[&str] {
+        &[
+            "ogg", "oga", "flac", "wav", "mp3", "m4a", "aiff", "opus", "ape", "wv", "mpc",
+        ]
+    }
+
+    fn supported_features(&self) -> MediaProviderFeatures {
+        MediaProviderFeatures::WRITES_TAGS
+    }
+
+    fn name(&self) -> &str {
+        "Lofty"
+    }
+
+    fn write_tags(&self, path: &Path, update: &TagUpdate) -> Result<(), TagWriteError> {
+        let file_type = FileType::from_path(path).ok_or(TagWriteError::UnsupportedFormat)?;
+        let mut file = File::open(path).map_err(|e| TagWriteError::IoError(e.to_string()))?;
+        // only the tags are needed
+        let options = ParseOptions::new().read_properties(false);
+        let cover = update.cover.as_deref().map(front_cover).transpose()?;
+
+        match file_type {
+            FileType::Flac => {
+                let mut flac = FlacFile::read_from(&mut file, options).map_err(unknown)?;
+                let mut tag = flac.vorbis_comments().cloned().unwrap_or_default();
+                update_vorbis_comments(&mut tag, update, false);
+                flac.set_vorbis_comments(apply_edits(tag, &update.fields, None));
+
+                // FLAC stores pictures in their own metadata blocks rather than in the comments,
+                // so the cover is set on the file and the whole file is saved
+                if let Some(cover) = cover {
+                    flac.remove_picture_type(PictureType::CoverFront);
+                    flac.insert_picture(cover, None).map_err(unknown)?;
+                }
+
+                flac.save_to_path(path, WriteOptions::default())
+                    .map_err(unknown)
+            }
+            FileType::Vorbis => {
+                let vorbis = VorbisFile::read_from(&mut file, options).map_err(unknown)?;
+                let mut tag = vorbis.vorbis_comments().clone();
+                update_vorbis_comments(&mut tag, update, false);
+                save(apply_edits(tag, &update.fields, cover), path)
+            }
+            FileType::Opus => {
+                let opus = OpusFile::read_from(&mut file, options).map_err(unknown)?;
+                let mut tag = opus.vorbis_comments().clone();
+                update_vorbis_comments(&mut tag, update, true);
+                save(apply_edits(tag, &update.fields, cover), path)
+            }
+            FileType::Mpeg | FileType::Aiff | FileType::Wav => {
+                let existing = match file_type {
+                    FileType::Mpeg => MpegFile::read_from(&mut file, options)
+                        .map_err(unknown)?
+                        .id3v2()
+                        .cloned(),
+                    FileType::Aiff => AiffFile::read_from(&mut file, options)
+                        .map_err(unknown)?
+                        .id3v2()
+                        .cloned(),
+                    _ => WavFile::read_from(&mut file, options)
+                        .map_err(unknown)?
+                        .id3v2()
+                        .cloned(),
+                };
+                let mut tag = existing.unwrap_or_default();
+                update_id3v2(&mut tag, update);
+                save(apply_edits(tag, &update.fields, cover), path)
+            }
+            FileType::Mp4 => {
+                let mp4 = Mp4File::read_from(&mut file, options).map_err(unknown)?;
+                let mut tag = mp4.ilst().cloned().unwrap_or_default();
+                update_ilst(&mut tag, update);
+                save(apply_edits(tag, &update.fields, cover), path)
+            }
+            FileType::Ape | FileType::WavPack | FileType::Mpc => {
+                let existing = match file_type {
+                    FileType::Ape => ApeFile::read_from(&mut file, options)
+                        .map_err(unknown)?
+                        .ape()
+                        .cloned(),
+                    FileType::WavPack => WavPackFile::read_from(&mut file, options)
+                        .map_err(unknown)?
+                        .ape()
+                        .cloned(),
+                    _ => MpcFile::read_from(&mut file, options)
+                        .map_err(unknown)?
+                        .ape()
+                        .cloned(),
+                };
+                let mut tag = existing.unwrap_or_default();
+                update_ape(&mut tag, update)?;
+                save(apply_edits(tag, &update.fields, cover), path)
+            }
+            _ => Err(TagWriteError::UnsupportedFormat),
+        }
+    }
+}
+
+fn unknown(error: impl std::fmt::Display) -> TagWriteError {
+    TagWriteError::Unknown(error.to_string())
+}
+
+fn save(tag: impl TagExt, path: &Path) -> Result<(), TagWriteError> {
+    tag.save_to_path(path, WriteOptions::default())
+        .map_err(unknown)
+}
+
+fn item_key(field: TagField) -> ItemKey {
+    match field {
+        TagField::Title => ItemKey::TrackTitle,
+        TagField::Artist => ItemKey::TrackArtist,
+        TagField::Album => ItemKey::AlbumTitle,
+        TagField::AlbumArtist => ItemKey::AlbumArtist,
+        TagField::TrackNumber => ItemKey::TrackNumber,
+        TagField::TrackTotal => ItemKey::TrackTotal,
+        TagField::DiscNumber => ItemKey::DiscNumber,
+        TagField::DiscTotal => ItemKey::DiscTotal,
+        TagField::Date => ItemKey::RecordingDate,
+        TagField::Genre => ItemKey::Genre,
+        TagField::Label => ItemKey::Label,
+        TagField::Catalog => ItemKey::CatalogNumber,
+        TagField::Isrc => ItemKey::Isrc,
+        TagField::Lyrics => ItemKey::Lyrics,
+    }
+}
+
+fn front_cover(data: &[u8]) -> Result<Picture, TagWriteError> {
+    let mut picture = Picture::from_reader(&mut &data[..]).map_err(unknown)?;
+    picture.set_pic_type(PictureType::CoverFront);
+    Ok(picture)
+}
+
+/// Applies text and cover edits through Lofty's format-independent tag, which maps each field to
+/// the right frame, comment or atom for the format. Items the generic tag can't represent are kept
+/// aside by the split and put back unchanged by the merge.
+fn apply_edits<T>(tag: T, fields: &[(TagField, String)], cover: Option<Picture>) -> T
+where
+    T: SplitTag,
+    T::Remainder: MergeTag<Merged = T>,
+{
+    if fields.is_empty() && cover.is_none() {
+        return tag;
+    }
+
+    let (remainder, mut generic) = tag.split_tag();
+
+    for (field, value) in fields {
+        let key = item_key(*field);
+        generic.retain(|item| item.key() != &key);
+        if !value.is_empty() {
+            generic.insert_text(key, value.clone());
+        }
+    }
+
+    if let Some(cover) = cover {
+        generic.remove_picture_type(PictureType::CoverFront);
+        generic.push_picture(cover);
+    }
+
+    remainder.merge_tag(generic)
+}
+
+fn format_gain(gain_db: f64) -> String {
+    format!("{:.2} dB", gain_db)
+}
+
+fn format_peak(peak: f64) -> String {
+    format!("{:.6}", peak)
+}
+
+/// Converts a ReplayGain value to the Q7.8 fixed point format used by the `R128_*` tags in Opus
+/// files (RFC 7845).
+fn r128_gain(gain_db: f64) -> String {
+    let gain = ((gain_db + R128_OFFSET_DB) * 256.0).round();
+    (gain.clamp(i16::MIN as f64, i16::MAX as f64) as i16).to_string()
+}
+
+fn replaygain_fields(values: &ReplayGainValues) -> Vec<(&'static str, String)> {
+    let mut fields = vec![
+        ("REPLAYGAIN_TRACK_GAIN", format_gain(values.track_gain)),
+        ("REPLAYGAIN_TRACK_PEAK", format_peak(values.track_peak)),
+    ];
+
+    if let Some(gain) = values.album_gain {
+        fields.push(("REPLAYGAIN_ALBUM_GAIN", format_gain(gain)));
+    }
+    if let Some(peak) = values.album_peak {
+        fields.push(("REPLAYGAIN_ALBUM_PEAK", format_peak(peak)));
+    }
+
+    fields
+}
+
+/// Opus players apply `R128_*` tags on top of the output gain in the header, and don't use
+/// peaks, so Opus files get those instead of the usual `REPLAYGAIN_*` comments.
+fn update_vorbis_comments(tag: &mut VorbisComments, update: &TagUpdate, opus: bool) {
+    let Some(values) = &update.replaygain else {
+        return;
+    };
+
+    if opus {
+        tag.insert("R128_TRACK_GAIN".to_string(), r128_gain(values.track_gain));
+        if let Some(gain) = values.album_gain {
+            tag.insert("R128_ALBUM_GAIN".to_string(), r128_gain(gain));
+        }
+        return;
+    }
+
+    for (key, value) in replaygain_fields(values) {
+        tag.insert(key.to_string(), value);
+    }
+}
+
+fn update_id3v2(tag: &mut Id3v2Tag, update: &TagUpdate) {
+    let Some(values) = &update.replaygain else {
+        return;
+    };
+
+    for (key, value) in replaygain_fields(values) {
+        tag.insert_user_text(key.to_string(), value);
+    }
+}
+
+fn update_ilst(tag: &mut Ilst, update: &TagUpdate) {
+    let Some(values) = &update.replaygain else {
+        return;
+    };
+
+    for (key, value) in replaygain_fields(values) {
+        let ident = AtomIdent::Freeform {
+            mean: "com.apple.iTunes".into(),
+            name: key.to_lowercase().into(),
+        };
+        tag.insert(Atom::new(ident, AtomData::UTF8(value)));
+    }
+}
+
+fn update_ape(tag: &mut ApeTag, update: &TagUpdate) -> Result<(), TagWriteError> {
+    let Some(values) = &update.replaygain else {
+        return Ok(());
+    };
+
+    for (key, value) in replaygain_fields(values) {
+        tag.insert(ApeItem::new(key.to_string(), ItemValue::Text(value)).map_err(unknown)?);
+    }
+
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn r128_gain_is_relative_to_ebu_reference() {
+        // -18 LUFS ReplayGain reference is 5dB louder than the -23 LUFS R128 reference
+        assert_eq!(r128_gain(5.0), "0");
+        assert_eq!(r128_gain(-1.0), "-1536");
+        assert_eq!(r128_gain(500.0), i16::MAX.to_string());
+    }
+}
@@ -6,7 +6,7 @@ use regex::Regex;
 use smallvec::SmallVec;
 use symphonia::{
     core::{
-        audio::{AudioBufferRef, Channels, Signal},
+        audio::{AudioBufferRef, Signal},
         codecs::{
             CODEC_TYPE_NULL, CODEC_TYPE_PCM_ALAW, CODEC_TYPE_PCM_F32BE,
             CODEC_TYPE_PCM_F32BE_PLANAR, CODEC_TYPE_PCM_F32LE, CODEC_TYPE_PCM_F32LE_PLANAR,
@@ -38,7 +38,7 @@ use symphonia::{
 use symphonia_adapter_libopus::OpusDecoder;
 
 use crate::{
-    devices::format::{ChannelSpec, SampleFormat},
+    devices::format::{ChannelLayout, ChannelSpec, SampleFormat},
     devices::resample::SampleInto,
     media::{
         errors::{
@@ -99,7 +99,7 @@ fn time_to_millis(time: Time) -> u64 {
 }
 
 #[derive(Debug, Clone, Copy, PartialEq, Eq)]
-enum ParsedReleaseDate {
+pub(crate) enum ParsedReleaseDate {
     FullDate(DateTime<Utc>),
     YearMonth(u16, u8),
     Year(u16),
@@ -172,7 +172,7 @@ fn parse_iso_release_date(value: &str) -> Result<Option<ParsedReleaseDate>, ()>
     }
 }
 
-fn parse_release_date(value: &str) -> Option<ParsedReleaseDate> {
+pub(crate) fn parse_release_date(value: &str) -> Option<ParsedReleaseDate> {
     match parse_iso_release_date(value) {
         Ok(Some(date)) => Some(date),
         Err(()) => None,
@@ -414,6 +414,8 @@ impl SymphoniaStream {
                     // ID3 shenanigans
                     } else if key.eq_ignore_ascii_case("TXXX:MusicBrainz Album Id") {
                         self.current_metadata.mbid_album = Some(tag.value.to_string());
+                    } else if key.eq_ignore_ascii_case("CUESHEET") {
+                        self.current_metadata.cuesheet = Some(tag.value.to_string());
                     }
                 }
             }
@@ -671,13 +673,18 @@ impl MediaStream for SymphoniaStream {
         // this "fixes" m4a container files but obviously poorly
         //
         // upstream issue: https://github.com/pdeljanov/Symphonia/issues/289
-        Ok(ChannelSpec::Count(
-            track
-                .codec_params
-                .channels
-                .map(Channels::count)
-                .unwrap_or(2) as u16,
-        ))
+        let Some(channels) = track.codec_params.channels else {
+            return Ok(ChannelSpec::Count(2));
+        };
+
+        // symphonia's positions match ours up to the last position defined by
+        // WAVE_FORMAT_EXTENSIBLE, anything after that is only known by count
+        let layout = ChannelLayout::from_bits_truncate(channels.bits());
+        if layout.count() as usize == channels.count() {
+            Ok(ChannelSpec::Layout(layout))
+        } else {
+            Ok(ChannelSpec::Count(channels.count() as u16))
+        }
     }
 
     fn sample_format(&self) -> Result<SampleFormat, ChannelRetrievalError> {
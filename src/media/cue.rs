@@ -0,0 +1,486 @@
+//! CUE sheet support, for single-file rips that are split into tracks by a `.cue` file.
+//!
+//! Each track of a CUE sheet is stored in the library as its own row. The row's location is the
+//! path of the audio file, like any other track, and its `start_ms` and `end_ms` columns hold the
+//! section of the file that is played.
+
+use std::path::Path;
+
+use camino::{Utf8Path, Utf8PathBuf};
+use serde::{Deserialize, Serialize};
+
+use super::metadata::Metadata;
+
+/// The section of a file that is played as a track.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+pub struct TrackRange {
+    pub start_ms: u64,
+    /// Where the track ends. `None` plays until the end of the file.
+    pub end_ms: Option<u64>,
+}
+
+impl TrackRange {
+    /// The section described by a track's `start_ms` and `end_ms` columns, or `None` if the
+    /// track is the whole file.
+    pub fn from_columns(start_ms: i64, end_ms: Option<i64>) -> Option<Self> {
+        (start_ms > 0 || end_ms.is_some()).then(|| TrackRange {
+            start_ms: start_ms.max(0) as u64,
+            end_ms: end_ms.map(|end_ms| end_ms.max(0) as u64),
+        })
+    }
+
+    /// The value of the `start_ms` column for a track that plays `range` of its file.
+    pub fn start_column(range: Option<TrackRange>) -> i64 {
+        range.map_or(0, |range| range.start_ms as i64)
+    }
+
+    /// The length of the section, given the duration of the whole file.
+    pub fn duration_ms(&self, file_duration_ms: u64) -> u64 {
+        self.end_ms
+            .unwrap_or(file_duration_ms)
+            .saturating_sub(self.start_ms)
+    }
+}
+
+#[derive(Debug, Default, Clone, PartialEq)]
+pub struct CueTrack {
+    pub number: u64,
+    pub title: Option<String>,
+    pub performer: Option<String>,
+    pub songwriter: Option<String>,
+    pub isrc: Option<String>,
+    /// The position of `INDEX 01`, where the track proper starts.
+    pub start_ms: Option<u64>,
+    pub replaygain_gain: Option<f64>,
+    pub replaygain_peak: Option<f64>,
+}
+
+#[derive(Debug, Default, Clone, PartialEq)]
+pub struct CueFile {
+    pub name: String,
+    pub tracks: Vec<CueTrack>,
+}
+
+#[derive(Debug, Default, Clone, PartialEq)]
+pub struct CueSheet {
+    pub title: Option<String>,
+    pub performer: Option<String>,
+    pub songwriter: Option<String>,
+    pub date: Option<String>,
+    pub genre: Option<String>,
+    pub catalog: Option<String>,
+    pub disc_number: Option<u64>,
+    pub disc_total: Option<u64>,
+    pub replaygain_album_gain: Option<f64>,
+    pub replaygain_album_peak: Option<f64>,
+    pub files: Vec<CueFile>,
+}
+
+/// Splits a CUE sheet line into its command and arguments. Arguments may be quoted.
+fn split_line(line: &str) -> Vec<&str> {
+    let mut parts = Vec::new();
+    let mut rest = line.trim();
+
+    while !rest.is_empty() {
+        if let Some(quoted) = rest.strip_prefix('"') {
+            let end = quoted.find('"').unwrap_or(quoted.len());
+            parts.push(&quoted[..end]);
+            rest = quoted.get(end + 1..).unwrap_or("").trim_start();
+        } else {
+            let end = rest.find(char::is_whitespace).unwrap_or(rest.len());
+            parts.push(&rest[..end]);
+            rest = rest[end..].trim_start();
+        }
+    }
+
+    parts
+}
+
+/// Parses an `mm:ss:ff` timestamp, where `ff` counts CD frames of 1/75 seconds.
+fn parse_timestamp(value: &str) -> Option<u64> {
+    let mut parts = value.split(':').map(|part| part.parse::<u64>().ok());
+    let (minutes, seconds, frames) = (parts.next()??, parts.next()??, parts.next()??);
+
+    if parts.next().is_some() || seconds >= 60 || frames >= 75 {
+        return None;
+    }
+
+    Some((minutes * 60 + seconds) * 1000 + (frames * 1000 + 37) / 75)
+}
+
+/// Parses a ReplayGain value like `-7.45 dB` or `0.988525`.
+fn parse_replaygain(value: &str) -> Option<f64> {
+    value
+        .trim()
+        .trim_end_matches(|c: char| c.is_ascii_alphabetic())
+        .trim()
+        .parse()
+        .ok()
+}
+
+/// Decodes the contents of a CUE sheet. Sheets written by older rippers are often in a legacy
+/// 8-bit encoding instead of UTF-8, which is read as Latin-1.
+pub fn decode(bytes: &[u8]) -> String {
+    let bytes = bytes.strip_prefix(b"\xEF\xBB\xBF").unwrap_or(bytes);
+
+    match std::str::from_utf8(bytes) {
+        Ok(text) => text.to_string(),
+        Err(_) => bytes.iter().map(|&b| b as char).collect(),
+    }
+}
+
+impl CueSheet {
+    pub fn parse(text: &str) -> Self {
+        let mut sheet = CueSheet::default();
+
+        for line in text.lines() {
+            let parts = split_line(line);
+            let Some((&command, args)) = parts.split_first() else {
+                continue;
+            };
+            let arg = args.first().map(|arg| arg.to_string());
+
+            let track = sheet.files.last_mut().and_then(|f| f.tracks.last_mut());
+
+            match (command.to_ascii_uppercase().as_str(), track) {
+                ("FILE", _) => sheet.files.push(CueFile {
+                    name: arg.unwrap_or_default(),
+                    tracks: Vec::new(),
+                }),
+                ("TRACK", _) => {
+                    let (Some(file), Some(number)) = (
+                        sheet.files.last_mut(),
+                        args.first().and_then(|n| n.parse().ok()),
+                    ) else {
+                        continue;
+                    };
+
+                    // data tracks on enhanced CDs aren't part of the audio file
+                    if args.get(1).is_some_and(|t| t.eq_ignore_ascii_case("AUDIO")) {
+                        file.tracks.push(CueTrack {
+                            number,
+                            ..CueTrack::default()
+                        });
+                    }
+                }
+                ("TITLE", Some(track)) => track.title = arg,
+                ("TITLE", None) => sheet.title = arg,
+                ("PERFORMER", Some(track)) => track.performer = arg,
+                ("PERFORMER", None) => sheet.performer = arg,
+                ("SONGWRITER", Some(track)) => track.songwriter = arg,
+                ("SONGWRITER", None) => sheet.songwriter = arg,
+                ("ISRC", Some(track)) => track.isrc = arg,
+                ("CATALOG", None) => sheet.catalog = arg,
+                ("INDEX", Some(track))
+                    if args.first().and_then(|i| i.parse::<u32>().ok()) == Some(1) =>
+                {
+                    track.start_ms = args.get(1).and_then(|t| parse_timestamp(t));
+                }
+                ("REM", track) => {
+                    let Some((&key, value)) = args.split_first() else {
+                        continue;
+                    };
+                    let value = value.join(" ");
+                    let key = key.to_ascii_uppercase();
+
+                    match (key.as_str(), track) {
+                        ("REPLAYGAIN_TRACK_GAIN", Some(track)) => {
+                            track.replaygain_gain = parse_replaygain(&value)
+                        }
+                        ("REPLAYGAIN_TRACK_PEAK", Some(track)) => {
+                            track.replaygain_peak = parse_replaygain(&value)
+                        }
+                        ("REPLAYGAIN_ALBUM_GAIN", None) => {
+                            sheet.replaygain_album_gain = parse_replaygain(&value)
+                        }
+                        ("REPLAYGAIN_ALBUM_PEAK", None) => {
+                            sheet.replaygain_album_peak = parse_replaygain(&value)
+                        }
+                        ("DATE", None) => sheet.date = Some(value),
+                        ("GENRE", None) => sheet.genre = Some(value),
+                        ("DISCNUMBER", None) => sheet.disc_number = value.parse().ok(),
+                        ("TOTALDISCS", None) => sheet.disc_total = value.parse().ok(),
+                        _ => {}
+                    }
+                }
+                _ => {}
+            }
+        }
+
+        sheet
+    }
+
+    /// Finds the entry of the sheet that refers to the file at `path`. Sheets often still refer
+    /// to the file they were ripped to (for example `album.wav`) after the file was converted, so
+    /// entries with the same file stem are accepted too, as is the only entry of a single-file
+    /// sheet.
+    fn file_for(&self, path: &Utf8Path) -> Option<&CueFile> {
+        let name = path.file_name()?;
+        let stem = path.file_stem()?;
+        let entry_name = |file: &CueFile| {
+            let entry = file.name.replace('\\', "/");
+            Utf8PathBuf::from(entry).file_name().map(str::to_string)
+        };
+
+        self.files
+            .iter()
+            .find(|file| entry_name(file).is_some_and(|n| n.eq_ignore_ascii_case(name)))
+            .or_else(|| {
+                self.files.iter().find(|file| {
+                    entry_name(file).is_some_and(|n| {
+                        Utf8Path::new(&n)
+                            .file_stem()
+                            .is_some_and(|s| s.eq_ignore_ascii_case(stem))
+                    })
+                })
+            })
+            .or_else(|| (self.files.len() == 1).then(|| &self.files[0]))
+    }
+
+    /// Splits the file at `path` into the tracks this sheet lists for it, each with the file's
+    /// own metadata overlaid with the sheet's. Returns nothing if the sheet doesn't list any
+    /// tracks for the file.
+    pub fn tracks_for(
+        &self,
+        path: &Utf8Path,
+        file_metadata: &Metadata,
+    ) -> Vec<(TrackRange, Metadata)> {
+        let Some(file) = self.file_for(path) else {
+            return Vec::new();
+        };
+
+        let tracks: Vec<(&CueTrack, u64)> = file
+            .tracks
+            .iter()
+            .filter_map(|track| Some((track, track.start_ms?)))
+            .collect();
+        let track_max = tracks.len() as u64;
+
+        tracks
+            .iter()
+            .enumerate()
+            .map(|(i, &(track, start_ms))| {
+                let range = TrackRange {
+                    start_ms,
+                    end_ms: tracks.get(i + 1).map(|&(_, next)| next),
+                };
+
+                let mut metadata = file_metadata.clone();
+                self.apply(track, track_max, &mut metadata);
+                (range, metadata)
+            })
+            .collect()
+    }
+
+    /// Overlays the sheet's metadata for the given track on `metadata`.
+    fn apply(&self, track: &CueTrack, track_max: u64, metadata: &mut Metadata) {
+        metadata.name = track.title.clone().or(metadata.name.take());
+        metadata.artist = track
+            .performer
+            .clone()
+            .or(self.performer.clone())
+            .or(metadata.artist.take());
+        metadata.album_artist = self.performer.clone().or(metadata.album_artist.take());
+        metadata.composer = track
+            .songwriter
+            .clone()
+            .or(self.songwriter.clone())
+            .or(metadata.composer.take());
+        metadata.album = self.title.clone().or(metadata.album.take());
+        metadata.genre = self.genre.clone().or(metadata.genre.take());
+        metadata.catalog = self.catalog.clone().or(metadata.catalog.take());
+        metadata.isrc = track.isrc.clone().or(metadata.isrc.take());
+        metadata.track_current = Some(track.number);
+        metadata.track_max = Some(track_max);
+        metadata.disc_current = self.disc_number.or(metadata.disc_current);
+        metadata.disc_max = self.disc_total.or(metadata.disc_max);
+
+        if let Some(year) = self
+            .date
+            .as_deref()
+            .and_then(|date| date.get(..4))
+            .and_then(|year| year.parse().ok())
+            && metadata.date.is_none()
+            && metadata.year_month.is_none()
+        {
+            metadata.year = Some(year);
+        }
+
+        // the file's own ReplayGain values were measured over the whole disc
+        metadata.replaygain_track_gain = track.replaygain_gain;
+        metadata.replaygain_track_peak = track.replaygain_peak;
+        metadata.replaygain_album_gain = self
+            .replaygain_album_gain
+            .or(metadata.replaygain_album_gain);
+        metadata.replaygain_album_peak = self
+            .replaygain_album_peak
+            .or(metadata.replaygain_album_peak);
+
+        // lyrics and the embedded sheet belong to the whole file
+        metadata.lyrics = None;
+        metadata.cuesheet = None;
+    }
+}
+
+/// The CUE sheets that may accompany the file at `path`: `album.cue` and `album.flac.cue` for
+/// `album.flac`.
+pub fn sidecar_paths(path: &Utf8Path) -> Vec<Utf8PathBuf> {
+    let (Some(parent), Some(stem), Some(name)) =
+        (path.parent(), path.file_stem(), path.file_name())
+    else {
+        return Vec::new();
+    };
+
+    vec![
+        parent.join(format!("{stem}.cue")),
+        parent.join(format!("{name}.cue")),
+    ]
+}
+
+/// Reads the CUE sheet for the file at `path`, preferring a sidecar file over a sheet embedded
+/// in the file's tags.
+pub fn read_sheet(path: &Utf8Path, embedded: Option<&str>) -> Option<CueSheet> {
+    sidecar_paths(path)
+        .iter()
+        .find_map(|sidecar| std::fs::read(sidecar).ok())
+        .map(|bytes| decode(&bytes))
+        .or_else(|| embedded.map(str::to_string))
+        .map(|text| CueSheet::parse(&text))
+}
+
+/// Finds the metadata of the track of the file at `path` that starts at `range`, as listed by
+/// the file's CUE sheet.
+pub fn track_metadata(
+    path: &Path,
+    range: TrackRange,
+    file_metadata: &Metadata,
+) -> Option<Metadata> {
+    let path = Utf8Path::from_path(path)?;
+    let sheet = read_sheet(path, file_metadata.cuesheet.as_deref())?;
+
+    sheet
+        .tracks_for(path, file_metadata)
+        .into_iter()
+        .find(|(track_range, _)| track_range.start_ms == range.start_ms)
+        .map(|(_, metadata)| metadata)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const SHEET: &str = r#"REM GENRE "Progressive Rock"
+REM DATE 1973
+REM DISCID 610A5A07
+REM REPLAYGAIN_ALBUM_GAIN -6.12 dB
+PERFORMER "Pink Floyd"
+TITLE "The Dark Side of the Moon"
+FILE "Pink Floyd - The Dark Side of the Moon.wav" WAVE
+  TRACK 01 AUDIO
+    TITLE "Speak to Me"
+    REM REPLAYGAIN_TRACK_GAIN -3.50 dB
+    INDEX 01 00:00:00
+  TRACK 02 AUDIO
+    TITLE "Breathe"
+    PERFORMER "Pink Floyd feat. Nobody"
+    INDEX 00 01:07:50
+    INDEX 01 01:08:00
+  TRACK 03 AUDIO
+    TITLE "On the Run"
+    INDEX 01 03:57:37
+"#;
+
+    #[test]
+    fn parses_sheets() {
+        let sheet = CueSheet::parse(SHEET);
+
+        assert_eq!(sheet.title.as_deref(), Some("The Dark Side of the Moon"));
+        assert_eq!(sheet.performer.as_deref(), Some("Pink Floyd"));
+        assert_eq!(sheet.genre.as_deref(), Some("Progressive Rock"));
+        assert_eq!(sheet.date.as_deref(), Some("1973"));
+        assert_eq!(sheet.replaygain_album_gain, Some(-6.12));
+        assert_eq!(sheet.files.len(), 1);
+
+        let tracks = &sheet.files[0].tracks;
+        assert_eq!(tracks.len(), 3);
+        assert_eq!(tracks[0].replaygain_gain, Some(-3.5));
+        assert_eq!(tracks[1].start_ms, Some(68_000));
+        assert_eq!(tracks[2].start_ms, Some(237_493));
+    }
+
+    #[test]
+    fn splits_converted_files_into_tracks() {
+        let sheet = CueSheet::parse(SHEET);
+        let file_metadata = Metadata {
+            name: Some("Whole disc".to_string()),
+            replaygain_track_gain: Some(-6.0),
+            ..Metadata::default()
+        };
+
+        let path = Utf8Path::new("/music/Pink Floyd - The Dark Side of the Moon.flac");
+        let tracks = sheet.tracks_for(path, &file_metadata);
+        assert_eq!(tracks.len(), 3);
+
+        let (range, metadata) = &tracks[1];
+        assert_eq!(
+            *range,
+            TrackRange {
+                start_ms: 68_000,
+                end_ms: Some(237_493)
+            }
+        );
+        assert_eq!(metadata.name.as_deref(), Some("Breathe"));
+        assert_eq!(metadata.artist.as_deref(), Some("Pink Floyd feat. Nobody"));
+        assert_eq!(metadata.album_artist.as_deref(), Some("Pink Floyd"));
+        assert_eq!(metadata.album.as_deref(), Some("The Dark Side of the Moon"));
+        assert_eq!(metadata.track_current, Some(2));
+        assert_eq!(metadata.track_max, Some(3));
+        assert_eq!(metadata.year, Some(1973));
+        assert_eq!(metadata.replaygain_track_gain, None);
+        assert_eq!(metadata.replaygain_album_gain, Some(-6.12));
+
+        assert_eq!(tracks[2].0.end_ms, None);
+    }
+
+    #[test]
+    fn ignores_files_the_sheet_does_not_list() {
+        let mut sheet = CueSheet::parse(SHEET);
+        sheet.files.push(CueFile::default());
+
+        let tracks = sheet.tracks_for(Utf8Path::new("/music/other.flac"), &Metadata::default());
+        assert!(tracks.is_empty());
+    }
+
+    #[test]
+    fn ranges_round_trip_through_columns() {
+        let range = TrackRange {
+            start_ms: 68_000,
+            end_ms: Some(237_493),
+        };
+        assert_eq!(
+            TrackRange::from_columns(TrackRange::start_column(Some(range)), Some(237_493)),
+            Some(range)
+        );
+
+        let last = TrackRange {
+            start_ms: 237_493,
+            end_ms: None,
+        };
+        assert_eq!(
+            TrackRange::from_columns(TrackRange::start_column(Some(last)), None),
+            Some(last)
+        );
+
+        assert_eq!(TrackRange::start_column(None), 0);
+        assert_eq!(TrackRange::from_columns(0, None), None);
+    }
+
+    #[test]
+    fn decodes_legacy_encodings() {
+        assert_eq!(
+            decode(b"\xEF\xBB\xBFTITLE \"Caf\xC3\xA9\""),
+            "TITLE \"Café\""
+        );
+        assert_eq!(decode(b"TITLE \"Caf\xE9\""), "TITLE \"Café\"");
+    }
+}
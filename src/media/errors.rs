@@ -119,3 +119,15 @@ pub enum ChannelRetrievalError {
     #[error("Unknown media provider error: `{0}`")]
     Unknown(String),
 }
+
+#[derive(PartialEq, Eq, Debug, Clone, Error)]
+pub enum TagWriteError {
+    #[error("The selected MediaProvider does not support writing tags")]
+    OperationUnsupported,
+    #[error("Format not supported by tag writer")]
+    UnsupportedFormat,
+    #[error("Could not access file: {0}")]
+    IoError(String),
+    #[error("Unknown media provider error: `{0}`")]
+    Unknown(String),
+}
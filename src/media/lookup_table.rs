@@ -1,4 +1,5 @@
 use std::{
+    ffi::OsStr,
     fs::File,
     path::Path,
     sync::{Arc, LazyLock},
@@ -8,13 +9,26 @@ use std::{
 use tokio::sync::RwLock;
 use tracing::info;
 
-use crate::media::traits::{MediaProvider, MediaProviderFeatures, MediaStream};
+use crate::media::{
+    metadata::TagUpdate,
+    traits::{MediaProvider, MediaProviderFeatures, MediaStream},
+};
 
 type LookupTableInnerType = Arc<RwLock<Vec<Box<dyn MediaProvider>>>>;
 
 pub static LOOKUP_TABLE: LazyLock<LookupTableInnerType> =
     LazyLock::new(|| Arc::new(RwLock::new(Vec::new())));
 
+/// When to look at a file's contents to find a provider for it. The contents are only looked at
+/// if no provider claims the file's extension.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum ContentProbe {
+    Never,
+    /// Only look at the contents of files without an extension.
+    Extensionless,
+    Always,
+}
+
 pub fn add_provider(provider: Box<dyn MediaProvider>) {
     info!(
         "Attempting to register media provider \"{}\"",
@@ -22,38 +36,138 @@ pub fn add_provider(provider: Box<dyn MediaProvider>) {
     );
 
     let mut write = LOOKUP_TABLE.blocking_write();
-    write.push(provider);
+    insert_by_priority(&mut write, provider);
+}
+
+/// Insert a provider after every provider with the same or a higher priority, so that providers
+/// with equal priorities are asked in the order they were registered.
+fn insert_by_priority(
+    providers: &mut Vec<Box<dyn MediaProvider>>,
+    provider: Box<dyn MediaProvider>,
+) {
+    let index = providers.partition_point(|p| p.priority() >= provider.priority());
+    providers.insert(index, provider);
 }
 
-#[allow(clippy::borrowed_box)]
-fn provider_can_read(
+fn handles_extension(provider: &dyn MediaProvider, ext: &str) -> bool {
+    provider
+        .supported_extensions()
+        .iter()
+        .any(|v| v.eq_ignore_ascii_case(ext))
+}
+
+/// The type of audio file detected from the file's first few kilobytes, as an extension.
+fn sniff_extension(path: &Path) -> Option<&'static str> {
+    infer::get_from_path(path)
+        .ok()
+        .flatten()
+        .filter(|kind| kind.matcher_type() == infer::MatcherType::Audio)
+        .map(|kind| kind.extension())
+}
+
+fn can_read(
+    provider: &dyn MediaProvider,
+    ext: &str,
+    required_features: MediaProviderFeatures,
+) -> bool {
+    handles_extension(provider, ext)
+        && provider.supported_features() & required_features == required_features
+}
+
+/// Find the provider for the type of audio detected from the file's contents, along with that
+/// type's extension.
+fn find_provider_by_contents<'a>(
+    providers: &'a [Box<dyn MediaProvider>],
     path: &Path,
     required_features: MediaProviderFeatures,
-    provider: &Box<dyn MediaProvider>,
-) -> anyhow::Result<bool> {
-    // mime-types are more reliable but windows is too slow to use them
-    // so now we only use extensions
-    if let Some(ext) = path.extension().and_then(|v| v.to_str())
-        && provider
-            .supported_extensions()
-            .iter()
-            .any(|v| v.eq_ignore_ascii_case(ext))
+) -> Option<(&'a dyn MediaProvider, &'static str)> {
+    let sniffed = sniff_extension(path)?;
+    providers
+        .iter()
+        .map(|p| p.as_ref())
+        .find(|p| can_read(*p, sniffed, required_features))
+        .map(|p| (p, sniffed))
+}
+
+/// Find the provider to use for the file, along with the extension to hand to it. This is the
+/// file's own extension, unless the file type had to be detected from its contents.
+fn find_provider<'a, 'p>(
+    providers: &'a [Box<dyn MediaProvider>],
+    path: &'p Path,
+    required_features: MediaProviderFeatures,
+    probe: ContentProbe,
+) -> Option<(&'a dyn MediaProvider, Option<&'p OsStr>)> {
+    // mime-types are more reliable but windows is too slow to use them, so the contents are
+    // only looked at if no provider claims the extension
+    let ext = path
+        .extension()
+        .and_then(|v| v.to_str())
+        .filter(|v| !v.is_empty());
+    if let Some(ext) = ext
+        && providers.iter().any(|p| handles_extension(p.as_ref(), ext))
     {
-        return Ok(provider.supported_features() & required_features == required_features);
+        return providers
+            .iter()
+            .map(|p| p.as_ref())
+            .find(|p| can_read(*p, ext, required_features))
+            .map(|p| (p, path.extension()));
     }
 
-    Ok(false)
+    let should_probe = match probe {
+        ContentProbe::Never => false,
+        ContentProbe::Extensionless => ext.is_none(),
+        ContentProbe::Always => true,
+    };
+    if !should_probe {
+        return None;
+    }
+
+    find_provider_by_contents(providers, path, required_features)
+        .map(|(provider, sniffed)| (provider, Some(OsStr::new(sniffed))))
 }
 
-pub fn can_be_read(path: &Path, required_features: MediaProviderFeatures) -> anyhow::Result<bool> {
-    let read = LOOKUP_TABLE.blocking_read();
-    for provider in read.iter() {
-        if provider_can_read(path, required_features, provider)? {
-            return Ok(true);
+/// Open the file with the provider found for it. A file with the wrong extension is handed to
+/// the provider for the type detected from its contents if the first provider can't open it.
+fn open_media(
+    providers: &[Box<dyn MediaProvider>],
+    path: &Path,
+    required_features: MediaProviderFeatures,
+) -> anyhow::Result<Option<Box<dyn MediaStream>>> {
+    let Some((provider, ext)) =
+        find_provider(providers, path, required_features, ContentProbe::Always)
+    else {
+        return Ok(None);
+    };
+
+    let error = match provider.open(File::open(path)?, ext) {
+        Ok(stream) => return Ok(Some(stream)),
+        Err(error) => error,
+    };
+
+    let ext = ext.and_then(|e| e.to_str()).unwrap_or_default();
+    match find_provider_by_contents(providers, path, required_features) {
+        Some((fallback, sniffed)) if !sniffed.eq_ignore_ascii_case(ext) => {
+            info!(
+                "\"{}\" couldn't open {:?} ({}), trying \"{}\" for its contents",
+                provider.name(),
+                path,
+                error,
+                fallback.name()
+            );
+            let file = File::open(path)?;
+            Ok(Some(fallback.open(file, Some(OsStr::new(sniffed)))?))
         }
+        _ => Err(error.into()),
     }
+}
 
-    Ok(false)
+pub fn can_be_read(
+    path: &Path,
+    required_features: MediaProviderFeatures,
+    probe: ContentProbe,
+) -> anyhow::Result<bool> {
+    let read = LOOKUP_TABLE.blocking_read();
+    Ok(find_provider(&read, path, required_features, probe).is_some())
 }
 
 pub fn try_open_media(
@@ -61,12 +175,187 @@ pub fn try_open_media(
     required_features: MediaProviderFeatures,
 ) -> anyhow::Result<Option<Box<dyn MediaStream>>> {
     let read = LOOKUP_TABLE.blocking_read();
-    for provider in read.iter() {
-        if provider_can_read(path, required_features, provider)? {
-            let file = File::open(path)?;
-            return Ok(Some(provider.open(file, path.extension())?));
+    open_media(&read, path, required_features)
+}
+
+/// Write tag changes to a file using the first provider able to. Returns `false` if no provider
+/// supports writing tags to the file.
+pub fn try_write_tags(path: &Path, update: &TagUpdate) -> anyhow::Result<bool> {
+    let read = LOOKUP_TABLE.blocking_read();
+    // tag writers pick the tag format from the extension, so the contents aren't looked at
+    if let Some((provider, _)) = find_provider(
+        &read,
+        path,
+        MediaProviderFeatures::WRITES_TAGS,
+        ContentProbe::Never,
+    ) {
+        provider.write_tags(path, update)?;
+        return Ok(true);
+    }
+
+    Ok(false)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{media::errors::OpenError, test_support::TestDir};
+
+    struct FakeProvider {
+        name: &'static str,
+        extensions: &'static [&'static str],
+        features: MediaProviderFeatures,
+        priority: i32,
+    }
+
+    impl MediaProvider for FakeProvider {
+        fn open(&self, _: File, _: Option<&OsStr>) -> Result<Box<dyn MediaStream>, OpenError> {
+            // tells the tests which provider was asked last
+            Err(OpenError::Unknown(self.name.to_string()))
+        }
+
+        fn supported_extensions(&self) -> &[&str] {
+            self.extensions
+        }
+
+        fn supported_features(&self) -> MediaProviderFeatures {
+            self.features
+        }
+
+        fn name(&self) -> &str {
+            self.name
+        }
+
+        fn priority(&self) -> i32 {
+            self.priority
+        }
+    }
+
+    fn provider(
+        name: &'static str,
+        extensions: &'static [&'static str],
+        priority: i32,
+    ) -> Box<dyn MediaProvider> {
+        Box::new(FakeProvider {
+            name,
+            extensions,
+            features: MediaProviderFeatures::PROVIDES_DECODER
+                | MediaProviderFeatures::PROVIDES_METADATA
+                | MediaProviderFeatures::ALLOWS_INDEXING,
+            priority,
+        })
+    }
+
+    fn table() -> Vec<Box<dyn MediaProvider>> {
+        let mut providers = Vec::new();
+        insert_by_priority(&mut providers, provider("General", &["flac", "mp3"], 0));
+        insert_by_priority(&mut providers, provider("Late", &["flac"], 0));
+        insert_by_priority(&mut providers, provider("Special", &["mp3"], 10));
+        providers
+    }
+
+    fn found(
+        providers: &[Box<dyn MediaProvider>],
+        path: &Path,
+        probe: ContentProbe,
+    ) -> Option<(String, Option<String>)> {
+        find_provider(
+            providers,
+            path,
+            MediaProviderFeatures::PROVIDES_DECODER,
+            probe,
+        )
+        .map(|(provider, ext)| {
+            (
+                provider.name().to_string(),
+                ext.map(|e| e.to_string_lossy().into_owned()),
+            )
+        })
+    }
+
+    #[test]
+    fn higher_priorities_come_first() {
+        let names: Vec<_> = table().iter().map(|p| p.name().to_string()).collect();
+        assert_eq!(names, ["Special", "General", "Late"]);
+
+        assert_eq!(
+            found(&table(), Path::new("song.mp3"), ContentProbe::Never),
+            Some(("Special".to_string(), Some("mp3".to_string())))
+        );
+        assert_eq!(
+            found(&table(), Path::new("song.FLAC"), ContentProbe::Never),
+            Some(("General".to_string(), Some("FLAC".to_string())))
+        );
+    }
+
+    #[test]
+    fn probes_contents_only_when_the_extension_is_unclaimed() {
+        let dir = TestDir::new("hummingbird-lookup");
+        let mut flac = b"fLaC".to_vec();
+        flac.resize(64, 0);
+        for name in ["song", "song.bin", "song.mp3"] {
+            std::fs::write(dir.join(name), &flac).unwrap();
         }
+
+        let providers = table();
+        let flac_provider = Some(("General".to_string(), Some("flac".to_string())));
+
+        assert_eq!(
+            found(&providers, &dir.join("song"), ContentProbe::Never),
+            None
+        );
+        assert_eq!(
+            found(&providers, &dir.join("song"), ContentProbe::Extensionless),
+            flac_provider
+        );
+
+        assert_eq!(
+            found(
+                &providers,
+                &dir.join("song.bin"),
+                ContentProbe::Extensionless
+            ),
+            None
+        );
+        assert_eq!(
+            found(&providers, &dir.join("song.bin"), ContentProbe::Always),
+            flac_provider
+        );
+
+        // a claimed extension is trusted, even if the contents disagree
+        assert_eq!(
+            found(&providers, &dir.join("song.mp3"), ContentProbe::Always),
+            Some(("Special".to_string(), Some("mp3".to_string())))
+        );
     }
 
-    Ok(None)
+    #[test]
+    fn falls_back_to_the_contents_when_the_extension_is_wrong() {
+        let dir = TestDir::new("hummingbird-lookup");
+        let mut flac = b"fLaC".to_vec();
+        flac.resize(64, 0);
+        std::fs::write(dir.join("mislabelled.mp3"), &flac).unwrap();
+        let mut mp3 = b"ID3".to_vec();
+        mp3.resize(64, 0);
+        std::fs::write(dir.join("song.mp3"), &mp3).unwrap();
+
+        let opened_by = |name: &str| {
+            let Err(error) = open_media(
+                &table(),
+                &dir.join(name),
+                MediaProviderFeatures::PROVIDES_DECODER,
+            ) else {
+                panic!("{name} shouldn't open");
+            };
+            match error.downcast::<OpenError>().unwrap() {
+                OpenError::Unknown(provider) => provider,
+                error => panic!("unexpected error {error:?}"),
+            }
+        };
+
+        // the mp3 provider fails, so the flac provider gets the file as well
+        assert_eq!(opened_by("mislabelled.mp3"), "General");
+        // the contents match the extension, so there's nothing else to try
+        assert_eq!(opened_by("song.mp3"), "Special");
+    }
 }
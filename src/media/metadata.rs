@@ -42,4 +42,45 @@ pub struct Metadata {
     pub replaygain_album_peak: Option<f64>,
 
     pub lyrics: Option<String>,
+    /// A CUE sheet embedded in the file's tags, splitting it into several tracks.
+    pub cuesheet: Option<String>,
+}
+
+/// Changes to write to a file's tags. Fields set to `None` are left as they are.
+#[derive(Debug, Default, PartialEq, Clone)]
+pub struct TagUpdate {
+    pub replaygain: Option<ReplayGainValues>,
+    /// Text tags to replace. Tags not listed are left as they are, and an empty value removes the
+    /// tag.
+    pub fields: Vec<(TagField, String)>,
+    /// Encoded image to store as the front cover, replacing the existing one.
+    pub cover: Option<Vec<u8>>,
+}
+
+/// A text tag that can be edited by the user.
+#[derive(Debug, PartialEq, Eq, Hash, Clone, Copy)]
+pub enum TagField {
+    Title,
+    Artist,
+    Album,
+    AlbumArtist,
+    TrackNumber,
+    TrackTotal,
+    DiscNumber,
+    DiscTotal,
+    Date,
+    Genre,
+    Label,
+    Catalog,
+    Isrc,
+    Lyrics,
+}
+
+/// ReplayGain values to store in a file, with gains in dB and peaks as linear amplitudes.
+#[derive(Debug, PartialEq, Clone, Copy)]
+pub struct ReplayGainValues {
+    pub track_gain: f64,
+    pub track_peak: f64,
+    pub album_gain: Option<f64>,
+    pub album_peak: Option<f64>,
 }
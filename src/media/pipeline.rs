@@ -1,4 +1,4 @@
-use rb::{Consumer, Producer, RB, RbConsumer, RbProducer, SpscRb};
+use rb::{Consumer, Producer, RB, RbConsumer, RbInspector, RbProducer, SpscRb};
 
 use crate::devices::format::SampleFormat;
 
@@ -32,7 +32,7 @@ impl<T: Copy + Default + Send + 'static> ChannelBuffers<T> {
         let mut producers = Vec::with_capacity(self.channel_count);
         let mut consumers = Vec::with_capacity(self.channel_count);
 
-        for rb in self.buffers {
+        for rb in &self.buffers {
             producers.push(rb.producer());
             consumers.push(rb.consumer());
         }
@@ -44,6 +44,7 @@ impl<T: Copy + Default + Send + 'static> ChannelBuffers<T> {
             },
             ChannelConsumers {
                 consumers,
+                rings: self.buffers,
                 channel_count: self.channel_count,
                 staging: (0..self.channel_count)
                     .map(|_| Vec::with_capacity(self.buffer_size))
@@ -59,6 +60,10 @@ pub struct ChannelProducers<T: Copy + Send + 'static> {
 }
 
 impl<T: Copy + Send + 'static> ChannelProducers<T> {
+    pub fn channel_count(&self) -> usize {
+        self.channel_count
+    }
+
     pub fn write_slices(&self, samples: &[&[T]]) {
         assert_eq!(samples.len(), self.channel_count);
 
@@ -88,6 +93,8 @@ impl<T: Copy + Send + 'static> ChannelProducers<T> {
 
 pub struct ChannelConsumers<T: Copy + Default + Send + 'static> {
     consumers: Vec<Consumer<T>>,
+    /// The ring buffers read by `consumers`, kept to see how much is waiting in them.
+    rings: Vec<SpscRb<T>>,
     channel_count: usize,
     staging: Vec<Vec<T>>,
 }
@@ -133,9 +140,98 @@ impl<T: Copy + Default + Send + 'static> ChannelConsumers<T> {
         min_read
     }
 
+    /// The number of frames waiting to be read.
+    pub fn buffered_frames(&self) -> usize {
+        self.rings.iter().map(RbInspector::count).min().unwrap_or(0)
+    }
+
     pub fn staging(&self) -> &[Vec<T>] {
         &self.staging
     }
+
+    pub fn staging_mut(&mut self) -> &mut [Vec<T>] {
+        &mut self.staging
+    }
+
+    /// Move up to `max_count` frames straight to the given producers, returning the number of
+    /// frames moved.
+    pub fn move_to(&mut self, output: &ChannelProducers<T>, max_count: usize) -> usize {
+        let read = self.try_read_to_staging(max_count);
+        if read > 0 {
+            output.write_vecs(&self.staging);
+        }
+        read
+    }
+}
+
+/// Holds each decoded packet back until it is known how much of it comes before a cut-off point,
+/// so that a stream can end partway through a packet instead of on a packet boundary.
+///
+/// Stream positions are only known to the millisecond, so the time of the first packet after a
+/// seek is used as a reference and later packets are placed by counting frames from it.
+pub struct PacketTrimmer<T: Copy + Default + Send + 'static> {
+    producers: ChannelProducers<T>,
+    consumers: ChannelConsumers<T>,
+    /// The time of the first packet since the last reset, and the frames decoded since then.
+    reference: Option<(u64, u64)>,
+}
+
+impl<T: Copy + Default + Send + 'static> PacketTrimmer<T> {
+    pub fn new(channel_count: usize, buffer_frames: usize) -> Self {
+        let (producers, consumers) = ChannelBuffers::<T>::new(channel_count, buffer_frames).split();
+        Self {
+            producers,
+            consumers,
+            reference: None,
+        }
+    }
+
+    /// The producers to decode the next packet into.
+    pub fn input(&self) -> &ChannelProducers<T> {
+        &self.producers
+    }
+
+    /// Forget where the previous packets were. Must be called after the stream seeks.
+    pub fn reset(&mut self) {
+        self.reference = None;
+    }
+
+    /// Write the frames of the packet that was just decoded into [`PacketTrimmer::input`] that
+    /// come before `end_ms` to `output`, and drop the rest. `packet_start_ms` is the stream's
+    /// position after decoding the packet, which is the time of its first frame.
+    ///
+    /// Returns the frames written, as [`DecodeResult::Eof`] if the whole packet lies past
+    /// `end_ms`, and whether the packet reached `end_ms`.
+    pub fn write_before(
+        &mut self,
+        output: &ChannelProducers<T>,
+        decoded: DecodeResult,
+        packet_start_ms: u64,
+        end_ms: u64,
+    ) -> (DecodeResult, bool) {
+        let DecodeResult::Decoded { frames, rate } = decoded else {
+            return (decoded, false);
+        };
+
+        let (reference_ms, counted) = *self.reference.get_or_insert((packet_start_ms, 0));
+        self.reference = Some((reference_ms, counted + frames as u64));
+
+        let before_end = (end_ms.saturating_sub(reference_ms) * rate as u64 / 1000)
+            .saturating_sub(counted) as usize;
+        let written = self.consumers.move_to(output, frames.min(before_end));
+        self.consumers.try_read_to_staging(frames - written);
+
+        let result = if written == 0 {
+            DecodeResult::Eof
+        } else {
+            DecodeResult::Decoded {
+                frames: written,
+                rate,
+            }
+        };
+
+        (result, before_end <= frames)
+    }
 }
 
 /// Pipeline that converts all audio to f64 for processing (resampling, format conversion)
@@ -146,35 +242,54 @@ impl<T: Copy + Default + Send + 'static> ChannelConsumers<T> {
 /// the f64 pipeline for everything except for pure f32 -> f32 output.
 pub struct ConvertPipeline {
     pub decoder_output: ChannelProducers<f64>,
+    pub stretch_input: ChannelConsumers<f64>,
+    pub stretch_output: ChannelProducers<f64>,
     pub resampler_input: ChannelConsumers<f64>,
+    pub resampler_output: ChannelProducers<f64>,
+    pub dsp_input: ChannelConsumers<f64>,
     pub device_input_producers: ChannelProducers<f64>,
     pub device_input: ChannelConsumers<f64>,
     pub source_rate: u32,
     pub target_rate: u32,
+    /// The number of channels decoded, and processed up to the device input.
     pub channel_count: usize,
+    /// The number of channels the device input has, after mixing.
+    pub output_channel_count: usize,
 }
 
 impl ConvertPipeline {
     pub fn new(
         channel_count: usize,
+        output_channel_count: usize,
         source_rate: u32,
         target_rate: u32,
         buffer_frames: usize,
     ) -> Self {
-        let (decoder_output, resampler_input) =
+        let (decoder_output, stretch_input) =
             ChannelBuffers::<f64>::new(channel_count, buffer_frames).split();
 
-        let (device_input_producers, device_input) =
+        let (stretch_output, resampler_input) =
             ChannelBuffers::<f64>::new(channel_count, buffer_frames).split();
 
+        let (resampler_output, dsp_input) =
+            ChannelBuffers::<f64>::new(channel_count, buffer_frames).split();
+
+        let (device_input_producers, device_input) =
+            ChannelBuffers::<f64>::new(output_channel_count, buffer_frames).split();
+
         Self {
             decoder_output,
+            stretch_input,
+            stretch_output,
             resampler_input,
+            resampler_output,
+            dsp_input,
             device_input_producers,
             device_input,
             source_rate,
             target_rate,
             channel_count,
+            output_channel_count,
         }
     }
 }
@@ -205,16 +320,21 @@ pub enum AudioPipeline {
 }
 
 impl AudioPipeline {
-    /// Create a new pipeline, automatically choosing passthrough if possible
+    /// Create a new pipeline, automatically choosing passthrough if possible and allowed
+    #[allow(clippy::too_many_arguments)]
     pub fn new(
         channel_count: usize,
+        output_channel_count: usize,
         source_format: SampleFormat,
         source_rate: u32,
         device_format: SampleFormat,
         device_rate: u32,
         buffer_frames: usize,
+        allow_passthrough: bool,
     ) -> Self {
-        if source_format == SampleFormat::Float32
+        if allow_passthrough
+            && channel_count == output_channel_count
+            && source_format == SampleFormat::Float32
             && device_format == SampleFormat::Float32
             && source_rate == device_rate
         {
@@ -222,6 +342,7 @@ impl AudioPipeline {
         } else {
             AudioPipeline::Convert(ConvertPipeline::new(
                 channel_count,
+                output_channel_count,
                 source_rate,
                 device_rate,
                 buffer_frames,
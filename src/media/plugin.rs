@@ -0,0 +1,833 @@
+//! Media providers loaded from WebAssembly plugins.
+//!
+//! Plugins are `.wasm` modules placed in the `plugins` folder of the data directory. Each module
+//! is registered as a [MediaProvider] after the built-in providers. By default a plugin only adds
+//! formats, since providers with the same priority are tried in the order they were registered,
+//! but a plugin that declares a higher priority takes over the extensions it shares with the
+//! built-in providers.
+//!
+//! Plugins are sandboxed: they have no access to WASI or the host system, only to the file they
+//! were asked to open, through the imports below. Their memory is limited, and every call into a
+//! plugin is given a fuel budget, so that a misbehaving plugin traps instead of hanging playback.
+//!
+//! # Guest ABI (version 1)
+//!
+//! Each instance of a plugin handles a single file. All integers are little-endian, and data is
+//! handed to the host by calling `output` any number of times during a call, which appends to
+//! the call's result.
+//!
+//! The module imports, from the `hummingbird` module:
+//! - `file_read(ptr: i32, len: i32) -> i32`: reads up to `len` bytes of the file into memory,
+//!   returning the number of bytes read (0 at the end of the file), or -1 on errors.
+//! - `file_seek(offset: i64, whence: i32) -> i64`: seeks relative to the start (0), current
+//!   position (1) or end (2) of the file, returning the new position, or -1 on errors.
+//! - `output(ptr: i32, len: i32)`: appends `len` bytes of memory to the result of the call.
+//!
+//! The module exports `memory`, and:
+//! - `hb_abi_version() -> i32`: must return 1.
+//! - `hb_provider_info() -> i32`: outputs the provider's name, the extensions it handles and its
+//!   features as JSON, like `{"name": "APE", "extensions": ["ape"], "features": ["decoder",
+//!   "metadata", "indexing"]}`, and returns 0. An optional `"priority"` lets the plugin take
+//!   over extensions from the built-in providers, which have a priority of 0.
+//! - `hb_open() -> i32`: probes the file, returning 0 on success, -1 if the format isn't
+//!   supported, -2 if the file is corrupt, or any other negative number for other errors.
+//! - `hb_stream_info() -> i32`: outputs the stream's format as JSON, like `{"channels": 2,
+//!   "sample_rate": 44100, "duration_ms": 180000, "sample_format": "S16"}`, and returns 0. The
+//!   sample format is optional and describes the file, not the decoded samples.
+//! - `hb_decode() -> i32`: decodes the next packet, outputting it as interleaved `f32` samples
+//!   and returning the number of frames decoded, 0 at the end of the stream, or a negative
+//!   number on errors.
+//! - `hb_seek(ms: i64) -> i64`: seeks to the given position, returning the position that was
+//!   actually reached in milliseconds, or a negative number on errors.
+//! - `hb_metadata() -> i32` (optional): outputs the file's tags as a JSON object with any of the
+//!   fields of [PluginMetadata], and returns 0.
+//! - `hb_image() -> i32` (optional): outputs the file's cover art, if it has any, and returns 0.
+
+use std::{
+    ffi::OsStr,
+    fs::File,
+    io::{Read, Seek, SeekFrom},
+    path::Path,
+};
+
+use serde::Deserialize;
+use tracing::{info, warn};
+use wasmtime::{
+    Caller, Config, Engine, Extern, Instance, InstancePre, Linker, Module, Store, StoreLimits,
+    StoreLimitsBuilder, TypedFunc, WasmParams, WasmResults,
+};
+
+use crate::{
+    devices::format::{ChannelSpec, SampleFormat},
+    media::{
+        builtin::symphonia::{ParsedReleaseDate, parse_release_date},
+        errors::{
+            ChannelRetrievalError, CloseError, FrameDurationError, MetadataError, OpenError,
+            PlaybackReadError, PlaybackStartError, PlaybackStopError, SeekError,
+            TrackDurationError,
+        },
+        lookup_table::add_provider,
+        metadata::Metadata,
+        pipeline::{ChannelProducers, DecodeResult},
+        traits::{F32DecodeResult, MediaProvider, MediaProviderFeatures, MediaStream},
+    },
+};
+
+pub const ABI_VERSION: i32 = 1;
+
+/// The most memory a single plugin instance may use.
+const MEMORY_LIMIT: usize = 256 * 1024 * 1024;
+/// The fuel given to every call into a plugin. Roughly one unit is used per instruction, which
+/// is orders of magnitude more than decoding a packet should take.
+const CALL_FUEL: u64 = 10_000_000_000;
+
+struct HostState {
+    file: Option<File>,
+    output: Vec<u8>,
+    limits: StoreLimits,
+}
+
+#[derive(Deserialize)]
+struct ProviderInfo {
+    name: String,
+    extensions: Vec<String>,
+    #[serde(default)]
+    features: Vec<String>,
+    #[serde(default)]
+    priority: i32,
+}
+
+#[derive(Deserialize)]
+struct StreamInfo {
+    channels: u16,
+    sample_rate: u32,
+    duration_ms: u64,
+    #[serde(default)]
+    sample_format: Option<String>,
+}
+
+/// The tags a plugin can report for a file.
+#[derive(Deserialize, Default)]
+#[serde(default)]
+struct PluginMetadata {
+    title: Option<String>,
+    artist: Option<String>,
+    album_artist: Option<String>,
+    album: Option<String>,
+    composer: Option<String>,
+    genre: Option<String>,
+    /// `YYYY`, `YYYY-MM`, `YYYY-MM-DD`, or any other date format tags commonly use.
+    date: Option<String>,
+    track: Option<u64>,
+    track_total: Option<u64>,
+    disc: Option<u64>,
+    disc_total: Option<u64>,
+    label: Option<String>,
+    catalog: Option<String>,
+    isrc: Option<String>,
+    lyrics: Option<String>,
+    replaygain_track_gain: Option<f64>,
+    replaygain_track_peak: Option<f64>,
+    replaygain_album_gain: Option<f64>,
+    replaygain_album_peak: Option<f64>,
+}
+
+impl From<PluginMetadata> for Metadata {
+    fn from(tags: PluginMetadata) -> Self {
+        let mut metadata = Metadata {
+            name: tags.title,
+            artist: tags.artist,
+            album_artist: tags.album_artist,
+            album: tags.album,
+            composer: tags.composer,
+            genre: tags.genre,
+            track_current: tags.track,
+            track_max: tags.track_total,
+            disc_current: tags.disc,
+            disc_max: tags.disc_total,
+            label: tags.label,
+            catalog: tags.catalog,
+            isrc: tags.isrc,
+            lyrics: tags.lyrics,
+            replaygain_track_gain: tags.replaygain_track_gain,
+            replaygain_track_peak: tags.replaygain_track_peak,
+            replaygain_album_gain: tags.replaygain_album_gain,
+            replaygain_album_peak: tags.replaygain_album_peak,
+            ..Metadata::default()
+        };
+
+        match tags.date.as_deref().and_then(parse_release_date) {
+            Some(ParsedReleaseDate::FullDate(date)) => metadata.date = Some(date),
+            Some(ParsedReleaseDate::YearMonth(year, month)) => {
+                metadata.year_month = Some((year, month))
+            }
+            Some(ParsedReleaseDate::Year(year)) => metadata.year = Some(year),
+            None => {}
+        }
+
+        metadata
+    }
+}
+
+/// Returns the guest memory and the host state of a call from a plugin.
+fn memory_and_state<'a>(
+    caller: &'a mut Caller<'_, HostState>,
+) -> Option<(&'a mut [u8], &'a mut HostState)> {
+    match caller.get_export("memory") {
+        Some(Extern::Memory(memory)) => Some(memory.data_and_store_mut(caller)),
+        _ => None,
+    }
+}
+
+/// Returns the range of guest memory a plugin passed to an import, if it is valid.
+fn guest_range(memory: &[u8], ptr: i32, len: i32) -> Option<std::ops::Range<usize>> {
+    let start = usize::try_from(ptr).ok()?;
+    let end = start.checked_add(usize::try_from(len).ok()?)?;
+    (end <= memory.len()).then_some(start..end)
+}
+
+fn create_linker(engine: &Engine) -> anyhow::Result<Linker<HostState>> {
+    let mut linker = Linker::new(engine);
+
+    linker.func_wrap(
+        "hummingbird",
+        "file_read",
+        |mut caller: Caller<'_, HostState>, ptr: i32, len: i32| -> i32 {
+            let Some((memory, state)) = memory_and_state(&mut caller) else {
+                return -1;
+            };
+            let (Some(range), Some(file)) = (guest_range(memory, ptr, len), &mut state.file) else {
+                return -1;
+            };
+
+            file.read(&mut memory[range]).map_or(-1, |read| read as i32)
+        },
+    )?;
+
+    linker.func_wrap(
+        "hummingbird",
+        "file_seek",
+        |mut caller: Caller<'_, HostState>, offset: i64, whence: i32| -> i64 {
+            let Some(file) = &mut caller.data_mut().file else {
+                return -1;
+            };
+            let position = match whence {
+                0 if offset >= 0 => SeekFrom::Start(offset as u64),
+                1 => SeekFrom::Current(offset),
+                2 => SeekFrom::End(offset),
+                _ => return -1,
+            };
+
+            file.seek(position).map_or(-1, |position| position as i64)
+        },
+    )?;
+
+    linker.func_wrap(
+        "hummingbird",
+        "output",
+        |mut caller: Caller<'_, HostState>, ptr: i32, len: i32| {
+            if let Some((memory, state)) = memory_and_state(&mut caller)
+                && let Some(range) = guest_range(memory, ptr, len)
+                && state.output.len() + range.len() <= MEMORY_LIMIT
+            {
+                state.output.extend_from_slice(&memory[range]);
+            }
+        },
+    )?;
+
+    Ok(linker)
+}
+
+/// A single instance of a plugin, handling one file.
+struct Guest {
+    store: Store<HostState>,
+    instance: Instance,
+}
+
+impl Guest {
+    fn new(pre: &InstancePre<HostState>, file: Option<File>) -> anyhow::Result<Self> {
+        let mut store = Store::new(
+            pre.module().engine(),
+            HostState {
+                file,
+                output: Vec::new(),
+                limits: StoreLimitsBuilder::new().memory_size(MEMORY_LIMIT).build(),
+            },
+        );
+        store.limiter(|state| &mut state.limits);
+        store.set_fuel(CALL_FUEL)?;
+
+        let instance = pre.instantiate(&mut store)?;
+        Ok(Self { store, instance })
+    }
+
+    fn func<P: WasmParams, R: WasmResults>(
+        &mut self,
+        name: &str,
+    ) -> anyhow::Result<TypedFunc<P, R>> {
+        self.instance.get_typed_func(&mut self.store, name)
+    }
+
+    fn has_export(&mut self, name: &str) -> bool {
+        self.instance.get_export(&mut self.store, name).is_some()
+    }
+
+    /// Call a function of the plugin, returning its result. Anything the plugin outputs during
+    /// the call is left in `self.store.data().output`.
+    fn call<P: WasmParams, R: WasmResults>(
+        &mut self,
+        func: &TypedFunc<P, R>,
+        params: P,
+    ) -> anyhow::Result<R> {
+        self.store.data_mut().output.clear();
+        self.store.set_fuel(CALL_FUEL)?;
+        func.call(&mut self.store, params)
+    }
+
+    fn call_export<P: WasmParams, R: WasmResults>(
+        &mut self,
+        name: &str,
+        params: P,
+    ) -> anyhow::Result<R> {
+        let func = self.func(name)?;
+        self.call(&func, params)
+    }
+
+    fn output(&self) -> &[u8] {
+        &self.store.data().output
+    }
+
+    /// Call an export that returns 0 on success, and parse what it output as JSON.
+    fn call_json<T: for<'de> Deserialize<'de>>(&mut self, name: &str) -> anyhow::Result<T> {
+        let status: i32 = self.call_export(name, ())?;
+        anyhow::ensure!(status == 0, "{name} failed with status {status}");
+        Ok(serde_json::from_slice(self.output())?)
+    }
+}
+
+/// A [MediaProvider] backed by a WebAssembly plugin.
+pub struct PluginProvider {
+    name: String,
+    extensions: Vec<&'static str>,
+    features: MediaProviderFeatures,
+    priority: i32,
+    pre: InstancePre<HostState>,
+}
+
+impl PluginProvider {
+    /// Compile the given module and ask it what it provides.
+    pub fn new(engine: &Engine, wasm: &[u8]) -> anyhow::Result<Self> {
+        let module = Module::new(engine, wasm)?;
+        let pre = create_linker(engine)?.instantiate_pre(&module)?;
+
+        let mut guest = Guest::new(&pre, None)?;
+        let version: i32 = guest.call_export("hb_abi_version", ())?;
+        anyhow::ensure!(
+            version == ABI_VERSION,
+            "plugin uses ABI version {version}, expected {ABI_VERSION}"
+        );
+
+        let info: ProviderInfo = guest.call_json("hb_provider_info")?;
+        let mut features = MediaProviderFeatures::PROVIDES_DECODER;
+        for feature in &info.features {
+            match feature.as_str() {
+                "decoder" => {}
+                "metadata" if guest.has_export("hb_metadata") => {
+                    features |= MediaProviderFeatures::PROVIDES_METADATA
+                }
+                "indexing" => features |= MediaProviderFeatures::ALLOWS_INDEXING,
+                _ => warn!("Plugin {} requested unknown feature {feature}", info.name),
+            }
+        }
+
+        Ok(Self {
+            name: info.name,
+            // providers are registered for the rest of the program's lifetime
+            extensions: info
+                .extensions
+                .into_iter()
+                .map(|ext| &*ext.leak())
+                .collect(),
+            features,
+            priority: info.priority,
+            pre,
+        })
+    }
+}
+
+impl MediaProvider for PluginProvider {
+    fn open(&self, file: File, _: Option<&OsStr>) -> Result<Box<dyn MediaStream>, OpenError> {
+        let unknown = |e: anyhow::Error| OpenError::Unknown(e.to_string());
+        let mut guest = Guest::new(&self.pre, Some(file)).map_err(unknown)?;
+
+        match guest
+            .call_export::<(), i32>("hb_open", ())
+            .map_err(unknown)?
+        {
+            0 => {}
+            -1 => return Err(OpenError::UnsupportedFormat),
+            -2 => return Err(OpenError::FileCorrupt),
+            status => {
+                return Err(OpenError::Unknown(format!(
+                    "hb_open failed with status {status}"
+                )));
+            }
+        }
+
+        let info: StreamInfo = guest.call_json("hb_stream_info").map_err(unknown)?;
+        if info.channels == 0 || info.sample_rate == 0 {
+            return Err(OpenError::Unknown("plugin reported an empty stream".into()));
+        }
+
+        let decode = guest.func("hb_decode").map_err(unknown)?;
+        let seek = guest.func("hb_seek").map_err(unknown)?;
+        let channels = info.channels as usize;
+
+        Ok(Box::new(PluginStream {
+            guest,
+            decode,
+            seek,
+            info,
+            metadata: None,
+            metadata_updated: true,
+            position: 0,
+            next_position: 0,
+            packet_frames: 0,
+            samples: vec![Vec::new(); channels],
+            samples_f32: vec![Vec::new(); channels],
+        }))
+    }
+
+    fn supported_extensions(&self) -> &[&str] {
+        &self.extensions
+    }
+
+    fn supported_features(&self) -> MediaProviderFeatures {
+        self.features
+    }
+
+    fn name(&self) -> &str {
+        &self.name
+    }
+
+    fn priority(&self) -> i32 {
+        self.priority
+    }
+}
+
+pub struct PluginStream {
+    guest: Guest,
+    decode: TypedFunc<(), i32>,
+    seek: TypedFunc<i64, i64>,
+    info: StreamInfo,
+    metadata: Option<Metadata>,
+    metadata_updated: bool,
+    /// The frame the last decoded packet started at.
+    position: u64,
+    /// The frame the next packet starts at.
+    next_position: u64,
+    packet_frames: u64,
+    samples: Vec<Vec<f64>>,
+    samples_f32: Vec<Vec<f32>>,
+}
+
+impl PluginStream {
+    /// Decode the next packet, leaving its interleaved samples in the guest's output. Returns the
+    /// number of frames decoded, or `None` at the end of the stream.
+    fn decode_packet(&mut self) -> Result<Option<usize>, PlaybackReadError> {
+        let frames = self
+            .guest
+            .call(&self.decode, ())
+            .map_err(|e| PlaybackReadError::DecodeFatal(e.to_string()))?;
+
+        if frames < 0 {
+            return Err(PlaybackReadError::DecodeFatal(format!(
+                "hb_decode failed with status {frames}"
+            )));
+        }
+        if frames == 0 {
+            return Ok(None);
+        }
+
+        let frames = frames as usize;
+        let expected = frames * self.info.channels as usize * size_of::<f32>();
+        if self.guest.output().len() != expected {
+            return Err(PlaybackReadError::DecodeFatal(format!(
+                "hb_decode returned {} bytes for {frames} frames",
+                self.guest.output().len()
+            )));
+        }
+
+        self.position = self.next_position;
+        self.next_position += frames as u64;
+        self.packet_frames = frames as u64;
+
+        Ok(Some(frames))
+    }
+
+    /// Split the interleaved samples of the last decoded packet into one buffer per channel.
+    fn deinterleave<T: Copy>(&self, buffers: &mut [Vec<T>], convert: impl Fn(f32) -> T) {
+        let channels = buffers.len();
+        for buffer in buffers.iter_mut() {
+            buffer.clear();
+        }
+
+        for (i, bytes) in self.guest.output().chunks_exact(4).enumerate() {
+            let sample = f32::from_le_bytes(bytes.try_into().expect("chunks are 4 bytes long"));
+            buffers[i % channels].push(convert(sample));
+        }
+    }
+}
+
+impl MediaStream for PluginStream {
+    fn close(&mut self) -> Result<(), CloseError> {
+        self.guest.store.data_mut().file = None;
+        Ok(())
+    }
+
+    fn start_playback(&mut self) -> Result<(), PlaybackStartError> {
+        Ok(())
+    }
+
+    fn stop_playback(&mut self) -> Result<(), PlaybackStopError> {
+        Ok(())
+    }
+
+    fn seek(&mut self, time: f64) -> Result<(), SeekError> {
+        let target = (time.max(0.0) * 1000.0) as i64;
+        let reached = self
+            .guest
+            .call(&self.seek, target)
+            .map_err(|e| SeekError::Unknown(e.to_string()))?;
+
+        if reached < 0 {
+            return Err(SeekError::Unknown(format!(
+                "hb_seek failed with status {reached}"
+            )));
+        }
+
+        self.position = reached as u64 * self.info.sample_rate as u64 / 1000;
+        self.next_position = self.position;
+        Ok(())
+    }
+
+    fn frame_duration(&self) -> Result<u64, FrameDurationError> {
+        if self.packet_frames == 0 {
+            Err(FrameDurationError::NeverStarted)
+        } else {
+            Ok(self.packet_frames)
+        }
+    }
+
+    fn read_metadata(&mut self) -> Result<&Metadata, MetadataError> {
+        self.metadata_updated = false;
+
+        if self.metadata.is_none() {
+            if !self.guest.has_export("hb_metadata") {
+                return Err(MetadataError::OperationUnsupported);
+            }
+
+            let tags: PluginMetadata = self
+                .guest
+                .call_json("hb_metadata")
+                .map_err(|e| MetadataError::Unknown(e.to_string()))?;
+            self.metadata = Some(tags.into());
+        }
+
+        self.metadata.as_ref().ok_or(MetadataError::InvalidState)
+    }
+
+    fn metadata_updated(&self) -> bool {
+        self.metadata_updated
+    }
+
+    fn read_image(&mut self) -> Result<Option<Box<[u8]>>, MetadataError> {
+        if !self.guest.has_export("hb_image") {
+            return Ok(None);
+        }
+
+        let status: i32 = self
+            .guest
+            .call_export("hb_image", ())
+            .map_err(|e| MetadataError::Unknown(e.to_string()))?;
+        if status != 0 {
+            return Err(MetadataError::Unknown(format!(
+                "hb_image failed with status {status}"
+            )));
+        }
+
+        let image = self.guest.output();
+        Ok((!image.is_empty()).then(|| image.into()))
+    }
+
+    fn duration_secs(&self) -> Result<u64, TrackDurationError> {
+        Ok(self.info.duration_ms / 1000)
+    }
+
+    fn position_ms(&self) -> Result<u64, TrackDurationError> {
+        Ok(self.position * 1000 / self.info.sample_rate as u64)
+    }
+
+    fn channels(&self) -> Result<ChannelSpec, ChannelRetrievalError> {
+        Ok(ChannelSpec::Count(self.info.channels))
+    }
+
+    fn sample_format(&self) -> Result<SampleFormat, ChannelRetrievalError> {
+        Ok(self
+            .info
+            .sample_format
+            .as_deref()
+            .and_then(|format| format.parse().ok())
+            .unwrap_or(SampleFormat::Float32))
+    }
+
+    fn sample_rate(&self) -> Result<u32, ChannelRetrievalError> {
+        Ok(self.info.sample_rate)
+    }
+
+    fn decode_into(
+        &mut self,
+        output: &ChannelProducers<f64>,
+    ) -> Result<DecodeResult, PlaybackReadError> {
+        let Some(frames) = self.decode_packet()? else {
+            return Ok(DecodeResult::Eof);
+        };
+
+        let mut samples = std::mem::take(&mut self.samples);
+        self.deinterleave(&mut samples, f64::from);
+        output.write_vecs(&samples);
+        self.samples = samples;
+
+        Ok(DecodeResult::Decoded {
+            frames,
+            rate: self.info.sample_rate,
+        })
+    }
+
+    fn decode_into_f32(
+        &mut self,
+        output: &ChannelProducers<f32>,
+    ) -> Result<F32DecodeResult, PlaybackReadError> {
+        if self.sample_format() != Ok(SampleFormat::Float32) {
+            return Ok(F32DecodeResult::NotF32);
+        }
+
+        let Some(frames) = self.decode_packet()? else {
+            return Ok(F32DecodeResult::Decoded(DecodeResult::Eof));
+        };
+
+        let mut samples = std::mem::take(&mut self.samples_f32);
+        self.deinterleave(&mut samples, |sample| sample);
+        output.write_vecs(&samples);
+        self.samples_f32 = samples;
+
+        Ok(F32DecodeResult::Decoded(DecodeResult::Decoded {
+            frames,
+            rate: self.info.sample_rate,
+        }))
+    }
+}
+
+/// Register every plugin in the given folder as a media provider. Plugins that fail to load are
+/// skipped.
+pub fn load_plugins(dir: &Path) {
+    let Ok(entries) = std::fs::read_dir(dir) else {
+        return;
+    };
+
+    let mut config = Config::new();
+    config.consume_fuel(true);
+    let engine = match Engine::new(&config) {
+        Ok(engine) => engine,
+        Err(e) => {
+            warn!("Could not create the plugin engine: {e:?}");
+            return;
+        }
+    };
+
+    for path in entries
+        .filter_map(|entry| entry.ok())
+        .map(|entry| entry.path())
+    {
+        if path.extension() != Some(OsStr::new("wasm")) {
+            continue;
+        }
+
+        let provider = std::fs::read(&path)
+            .map_err(anyhow::Error::from)
+            .and_then(|wasm| PluginProvider::new(&engine, &wasm));
+
+        match provider {
+            Ok(provider) => {
+                info!("Loaded plugin '{}' from {}", provider.name, path.display());
+                add_provider(Box::new(provider));
+            }
+            Err(e) => warn!("Could not load plugin {}: {e:?}", path.display()),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{media::pipeline::ChannelBuffers, test_support::TestDir};
+
+    const PROVIDER_INFO: &str = r#"{"name": "Tone", "extensions": ["tone"], "features": ["decoder", "metadata", "indexing"]}"#;
+    const STREAM_INFO: &str = r#"{"channels": 2, "sample_rate": 1000, "duration_ms": 2000}"#;
+    const METADATA: &str = r#"{"title": "Tone", "date": "2001-02", "track": 3}"#;
+
+    /// A plugin for files starting with `TONE`, which contain two seconds of stereo audio at
+    /// 1 kHz whose samples count up on the left and down on the right, in packets of 100 frames.
+    fn tone_plugin() -> String {
+        let data = |offset: usize, json: &str| {
+            format!(
+                "(data (i32.const {offset}) \"{}\")",
+                json.replace('"', "\\\"")
+            )
+        };
+
+        format!(
+            r#"(module
+                (import "hummingbird" "file_read" (func $file_read (param i32 i32) (result i32)))
+                (import "hummingbird" "output" (func $output (param i32 i32)))
+                (memory (export "memory") 1)
+                {}
+                {}
+                {}
+                (global $pos (mut i32) (i32.const 0))
+                (func (export "hb_abi_version") (result i32) (i32.const 1))
+                (func (export "hb_provider_info") (result i32)
+                    (call $output (i32.const 0) (i32.const {}))
+                    (i32.const 0))
+                (func (export "hb_stream_info") (result i32)
+                    (call $output (i32.const 256) (i32.const {}))
+                    (i32.const 0))
+                (func (export "hb_metadata") (result i32)
+                    (call $output (i32.const 512) (i32.const {}))
+                    (i32.const 0))
+                (func (export "hb_open") (result i32)
+                    (if (i32.ne (call $file_read (i32.const 2048) (i32.const 4)) (i32.const 4))
+                        (then (return (i32.const -1))))
+                    (if (result i32) (i32.eq (i32.load (i32.const 2048)) (i32.const 0x454E4F54))
+                        (then (i32.const 0))
+                        (else (i32.const -1))))
+                (func (export "hb_seek") (param $ms i64) (result i64)
+                    (global.set $pos (i32.mul (i32.div_u (i32.wrap_i64 (local.get $ms))
+                        (i32.const 100)) (i32.const 100)))
+                    (i64.extend_i32_u (global.get $pos)))
+                (func (export "hb_decode") (result i32)
+                    (local $i i32)
+                    (if (i32.ge_u (global.get $pos) (i32.const 2000))
+                        (then (return (i32.const 0))))
+                    (loop $fill
+                        (f32.store (i32.add (i32.const 1024) (i32.mul (local.get $i) (i32.const 8)))
+                            (f32.convert_i32_u (i32.add (global.get $pos) (local.get $i))))
+                        (f32.store (i32.add (i32.const 1028) (i32.mul (local.get $i) (i32.const 8)))
+                            (f32.neg (f32.convert_i32_u (i32.add (global.get $pos) (local.get $i)))))
+                        (local.set $i (i32.add (local.get $i) (i32.const 1)))
+                        (br_if $fill (i32.lt_u (local.get $i) (i32.const 100))))
+                    (global.set $pos (i32.add (global.get $pos) (i32.const 100)))
+                    (call $output (i32.const 1024) (i32.const 800))
+                    (i32.const 100)))"#,
+            data(0, PROVIDER_INFO),
+            data(256, STREAM_INFO),
+            data(512, METADATA),
+            PROVIDER_INFO.len(),
+            STREAM_INFO.len(),
+            METADATA.len(),
+        )
+    }
+
+    fn engine() -> Engine {
+        Engine::new(Config::new().consume_fuel(true)).unwrap()
+    }
+
+    fn open(provider: &PluginProvider, contents: &[u8]) -> Result<Box<dyn MediaStream>, OpenError> {
+        let dir = TestDir::new("hummingbird-plugin");
+        let path = dir.join("test.tone");
+        std::fs::write(&path, contents).unwrap();
+        provider.open(File::open(&path).unwrap(), None)
+    }
+
+    #[test]
+    fn loads_provider_info() {
+        let provider = PluginProvider::new(&engine(), tone_plugin().as_bytes()).unwrap();
+
+        assert_eq!(provider.name(), "Tone");
+        assert_eq!(provider.supported_extensions(), ["tone"]);
+        assert_eq!(provider.priority(), 0);
+        assert_eq!(
+            provider.supported_features(),
+            MediaProviderFeatures::PROVIDES_DECODER
+                | MediaProviderFeatures::PROVIDES_METADATA
+                | MediaProviderFeatures::ALLOWS_INDEXING
+        );
+    }
+
+    #[test]
+    fn decodes_and_seeks() {
+        let provider = PluginProvider::new(&engine(), tone_plugin().as_bytes()).unwrap();
+        let mut stream = open(&provider, b"TONE").unwrap();
+        let (producers, mut consumers) = ChannelBuffers::<f64>::new(2, 1024).split();
+
+        assert_eq!(stream.duration_secs(), Ok(2));
+        assert_eq!(stream.channels(), Ok(ChannelSpec::Count(2)));
+        assert_eq!(
+            stream.decode_into(&producers),
+            Ok(DecodeResult::Decoded {
+                frames: 100,
+                rate: 1000
+            })
+        );
+        assert_eq!(consumers.try_read_to_staging(100), 100);
+        assert_eq!(consumers.staging()[0][5], 5.0);
+        assert_eq!(consumers.staging()[1][5], -5.0);
+
+        stream.seek(0.25).unwrap();
+        assert_eq!(stream.position_ms(), Ok(200));
+        stream.decode_into(&producers).unwrap();
+        assert_eq!(consumers.try_read_to_staging(100), 100);
+        assert_eq!(consumers.staging()[0][0], 200.0);
+
+        let mut packets = 0;
+        while stream.decode_into(&producers) != Ok(DecodeResult::Eof) {
+            consumers.try_read_to_staging(100);
+            packets += 1;
+        }
+        assert_eq!(packets, 17);
+    }
+
+    #[test]
+    fn reads_metadata() {
+        let provider = PluginProvider::new(&engine(), tone_plugin().as_bytes()).unwrap();
+        let mut stream = open(&provider, b"TONE").unwrap();
+
+        assert!(stream.metadata_updated());
+        let metadata = stream.read_metadata().unwrap();
+        assert_eq!(metadata.name.as_deref(), Some("Tone"));
+        assert_eq!(metadata.year_month, Some((2001, 2)));
+        assert_eq!(metadata.track_current, Some(3));
+        assert!(!stream.metadata_updated());
+        assert_eq!(stream.read_image(), Ok(None));
+    }
+
+    #[test]
+    fn rejects_unsupported_files() {
+        let provider = PluginProvider::new(&engine(), tone_plugin().as_bytes()).unwrap();
+        assert_eq!(
+            open(&provider, b"RIFF").err(),
+            Some(OpenError::UnsupportedFormat)
+        );
+    }
+
+    #[test]
+    fn plugins_cannot_import_the_host_system() {
+        let wasi = r#"(module
+            (import "wasi_snapshot_preview1" "fd_write"
+                (func (param i32 i32 i32 i32) (result i32)))
+            (memory (export "memory") 1)
+            (func (export "hb_abi_version") (result i32) (i32.const 1)))"#;
+
+        assert!(PluginProvider::new(&engine(), wasi.as_bytes()).is_err());
+    }
+}
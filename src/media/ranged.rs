@@ -0,0 +1,344 @@
+//! Playback of a section of a file, for tracks split from a file by a CUE sheet.
+
+use std::path::{Path, PathBuf};
+
+use crate::{
+    devices::format::{ChannelSpec, SampleFormat},
+    media::{
+        cue::{self, TrackRange},
+        errors::{
+            ChannelRetrievalError, CloseError, FrameDurationError, MetadataError,
+            PlaybackReadError, PlaybackStartError, PlaybackStopError, SeekError,
+            TrackDurationError,
+        },
+        metadata::Metadata,
+        pipeline::{ChannelProducers, DEFAULT_BUFFER_FRAMES, DecodeResult, PacketTrimmer},
+        traits::{F32DecodeResult, MediaStream},
+    },
+};
+
+/// A stream that only plays a section of its file, for tracks split from a file by a CUE sheet.
+///
+/// Positions, durations and seeks are relative to the start of the section. The packet that
+/// crosses the end of the section is cut at the end, so the next track's audio is never heard.
+pub struct RangedStream {
+    stream: Box<dyn MediaStream>,
+    /// The path of the file the section is played from.
+    path: PathBuf,
+    range: TrackRange,
+    metadata: Option<Metadata>,
+    /// Holds each packet back until it's known how much of it is part of the section. Only used
+    /// when the section ends before the end of the file.
+    trimmer: Option<PacketTrimmer<f64>>,
+    trimmer_f32: Option<PacketTrimmer<f32>>,
+    ended: bool,
+}
+
+impl RangedStream {
+    /// Wrap a stream that has already started playback, and seek it to the start of the section.
+    pub fn new(
+        mut stream: Box<dyn MediaStream>,
+        path: &Path,
+        range: TrackRange,
+    ) -> Result<Self, SeekError> {
+        stream.seek(range.start_ms as f64 / 1000.0)?;
+
+        Ok(Self {
+            stream,
+            path: path.to_path_buf(),
+            range,
+            metadata: None,
+            trimmer: None,
+            trimmer_f32: None,
+            ended: false,
+        })
+    }
+}
+
+impl MediaStream for RangedStream {
+    fn close(&mut self) -> Result<(), CloseError> {
+        self.stream.close()
+    }
+
+    fn start_playback(&mut self) -> Result<(), PlaybackStartError> {
+        self.stream.start_playback()
+    }
+
+    fn stop_playback(&mut self) -> Result<(), PlaybackStopError> {
+        self.stream.stop_playback()
+    }
+
+    fn seek(&mut self, time: f64) -> Result<(), SeekError> {
+        self.ended = false;
+        if let Some(trimmer) = &mut self.trimmer {
+            trimmer.reset();
+        }
+        if let Some(trimmer) = &mut self.trimmer_f32 {
+            trimmer.reset();
+        }
+        self.stream.seek(self.range.start_ms as f64 / 1000.0 + time)
+    }
+
+    fn frame_duration(&self) -> Result<u64, FrameDurationError> {
+        self.stream.frame_duration()
+    }
+
+    fn read_metadata(&mut self) -> Result<&Metadata, MetadataError> {
+        let file_metadata = self.stream.read_metadata()?.clone();
+        let metadata = cue::track_metadata(&self.path, self.range, &file_metadata);
+
+        Ok(self.metadata.insert(metadata.unwrap_or(file_metadata)))
+    }
+
+    fn metadata_updated(&self) -> bool {
+        self.stream.metadata_updated()
+    }
+
+    fn read_image(&mut self) -> Result<Option<Box<[u8]>>, MetadataError> {
+        self.stream.read_image()
+    }
+
+    fn duration_secs(&self) -> Result<u64, TrackDurationError> {
+        let file_duration_ms = self.stream.duration_secs()? * 1000;
+        Ok(self.range.duration_ms(file_duration_ms) / 1000)
+    }
+
+    fn position_ms(&self) -> Result<u64, TrackDurationError> {
+        Ok(self
+            .stream
+            .position_ms()?
+            .saturating_sub(self.range.start_ms))
+    }
+
+    fn channels(&self) -> Result<ChannelSpec, ChannelRetrievalError> {
+        self.stream.channels()
+    }
+
+    fn sample_format(&self) -> Result<SampleFormat, ChannelRetrievalError> {
+        self.stream.sample_format()
+    }
+
+    fn sample_rate(&self) -> Result<u32, ChannelRetrievalError> {
+        self.stream.sample_rate()
+    }
+
+    fn decode_into(
+        &mut self,
+        output: &ChannelProducers<f64>,
+    ) -> Result<DecodeResult, PlaybackReadError> {
+        if self.ended {
+            return Ok(DecodeResult::Eof);
+        }
+
+        let Some(end_ms) = self.range.end_ms else {
+            return self.stream.decode_into(output);
+        };
+
+        let trimmer = self.trimmer.get_or_insert_with(|| {
+            PacketTrimmer::new(output.channel_count(), DEFAULT_BUFFER_FRAMES)
+        });
+        let decoded = self.stream.decode_into(trimmer.input())?;
+        let packet_start_ms = self.stream.position_ms().unwrap_or(0);
+
+        let (result, ended) = trimmer.write_before(output, decoded, packet_start_ms, end_ms);
+        self.ended = ended;
+        Ok(result)
+    }
+
+    fn decode_into_f32(
+        &mut self,
+        output: &ChannelProducers<f32>,
+    ) -> Result<F32DecodeResult, PlaybackReadError> {
+        if self.ended {
+            return Ok(F32DecodeResult::Decoded(DecodeResult::Eof));
+        }
+
+        let Some(end_ms) = self.range.end_ms else {
+            return self.stream.decode_into_f32(output);
+        };
+
+        let trimmer = self.trimmer_f32.get_or_insert_with(|| {
+            PacketTrimmer::new(output.channel_count(), DEFAULT_BUFFER_FRAMES)
+        });
+        let F32DecodeResult::Decoded(decoded) = self.stream.decode_into_f32(trimmer.input())?
+        else {
+            return Ok(F32DecodeResult::NotF32);
+        };
+        let packet_start_ms = self.stream.position_ms().unwrap_or(0);
+
+        let (result, ended) = trimmer.write_before(output, decoded, packet_start_ms, end_ms);
+        self.ended = ended;
+        Ok(F32DecodeResult::Decoded(result))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::media::pipeline::ChannelBuffers;
+
+    const RATE: u32 = 44_100;
+    const PACKET_FRAMES: usize = 4_096;
+
+    /// A stereo file that decodes into fixed-size packets, with each sample holding the index of
+    /// its frame in the file.
+    struct PacketStream {
+        total_frames: u64,
+        next_frame: u64,
+        position_ms: u64,
+    }
+
+    impl PacketStream {
+        fn new(total_frames: u64) -> Self {
+            Self {
+                total_frames,
+                next_frame: 0,
+                position_ms: 0,
+            }
+        }
+
+        fn next_packet(&mut self) -> Option<Vec<f64>> {
+            if self.next_frame >= self.total_frames {
+                return None;
+            }
+
+            let end = (self.next_frame + PACKET_FRAMES as u64).min(self.total_frames);
+            let packet = (self.next_frame..end).map(|frame| frame as f64).collect();
+            self.position_ms = self.next_frame * 1000 / RATE as u64;
+            self.next_frame = end;
+            Some(packet)
+        }
+    }
+
+    impl MediaStream for PacketStream {
+        fn close(&mut self) -> Result<(), CloseError> {
+            Ok(())
+        }
+
+        fn start_playback(&mut self) -> Result<(), PlaybackStartError> {
+            Ok(())
+        }
+
+        fn stop_playback(&mut self) -> Result<(), PlaybackStopError> {
+            Ok(())
+        }
+
+        fn seek(&mut self, time: f64) -> Result<(), SeekError> {
+            self.next_frame = (time * RATE as f64).round() as u64;
+            self.position_ms = (time * 1000.0) as u64;
+            Ok(())
+        }
+
+        fn frame_duration(&self) -> Result<u64, FrameDurationError> {
+            Ok(PACKET_FRAMES as u64)
+        }
+
+        fn read_metadata(&mut self) -> Result<&Metadata, MetadataError> {
+            Err(MetadataError::OperationUnsupported)
+        }
+
+        fn metadata_updated(&self) -> bool {
+            false
+        }
+
+        fn read_image(&mut self) -> Result<Option<Box<[u8]>>, MetadataError> {
+            Ok(None)
+        }
+
+        fn duration_secs(&self) -> Result<u64, TrackDurationError> {
+            Ok(self.total_frames / RATE as u64)
+        }
+
+        fn position_ms(&self) -> Result<u64, TrackDurationError> {
+            Ok(self.position_ms)
+        }
+
+        fn channels(&self) -> Result<ChannelSpec, ChannelRetrievalError> {
+            Ok(ChannelSpec::Count(2))
+        }
+
+        fn sample_format(&self) -> Result<SampleFormat, ChannelRetrievalError> {
+            Ok(SampleFormat::Float64)
+        }
+
+        fn sample_rate(&self) -> Result<u32, ChannelRetrievalError> {
+            Ok(RATE)
+        }
+
+        fn decode_into(
+            &mut self,
+            output: &ChannelProducers<f64>,
+        ) -> Result<DecodeResult, PlaybackReadError> {
+            let Some(packet) = self.next_packet() else {
+                return Ok(DecodeResult::Eof);
+            };
+
+            let frames = packet.len();
+            output.write_vecs(&[packet.clone(), packet]);
+            Ok(DecodeResult::Decoded { frames, rate: RATE })
+        }
+
+        fn decode_into_f32(
+            &mut self,
+            _: &ChannelProducers<f32>,
+        ) -> Result<F32DecodeResult, PlaybackReadError> {
+            Ok(F32DecodeResult::NotF32)
+        }
+    }
+
+    /// Decode the stream until it ends, returning the frames it produced.
+    fn decode_all(stream: &mut RangedStream) -> Vec<f64> {
+        let (producers, mut consumers) = ChannelBuffers::<f64>::new(2, PACKET_FRAMES * 2).split();
+        let mut frames = Vec::new();
+
+        while let DecodeResult::Decoded { frames: count, .. } =
+            stream.decode_into(&producers).unwrap()
+        {
+            assert_eq!(consumers.try_read_to_staging(count), count);
+            assert_eq!(consumers.staging()[0], consumers.staging()[1]);
+            frames.extend_from_slice(&consumers.staging()[0]);
+        }
+
+        frames
+    }
+
+    #[test]
+    fn section_ends_on_the_exact_frame() {
+        let range = TrackRange {
+            start_ms: 1_000,
+            end_ms: Some(2_500),
+        };
+        let mut stream = RangedStream::new(
+            Box::new(PacketStream::new(RATE as u64 * 10)),
+            Path::new(""),
+            range,
+        )
+        .unwrap();
+
+        // the section ends partway through a packet
+        let expected: Vec<f64> = (44_100..110_250).map(|frame| frame as f64).collect();
+        assert_ne!(expected.len() % PACKET_FRAMES, 0);
+        assert_eq!(decode_all(&mut stream), expected);
+
+        // seeking within the section cuts at the same frame
+        stream.seek(1.0).unwrap();
+        let expected: Vec<f64> = (88_200..110_250).map(|frame| frame as f64).collect();
+        assert_eq!(decode_all(&mut stream), expected);
+    }
+
+    #[test]
+    fn last_section_plays_to_end_of_file() {
+        let range = TrackRange {
+            start_ms: 9_000,
+            end_ms: None,
+        };
+        let mut stream = RangedStream::new(
+            Box::new(PacketStream::new(RATE as u64 * 10)),
+            Path::new(""),
+            range,
+        )
+        .unwrap();
+
+        assert_eq!(decode_all(&mut stream).len(), RATE as usize);
+    }
+}
@@ -1,4 +1,4 @@
-use std::{ffi::OsStr, fs::File};
+use std::{ffi::OsStr, fs::File, path::Path};
 
 use bitflags::bitflags;
 
@@ -7,9 +7,10 @@ use crate::devices::format::{ChannelSpec, SampleFormat};
 use super::{
     errors::{
         ChannelRetrievalError, CloseError, FrameDurationError, MetadataError, OpenError,
-        PlaybackReadError, PlaybackStartError, PlaybackStopError, SeekError, TrackDurationError,
+        PlaybackReadError, PlaybackStartError, PlaybackStopError, SeekError, TagWriteError,
+        TrackDurationError,
     },
-    metadata::Metadata,
+    metadata::{Metadata, TagUpdate},
     pipeline::{ChannelProducers, DecodeResult},
 };
 
@@ -29,6 +30,8 @@ bitflags! {
         const PROVIDES_DECODER         = 0b00000010;
         /// Indicates the provider should be considered for indexing files while scanning.
         const ALLOWS_INDEXING          = 0b00000100;
+        /// Indicates the provider should be used for writing tags to media files.
+        const WRITES_TAGS              = 0b00001000;
     }
 }
 
@@ -54,6 +57,22 @@ pub trait MediaProvider: Send + Sync {
 
     /// Returns the provider's name.
     fn name(&self) -> &str;
+
+    /// Returns the provider's priority. When several Providers support a file, the one with the
+    /// highest priority is used, which lets a specialized Provider take over a format from a
+    /// general one. Providers with equal priorities are used in the order they were registered.
+    /// The built-in Providers have a priority of 0.
+    fn priority(&self) -> i32 {
+        0
+    }
+
+    /// Requests the Provider write the specified changes to the tags of the file at the given
+    /// path, leaving all other tags untouched. This is only called on Providers that support
+    /// [MediaProviderFeatures::WRITES_TAGS].
+    fn write_tags(&self, path: &Path, update: &TagUpdate) -> Result<(), TagWriteError> {
+        let _ = (path, update);
+        Err(TagWriteError::OperationUnsupported)
+    }
 }
 
 /// The MediaStream trait defines the methods used to interact with an open media stream. A media
@@ -1,5 +1,6 @@
 pub mod events;
 pub mod interface;
 pub mod queue;
+pub mod resume;
 pub mod session_storage;
 pub mod thread;
@@ -2,9 +2,18 @@
 
 use serde::{Deserialize, Serialize};
 
-use crate::{media::metadata::Metadata, settings::playback::PlaybackSettings};
+use crate::{
+    devices::visualizer::VisualizerFrame,
+    media::metadata::Metadata,
+    settings::{
+        equalizer::EqualizerSettings, output::OutputDeviceSettings, playback::PlaybackSettings,
+    },
+};
 
-use super::{queue::QueueItemData, thread::PlaybackState};
+use super::{
+    queue::{QueueItemData, TrackSource},
+    thread::PlaybackState,
+};
 use std::path::PathBuf;
 
 #[derive(Debug, Clone, PartialEq, Copy, Serialize, Deserialize)]
@@ -14,6 +23,41 @@ pub enum RepeatState {
     RepeatingOne,
 }
 
+/// When the sleep timer should stop playback.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum SleepTimer {
+    /// Stop after the given number of minutes, fading out towards the end.
+    Minutes(u32),
+    /// Stop once the current track ends.
+    EndOfTrack,
+    /// Stop once the last track of the current album ends.
+    EndOfAlbum,
+}
+
+/// The state of a running sleep timer, as reported by the playback thread.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum SleepTimerState {
+    /// Playback stops once this many seconds have passed.
+    Remaining(u64),
+    EndOfTrack,
+    EndOfAlbum,
+}
+
+/// The A/B loop points of the current track, in milliseconds. The section between them is
+/// looped once point B is set; without point A, the loop starts at the beginning of the track.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub struct AbLoop {
+    pub start_ms: Option<u64>,
+    pub end_ms: Option<u64>,
+}
+
+/// An output device offered by the current device provider.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct OutputDevice {
+    pub uid: String,
+    pub name: String,
+}
+
 /// A command to the playback thread. This is used to control the playback thread from other
 /// threads. The playback thread recieves these commands from an MPSC channel, and processes them
 /// in the order they are recieved. They are processed every 10ms when playback is stopped, or
@@ -93,6 +137,44 @@ pub enum PlaybackCommand {
     /// Unlike ReplaceQueue, the playback thread will jump to the specified index in the new queue,
     /// instead of the first item.
     ReplaceQueueWithIndex(Vec<QueueItemData>, usize),
+    /// Requests that the playback thread apply the specified equalizer configuration to the audio
+    /// that is currently playing.
+    SetEqualizer(EqualizerSettings),
+    /// Requests that the playback thread change the playback speed. 1.0 is the original speed,
+    /// and the speed is clamped between 0.5 and 3.0.
+    SetSpeed(f64),
+    /// Provides the ReplayGain values stored in the library for the specified track. These are
+    /// used when the file itself isn't tagged, for example when the values were measured by the
+    /// loudness analyzer.
+    SetLibraryReplayGain {
+        source: TrackSource,
+        track_gain: Option<f64>,
+        album_gain: Option<f64>,
+    },
+    /// Provides the resume point stored in the library for the specified track. If the track has
+    /// only just started playing, playback continues from the resume point.
+    ResumeAt {
+        source: TrackSource,
+        position_ms: u64,
+    },
+    /// Requests that the playback thread start the specified sleep timer, replacing any running
+    /// timer. `None` cancels the running timer.
+    SetSleepTimer(Option<SleepTimer>),
+    /// Requests that the playback thread mark the current position as the start of the A/B loop.
+    SetLoopStart,
+    /// Requests that the playback thread mark the current position as the end of the A/B loop,
+    /// and start looping.
+    SetLoopEnd,
+    /// Requests that the playback thread stop looping and forget both loop points.
+    ClearLoop,
+    /// Requests that the playback thread switch to the specified output device. The current
+    /// track continues on the new device from where it was.
+    SetOutputDevice(OutputDeviceSettings),
+    /// Requests that the playback thread list the output devices of the current device provider.
+    ListOutputDevices,
+    /// Informs the playback thread whether any view is showing visualizer data. Audio is only
+    /// analyzed while this is set.
+    SetVisualizerActive(bool),
 }
 
 /// An event from the playback thread. This is used to communicate information from the playback
@@ -102,8 +184,8 @@ pub enum PlaybackCommand {
 pub enum PlaybackEvent {
     /// Indicates that the playback state has changed.
     StateChanged(PlaybackState),
-    /// Indicates that the current file has changed providing the path to the new file.
-    SongChanged(PathBuf),
+    /// Indicates that the current track has changed, providing what the new track plays.
+    SongChanged(TrackSource),
     /// Indicates that the duration of the current file has changed. The f64 is the new duration,
     /// in seconds.
     DurationChanged(u64),
@@ -125,4 +207,27 @@ pub enum PlaybackEvent {
     RepeatChanged(RepeatState),
     /// Indicates that the volume has changed. The f64 is the new volume, from 0.0 to 1.0.
     VolumeChanged(f64),
+    /// Indicates that the playback speed has changed. Positions are still reported in the
+    /// source's own timeline.
+    SpeedChanged(f64),
+    /// Indicates that the sleep timer has been started, cancelled, or has fired, or that the
+    /// remaining time has changed. `None` means no timer is running.
+    SleepTimerChanged(Option<SleepTimerState>),
+    /// Indicates that the A/B loop points have changed. Both points are cleared whenever the
+    /// current track changes.
+    LoopChanged(AbLoop),
+    /// Lists the output devices offered by the current device provider.
+    OutputDevicesListed(Vec<OutputDevice>),
+    /// Indicates that the selected output device couldn't be found, and that the provider's
+    /// default device is being used instead. The string is the name of the missing device.
+    OutputDeviceUnavailable(String),
+    /// Indicates that the selected output device was reconnected and is being played to again.
+    OutputDeviceReconnected,
+    /// Indicates whether the current track is reaching the device without any changes to its
+    /// samples.
+    BitPerfectChanged(bool),
+    /// Provides the spectrum and levels of the audio being played, about 30 times per second
+    /// while the visualizer is active and audio is playing. The frame is boxed to avoid enum size
+    /// bloat.
+    VisualizerUpdated(Box<VisualizerFrame>),
 }
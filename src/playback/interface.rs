@@ -1,20 +1,32 @@
 #![allow(dead_code)]
 
-use std::{path::PathBuf, sync::Arc};
+use std::{
+    path::PathBuf,
+    sync::{
+        Arc,
+        atomic::{AtomicUsize, Ordering},
+    },
+};
 
 use gpui::App;
 use tokio::sync::mpsc::{UnboundedReceiver, UnboundedSender};
 
 use crate::{
-    playback::events::RepeatState,
+    library::db::get_track_by_path,
+    playback::events::{RepeatState, SleepTimer},
     power::PowerManager,
-    settings::playback::PlaybackSettings,
-    ui::models::{CurrentTrack, ImageEvent, MMBSEvent, Models, PlaybackInfo},
+    settings::{
+        equalizer::EqualizerSettings, output::OutputDeviceSettings, playback::PlaybackSettings,
+    },
+    ui::{
+        app::Pool,
+        models::{CurrentTrack, ImageEvent, MMBSEvent, Models, PlaybackInfo},
+    },
 };
 
 use super::{
     events::{PlaybackCommand, PlaybackEvent},
-    queue::QueueItemData,
+    queue::{QueueItemData, TrackSource},
     thread::PlaybackState,
 };
 
@@ -32,6 +44,8 @@ use super::{
 pub struct PlaybackInterface {
     cmd_tx: UnboundedSender<PlaybackCommand>,
     events_rx: Option<UnboundedReceiver<PlaybackEvent>>,
+    /// The number of live [`VisualizerSubscription`]s.
+    visualizer_subscribers: Arc<AtomicUsize>,
 }
 
 impl gpui::Global for PlaybackInterface {}
@@ -44,6 +58,7 @@ impl PlaybackInterface {
         Self {
             cmd_tx,
             events_rx: Some(events_rx),
+            visualizer_subscribers: Arc::new(AtomicUsize::new(0)),
         }
     }
 
@@ -173,6 +188,70 @@ impl PlaybackInterface {
             .unwrap();
     }
 
+    pub fn set_equalizer(&self, settings: EqualizerSettings) {
+        self.cmd_tx
+            .send(PlaybackCommand::SetEqualizer(settings))
+            .unwrap();
+    }
+
+    pub fn set_speed(&self, speed: f64) {
+        self.cmd_tx.send(PlaybackCommand::SetSpeed(speed)).unwrap();
+    }
+
+    pub fn set_sleep_timer(&self, timer: Option<SleepTimer>) {
+        self.cmd_tx
+            .send(PlaybackCommand::SetSleepTimer(timer))
+            .unwrap();
+    }
+
+    pub fn set_loop_start(&self) {
+        self.cmd_tx.send(PlaybackCommand::SetLoopStart).unwrap();
+    }
+
+    pub fn set_loop_end(&self) {
+        self.cmd_tx.send(PlaybackCommand::SetLoopEnd).unwrap();
+    }
+
+    pub fn clear_loop(&self) {
+        self.cmd_tx.send(PlaybackCommand::ClearLoop).unwrap();
+    }
+
+    pub fn set_output_device(&self, output: OutputDeviceSettings) {
+        self.cmd_tx
+            .send(PlaybackCommand::SetOutputDevice(output))
+            .unwrap();
+    }
+
+    pub fn list_output_devices(&self) {
+        self.cmd_tx
+            .send(PlaybackCommand::ListOutputDevices)
+            .unwrap();
+    }
+
+    /// Start receiving visualizer data in [`PlaybackInfo::visualizer`]. The playback thread only
+    /// analyzes audio while at least one subscription is held.
+    pub fn subscribe_visualizer(&self) -> VisualizerSubscription {
+        if self.visualizer_subscribers.fetch_add(1, Ordering::SeqCst) == 0 {
+            self.cmd_tx
+                .send(PlaybackCommand::SetVisualizerActive(true))
+                .unwrap();
+        }
+
+        VisualizerSubscription {
+            cmd_tx: self.cmd_tx.clone(),
+            subscribers: self.visualizer_subscribers.clone(),
+        }
+    }
+
+    pub fn resume_at(&self, source: TrackSource, position_ms: u64) {
+        self.cmd_tx
+            .send(PlaybackCommand::ResumeAt {
+                source,
+                position_ms,
+            })
+            .unwrap();
+    }
+
     pub fn get_sender(&self) -> UnboundedSender<PlaybackCommand> {
         self.cmd_tx.clone()
     }
@@ -194,6 +273,8 @@ impl PlaybackInterface {
 
         let playback_info = app.global::<PlaybackInfo>().clone();
         let power_manager = app.global::<PowerManager>().clone();
+        let pool = app.global::<Pool>().0.clone();
+        let cmd_tx = self.cmd_tx.clone();
 
         let Some(mut events_rx) = events_rx else {
             panic!("broadcast thread already started");
@@ -248,6 +329,14 @@ impl PlaybackInterface {
                                 });
                             }
 
+                            // frames stop arriving once playback stops, so meters fall silent
+                            if v != PlaybackState::Playing {
+                                playback_info.visualizer.update(cx, |m, cx| {
+                                    *m = None;
+                                    cx.notify()
+                                });
+                            }
+
                             power_manager.set_state(cx, v);
 
                             mmbs_model.update(cx, |_, cx| {
@@ -272,13 +361,30 @@ impl PlaybackInterface {
                                 cx.emit(MMBSEvent::DurationChanged(v));
                             });
                         }
-                        PlaybackEvent::SongChanged(path) => {
+                        PlaybackEvent::SongChanged(source) => {
+                            // untagged files may still have values measured by the loudness
+                            // analyzer in the library
+                            let pool = pool.clone();
+                            let cmd_tx = cmd_tx.clone();
+                            let lookup = source.clone();
+                            crate::RUNTIME.spawn(async move {
+                                if let Ok(Some(track)) =
+                                    get_track_by_path(&pool, &lookup.path, lookup.range).await
+                                {
+                                    let _ = cmd_tx.send(PlaybackCommand::SetLibraryReplayGain {
+                                        source: lookup,
+                                        track_gain: track.rg_track_gain,
+                                        album_gain: track.rg_album_gain,
+                                    });
+                                }
+                            });
+
                             playback_info.current_track.update(cx, |m, cx| {
-                                *m = Some(CurrentTrack::new(path.clone()));
+                                *m = Some(CurrentTrack::new(source.clone()));
                                 cx.notify()
                             });
                             mmbs_model.update(cx, |_, cx| {
-                                cx.emit(MMBSEvent::NewTrack(path));
+                                cx.emit(MMBSEvent::NewTrack(source.path));
                             });
                         }
                         PlaybackEvent::QueueUpdated => {
@@ -307,6 +413,54 @@ impl PlaybackInterface {
                                 });
                             }
                         }
+                        PlaybackEvent::SpeedChanged(v) => {
+                            playback_info.speed.update(cx, |m, cx| {
+                                *m = v;
+                                cx.notify()
+                            });
+                        }
+                        PlaybackEvent::SleepTimerChanged(v) => {
+                            playback_info.sleep_timer.update(cx, |m, cx| {
+                                *m = v;
+                                cx.notify()
+                            });
+                        }
+                        PlaybackEvent::LoopChanged(v) => {
+                            playback_info.ab_loop.update(cx, |m, cx| {
+                                *m = v;
+                                cx.notify()
+                            });
+                        }
+                        PlaybackEvent::OutputDevicesListed(v) => {
+                            playback_info.output_devices.update(cx, |m, cx| {
+                                *m = Arc::new(v);
+                                cx.notify()
+                            });
+                        }
+                        PlaybackEvent::OutputDeviceUnavailable(v) => {
+                            playback_info.unavailable_output_device.update(cx, |m, cx| {
+                                *m = Some(v.into());
+                                cx.notify()
+                            });
+                        }
+                        PlaybackEvent::OutputDeviceReconnected => {
+                            playback_info.unavailable_output_device.update(cx, |m, cx| {
+                                *m = None;
+                                cx.notify()
+                            });
+                        }
+                        PlaybackEvent::BitPerfectChanged(v) => {
+                            playback_info.bit_perfect.update(cx, |m, cx| {
+                                *m = v;
+                                cx.notify()
+                            });
+                        }
+                        PlaybackEvent::VisualizerUpdated(v) => {
+                            playback_info.visualizer.update(cx, |m, cx| {
+                                *m = Some(Arc::new(*v));
+                                cx.notify()
+                            });
+                        }
                         PlaybackEvent::QueuePositionChanged(v) => {
                             queue_model.update(cx, |m, cx| {
                                 m.position = v;
@@ -327,6 +481,24 @@ impl PlaybackInterface {
     }
 }
 
+/// Keeps the playback thread analyzing audio for the visualizer. Analysis stops once every
+/// subscription has been dropped.
+pub struct VisualizerSubscription {
+    cmd_tx: UnboundedSender<PlaybackCommand>,
+    subscribers: Arc<AtomicUsize>,
+}
+
+impl Drop for VisualizerSubscription {
+    fn drop(&mut self) {
+        if self.subscribers.fetch_sub(1, Ordering::SeqCst) == 1 {
+            // the playback thread may already be gone during shutdown
+            let _ = self
+                .cmd_tx
+                .send(PlaybackCommand::SetVisualizerActive(false));
+        }
+    }
+}
+
 // TODO: this should be in a trait for AppContext
 /// Replace the current queue with the given items.
 pub fn replace_queue(items: Vec<QueueItemData>, app: &mut App) {
@@ -4,7 +4,42 @@ use std::sync::{Arc, RwLock};
 use gpui::{App, AppContext, Entity, SharedString};
 use std::path::PathBuf;
 
-use crate::{library::db::LibraryAccess, ui::data::Decode};
+use crate::{library::db::LibraryAccess, media::cue::TrackRange, ui::data::Decode};
+
+/// What a queue item plays: a whole file, or the section of a file that a CUE sheet splits into
+/// a track.
+#[derive(Clone, Debug, PartialEq, Eq, serde::Serialize, serde::Deserialize)]
+pub struct TrackSource {
+    pub path: PathBuf,
+    #[serde(default)]
+    pub range: Option<TrackRange>,
+}
+
+impl TrackSource {
+    pub fn new(path: PathBuf, range: Option<TrackRange>) -> Self {
+        TrackSource { path, range }
+    }
+}
+
+impl From<PathBuf> for TrackSource {
+    fn from(path: PathBuf) -> Self {
+        TrackSource { path, range: None }
+    }
+}
+
+impl Display for TrackSource {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(f, "{}", self.path.display())?;
+        match self.range {
+            Some(TrackRange {
+                start_ms,
+                end_ms: Some(end_ms),
+            }) => write!(f, " ({start_ms}ms to {end_ms}ms)"),
+            Some(TrackRange { start_ms, .. }) => write!(f, " (from {start_ms}ms)"),
+            None => Ok(()),
+        }
+    }
+}
 
 #[derive(Clone, Debug)]
 pub struct QueueItemData {
@@ -20,6 +55,8 @@ pub struct QueueItemData {
     db_album_id: Option<i64>,
     /// The path to the track file.
     path: PathBuf,
+    /// The section of the file that is played, if the track is split from a file by a CUE sheet.
+    range: Option<TrackRange>,
 }
 
 impl serde::Serialize for QueueItemData {
@@ -28,10 +65,11 @@ impl serde::Serialize for QueueItemData {
         S: serde::Serializer,
     {
         use serde::ser::SerializeStruct;
-        let mut state = serializer.serialize_struct("QueueItemData", 3)?;
+        let mut state = serializer.serialize_struct("QueueItemData", 4)?;
         state.serialize_field("db_id", &self.db_id)?;
         state.serialize_field("db_album_id", &self.db_album_id)?;
         state.serialize_field("path", &self.path)?;
+        state.serialize_field("range", &self.range)?;
         state.end()
     }
 }
@@ -46,6 +84,8 @@ impl<'de> serde::Deserialize<'de> for QueueItemData {
             db_id: Option<i64>,
             db_album_id: Option<i64>,
             path: PathBuf,
+            #[serde(default)]
+            range: Option<TrackRange>,
         }
 
         let raw = QueueItemDataRaw::deserialize(deserializer)?;
@@ -54,6 +94,7 @@ impl<'de> serde::Deserialize<'de> for QueueItemData {
             db_id: raw.db_id,
             db_album_id: raw.db_album_id,
             path: raw.path,
+            range: raw.range,
         })
     }
 }
@@ -91,14 +132,22 @@ impl PartialEq for QueueItemData {
         self.db_id == other.db_id
             && self.db_album_id == other.db_album_id
             && self.path == other.path
+            && self.range == other.range
     }
 }
 
 impl QueueItemData {
     /// Creates a new `QueueItemData` instance with the given information.
-    pub fn new(cx: &mut App, path: PathBuf, db_id: Option<i64>, db_album_id: Option<i64>) -> Self {
+    pub fn new(
+        cx: &mut App,
+        path: PathBuf,
+        range: Option<TrackRange>,
+        db_id: Option<i64>,
+        db_album_id: Option<i64>,
+    ) -> Self {
         QueueItemData {
             path,
+            range,
             db_id,
             db_album_id,
             data: Arc::new(RwLock::new(Some(cx.new(|_| None)))),
@@ -133,7 +182,7 @@ impl QueueItemData {
             .clone();
         let track_id = self.db_id;
         let album_id = self.db_album_id;
-        let path = self.path.clone();
+        let source = self.source();
         model.update(cx, move |m, cx| {
             // if we already have the data, exit the function
             if m.is_some() {
@@ -174,7 +223,7 @@ impl QueueItemData {
 
             // vital information left blank, try retriving the metadata from disk
             // much slower, especially on windows
-            cx.read_metadata(path, cx.entity()).detach();
+            cx.read_metadata(source, cx.entity()).detach();
         });
 
         model
@@ -196,6 +245,16 @@ impl QueueItemData {
         &self.path
     }
 
+    /// Returns the section of the file that is played, if the track is split from a file.
+    pub fn get_range(&self) -> Option<TrackRange> {
+        self.range
+    }
+
+    /// Returns what the queue item plays.
+    pub fn source(&self) -> TrackSource {
+        TrackSource::new(self.path.clone(), self.range)
+    }
+
     /// Returns the album ID of the queue item, if it exists.
     pub fn get_db_album_id(&self) -> Option<i64> {
         self.db_album_id
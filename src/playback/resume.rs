@@ -0,0 +1,138 @@
+/// How often the position of a resumable track is saved while it plays.
+const SAVE_INTERVAL_MS: u64 = 10_000;
+/// Positions this close to the start aren't saved, so briefly opening a track doesn't overwrite
+/// its resume point.
+const MIN_POSITION_MS: u64 = 10_000;
+/// Tracks stopped this close to their end are considered finished.
+const FINISHED_MARGIN_MS: u64 = 30_000;
+
+/// A position to write to the library for a resumable track.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct ProgressUpdate {
+    pub track_id: i64,
+    pub position_ms: u64,
+    pub finished: bool,
+}
+
+#[derive(Debug, Clone, Copy)]
+struct TrackedTrack {
+    track_id: i64,
+    duration_ms: u64,
+    position_ms: u64,
+    last_saved_ms: Option<u64>,
+}
+
+impl TrackedTrack {
+    fn update(&mut self) -> Option<ProgressUpdate> {
+        if self.position_ms < MIN_POSITION_MS || self.last_saved_ms == Some(self.position_ms) {
+            return None;
+        }
+
+        self.last_saved_ms = Some(self.position_ms);
+        let finished = self.position_ms.saturating_add(FINISHED_MARGIN_MS) >= self.duration_ms;
+
+        Some(ProgressUpdate {
+            track_id: self.track_id,
+            position_ms: if finished { 0 } else { self.position_ms },
+            finished,
+        })
+    }
+}
+
+/// Follows the playback position of the current resumable track, and decides when it should be
+/// written to the library.
+#[derive(Debug, Default)]
+pub struct ResumeTracker {
+    current: Option<TrackedTrack>,
+}
+
+impl ResumeTracker {
+    /// Start following a new track, returning the final position of the previous one.
+    pub fn start(&mut self, track_id: i64, duration_ms: u64) -> Option<ProgressUpdate> {
+        let previous = self.finish();
+
+        self.current = Some(TrackedTrack {
+            track_id,
+            duration_ms,
+            position_ms: 0,
+            last_saved_ms: None,
+        });
+
+        previous
+    }
+
+    /// Record the current position, returning an update if enough time has passed since the
+    /// last save.
+    pub fn position_changed(&mut self, position_ms: u64) -> Option<ProgressUpdate> {
+        let current = self.current.as_mut()?;
+        current.position_ms = position_ms;
+
+        let due = current
+            .last_saved_ms
+            .is_none_or(|saved| saved.abs_diff(position_ms) >= SAVE_INTERVAL_MS);
+
+        if due { current.update() } else { None }
+    }
+
+    /// Save the current position now, for example because playback was paused.
+    pub fn checkpoint(&mut self) -> Option<ProgressUpdate> {
+        self.current.as_mut()?.update()
+    }
+
+    /// Stop following the current track, returning its final position.
+    pub fn finish(&mut self) -> Option<ProgressUpdate> {
+        self.current.take()?.update()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn saves_periodically_and_ignores_the_start() {
+        let mut tracker = ResumeTracker::default();
+        assert_eq!(tracker.start(1, 3_600_000), None);
+
+        assert_eq!(tracker.position_changed(0), None);
+        assert_eq!(tracker.position_changed(5_000), None);
+        assert_eq!(
+            tracker.position_changed(12_000),
+            Some(ProgressUpdate {
+                track_id: 1,
+                position_ms: 12_000,
+                finished: false
+            })
+        );
+        assert_eq!(tracker.position_changed(18_000), None);
+        assert_eq!(tracker.checkpoint().map(|u| u.position_ms), Some(18_000));
+        assert_eq!(tracker.checkpoint(), None);
+
+        // switching tracks reports where the previous one was left
+        tracker.position_changed(25_000);
+        assert_eq!(tracker.start(2, 3_600_000).map(|u| u.track_id), Some(1));
+        assert_eq!(tracker.finish(), None);
+    }
+
+    #[test]
+    fn marks_tracks_near_their_end_as_finished() {
+        let mut tracker = ResumeTracker::default();
+        tracker.start(1, 3_600_000);
+        assert_eq!(
+            tracker.position_changed(3_500_000).map(|u| u.finished),
+            Some(false)
+        );
+        assert_eq!(tracker.position_changed(3_505_000), None);
+
+        tracker.position_changed(3_580_000);
+        tracker.position_changed(3_585_000);
+        assert_eq!(
+            tracker.finish(),
+            Some(ProgressUpdate {
+                track_id: 1,
+                position_ms: 0,
+                finished: true
+            })
+        );
+    }
+}
@@ -13,6 +13,9 @@ pub struct PlaybackSessionData {
     pub queue_position: Option<usize>,
     pub shuffle: bool,
     pub repeat: RepeatState,
+    /// The position inside the current track, in milliseconds.
+    #[serde(default)]
+    pub position_ms: Option<u64>,
 }
 
 impl Default for PlaybackSessionData {
@@ -23,6 +26,7 @@ impl Default for PlaybackSessionData {
             queue_position: None,
             shuffle: false,
             repeat: RepeatState::NotRepeating,
+            position_ms: None,
         }
     }
 }
@@ -135,6 +139,7 @@ mod tests {
             queue_position: Some(3),
             shuffle: true,
             repeat: RepeatState::RepeatingOne,
+            position_ms: Some(754_000),
         };
 
         fs::write(&path, serde_json::to_vec(&expected).unwrap()).unwrap();
@@ -146,5 +151,22 @@ mod tests {
         assert_eq!(session.queue_position, expected.queue_position);
         assert_eq!(session.shuffle, expected.shuffle);
         assert_eq!(session.repeat, expected.repeat);
+        assert_eq!(session.position_ms, expected.position_ms);
+    }
+
+    #[test]
+    fn load_accepts_session_without_track_position() {
+        let dir = create_test_dir();
+        let path = dir.join("session.json");
+        fs::write(
+            &path,
+            r#"{"queue":[],"original_queue":[],"queue_position":0,"shuffle":false,"repeat":"NotRepeating"}"#,
+        )
+        .unwrap();
+
+        let session = PlaybackSessionStorageWorker::load(&path);
+
+        assert_eq!(session.queue_position, Some(0));
+        assert_eq!(session.position_ms, None);
     }
 }
@@ -1,12 +1,15 @@
+mod ab_loop;
 mod audio_engine;
+mod crossfade;
 mod device_controller;
 mod media_controller;
 mod queue_manager;
+mod sleep_timer;
 
 use std::{
-    path::Path,
     sync::{Arc, RwLock},
     thread::sleep,
+    time::{Duration, Instant},
 };
 
 use itertools::Itertools as _;
@@ -17,9 +20,15 @@ use tokio::sync::{
 use tracing::{debug, error, info, warn};
 
 use crate::{
+    devices::stretch::{MAX_SPEED, MIN_SPEED},
     media::errors::PlaybackStartError,
-    playback::{events::RepeatState, session_storage::PlaybackSessionData},
+    playback::{
+        events::{RepeatState, SleepTimer, SleepTimerState},
+        session_storage::PlaybackSessionData,
+    },
     settings::{
+        equalizer::EqualizerSettings,
+        output::OutputDeviceSettings,
         playback::PlaybackSettings,
         replaygain::{ReplayGainAutoHint, calculate_gain},
     },
@@ -28,19 +37,34 @@ use crate::{
 use super::{
     events::{PlaybackCommand, PlaybackEvent},
     interface::PlaybackInterface,
-    queue::QueueItemData,
+    queue::{QueueItemData, TrackSource},
 };
 
+use ab_loop::LoopPoints;
 use audio_engine::{AudioEngine, EngineCycleResult, EngineState};
+use device_controller::DeviceChangeAction;
 use queue_manager::{
     DequeueManyResult, DequeueResult, InsertResult, JumpResult, MoveResult, QueueManager,
     QueueNavigationResult, ReplaceResult, Reshuffled, ShuffleResult, UndoResult,
 };
+use sleep_timer::ActiveSleepTimer;
 
 // throttle position broadcasts to prevent excees CPU utilization, especially while the application isn't
 // focused
 const ACTIVE_POSITION_BROADCAST_INTERVAL_MS: u64 = 33;
 const BACKGROUND_POSITION_BROADCAST_INTERVAL_MS: u64 = 250;
+// visualizer frames are sent at about 30 Hz
+const VISUALIZER_INTERVAL: Duration = Duration::from_millis(33);
+// the position inside the current track is written to the session file at most this often while
+// playing
+const SESSION_POSITION_INTERVAL_MS: u64 = 5_000;
+// resume points are only applied if they arrive within this long of the track starting, so that
+// they don't interrupt a track the user has already seeked in
+const RESUME_WINDOW_MS: u64 = 5_000;
+
+// how long before the end of the current track the next one is opened, so that it can be played
+// without a gap
+const GAPLESS_PREPARE_WINDOW_MS: u64 = 10_000;
 
 #[derive(Debug, Clone, Copy, PartialEq, Eq)]
 pub enum PlaybackState {
@@ -78,12 +102,37 @@ pub struct PlaybackThread {
     queue: QueueManager,
     /// The volume to apply on startup (restored from persisted settings).
     initial_volume: f64,
+    /// The volume last requested by the user, before any sleep timer fade is applied.
+    volume: f64,
     /// Current auto-mode hint for ReplayGain.
     rg_auto_hint: ReplayGainAutoHint,
     /// Cached track gain from last metadata update.
     last_track_gain: Option<f64>,
     /// Cached album gain from last metadata update.
     last_album_gain: Option<f64>,
+    /// Track gain stored in the library, used if the file has no ReplayGain tags.
+    library_track_gain: Option<f64>,
+    /// Album gain stored in the library, used if the file has no ReplayGain tags.
+    library_album_gain: Option<f64>,
+    /// The duration of the current track in seconds, if known.
+    duration_secs: Option<u64>,
+    /// The last track that the engine was asked to prepare for gapless playback. This is used to
+    /// avoid repeatedly trying to open a file that can't be opened.
+    last_prepare_attempt: Option<TrackSource>,
+    /// The running sleep timer, if any.
+    sleep_timer: Option<ActiveSleepTimer>,
+    /// The sleep timer fade currently applied on top of the volume.
+    sleep_fade_gain: f64,
+    /// The sleep timer state last sent to the UI.
+    last_sleep_timer_state: Option<SleepTimerState>,
+    /// The A/B loop points set for the current track.
+    loop_points: LoopPoints,
+    /// The position inside the current track last written to the playback session.
+    last_session_position: Option<u64>,
+    /// Whether playback was bit-perfect when last reported to the UI.
+    last_bit_perfect: bool,
+    /// When the last visualizer frame was sent to the UI.
+    last_visualizer_frame: Instant,
 }
 
 impl PlaybackThread {
@@ -101,23 +150,15 @@ impl PlaybackThread {
         std::thread::Builder::new()
             .name("playback".to_string())
             .spawn(move || {
-                let queue_manager =
-                    QueueManager::new(queue, playback_settings.clone(), session, storage_tx);
-
-                let mut thread = PlaybackThread {
+                let mut thread = PlaybackThread::new(
+                    queue,
                     playback_settings,
+                    last_volume,
+                    session,
+                    storage_tx,
                     commands_rx,
                     events_tx,
-                    last_timestamp: u64::MAX,
-                    last_broadcast_timestamp: u64::MAX,
-                    position_broadcast_active: true,
-                    engine: AudioEngine::new(),
-                    queue: queue_manager,
-                    initial_volume: last_volume,
-                    rg_auto_hint: ReplayGainAutoHint::PreferTrack,
-                    last_track_gain: None,
-                    last_album_gain: None,
-                };
+                );
 
                 thread.run();
             })
@@ -126,12 +167,64 @@ impl PlaybackThread {
         PlaybackInterface::new(commands_tx, events_rx)
     }
 
+    fn new(
+        queue: Arc<RwLock<Vec<QueueItemData>>>,
+        playback_settings: PlaybackSettings,
+        last_volume: f64,
+        session: PlaybackSessionData,
+        storage_tx: watch::Sender<PlaybackSessionData>,
+        commands_rx: UnboundedReceiver<PlaybackCommand>,
+        events_tx: UnboundedSender<PlaybackEvent>,
+    ) -> Self {
+        let queue_manager =
+            QueueManager::new(queue, playback_settings.clone(), session, storage_tx);
+
+        PlaybackThread {
+            playback_settings,
+            commands_rx,
+            events_tx,
+            last_timestamp: u64::MAX,
+            last_broadcast_timestamp: u64::MAX,
+            position_broadcast_active: true,
+            engine: AudioEngine::new(),
+            queue: queue_manager,
+            initial_volume: last_volume,
+            volume: last_volume,
+            rg_auto_hint: ReplayGainAutoHint::PreferTrack,
+            last_track_gain: None,
+            last_album_gain: None,
+            library_track_gain: None,
+            library_album_gain: None,
+            duration_secs: None,
+            last_prepare_attempt: None,
+            sleep_timer: None,
+            sleep_fade_gain: 1.0,
+            last_sleep_timer_state: None,
+            loop_points: LoopPoints::default(),
+            last_session_position: None,
+            last_bit_perfect: false,
+            last_visualizer_frame: Instant::now(),
+        }
+    }
+
     /// Initialize engine and run the main loop.
     pub fn run(&mut self) {
         // Initialize the audio engine (media provider, device provider, initial stream)
-        if let Err(e) = self.engine.initialize() {
+        if let Err(e) = self.engine.initialize(&self.playback_settings.output) {
             error!("Failed to initialize audio engine: {:?}", e);
         }
+        self.apply_initial_state();
+
+        loop {
+            self.main_loop();
+        }
+    }
+
+    /// Apply the settings and volume the thread was started with, once the engine is initialized.
+    fn apply_initial_state(&mut self) {
+        self.check_output_device();
+
+        self.engine.update_settings(&self.playback_settings);
 
         self.set_volume(self.initial_volume);
         self.send_event(PlaybackEvent::RepeatChanged(self.queue.repeat_state()));
@@ -139,15 +232,13 @@ impl PlaybackThread {
             self.queue.is_shuffle_enabled(),
             self.queue.current_position().unwrap_or(0),
         ));
-
-        loop {
-            self.main_loop();
-        }
     }
 
     /// Start command intake and audio playback loop.
     pub fn main_loop(&mut self) {
         self.command_intake();
+        self.update_sleep_timer();
+        self.check_device_changes();
 
         if self.engine.state() == EngineState::Playing {
             self.play_audio();
@@ -161,6 +252,12 @@ impl PlaybackThread {
     /// Check for updated metadata and album art, and broadcast it to the UI.
     pub fn broadcast_events(&mut self) {
         self.process_metadata_update();
+
+        let bit_perfect = self.engine.is_bit_perfect();
+        if bit_perfect != self.last_bit_perfect {
+            self.last_bit_perfect = bit_perfect;
+            self.send_event(PlaybackEvent::BitPerfectChanged(bit_perfect));
+        }
     }
 
     /// Read incoming commands from the command channel, and process them.
@@ -171,8 +268,9 @@ impl PlaybackThread {
                 PlaybackCommand::Pause => self.pause(),
                 PlaybackCommand::TogglePlayPause => self.toggle_play_pause(),
                 PlaybackCommand::Open(path) => {
-                    if let Err(err) = self.open(&path) {
-                        error!(path = %path.display(), ?err, "Failed to open media: {err}");
+                    let source = TrackSource::from(path);
+                    if let Err(err) = self.open(&source) {
+                        error!(%source, ?err, "Failed to open media: {err}");
                     }
                 }
                 PlaybackCommand::Queue(v) => self.queue_item(&v),
@@ -204,6 +302,26 @@ impl PlaybackThread {
                 PlaybackCommand::ReplaceQueueWithIndex(v, idx) => {
                     self.replace_queue_with_index(v, idx)
                 }
+                PlaybackCommand::SetEqualizer(settings) => self.set_equalizer(settings),
+                PlaybackCommand::SetSpeed(speed) => self.set_speed(speed),
+                PlaybackCommand::SetLibraryReplayGain {
+                    source,
+                    track_gain,
+                    album_gain,
+                } => self.set_library_replaygain(&source, track_gain, album_gain),
+                PlaybackCommand::ResumeAt {
+                    source,
+                    position_ms,
+                } => self.resume_at(&source, position_ms),
+                PlaybackCommand::SetSleepTimer(timer) => self.set_sleep_timer(timer),
+                PlaybackCommand::SetLoopStart => self.set_loop_start(),
+                PlaybackCommand::SetLoopEnd => self.set_loop_end(),
+                PlaybackCommand::ClearLoop => self.clear_loop(),
+                PlaybackCommand::SetOutputDevice(output) => self.set_output_device(output),
+                PlaybackCommand::ListOutputDevices => self.list_output_devices(),
+                PlaybackCommand::SetVisualizerActive(active) => {
+                    self.engine.set_visualizer_active(active)
+                }
             }
         }
     }
@@ -225,6 +343,10 @@ impl PlaybackThread {
             }
 
             self.send_event(PlaybackEvent::StateChanged(PlaybackState::Paused));
+
+            if let Some(position) = self.engine.position_ms() {
+                self.persist_track_position(position, true);
+            }
         }
     }
 
@@ -250,10 +372,10 @@ impl PlaybackThread {
         if current_state == PlaybackState::Stopped
             && let Some((first, index)) = self.queue.first_with_index()
         {
-            let path = first.get_path().clone();
+            let source = first.source();
 
-            if let Err(err) = self.open(&path) {
-                error!(path = %path.display(), ?err, "Unable to open file: {err}");
+            if let Err(err) = self.open(&source) {
+                error!(%source, ?err, "Unable to open file: {err}");
             }
             self.queue.set_position(index);
             self.send_event(PlaybackEvent::QueuePositionChanged(index));
@@ -261,27 +383,124 @@ impl PlaybackThread {
     }
 
     /// Open a media file and prepare it for playback.
-    fn open(&mut self, path: &Path) -> Result<(), PlaybackStartError> {
-        info!("Opening track '{}'", path.display());
+    fn open(&mut self, source: &TrackSource) -> Result<(), PlaybackStartError> {
+        info!("Opening track '{}'", source);
+
+        let info = self.engine.open(source)?;
+
+        self.track_changed(source, info.duration_secs);
 
+        self.send_event(PlaybackEvent::StateChanged(PlaybackState::Playing));
+
+        Ok(())
+    }
+
+    /// Reset per-track state and announce a newly started track.
+    fn track_changed(&mut self, source: &TrackSource, duration_secs: Option<u64>) {
         self.last_track_gain = None;
         self.last_album_gain = None;
+        self.library_track_gain = None;
+        self.library_album_gain = None;
+        self.duration_secs = duration_secs;
+        self.last_prepare_attempt = None;
+        self.clear_loop();
 
-        let info = self.engine.open(path)?;
-
-        self.send_event(PlaybackEvent::SongChanged(path.to_owned()));
+        self.send_event(PlaybackEvent::SongChanged(source.clone()));
 
-        self.send_event(PlaybackEvent::DurationChanged(
-            info.duration_secs.unwrap_or(0),
-        ));
+        self.send_event(PlaybackEvent::DurationChanged(duration_secs.unwrap_or(0)));
 
         self.process_metadata_update();
 
         self.update_ts(true);
+    }
 
-        self.send_event(PlaybackEvent::StateChanged(PlaybackState::Playing));
+    /// Open the upcoming track in the engine once the current one is close to ending, so that
+    /// playback can continue into it without a gap (or crossfade into it, if enabled).
+    fn prepare_next(&mut self) {
+        let Some(duration_ms) = self.duration_secs.map(|secs| secs * 1000) else {
+            return;
+        };
 
-        Ok(())
+        if self.sleep_timer_stops_after_current() {
+            // nothing should play after this track, so there's nothing to splice or crossfade
+            if self.engine.prepared_source().is_some() {
+                self.engine.discard_prepared();
+            }
+            return;
+        }
+
+        let crossfade = &self.playback_settings.crossfade;
+        let window_ms = GAPLESS_PREPARE_WINDOW_MS
+            + crossfade
+                .fade_length(false)
+                .map_or(0, |length| length.as_millis() as u64);
+
+        if self.last_timestamp.saturating_add(window_ms) < duration_ms {
+            return;
+        }
+
+        let length = crossfade.fade_length(self.queue.next_shares_album());
+        self.engine.set_crossfade(length);
+
+        let next = self.queue.peek_next();
+
+        if next.as_ref() == self.engine.prepared_source() {
+            return;
+        }
+
+        let Some(next) = next else {
+            // The queue changed and nothing follows the current track anymore
+            self.engine.discard_prepared();
+            return;
+        };
+
+        if self.last_prepare_attempt.as_ref() == Some(&next) {
+            return;
+        }
+
+        debug!("Preparing '{}' for gapless playback", next);
+        self.engine.prepare_next(&next);
+        self.last_prepare_attempt = Some(next);
+    }
+
+    /// Advance the queue after the engine continued directly into the prepared track.
+    fn next_spliced(&mut self, duration_secs: Option<u64>) {
+        let Some(spliced) = self.engine.current_source().cloned() else {
+            return;
+        };
+
+        match self.queue.next(false) {
+            QueueNavigationResult::Changed {
+                index,
+                source,
+                reshuffled,
+            } => {
+                if reshuffled == Reshuffled::Reshuffled {
+                    self.send_event(PlaybackEvent::QueueUpdated);
+                }
+
+                if source == spliced {
+                    self.track_changed(&source, duration_secs);
+                } else if let Err(err) = self.open(&source) {
+                    // The queue was changed after the track was prepared
+                    error!(%source, ?err, "Unable to open file: {err}");
+                }
+
+                self.send_event(PlaybackEvent::QueuePositionChanged(index));
+            }
+            QueueNavigationResult::Unchanged { source } => {
+                info!("Repeating current track");
+                if source == spliced {
+                    self.track_changed(&source, duration_secs);
+                } else if let Err(err) = self.open(&source) {
+                    error!(%source, ?err, "Unable to open file: {err}");
+                }
+            }
+            QueueNavigationResult::EndOfQueue => {
+                info!("Playback queue ended, stopping playback");
+                self.stop();
+            }
+        }
     }
 
     fn process_metadata_update(&mut self) {
@@ -291,17 +510,38 @@ impl PlaybackThread {
 
             self.reapply_replaygain();
 
+            if let Err(e) = self.engine.set_media_name(metadata.metadata.name.clone()) {
+                warn!("Failed to set media name: {}", e);
+            }
+
             self.send_event(PlaybackEvent::MetadataUpdate(metadata.metadata));
             self.send_event(PlaybackEvent::AlbumArtUpdate(metadata.album_art));
         }
     }
 
+    /// Apply the ReplayGain values the library has for the current track, once the UI has looked
+    /// them up. Values for any other track arrived too late and are ignored.
+    fn set_library_replaygain(
+        &mut self,
+        source: &TrackSource,
+        track_gain: Option<f64>,
+        album_gain: Option<f64>,
+    ) {
+        if self.engine.current_source() != Some(source) {
+            return;
+        }
+
+        self.library_track_gain = track_gain;
+        self.library_album_gain = album_gain;
+        self.reapply_replaygain();
+    }
+
     fn reapply_replaygain(&mut self) {
         let gain = calculate_gain(
             &self.playback_settings.replaygain,
             self.rg_auto_hint,
-            self.last_track_gain,
-            self.last_album_gain,
+            self.last_track_gain.or(self.library_track_gain),
+            self.last_album_gain.or(self.library_album_gain),
         );
         if let Err(e) = self.engine.set_replaygain(gain) {
             warn!("Failed to set ReplayGain: {:?}", e);
@@ -334,7 +574,7 @@ impl PlaybackThread {
         match self.queue.next(user_initiated) {
             QueueNavigationResult::Changed {
                 index,
-                path,
+                source,
                 reshuffled,
             } => {
                 info!("Opening next file in queue at index {}", index);
@@ -343,16 +583,16 @@ impl PlaybackThread {
                     self.send_event(PlaybackEvent::QueueUpdated);
                 }
 
-                if let Err(err) = self.open(&path) {
-                    error!(path = %path.display(), ?err, "Unable to open file: {err}");
+                if let Err(err) = self.open(&source) {
+                    error!(%source, ?err, "Unable to open file: {err}");
                 }
 
                 self.send_event(PlaybackEvent::QueuePositionChanged(index));
             }
-            QueueNavigationResult::Unchanged { path } => {
+            QueueNavigationResult::Unchanged { source } => {
                 info!("Repeating current track");
-                if let Err(err) = self.open(&path) {
-                    error!(path = %path.display(), ?err, "Unable to open file: {err}");
+                if let Err(err) = self.open(&source) {
+                    error!(%source, ?err, "Unable to open file: {err}");
                 }
             }
             QueueNavigationResult::EndOfQueue => {
@@ -376,10 +616,10 @@ impl PlaybackThread {
         // Handle stopped state - start playing from the last track
         if self.state() == PlaybackState::Stopped {
             if let Some((last, _)) = self.queue.last_with_index() {
-                let path = last.get_path().clone();
+                let source = last.source();
 
-                if let Err(err) = self.open(&path) {
-                    error!(path = %path.display(), ?err, "Unable to open file: {err}");
+                if let Err(err) = self.open(&source) {
+                    error!(%source, ?err, "Unable to open file: {err}");
                 }
                 let last_index = self.queue.len().saturating_sub(1);
                 self.queue.set_position(last_index);
@@ -391,21 +631,21 @@ impl PlaybackThread {
         match self.queue.previous() {
             QueueNavigationResult::Changed {
                 index,
-                path,
+                source,
                 reshuffled: _,
             } => {
                 info!("Opening previous file in queue at index {}", index);
 
-                if let Err(err) = self.open(&path) {
-                    error!(path = %path.display(), ?err, "Unable to open file: {err}");
+                if let Err(err) = self.open(&source) {
+                    error!(%source, ?err, "Unable to open file: {err}");
                 }
 
                 self.send_event(PlaybackEvent::QueuePositionChanged(index));
             }
-            QueueNavigationResult::Unchanged { path } => {
+            QueueNavigationResult::Unchanged { source } => {
                 info!("At beginning of queue, replaying current track");
-                if let Err(err) = self.open(&path) {
-                    error!(path = %path.display(), ?err, "Unable to open file: {err}");
+                if let Err(err) = self.open(&source) {
+                    error!(%source, ?err, "Unable to open file: {err}");
                 }
             }
             QueueNavigationResult::EndOfQueue => {
@@ -427,10 +667,10 @@ impl PlaybackThread {
                 return;
             }
 
-            let path = item.get_path();
+            let source = item.source();
 
-            if let Err(err) = self.open(path) {
-                error!(path = %path.display(), ?err, "Unable to open file: {err}");
+            if let Err(err) = self.open(&source) {
+                error!(%source, ?err, "Unable to open file: {err}");
             }
             self.queue.set_position(index);
             self.send_event(PlaybackEvent::QueuePositionChanged(index));
@@ -460,10 +700,10 @@ impl PlaybackThread {
         if self.state() == PlaybackState::Stopped
             && let Some((relative_idx, first)) = first
         {
-            let path = first.get_path();
+            let source = first.source();
 
-            if let Err(err) = self.open(path) {
-                error!(path = %path.display(), ?err, "Unable to open file: {err}");
+            if let Err(err) = self.open(&source) {
+                error!(%source, ?err, "Unable to open file: {err}");
             }
             let position = first_index + relative_idx;
             self.queue.set_position(position);
@@ -510,17 +750,17 @@ impl PlaybackThread {
         match self.queue.undo_last_action() {
             UndoResult::Ok {
                 current_idx,
-                current_path,
+                current_source,
                 shuffle,
             } => {
                 self.refresh_rg_auto_hint();
 
                 if previous_state != PlaybackState::Stopped {
-                    let should_reopen = self.engine.current_path() != Some(current_path.as_path());
+                    let should_reopen = self.engine.current_source() != Some(&current_source);
 
                     if should_reopen {
-                        if let Err(err) = self.open(&current_path) {
-                            error!(path = %current_path.display(), ?err, "Unable to open file: {err}");
+                        if let Err(err) = self.open(&current_source) {
+                            error!(source = %current_source, ?err, "Unable to open file: {err}");
                         }
 
                         if previous_state == PlaybackState::Paused {
@@ -574,14 +814,14 @@ impl PlaybackThread {
                     self.send_event(PlaybackEvent::QueuePositionChanged(new_position));
                 }
             }
-            DequeueResult::RemovedCurrent { new_path } => {
+            DequeueResult::RemovedCurrent { new_source } => {
                 self.refresh_rg_auto_hint();
                 self.send_event(PlaybackEvent::QueueUpdated);
 
                 // Play the next track if there is one
-                if let Some(path) = new_path {
-                    if let Err(err) = self.open(&path) {
-                        error!(path = %path.display(), ?err, "Unable to open file: {err}");
+                if let Some(source) = new_source {
+                    if let Err(err) = self.open(&source) {
+                        error!(%source, ?err, "Unable to open file: {err}");
                     }
                     if let Some(pos) = self.queue.current_position() {
                         self.send_event(PlaybackEvent::QueuePositionChanged(pos));
@@ -606,13 +846,13 @@ impl PlaybackThread {
                     self.send_event(PlaybackEvent::QueuePositionChanged(new_position));
                 }
             }
-            DequeueManyResult::RemovedCurrent { new_path } => {
+            DequeueManyResult::RemovedCurrent { new_source } => {
                 self.refresh_rg_auto_hint();
                 self.send_event(PlaybackEvent::QueueUpdated);
 
-                if let Some(path) = new_path {
-                    if let Err(err) = self.open(&path) {
-                        error!(path = %path.display(), ?err, "Unable to open file: {err}");
+                if let Some(source) = new_source {
+                    if let Err(err) = self.open(&source) {
+                        error!(%source, ?err, "Unable to open file: {err}");
                     }
                     if let Some(pos) = self.queue.current_position() {
                         self.send_event(PlaybackEvent::QueuePositionChanged(pos));
@@ -640,10 +880,10 @@ impl PlaybackThread {
                         return;
                     }
 
-                    let path = item.get_path();
+                    let source = item.source();
 
-                    if let Err(err) = self.open(path) {
-                        error!(path = %path.display(), ?err, "Unable to open file: {err}");
+                    if let Err(err) = self.open(&source) {
+                        error!(%source, ?err, "Unable to open file: {err}");
                     }
                     self.queue.set_position(first_index);
                     self.send_event(PlaybackEvent::QueuePositionChanged(first_index));
@@ -663,10 +903,10 @@ impl PlaybackThread {
                         return;
                     }
 
-                    let path = item.get_path();
+                    let source = item.source();
 
-                    if let Err(err) = self.open(path) {
-                        error!(path = %path.display(), ?err, "Unable to open file: {err}");
+                    if let Err(err) = self.open(&source) {
+                        error!(%source, ?err, "Unable to open file: {err}");
                     }
                     self.queue.set_position(first_index);
                     self.send_event(PlaybackEvent::QueuePositionChanged(first_index));
@@ -704,10 +944,10 @@ impl PlaybackThread {
                 if self.state() == PlaybackState::Stopped
                     && let Some((relative_idx, first)) = first
                 {
-                    let path = first.get_path();
+                    let source = first.source();
 
-                    if let Err(err) = self.open(path) {
-                        error!(path = %path.display(), ?err, "Unable to open file: {err}");
+                    if let Err(err) = self.open(&source) {
+                        error!(%source, ?err, "Unable to open file: {err}");
                     }
                     let position = first_index + relative_idx;
                     self.queue.set_position(position);
@@ -725,10 +965,10 @@ impl PlaybackThread {
                 if self.state() == PlaybackState::Stopped
                     && let Some((relative_idx, first)) = first
                 {
-                    let path = first.get_path();
+                    let source = first.source();
 
-                    if let Err(err) = self.open(path) {
-                        error!(path = %path.display(), ?err, "Unable to open file: {err}");
+                    if let Err(err) = self.open(&source) {
+                        error!(%source, ?err, "Unable to open file: {err}");
                     }
                     let position = first_index + relative_idx;
                     self.queue.set_position(position);
@@ -745,6 +985,7 @@ impl PlaybackThread {
     fn update_ts(&mut self, force: bool) {
         if let Some(timestamp) = self.engine.position_ms() {
             self.last_timestamp = timestamp;
+            self.persist_track_position(timestamp, force);
 
             if timestamp == self.last_broadcast_timestamp {
                 return;
@@ -767,6 +1008,36 @@ impl PlaybackThread {
         }
     }
 
+    /// Save the position inside the current track to the playback session. Unless `force` is set,
+    /// this is throttled to avoid rewriting the session file on every position update.
+    fn persist_track_position(&mut self, position_ms: u64, force: bool) {
+        if !force
+            && self
+                .last_session_position
+                .is_some_and(|last| last.abs_diff(position_ms) < SESSION_POSITION_INTERVAL_MS)
+        {
+            return;
+        }
+
+        self.last_session_position = Some(position_ms);
+        self.queue.persist_track_position(Some(position_ms));
+    }
+
+    /// Continue the current track from its resume point, if it has only just started playing.
+    fn resume_at(&mut self, source: &TrackSource, position_ms: u64) {
+        if self.engine.current_source() != Some(source)
+            || self
+                .engine
+                .position_ms()
+                .is_none_or(|position| position >= RESUME_WINDOW_MS)
+        {
+            return;
+        }
+
+        info!("Resuming '{}' at {}ms", source, position_ms);
+        self.seek(position_ms as f64 / 1000.0);
+    }
+
     /// Seek to the specified timestamp (in seconds).
     fn seek(&mut self, timestamp: f64) {
         if let Err(e) = self.engine.seek(timestamp) {
@@ -779,9 +1050,9 @@ impl PlaybackThread {
     /// Jump to the specified index in the queue.
     fn jump(&mut self, index: usize) {
         match self.queue.jump(index) {
-            JumpResult::Jumped { path } => {
-                if let Err(err) = self.open(&path) {
-                    error!(path = %path.display(), ?err, "Unable to open file: {err}");
+            JumpResult::Jumped { source } => {
+                if let Err(err) = self.open(&source) {
+                    error!(%source, ?err, "Unable to open file: {err}");
                 }
                 self.send_event(PlaybackEvent::QueuePositionChanged(index));
             }
@@ -795,9 +1066,9 @@ impl PlaybackThread {
     /// original queue item at the specified index will be played, rather than the shuffled item.
     fn jump_unshuffled(&mut self, index: usize) {
         match self.queue.jump_unshuffled(index) {
-            JumpResult::Jumped { path } => {
-                if let Err(err) = self.open(&path) {
-                    error!(path = %path.display(), ?err, "Unable to open file: {err}");
+            JumpResult::Jumped { source } => {
+                if let Err(err) = self.open(&source) {
+                    error!(%source, ?err, "Unable to open file: {err}");
                 }
                 // Get the actual position in the (possibly shuffled) queue
                 if let Some(pos) = self.queue.current_position() {
@@ -814,6 +1085,8 @@ impl PlaybackThread {
     fn replace_queue(&mut self, paths: Vec<QueueItemData>) {
         debug!("Replacing queue with: '{}'", paths.iter().format(":"));
 
+        self.cancel_sleep_timer();
+
         match self.queue.replace_queue(paths) {
             ReplaceResult::Replaced { first_item } => {
                 self.refresh_rg_auto_hint();
@@ -833,6 +1106,8 @@ impl PlaybackThread {
     }
 
     fn replace_queue_with_index(&mut self, paths: Vec<QueueItemData>, idx: usize) {
+        self.cancel_sleep_timer();
+
         match self.queue.replace_queue(paths) {
             ReplaceResult::Replaced { .. } => {
                 self.refresh_rg_auto_hint();
@@ -869,6 +1144,13 @@ impl PlaybackThread {
         self.engine.stop();
         self.last_track_gain = None;
         self.last_album_gain = None;
+        self.library_track_gain = None;
+        self.library_album_gain = None;
+        self.duration_secs = None;
+        self.last_prepare_attempt = None;
+        self.clear_loop();
+        self.last_session_position = None;
+        self.queue.persist_track_position(None);
 
         self.send_event(PlaybackEvent::StateChanged(PlaybackState::Stopped));
     }
@@ -895,11 +1177,219 @@ impl PlaybackThread {
 
     /// Sets the volume of the playback stream.
     fn set_volume(&mut self, volume: f64) {
-        if let Err(e) = self.engine.set_volume(volume) {
+        self.volume = volume;
+        self.apply_volume();
+
+        self.send_event(PlaybackEvent::VolumeChanged(volume));
+    }
+
+    /// Pass the volume to the engine, including any sleep timer fade.
+    fn apply_volume(&mut self) {
+        if let Err(e) = self.engine.set_volume(self.volume * self.sleep_fade_gain) {
             warn!("Failed to set volume: {:?}", e);
         }
+    }
 
-        self.send_event(PlaybackEvent::VolumeChanged(volume));
+    /// Start (or with `None`, cancel) the sleep timer.
+    fn set_sleep_timer(&mut self, timer: Option<SleepTimer>) {
+        match timer {
+            Some(timer) => {
+                info!("Starting sleep timer: {:?}", timer);
+                self.sleep_timer = Some(ActiveSleepTimer::new(timer, Instant::now()));
+            }
+            None => {
+                if self.sleep_timer.is_some() {
+                    info!("Sleep timer cancelled");
+                }
+                self.sleep_timer = None;
+            }
+        }
+
+        self.update_sleep_timer();
+    }
+
+    fn cancel_sleep_timer(&mut self) {
+        if self.sleep_timer.is_some() {
+            self.set_sleep_timer(None);
+        }
+    }
+
+    /// Whether the sleep timer should stop playback once the current track ends.
+    fn sleep_timer_stops_after_current(&self) -> bool {
+        self.sleep_timer
+            .as_ref()
+            .is_some_and(|timer| timer.stops_after_current(self.queue.next_shares_album()))
+    }
+
+    /// Stop playback because the sleep timer has fired. The volume is restored afterwards, so
+    /// that the next track doesn't start out silent.
+    fn fire_sleep_timer(&mut self) {
+        self.sleep_timer = None;
+        self.stop();
+        self.update_sleep_timer();
+    }
+
+    /// Fire the sleep timer if it has run out, update the fade-out, and let the UI know about the
+    /// remaining time. The fade steps the device volume, which ramps between the steps with its
+    /// own [`GainRamp`](crate::devices::util::GainRamp) so that they aren't audible.
+    fn update_sleep_timer(&mut self) {
+        let now = Instant::now();
+
+        if self
+            .sleep_timer
+            .as_ref()
+            .is_some_and(|timer| timer.is_due(now))
+        {
+            info!("Sleep timer expired, stopping playback");
+            self.fire_sleep_timer();
+            return;
+        }
+
+        let fade_gain = self
+            .sleep_timer
+            .as_ref()
+            .map_or(1.0, |timer| timer.fade_gain(now));
+        if fade_gain != self.sleep_fade_gain {
+            self.sleep_fade_gain = fade_gain;
+            self.apply_volume();
+        }
+
+        let state = self.sleep_timer.as_ref().map(|timer| timer.state(now));
+        if state != self.last_sleep_timer_state {
+            self.last_sleep_timer_state = state;
+            self.send_event(PlaybackEvent::SleepTimerChanged(state));
+        }
+    }
+
+    /// Marks the position being heard as the start of the A/B loop.
+    fn set_loop_start(&mut self) {
+        let Some(position) = self.engine.audible_position_ms() else {
+            return;
+        };
+
+        self.loop_points.set_start(position);
+        self.loop_changed();
+    }
+
+    /// Marks the position being heard as the end of the A/B loop.
+    fn set_loop_end(&mut self) {
+        let Some(position) = self.engine.audible_position_ms() else {
+            return;
+        };
+
+        if !self.loop_points.set_end(position) {
+            warn!("Loop end must come after the loop start, ignoring");
+            return;
+        }
+
+        self.loop_changed();
+    }
+
+    /// Forgets both loop points.
+    fn clear_loop(&mut self) {
+        if self.loop_points.is_empty() {
+            return;
+        }
+
+        self.loop_points = LoopPoints::default();
+        self.loop_changed();
+    }
+
+    fn loop_changed(&mut self) {
+        self.engine.set_loop(self.loop_points.range());
+
+        self.send_event(PlaybackEvent::LoopChanged(self.loop_points.state()));
+    }
+
+    /// Switch to the specified output device.
+    fn set_output_device(&mut self, output: OutputDeviceSettings) {
+        info!(
+            "Switching output device to {}",
+            output.display_name().unwrap_or("the default device")
+        );
+
+        if let Err(e) = self.engine.set_output_device(&output) {
+            error!("Failed to switch output device: {}", e);
+        }
+
+        self.playback_settings.output = output;
+        self.check_output_device();
+    }
+
+    /// Let the UI know if the selected output device couldn't be found.
+    fn check_output_device(&mut self) {
+        if self.engine.output_device_missing() {
+            let output = &self.playback_settings.output;
+            let name = output.display_name().unwrap_or_default().to_string();
+
+            self.send_event(PlaybackEvent::OutputDeviceUnavailable(name));
+        }
+    }
+
+    /// Follow output devices being connected and disconnected, and default device changes.
+    fn check_device_changes(&mut self) {
+        let Some(action) = self.engine.poll_device_changes() else {
+            return;
+        };
+
+        match action {
+            DeviceChangeAction::Stay => {}
+            DeviceChangeAction::Move => {
+                info!("Output devices changed, moving playback to the new device");
+                self.reopen_output();
+            }
+            DeviceChangeAction::Lost => self.output_device_lost(),
+        }
+
+        self.list_output_devices();
+    }
+
+    /// Move playback to the default device after the current device was disconnected, pausing
+    /// first if the user asked for that.
+    fn output_device_lost(&mut self) {
+        info!("Output device was disconnected");
+
+        if self.playback_settings.output.pause_on_disconnect {
+            self.pause();
+        }
+
+        self.reopen_output();
+    }
+
+    /// Reopen the stream on the device that should currently be played to.
+    fn reopen_output(&mut self) {
+        let was_missing = self.engine.output_device_missing();
+
+        if let Err(e) = self.engine.reopen_output() {
+            error!("Failed to reopen output device: {}", e);
+            return;
+        }
+
+        if was_missing && !self.engine.output_device_missing() {
+            info!("Selected output device was reconnected");
+            self.send_event(PlaybackEvent::OutputDeviceReconnected);
+        }
+    }
+
+    /// List the output devices of the current device provider.
+    fn list_output_devices(&mut self) {
+        let devices = match self.engine.list_output_devices() {
+            Ok(devices) => devices,
+            Err(e) => {
+                warn!("Failed to list output devices: {}", e);
+                Vec::new()
+            }
+        };
+
+        self.send_event(PlaybackEvent::OutputDevicesListed(devices));
+    }
+
+    /// Sets the playback speed.
+    fn set_speed(&mut self, speed: f64) {
+        let speed = speed.clamp(MIN_SPEED, MAX_SPEED);
+        self.engine.set_speed(speed);
+
+        self.send_event(PlaybackEvent::SpeedChanged(speed));
     }
 
     /// Sets the repeat mode.
@@ -927,11 +1417,30 @@ impl PlaybackThread {
         self.reapply_replaygain();
     }
 
+    /// Applies a new equalizer configuration.
+    fn set_equalizer(&mut self, settings: EqualizerSettings) {
+        self.engine.set_equalizer(settings.clone());
+        self.playback_settings.equalizer = settings;
+    }
+
     fn set_position_broadcast_active(&mut self, active: bool) {
         self.position_broadcast_active = active;
         self.update_ts(true);
     }
 
+    /// Emit a [`VisualizerUpdated`] event if the visualizer is active and the last one was sent
+    /// long enough ago.
+    fn update_visualizer(&mut self) {
+        if self.last_visualizer_frame.elapsed() < VISUALIZER_INTERVAL {
+            return;
+        }
+
+        if let Some(frame) = self.engine.visualizer_frame() {
+            self.last_visualizer_frame = Instant::now();
+            self.send_event(PlaybackEvent::VisualizerUpdated(Box::new(frame)));
+        }
+    }
+
     /// Process audio samples through the engine and send to device.
     ///
     /// This is called in the main loop when the engine is playing.
@@ -939,15 +1448,27 @@ impl PlaybackThread {
         match self.engine.process_cycle() {
             EngineCycleResult::Continue => {
                 self.update_ts(false);
+                self.update_visualizer();
+                self.prepare_next();
+            }
+            EngineCycleResult::TrackSpliced(info) => {
+                info!("EOF, continuing into the next song");
+                self.next_spliced(info.duration_secs);
             }
             EngineCycleResult::Eof => {
-                info!("EOF, moving to next song");
-                self.next(false);
+                if self.sleep_timer_stops_after_current() {
+                    info!("EOF, stopping playback for sleep timer");
+                    self.fire_sleep_timer();
+                } else {
+                    info!("EOF, moving to next song");
+                    self.next(false);
+                }
             }
             EngineCycleResult::FatalError(msg) => {
                 error!("Fatal error in audio engine: {}, moving to next song", msg);
                 self.next(false);
             }
+            EngineCycleResult::DeviceLost => self.output_device_lost(),
             EngineCycleResult::NothingToDo => {
                 // Nothing to process
             }
@@ -958,3 +1479,130 @@ impl PlaybackThread {
         self.events_tx.send(event).expect("unable to send event");
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use std::{
+        f64::consts::TAU,
+        path::Path,
+        sync::{Arc, RwLock},
+    };
+
+    use serde_json::json;
+    use tokio::sync::{mpsc::unbounded_channel, watch};
+
+    use super::{PlaybackState, PlaybackThread};
+    use crate::{
+        devices::{
+            builtin::file::{FileProvider, FileSinkConfig},
+            format::SampleFormat,
+            resample::{SampleFrom, SampleInto},
+        },
+        playback::{queue::QueueItemData, session_storage::PlaybackSessionData},
+        settings::playback::PlaybackSettings,
+        test_support::TestDir,
+    };
+
+    const RATE: u32 = 44100;
+
+    /// Writes a 16 bit stereo WAV file with a different tone in each channel, returning its
+    /// interleaved samples.
+    fn write_track(path: &Path, frames: usize, freq: f64) -> Vec<i16> {
+        let spec = hound::WavSpec {
+            channels: 2,
+            sample_rate: RATE,
+            bits_per_sample: 16,
+            sample_format: hound::SampleFormat::Int,
+        };
+        let mut writer = hound::WavWriter::create(path, spec).unwrap();
+
+        let samples: Vec<i16> = (0..frames)
+            .flat_map(|i| {
+                let phase = i as f64 / f64::from(RATE) * freq * TAU;
+                [phase.sin() * 0.5, (phase * 1.5).sin() * 0.25]
+            })
+            .map(i16::sample_from)
+            .collect();
+        for &sample in &samples {
+            writer.write_sample(sample).unwrap();
+        }
+        writer.finalize().unwrap();
+
+        samples
+    }
+
+    fn item(id: i64, path: &Path) -> QueueItemData {
+        serde_json::from_value(json!({
+            "db_id": id,
+            "db_album_id": 1,
+            "path": path,
+        }))
+        .expect("valid queue item")
+    }
+
+    /// Plays the queue on a playback thread writing to `config`, until the queue has ended.
+    fn render(items: Vec<QueueItemData>, config: FileSinkConfig) {
+        let (_commands_tx, commands_rx) = unbounded_channel();
+        let (events_tx, _events_rx) = unbounded_channel();
+        let (storage_tx, _storage_rx) = watch::channel(PlaybackSessionData::default());
+
+        let mut thread = PlaybackThread::new(
+            Arc::new(RwLock::new(Vec::new())),
+            PlaybackSettings::default(),
+            1.0,
+            PlaybackSessionData::default(),
+            storage_tx,
+            commands_rx,
+            events_tx,
+        );
+        thread
+            .engine
+            .initialize_with_provider("file", Box::new(FileProvider::with_config(Some(config))))
+            .unwrap();
+        thread.apply_initial_state();
+
+        thread.replace_queue(items);
+        for _ in 0..10_000 {
+            if thread.state() == PlaybackState::Stopped {
+                break;
+            }
+            thread.main_loop();
+        }
+        assert_eq!(thread.state(), PlaybackState::Stopped);
+
+        // the file is completed when the provider is dropped along with the thread
+    }
+
+    #[test]
+    fn renders_queue_to_file() {
+        let dir = TestDir::new("hummingbird-render");
+        let first = dir.join("first.wav");
+        let second = dir.join("second.wav");
+        let out = dir.join("out.wav");
+
+        let mut expected = write_track(&first, 20_000, 440.0);
+        expected.extend(write_track(&second, 15_000, 330.0));
+
+        render(
+            vec![item(1, &first), item(2, &second)],
+            FileSinkConfig::new(&out, RATE, SampleFormat::Float32),
+        );
+
+        let mut reader = hound::WavReader::open(&out).unwrap();
+        assert_eq!(reader.spec().channels, 2);
+        assert_eq!(reader.spec().sample_rate, RATE);
+
+        // at the track's own rate and full volume, every sample reaches the device unchanged and
+        // the tracks follow each other without a gap
+        let rendered: Vec<f32> = reader.samples().map(Result::unwrap).collect();
+        let expected: Vec<f32> = expected
+            .into_iter()
+            .map(|sample| f32::sample_from(SampleInto::<f64>::sample_into(sample)))
+            .collect();
+        assert_eq!(rendered.len(), expected.len());
+        assert!(
+            rendered == expected,
+            "rendered audio differs from the source"
+        );
+    }
+}
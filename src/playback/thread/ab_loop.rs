@@ -0,0 +1,161 @@
+use crate::{
+    media::{
+        errors::PlaybackReadError,
+        pipeline::{ChannelProducers, DEFAULT_BUFFER_FRAMES, DecodeResult, PacketTrimmer},
+        traits::F32DecodeResult,
+    },
+    playback::events::AbLoop,
+};
+
+use super::media_controller::MediaController;
+
+/// The A/B loop points set for the current track.
+#[derive(Debug, Default, Clone, Copy, PartialEq, Eq)]
+pub struct LoopPoints {
+    start_ms: Option<u64>,
+    end_ms: Option<u64>,
+}
+
+impl LoopPoints {
+    /// Mark point A. Point B is dropped if it would no longer come after A.
+    pub fn set_start(&mut self, position_ms: u64) {
+        self.start_ms = Some(position_ms);
+
+        if self.end_ms.is_some_and(|end| end <= position_ms) {
+            self.end_ms = None;
+        }
+    }
+
+    /// Mark point B. Returns `false`, leaving the points untouched, if B would not come after A.
+    pub fn set_end(&mut self, position_ms: u64) -> bool {
+        if self.start_ms.is_some_and(|start| position_ms <= start) {
+            return false;
+        }
+
+        self.end_ms = Some(position_ms);
+        true
+    }
+
+    /// The section to loop, in milliseconds. A loop without point A starts at the beginning of
+    /// the track.
+    pub fn range(&self) -> Option<(u64, u64)> {
+        self.end_ms
+            .map(|end| (self.start_ms.unwrap_or(0), end))
+            .filter(|(start, end)| start < end)
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.start_ms.is_none() && self.end_ms.is_none()
+    }
+
+    pub fn state(&self) -> AbLoop {
+        AbLoop {
+            start_ms: self.start_ms,
+            end_ms: self.end_ms,
+        }
+    }
+}
+
+/// Cuts the packet that crosses the end of an A/B loop, so that nothing after point B is heard
+/// before playback returns to point A.
+#[derive(Default)]
+pub struct LoopTrimmer {
+    trimmer: Option<PacketTrimmer<f64>>,
+    trimmer_f32: Option<PacketTrimmer<f32>>,
+    /// Whether the end of the loop has been decoded since the last reset.
+    reached_end: bool,
+}
+
+impl LoopTrimmer {
+    /// Decode the next packet into `output`, leaving out everything from `end_ms` on.
+    pub fn decode_into(
+        &mut self,
+        media: &mut MediaController,
+        output: &ChannelProducers<f64>,
+        end_ms: u64,
+    ) -> Result<DecodeResult, PlaybackReadError> {
+        let trimmer = self.trimmer.get_or_insert_with(|| {
+            PacketTrimmer::new(output.channel_count(), DEFAULT_BUFFER_FRAMES)
+        });
+        let decoded = media.decode_into(trimmer.input())?;
+        let packet_start_ms = media.position_ms().unwrap_or(0);
+
+        let (result, reached_end) = trimmer.write_before(output, decoded, packet_start_ms, end_ms);
+        self.reached_end |= reached_end;
+        Ok(result)
+    }
+
+    /// Decode the next packet into `output` as f32, leaving out everything from `end_ms` on.
+    pub fn decode_into_f32(
+        &mut self,
+        media: &mut MediaController,
+        output: &ChannelProducers<f32>,
+        end_ms: u64,
+    ) -> Result<F32DecodeResult, PlaybackReadError> {
+        let trimmer = self.trimmer_f32.get_or_insert_with(|| {
+            PacketTrimmer::new(output.channel_count(), DEFAULT_BUFFER_FRAMES)
+        });
+        let F32DecodeResult::Decoded(decoded) = media.decode_into_f32(trimmer.input())? else {
+            return Ok(F32DecodeResult::NotF32);
+        };
+        let packet_start_ms = media.position_ms().unwrap_or(0);
+
+        let (result, reached_end) = trimmer.write_before(output, decoded, packet_start_ms, end_ms);
+        self.reached_end |= reached_end;
+        Ok(F32DecodeResult::Decoded(result))
+    }
+
+    /// Whether the end of the loop has been decoded, so the stream should return to the start.
+    pub fn reached_end(&self) -> bool {
+        self.reached_end
+    }
+
+    /// Forget where the previous packets were. Must be called after the stream seeks.
+    pub fn reset(&mut self) {
+        self.reached_end = false;
+        if let Some(trimmer) = &mut self.trimmer {
+            trimmer.reset();
+        }
+        if let Some(trimmer) = &mut self.trimmer_f32 {
+            trimmer.reset();
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn loop_requires_end_after_start() {
+        let mut points = LoopPoints::default();
+        assert!(points.is_empty());
+        assert_eq!(points.range(), None);
+
+        points.set_start(10_000);
+        assert_eq!(points.range(), None);
+
+        assert!(!points.set_end(5_000));
+        assert!(points.set_end(15_000));
+        assert_eq!(points.range(), Some((10_000, 15_000)));
+
+        // moving A past B drops B
+        points.set_start(20_000);
+        assert_eq!(points.range(), None);
+        assert_eq!(
+            points.state(),
+            AbLoop {
+                start_ms: Some(20_000),
+                end_ms: None
+            }
+        );
+    }
+
+    #[test]
+    fn loop_without_start_begins_at_track_start() {
+        let mut points = LoopPoints::default();
+
+        assert!(points.set_end(8_000));
+        assert_eq!(points.range(), Some((0, 8_000)));
+    }
+}
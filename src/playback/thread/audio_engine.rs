@@ -1,23 +1,43 @@
-use std::path::Path;
+use std::time::Duration;
 
 use tracing::{error, info, trace_span, warn};
 
 use crate::{
     devices::{
+        dither::Dither,
+        dsp::{chain::DspChain, equalizer::Equalizer},
+        errors::SubmissionError,
         format::{ChannelSpec, FormatInfo, SampleFormat},
+        mix::ChannelMixer,
         resample::Resampler,
+        stretch::{MAX_SPEED, MIN_SPEED, TimeStretcher},
+        visualizer::{Visualizer, VisualizerFrame},
     },
     media::{
         errors::{PlaybackStartError, SeekError},
-        pipeline::{AudioPipeline, DEFAULT_BUFFER_FRAMES, DecodeResult},
+        pipeline::{
+            AudioPipeline, ChannelConsumers, ChannelProducers, ConvertPipeline,
+            DEFAULT_BUFFER_FRAMES, DecodeResult,
+        },
         traits::F32DecodeResult,
     },
-    playback::thread::media_controller::CompleteMetadata,
-    settings::playback::PlaybackSettings,
+    playback::{
+        events::OutputDevice, queue::TrackSource, thread::media_controller::CompleteMetadata,
+    },
+    settings::{
+        channels::{ChannelMode, ChannelSettings},
+        dsp::DspSettings,
+        equalizer::EqualizerSettings,
+        output::OutputDeviceSettings,
+        playback::PlaybackSettings,
+        resampler::ResamplerProfile,
+    },
 };
 
-use super::device_controller::DeviceController;
-use super::media_controller::MediaController;
+use super::ab_loop::LoopTrimmer;
+use super::crossfade::Crossfade;
+use super::device_controller::{DeviceChangeAction, DeviceController, DeviceError};
+use super::media_controller::{MediaController, MediaInfo};
 
 #[derive(Debug, Clone, Copy, PartialEq, Eq)]
 #[allow(dead_code)]
@@ -34,13 +54,17 @@ pub enum EngineState {
 pub enum EngineCycleResult {
     Continue,
     Eof,
+    /// The current track ended and playback continued directly into the prepared track.
+    TrackSpliced(OpenInfo),
     /// A fatal decode error occurred - should skip to next track.
     FatalError(String),
+    /// The output device stopped working, most likely because it was disconnected.
+    DeviceLost,
     /// Nothing to do - not in playing state or no stream available.
     NothingToDo,
 }
 
-#[derive(Debug, Clone)]
+#[derive(Debug, Clone, PartialEq, Eq)]
 #[allow(dead_code)]
 pub struct OpenInfo {
     pub duration_secs: Option<u64>,
@@ -76,10 +100,48 @@ pub struct AudioEngine {
     media: MediaController,
     device: DeviceController,
     pipeline: Option<AudioPipeline>,
+    /// Audio that was left in the passthrough pipeline when processing was enabled. It is fed
+    /// into the conversion pipeline before decoding continues.
+    passthrough_backlog: Option<ChannelConsumers<f32>>,
     resampler: Option<Resampler>,
     state: EngineState,
     /// Whether a stream reset is pending (e.g., after seek).
     pending_reset: bool,
+    /// The crossfade currently in progress, if any.
+    crossfade: Option<Crossfade>,
+    /// How long to crossfade into the prepared track. `None` plays it gaplessly instead.
+    crossfade_length: Option<Duration>,
+    equalizer: Option<Equalizer>,
+    equalizer_settings: EqualizerSettings,
+    /// Effects run after the equalizer.
+    dsp_chain: DspChain,
+    dsp_settings: DspSettings,
+    /// The ReplayGain multiplier last passed to the device, which effects may need to know about.
+    replaygain: f64,
+    /// Playback speed, where 1.0 is the original speed.
+    speed: f64,
+    /// Whether speed changes should keep the original pitch.
+    preserve_pitch: bool,
+    /// Time stretcher, created once the speed is changed with pitch preservation enabled.
+    stretcher: Option<TimeStretcher>,
+    /// The section of the current track to loop, in milliseconds.
+    loop_range: Option<(u64, u64)>,
+    /// Cuts the packet that crosses the end of the loop.
+    loop_trimmer: LoopTrimmer,
+    /// Whether tracks should be played at their own sample format and rate when the device
+    /// supports it, without volume or ReplayGain.
+    bit_perfect: bool,
+    channel_settings: ChannelSettings,
+    /// Mixes the track's channels to the device's, if they differ.
+    mixer: Option<ChannelMixer>,
+    resampler_profile: ResamplerProfile,
+    noise_shaping: bool,
+    /// Dithers audio before the device quantizes it, if the device is fed 16 or 24 bit integers.
+    dither: Option<Dither>,
+    /// Whether any view is showing visualizer data.
+    visualizer_active: bool,
+    /// Measures the audio sent to the device, while the visualizer is active.
+    visualizer: Option<Visualizer>,
 }
 
 impl AudioEngine {
@@ -88,18 +150,57 @@ impl AudioEngine {
             media: MediaController::new(),
             device: DeviceController::new(),
             pipeline: None,
+            passthrough_backlog: None,
             resampler: None,
             state: EngineState::Idle,
             pending_reset: false,
+            crossfade: None,
+            crossfade_length: None,
+            equalizer: None,
+            equalizer_settings: EqualizerSettings::default(),
+            dsp_chain: DspChain::default(),
+            dsp_settings: DspSettings::default(),
+            replaygain: 1.0,
+            speed: 1.0,
+            preserve_pitch: true,
+            stretcher: None,
+            loop_range: None,
+            loop_trimmer: LoopTrimmer::default(),
+            bit_perfect: false,
+            channel_settings: ChannelSettings::default(),
+            mixer: None,
+            resampler_profile: ResamplerProfile::default(),
+            noise_shaping: false,
+            dither: None,
+            visualizer_active: false,
+            visualizer: None,
         }
     }
 
     /// Initialize the audio engine's providers and create the initial device stream.
     ///
     /// This should be called once at startup.
-    pub fn initialize(&mut self) -> Result<(), EngineError> {
-        self.device.initialize_provider();
+    pub fn initialize(&mut self, output: &OutputDeviceSettings) -> Result<(), EngineError> {
+        self.device.initialize_provider(output.provider.as_deref());
+        self.device.select_device(output.device_uid.clone());
+        self.device.select_buffer_size(output.buffer_size());
+
+        self.create_initial_stream()
+    }
 
+    /// Initialize the audio engine with the given device provider instead of the one chosen by
+    /// the settings.
+    #[cfg(test)]
+    pub fn initialize_with_provider(
+        &mut self,
+        provider_name: &'static str,
+        provider: Box<dyn crate::devices::traits::DeviceProvider>,
+    ) -> Result<(), EngineError> {
+        self.device.use_provider(provider_name, provider);
+        self.create_initial_stream()
+    }
+
+    fn create_initial_stream(&mut self) -> Result<(), EngineError> {
         if let Err(e) = self.device.create_stream(None) {
             error!("Failed to create initial stream: {:?}", e);
             return Err(EngineError::DeviceError(format!(
@@ -111,14 +212,112 @@ impl AudioEngine {
         Ok(())
     }
 
+    /// Switch to a different output device, recreating the stream. The current track continues
+    /// on the new device from where it was.
+    pub fn set_output_device(&mut self, output: &OutputDeviceSettings) -> Result<(), EngineError> {
+        // Switching providers closes the stream, so what it hasn't played yet is measured first
+        self.cancel_crossfade();
+        let resume_ms = self.discard_unplayed();
+
+        self.device.switch_provider(output.provider.as_deref());
+        self.device.select_device(output.device_uid.clone());
+        self.device.select_buffer_size(output.buffer_size());
+        self.reopen_output_from(resume_ms)
+    }
+
+    /// Reopen the stream on the device that should currently be played to, e.g. after the
+    /// default device changed. The current track continues from where it was.
+    pub fn reopen_output(&mut self) -> Result<(), EngineError> {
+        self.cancel_crossfade();
+        let resume_ms = self.discard_unplayed();
+        self.reopen_output_from(resume_ms)
+    }
+
+    /// Recreate the stream and rewind the decoder to `resume_ms`, the first frame that wasn't
+    /// played before the pipeline was discarded.
+    fn reopen_output_from(&mut self, resume_ms: Option<u64>) -> Result<(), EngineError> {
+        // The pipeline is set up for the previous device's format, so it has to be rebuilt. The
+        // audio buffered in it and the old device was never heard, so playback continues from
+        // the first frame that wasn't.
+        self.resampler = None;
+        self.dsp_chain.reset();
+        if let Some(stretcher) = &mut self.stretcher {
+            stretcher.reset();
+        }
+
+        let channels = match self.media.channels() {
+            Ok(channels) => self.requested_channels(channels),
+            Err(_) => self.device.current_format().map(|f| f.channels),
+        };
+        if let Err(e) = self.device.recreate_stream(true, channels) {
+            return Err(EngineError::DeviceError(format!(
+                "Failed to open output device: {:?}",
+                e
+            )));
+        }
+
+        if self.state == EngineState::Playing
+            && let Err(e) = self.device.play()
+        {
+            return Err(EngineError::DeviceError(format!(
+                "Failed to start output device: {:?}",
+                e
+            )));
+        }
+
+        if let Some(resume_ms) = resume_ms
+            && let Err(e) = self.seek(resume_ms as f64 / 1000.0)
+        {
+            warn!("Failed to rewind to the unplayed audio: {:?}", e);
+        }
+
+        self.pending_reset = false;
+        Ok(())
+    }
+
+    /// Drop the pipeline along with the audio buffered in it and the device, returning the
+    /// position of the first frame that hasn't been played, in milliseconds.
+    fn discard_unplayed(&mut self) -> Option<u64> {
+        let position_ms = self.audible_position_ms();
+        self.pipeline = None;
+        self.passthrough_backlog = None;
+        position_ms
+    }
+
+    /// Check for output devices being connected or disconnected. Returns `None` if no devices
+    /// changed.
+    pub fn poll_device_changes(&mut self) -> Option<DeviceChangeAction> {
+        self.device.poll_device_changes()
+    }
+
+    /// List the output devices offered by the current device provider.
+    pub fn list_output_devices(&mut self) -> Result<Vec<OutputDevice>, EngineError> {
+        self.device
+            .list_devices()
+            .map_err(|e| EngineError::DeviceError(e.to_string()))
+    }
+
+    /// Whether the selected output device couldn't be found, so the default device is being
+    /// used instead.
+    pub fn output_device_missing(&self) -> bool {
+        self.device.selected_device_missing()
+    }
+
     pub fn state(&self) -> EngineState {
         self.state
     }
 
-    pub fn open(&mut self, path: &Path) -> Result<OpenInfo, PlaybackStartError> {
-        info!("AudioEngine: Opening track '{}'", path.display());
+    pub fn open(&mut self, source: &TrackSource) -> Result<OpenInfo, PlaybackStartError> {
+        info!("AudioEngine: Opening track '{}'", source);
 
+        self.cancel_crossfade();
+        self.loop_range = None;
+        // the trimmers are set up for the previous track's channels
+        self.loop_trimmer = LoopTrimmer::default();
         self.reset_resampler();
+        if let Some(stretcher) = &mut self.stretcher {
+            stretcher.reset();
+        }
 
         // Handle paused state - reset device if needed
         let mut recreation_required = false;
@@ -141,19 +340,38 @@ impl AudioEngine {
         // Clear the pipeline for the new track, but preserve the resampler for gapless playback
         // The resampler will be reused if params match, or recreated in process_decode_resample if needed
         self.pipeline = None;
+        self.passthrough_backlog = None;
 
-        let media_info = self.media.open(path)?;
+        let media_info = self.media.open(source)?;
 
         // Check if we need to recreate the stream for different channel count
-        if self.device.needs_format_change(media_info.channels) {
+        if self.needs_channel_reopen(media_info.channels) {
             info!(
                 "Channel count mismatch, re-opening with the correct channel count (if supported)"
             );
             recreation_required = true;
         }
 
+        if self.bit_perfect {
+            let native = Self::native_format(&media_info);
+            self.device.set_native_format(native);
+
+            if let Some((sample_type, sample_rate)) = native
+                && self
+                    .device
+                    .needs_native_format_change(sample_type, sample_rate)
+            {
+                info!(
+                    "Re-opening device at the track's native format: {:?} at {} Hz",
+                    sample_type, sample_rate
+                );
+                recreation_required = true;
+            }
+        }
+
         let device_recreated = if recreation_required {
-            if let Err(e) = self.device.recreate_stream(true, Some(media_info.channels)) {
+            let channels = self.requested_channels(media_info.channels);
+            if let Err(e) = self.device.recreate_stream(true, channels) {
                 error!("Failed to recreate stream: {:?}", e);
                 return Err(PlaybackStartError::StreamError(format!(
                     "Failed to recreate stream: {:?}",
@@ -264,20 +482,78 @@ impl AudioEngine {
 
     /// Stop playback and clear all state.
     pub fn stop(&mut self) {
+        self.cancel_crossfade();
+        self.loop_range = None;
         self.media.close();
+        self.media.discard_prepared();
         self.clear_pipeline();
         self.state = EngineState::Idle;
     }
 
     /// Seek to the specified time in seconds.
     pub fn seek(&mut self, time: f64) -> Result<(), SeekError> {
+        self.cancel_crossfade();
         let result = self.media.seek(time);
         if result.is_ok() {
             self.pending_reset = true;
+            self.loop_trimmer.reset();
+            self.dsp_chain.reset();
+            if let Some(stretcher) = &mut self.stretcher {
+                stretcher.reset();
+            }
+            if let Some(visualizer) = &mut self.visualizer {
+                visualizer.reset();
+            }
         }
         result
     }
 
+    /// Set the section of the current track to loop between, in milliseconds. `None` stops
+    /// looping.
+    ///
+    /// Playback jumps back to the start of the section once a decoded frame reaches the end of it,
+    /// with the packet crossing the end cut at the exact frame. Unlike [`AudioEngine::seek`], this
+    /// leaves the pipeline and device buffers alone, so the audio continues without a gap.
+    pub fn set_loop(&mut self, range: Option<(u64, u64)>) {
+        self.loop_range = range;
+        self.loop_trimmer.reset();
+
+        if range.is_some() {
+            self.cancel_crossfade();
+        }
+    }
+
+    /// Seek back to the start of the loop if the end of it has been decoded.
+    ///
+    /// Returns `true` if the stream was rewound.
+    fn wrap_loop(&mut self, at_eof: bool) -> bool {
+        let Some((start_ms, end_ms)) = self.loop_range else {
+            return false;
+        };
+
+        let past_end = self.loop_trimmer.reached_end()
+            || self
+                .media
+                .position_ms()
+                .is_ok_and(|position| position >= end_ms);
+
+        if !at_eof && !past_end {
+            return false;
+        }
+
+        match self.media.seek(start_ms as f64 / 1000.0) {
+            Ok(()) => {
+                self.loop_trimmer.reset();
+                true
+            }
+            Err(e) => {
+                warn!("Failed to rewind to loop start: {:?}", e);
+                self.loop_range = None;
+                false
+            }
+        }
+    }
+
     /// Set the playback volume (0.0 to 1.0).
     pub fn set_volume(&mut self, volume: f64) -> Result<(), EngineError> {
         self.device
@@ -285,21 +561,89 @@ impl AudioEngine {
             .map_err(|e| EngineError::DeviceError(format!("Failed to set volume: {:?}", e)))
     }
 
-    /// Set the ReplayGain multiplier (linear).
+    /// Set the ReplayGain multiplier (linear). Ignored while bit-perfect playback is enabled.
     pub fn set_replaygain(&mut self, gain: f64) -> Result<(), EngineError> {
+        self.replaygain = gain;
+        self.update_output_gain();
+
         self.device
             .set_replaygain(gain)
             .map_err(|e| EngineError::DeviceError(format!("Failed to set RG: {:?}", e)))
     }
 
+    /// Tell the effect chain the gain the device applies after it, which is ReplayGain unless
+    /// bit-perfect playback bypasses it.
+    fn update_output_gain(&mut self) {
+        self.dsp_chain.set_output_gain(if self.bit_perfect {
+            1.0
+        } else {
+            self.replaygain
+        });
+    }
+
+    /// Set the name of the media being played, for device providers that publish it.
+    pub fn set_media_name(&mut self, name: Option<String>) -> Result<(), EngineError> {
+        self.device
+            .set_media_name(name)
+            .map_err(|e| EngineError::DeviceError(format!("Failed to set media name: {:?}", e)))
+    }
+
     /// Get the current playback position in milliseconds.
     pub fn position_ms(&self) -> Option<u64> {
         self.media.position_ms().ok()
     }
 
-    /// Get the currently loaded track path, if any.
-    pub fn current_path(&self) -> Option<&Path> {
-        self.media.current_path()
+    /// The position of the first frame that hasn't been played, in milliseconds. The decoder
+    /// runs ahead of what is being heard by the audio buffered in the pipeline and the device.
+    pub fn audible_position_ms(&self) -> Option<u64> {
+        let position_ms = self.media.position_ms().ok()?;
+        Some(position_ms.saturating_sub((self.unplayed_secs() * 1000.0).round() as u64))
+    }
+
+    /// The duration of the track that has been decoded but not played yet, in seconds.
+    fn unplayed_secs(&self) -> f64 {
+        let device_rate = self.device.current_format().map(|f| f.sample_rate as f64);
+        // Each frame after the time stretcher, or the resampler when speed is changed without
+        // preserving pitch, covers `speed` frames of the track
+        let stretched_speed = if self.stretcher.is_some() {
+            self.speed
+        } else {
+            1.0
+        };
+
+        let mut unplayed_secs = match &self.pipeline {
+            Some(AudioPipeline::Convert(p)) => {
+                let source_rate = p.source_rate as f64;
+                let backlog = self.stretcher.as_ref().map_or(0, TimeStretcher::backlog);
+
+                (p.stretch_input.buffered_frames() + backlog) as f64 / source_rate
+                    + p.resampler_input.buffered_frames() as f64 * stretched_speed / source_rate
+                    + (p.dsp_input.buffered_frames() + p.device_input.buffered_frames()) as f64
+                        * self.speed
+                        / p.target_rate as f64
+            }
+            Some(AudioPipeline::F32Passthrough(p)) => {
+                device_rate.map_or(0.0, |rate| p.device_input.buffered_frames() as f64 / rate)
+            }
+            None => 0.0,
+        };
+
+        if let Some(rate) = device_rate {
+            // passthrough is only used when the track is at the device's rate
+            let backlog = self
+                .passthrough_backlog
+                .as_ref()
+                .map_or(0, ChannelConsumers::buffered_frames);
+            unplayed_secs += backlog as f64 / rate;
+            unplayed_secs += self.device.buffered_frames() as f64 * self.speed / rate;
+        }
+
+        unplayed_secs
+    }
+
+    /// Get the currently loaded track, if any.
+    pub fn current_source(&self) -> Option<&TrackSource> {
+        self.media.current_source()
     }
 
     /// Check for metadata updates and return them if available.
@@ -307,6 +651,35 @@ impl AudioEngine {
         self.media.check_metadata_update()
     }
 
+    /// Open the given track ahead of time, so that it can follow the current one without a gap.
+    ///
+    /// Returns `false` if the track could not be opened.
+    pub fn prepare_next(&mut self, source: &TrackSource) -> bool {
+        match self.media.prepare(source) {
+            Ok(_) => true,
+            Err(e) => {
+                warn!("Failed to prepare next track '{}': {:?}", source, e);
+                false
+            }
+        }
+    }
+
+    /// Get the track prepared with [`AudioEngine::prepare_next`], if any.
+    pub fn prepared_source(&self) -> Option<&TrackSource> {
+        self.media.prepared_source()
+    }
+
+    /// Discard the prepared track, if any.
+    pub fn discard_prepared(&mut self) {
+        self.media.discard_prepared();
+    }
+
+    /// Set how long to crossfade into the prepared track once the current track nears its end.
+    /// `None` plays the prepared track gaplessly instead.
+    pub fn set_crossfade(&mut self, length: Option<Duration>) {
+        self.crossfade_length = length;
+    }
+
     /// Get the current device format, if available.
     #[allow(dead_code)]
     pub fn current_format(&self) -> Option<&FormatInfo> {
@@ -314,12 +687,237 @@ impl AudioEngine {
     }
 
     /// Update settings that affect playback.
-    ///
-    /// Currently this is a placeholder for future settings that might affect
-    /// the audio engine directly (e.g., resampler quality settings).
-    pub fn update_settings(&mut self, _settings: &PlaybackSettings) {
-        // Currently no engine-specific settings to update.
-        // This method exists for future extensibility.
+    pub fn update_settings(&mut self, settings: &PlaybackSettings) {
+        if settings.equalizer != self.equalizer_settings {
+            self.set_equalizer(settings.equalizer.clone());
+        }
+
+        if settings.dsp != self.dsp_settings {
+            self.set_dsp(settings.dsp.clone());
+        }
+
+        // Takes effect on the next decoded packet
+        self.preserve_pitch = settings.preserve_pitch;
+        self.resampler_profile = settings.resampler;
+
+        if settings.noise_shaping != self.noise_shaping {
+            self.noise_shaping = settings.noise_shaping;
+            if let Some(format) = self.device.current_format() {
+                self.dither = Self::create_dither(&format, self.noise_shaping);
+            }
+        }
+
+        if settings.bit_perfect != self.bit_perfect {
+            self.set_bit_perfect(settings.bit_perfect);
+        }
+
+        if settings.channels != self.channel_settings {
+            self.set_channel_settings(settings.channels);
+        }
+    }
+
+    /// Replace the channel settings. New mix levels apply to audio that is already playing, and a
+    /// new mode reopens the device.
+    pub fn set_channel_settings(&mut self, settings: ChannelSettings) {
+        let mode_changed = settings.mode != self.channel_settings.mode;
+        self.channel_settings = settings;
+
+        if mode_changed && self.device.has_stream() {
+            // the pipeline is rebuilt with a new mixer once the device is open again
+            if let Err(e) = self.reopen_output() {
+                warn!("Failed to reopen device for the new channel mode: {}", e);
+            }
+        } else if let Some(mixer) = &self.mixer {
+            let (input, output) = (mixer.input(), mixer.output());
+            self.mixer = ChannelMixer::new(input, output, &settings);
+        }
+    }
+
+    /// Whether the stream has to be reopened to play a track with the given channels.
+    fn needs_channel_reopen(&self, channels: ChannelSpec) -> bool {
+        match self.channel_settings.mode {
+            ChannelMode::Native => self.device.needs_format_change(channels),
+            ChannelMode::Downmix => self.device.current_format().is_none(),
+        }
+    }
+
+    /// The channels to open the device with to play a track with the given channels. `None`
+    /// opens the device with its own channels.
+    fn requested_channels(&self, channels: ChannelSpec) -> Option<ChannelSpec> {
+        match self.channel_settings.mode {
+            ChannelMode::Native => Some(channels),
+            ChannelMode::Downmix => None,
+        }
+    }
+
+    /// Enable or disable bit-perfect playback. When enabled, the device is reopened at the
+    /// current track's native format if it supports it.
+    pub fn set_bit_perfect(&mut self, enabled: bool) {
+        self.bit_perfect = enabled;
+
+        if let Err(e) = self.device.set_gain_bypass(enabled) {
+            warn!("Failed to bypass volume: {:?}", e);
+        }
+        self.update_output_gain();
+
+        let native = if enabled {
+            self.media
+                .sample_format()
+                .ok()
+                .zip(self.media.sample_rate().ok())
+        } else {
+            None
+        };
+        self.device.set_native_format(native);
+
+        if let Some((sample_type, sample_rate)) = native
+            && self
+                .device
+                .needs_native_format_change(sample_type, sample_rate)
+            && let Err(e) = self.reopen_output()
+        {
+            warn!("Failed to reopen device at the native format: {}", e);
+        }
+    }
+
+    /// Whether the current track is reaching the device unchanged: the device is open at the
+    /// track's own format and rate, and no processing, volume or ReplayGain is applied.
+    pub fn is_bit_perfect(&self) -> bool {
+        if !self.bit_perfect
+            || self.state != EngineState::Playing
+            || self.needs_conversion()
+            || self.crossfade.is_some()
+        {
+            return false;
+        }
+
+        let Some(format) = self.device.current_format() else {
+            return false;
+        };
+
+        self.mixer.is_none()
+            && self.media.sample_format().ok() == Some(format.sample_type)
+            && self.media.sample_rate().ok() == Some(format.sample_rate)
+            && self.media.channels().ok().map(ChannelSpec::count) == Some(format.channels.count())
+    }
+
+    /// The sample format and rate a track would be played at in bit-perfect mode.
+    fn native_format(info: &MediaInfo) -> Option<(SampleFormat, u32)> {
+        info.sample_format.zip(info.sample_rate)
+    }
+
+    /// Whether the stream would have to be reopened for the given track to play bit-perfectly.
+    fn needs_native_reopen(&self, info: &MediaInfo) -> bool {
+        self.bit_perfect
+            && Self::native_format(info).is_some_and(|(sample_type, sample_rate)| {
+                self.device
+                    .needs_native_format_change(sample_type, sample_rate)
+            })
+    }
+
+    /// Set the playback speed. Positions reported by the engine always stay in the source's own
+    /// timeline, regardless of speed.
+    pub fn set_speed(&mut self, speed: f64) {
+        self.speed = speed.clamp(MIN_SPEED, MAX_SPEED);
+
+        if let Some(stretcher) = &mut self.stretcher {
+            stretcher.set_speed(self.speed);
+        }
+
+        if self.needs_conversion() {
+            self.leave_passthrough();
+        }
+    }
+
+    /// Whether any processing is enabled that requires the conversion pipeline.
+    fn needs_conversion(&self) -> bool {
+        self.equalizer_settings.enabled || self.dsp_settings.any_enabled() || self.speed != 1.0
+    }
+
+    /// Drop the current pipeline if it is a passthrough pipeline, so that the next cycle sets up
+    /// a conversion pipeline instead. The audio it still holds is played through the new
+    /// pipeline, so nothing is skipped.
+    fn leave_passthrough(&mut self) {
+        if self
+            .pipeline
+            .as_ref()
+            .is_some_and(AudioPipeline::is_passthrough)
+        {
+            info!("Processing enabled, switching to conversion pipeline");
+            if let Some(AudioPipeline::F32Passthrough(passthrough)) = self.pipeline.take() {
+                self.passthrough_backlog = Some(passthrough.device_input);
+            }
+        }
+    }
+
+    /// Replace the equalizer configuration. Changes are applied to audio that is already playing.
+    pub fn set_equalizer(&mut self, settings: EqualizerSettings) {
+        if let Some(equalizer) = &mut self.equalizer {
+            equalizer.update(&settings);
+        }
+
+        self.equalizer_settings = settings;
+
+        // The equalizer only runs in the conversion pipeline, so passthrough has to be dropped
+        if self.needs_conversion() {
+            self.leave_passthrough();
+        }
+    }
+
+    /// Replace the effect chain configuration. Effects that stay in the chain keep their state, so
+    /// only newly added effects with latency briefly output silence.
+    pub fn set_dsp(&mut self, settings: DspSettings) {
+        self.dsp_chain.update(&settings);
+        self.update_output_gain();
+
+        if let Some(format) = self.device.current_format() {
+            let latency = self.dsp_chain.latency_frames(format.sample_rate);
+            if latency > 0 {
+                info!("DSP chain latency: {} frames", latency);
+            }
+        }
+
+        self.dsp_settings = settings;
+
+        if self.needs_conversion() {
+            self.leave_passthrough();
+        }
+    }
+
+    /// Start or stop analyzing audio for the visualizer. The f32 passthrough pipeline hands
+    /// decoded audio straight to the device, so the conversion pipeline is used instead while
+    /// the visualizer is active.
+    pub fn set_visualizer_active(&mut self, active: bool) {
+        self.visualizer_active = active;
+
+        if !active {
+            self.visualizer = None;
+            return;
+        }
+
+        if let Some(format) = self.device.current_format() {
+            self.visualizer = Some(Visualizer::new(
+                format.channels.count() as usize,
+                format.sample_rate,
+            ));
+        }
+
+        self.leave_passthrough();
+    }
+
+    /// Analyze the audio that is being heard, if the visualizer is active. The visualizer sees
+    /// audio before it is handed to the device, so analysis is delayed by what's still waiting
+    /// for the device and what the device hasn't played yet.
+    pub fn visualizer_frame(&mut self) -> Option<VisualizerFrame> {
+        let queued = match &self.pipeline {
+            Some(AudioPipeline::Convert(p)) => p.device_input.buffered_frames(),
+            _ => 0,
+        };
+        let delay = queued + self.device.buffered_frames();
+
+        self.visualizer
+            .as_mut()
+            .map(|visualizer| visualizer.frame(delay))
     }
 
     /// Process one cycle of the audio pipeline.
@@ -350,6 +948,12 @@ impl AudioEngine {
             }
         }
 
+        if let Some(info) = self.start_crossfade() {
+            return EngineCycleResult::TrackSpliced(info);
+        }
+
+        self.wrap_loop(false);
+
         // Process decode -> resample (or passthrough)
         let result = match self.process_decode_resample() {
             Ok(result) => result,
@@ -360,8 +964,16 @@ impl AudioEngine {
         };
 
         match result {
+            DecodeStepResult::Eof if self.wrap_loop(true) => {
+                // Loop end lies beyond the end of the track
+            }
             DecodeStepResult::Eof => {
                 info!("EOF, track finished");
+
+                if let Some(info) = self.try_splice_prepared() {
+                    return EngineCycleResult::TrackSpliced(info);
+                }
+
                 return EngineCycleResult::Eof;
             }
             DecodeStepResult::FatalError(msg) => {
@@ -401,6 +1013,19 @@ impl AudioEngine {
             }
         };
 
+        match consume_result {
+            Err(DeviceError::SubmissionError(SubmissionError::DeviceLost)) => {
+                warn!(parent: &s, "Output device is no longer available");
+                return EngineCycleResult::DeviceLost;
+            }
+            // the stream has already recovered, and the samples were played
+            Err(DeviceError::SubmissionError(SubmissionError::Underrun)) => {
+                warn!(parent: &s, "Output device underrun");
+                return EngineCycleResult::Continue;
+            }
+            _ => {}
+        }
+
         if let Err(err) = consume_result {
             warn!(parent: &s, ?err, "Failed to consume from pipeline: {err}");
             warn!(parent: &s, "Recreating device and retrying...");
@@ -420,7 +1045,7 @@ impl AudioEngine {
                 AudioPipeline::F32Passthrough(p) => self
                     .device
                     .consume_from_f32(&mut p.device_input)
-                    .unwrap_or(Err(super::device_controller::DeviceError::NoStream)),
+                    .unwrap_or(Err(DeviceError::NoStream)),
             };
 
             if let Err(err) = retry_result {
@@ -467,19 +1092,53 @@ impl AudioEngine {
             .sample_rate()
             .unwrap_or(device_format.sample_rate); // Fallback to device rate if unavailable
 
+        self.mixer = ChannelMixer::new(channels, device_format.channels, &self.channel_settings);
+        if self.mixer.is_some() {
+            info!(
+                "Mixing {:?} to the device's {:?}",
+                channels, device_format.channels
+            );
+        }
+
+        self.dither = Self::create_dither(device_format, self.noise_shaping);
+
+        let output_channels = device_format.channels.count() as usize;
+        if self.visualizer_active
+            && !self
+                .visualizer
+                .as_ref()
+                .is_some_and(|v| v.matches_format(output_channels, device_format.sample_rate))
+        {
+            self.visualizer = Some(Visualizer::new(output_channels, device_format.sample_rate));
+        }
+
         let pipeline = AudioPipeline::new(
             channel_count,
+            output_channels,
             source_format,
             source_rate,
             device_format.sample_type,
             device_format.sample_rate,
             DEFAULT_BUFFER_FRAMES,
+            !self.needs_conversion() && self.mixer.is_none() && !self.visualizer_active,
         );
 
         if pipeline.is_passthrough() {
             info!("Using f32 passthrough pipeline (no conversion needed)");
         } else {
             info!("Using f64 conversion pipeline");
+
+            if !self
+                .equalizer
+                .as_ref()
+                .is_some_and(|eq| eq.matches_format(device_format.sample_rate, channel_count))
+            {
+                self.equalizer = Some(Equalizer::new(
+                    &self.equalizer_settings,
+                    device_format.sample_rate,
+                    channel_count,
+                ));
+            }
         }
 
         self.pipeline = Some(pipeline);
@@ -487,11 +1146,125 @@ impl AudioEngine {
         Ok(())
     }
 
+    fn create_dither(device_format: &FormatInfo, noise_shaping: bool) -> Option<Dither> {
+        Dither::new(
+            device_format.sample_type,
+            device_format.channels.count() as usize,
+            noise_shaping,
+        )
+    }
+
+    /// Continue directly into the prepared track after the current one has ended.
+    ///
+    /// The pipeline and device stream are kept as they are, so this only succeeds if the prepared
+    /// track can be fed into them unchanged. Otherwise the prepared stream is left alone and will
+    /// be picked up by the next call to [`AudioEngine::open`].
+    fn try_splice_prepared(&mut self) -> Option<OpenInfo> {
+        let info = *self.media.prepared_info()?;
+        let pipeline = self.pipeline.as_ref()?;
+
+        if self.needs_channel_reopen(info.channels) || self.needs_native_reopen(&info) {
+            return None;
+        }
+
+        let compatible = match pipeline {
+            // A change in sample rate is handled by the resampler on the next decode
+            AudioPipeline::Convert(p) => p.channel_count == info.channels.count() as usize,
+            AudioPipeline::F32Passthrough(_) => {
+                info.sample_format == Some(SampleFormat::Float32)
+                    && info.sample_rate.is_some()
+                    && info.sample_rate == self.device.current_format().map(|f| f.sample_rate)
+            }
+        };
+
+        if !compatible {
+            info!("Prepared track is incompatible with the current pipeline, reopening");
+            return None;
+        }
+
+        self.media.promote_prepared()?;
+        info!("Continuing gaplessly into the prepared track");
+
+        Some(OpenInfo {
+            duration_secs: info.duration_secs,
+            channels: info.channels,
+            device_recreated: false,
+        })
+    }
+
+    /// Start crossfading into the prepared track if the current track is close enough to its end.
+    ///
+    /// Crossfades are only possible in the conversion pipeline, and only if the prepared track
+    /// has the same channel count and sample rate as the current one, since both are mixed
+    /// before resampling. Otherwise the prepared track is played gaplessly once the current
+    /// track ends.
+    fn start_crossfade(&mut self) -> Option<OpenInfo> {
+        let length = self.crossfade_length?;
+
+        if self.crossfade.is_some() || self.loop_range.is_some() {
+            return None;
+        }
+
+        let Some(AudioPipeline::Convert(p)) = &self.pipeline else {
+            return None;
+        };
+
+        let info = *self.media.prepared_info()?;
+        let length_ms = length.as_millis() as u64;
+        let duration_ms = self.media.duration_secs().ok()? * 1000;
+        let position_ms = self.media.position_ms().ok()?;
+
+        // Don't fade through the entirety of very short tracks
+        if duration_ms < length_ms * 2 || position_ms + length_ms < duration_ms {
+            return None;
+        }
+
+        if info.channels.count() as usize != p.channel_count
+            || info.sample_rate != Some(p.source_rate)
+            || self.needs_channel_reopen(info.channels)
+            || self.needs_native_reopen(&info)
+        {
+            return None;
+        }
+
+        let channel_count = p.channel_count;
+        let sample_rate = p.source_rate;
+
+        let (info, Some(outgoing)) = self.media.swap_prepared()? else {
+            return None;
+        };
+
+        self.crossfade = Some(Crossfade::new(
+            outgoing,
+            channel_count,
+            sample_rate,
+            length,
+            DEFAULT_BUFFER_FRAMES,
+        ));
+        self.crossfade_length = None;
+
+        Some(OpenInfo {
+            duration_secs: info.duration_secs,
+            channels: info.channels,
+            device_recreated: false,
+        })
+    }
+
+    /// Abort the crossfade in progress, if any, dropping the rest of the outgoing track.
+    fn cancel_crossfade(&mut self) {
+        if let Some(crossfade) = self.crossfade.take() {
+            crossfade.close();
+        }
+        self.crossfade_length = None;
+    }
+
     /// Clear the pipeline and resampler completely (e.g., on stop).
     /// For track transitions, prefer clearing only the pipeline to preserve the resampler for gapless playback.
     fn clear_pipeline(&mut self) {
         self.pipeline = None;
+        self.passthrough_backlog = None;
         self.resampler = None;
+        self.stretcher = None;
     }
 
     /// Reset the resampler's internal buffers (e.g., on track change).
@@ -503,11 +1276,22 @@ impl AudioEngine {
 
     /// Process the decode and resample steps.
     fn process_decode_resample(&mut self) -> Result<DecodeStepResult, EngineError> {
+        // Dithering would change bit-perfect audio. Otherwise the grid the device quantizes to
+        // depends on the volume and ReplayGain it applies first
+        let dither_gain = (!self.is_bit_perfect()).then(|| self.device.output_gain());
         let pipeline = self.pipeline.as_mut().ok_or(EngineError::NoPipeline)?;
 
         match pipeline {
             AudioPipeline::F32Passthrough(p) => {
-                let decode_result = match self.media.decode_into_f32(&p.decoder_output) {
+                let decoded = match self.loop_range {
+                    Some((_, end_ms)) => self.loop_trimmer.decode_into_f32(
+                        &mut self.media,
+                        &p.decoder_output,
+                        end_ms,
+                    ),
+                    None => self.media.decode_into_f32(&p.decoder_output),
+                };
+                let decode_result = match decoded {
                     Ok(F32DecodeResult::Decoded(result)) => result,
                     Ok(F32DecodeResult::NotF32) => {
                         // Source is not f32, need to switch to conversion pipeline
@@ -533,57 +1317,221 @@ impl AudioEngine {
                 }
             }
             AudioPipeline::Convert(p) => {
-                let decode_result = match self.media.decode_into(&p.decoder_output) {
-                    Ok(result) => result,
-                    Err(e) => {
-                        return Self::handle_decode_error(e);
+                // When slowing down, the time stretcher consumes less than it is given, so
+                // decoding pauses until it has caught up
+                let backlogged = self
+                    .stretcher
+                    .as_ref()
+                    .is_some_and(|stretcher| stretcher.backlog() >= DEFAULT_BUFFER_FRAMES / 2);
+
+                if !backlogged {
+                    let from_backlog = self.passthrough_backlog.as_mut().and_then(|backlog| {
+                        Self::drain_backlog(backlog, &p.decoder_output, p.source_rate)
+                    });
+                    if from_backlog.is_none() {
+                        self.passthrough_backlog = None;
                     }
-                };
 
-                match decode_result {
-                    DecodeResult::Eof => {
-                        info!("EOF from decode_into");
-                        return Ok(DecodeStepResult::Eof);
-                    }
-                    DecodeResult::Decoded { rate, .. } => {
-                        // Only recreate resampler if parameters actually changed
-                        let duration = self.media.frame_duration().unwrap_or(1024);
-                        let needs_new_resampler = match &self.resampler {
-                            Some(resampler) => !resampler.matches_params(
-                                rate,
-                                p.target_rate,
-                                duration,
-                                p.channel_count,
+                    let decode_result = match (from_backlog, &mut self.crossfade) {
+                        (Some(result), _) => Ok(result),
+                        (None, Some(crossfade)) => {
+                            crossfade.decode_into(&mut self.media, &p.decoder_output)
+                        }
+                        (None, None) => match self.loop_range {
+                            Some((_, end_ms)) => self.loop_trimmer.decode_into(
+                                &mut self.media,
+                                &p.decoder_output,
+                                end_ms,
                             ),
-                            None => true,
-                        };
-
-                        if needs_new_resampler {
-                            self.resampler = Some(Resampler::new(
-                                rate,
-                                p.target_rate,
-                                duration,
-                                p.channel_count as u16,
-                            ));
+                            None => self.media.decode_into(&p.decoder_output),
+                        },
+                    };
+
+                    if self.crossfade.as_ref().is_some_and(Crossfade::is_finished)
+                        && let Some(crossfade) = self.crossfade.take()
+                    {
+                        crossfade.finish(&p.decoder_output);
+                    }
+
+                    let decode_result = match decode_result {
+                        Ok(result) => result,
+                        Err(e) => {
+                            return Self::handle_decode_error(e);
+                        }
+                    };
+
+                    match decode_result {
+                        DecodeResult::Eof => {
+                            info!("EOF from decode_into");
+                            if let Some(crossfade) = self.crossfade.take() {
+                                crossfade.close();
+                            }
+                            return Ok(DecodeStepResult::Eof);
                         }
+                        DecodeResult::Decoded { rate, .. } => {
+                            // Anything still buffered belongs to the previous track or speed
+                            // setting, so it is written out before the stages are replaced
+                            let stretcher_matches = self
+                                .stretcher
+                                .as_ref()
+                                .is_some_and(|s| s.matches_params(rate, p.channel_count));
+                            if (!stretcher_matches || !self.preserve_pitch)
+                                && let Some(mut stretcher) = self.stretcher.take()
+                            {
+                                stretcher.flush(&p.stretch_output);
+                            }
+
+                            if self.preserve_pitch && self.speed != 1.0 && self.stretcher.is_none()
+                            {
+                                self.stretcher =
+                                    Some(TimeStretcher::new(rate, p.channel_count, self.speed));
+                            }
+
+                            // Without pitch preservation, speed is changed by resampling from a
+                            // higher or lower rate than the source's actual rate
+                            let input_rate = if self.preserve_pitch {
+                                rate
+                            } else {
+                                (rate as f64 * self.speed).round() as u32
+                            };
+
+                            // Only recreate resampler if parameters actually changed
+                            let duration = self.media.frame_duration().unwrap_or(1024);
+                            let needs_new_resampler = match &self.resampler {
+                                Some(resampler) => !resampler.matches_params(
+                                    self.resampler_profile,
+                                    input_rate,
+                                    p.target_rate,
+                                    duration,
+                                    p.channel_count,
+                                ),
+                                None => true,
+                            };
+
+                            if needs_new_resampler {
+                                if let Some(resampler) = &mut self.resampler {
+                                    resampler.process_ring_buffers(
+                                        &mut p.resampler_input,
+                                        &p.resampler_output,
+                                        DEFAULT_BUFFER_FRAMES,
+                                    );
+                                    resampler.flush(&p.resampler_output);
+                                }
+
+                                self.resampler = Some(Resampler::new(
+                                    self.resampler_profile,
+                                    input_rate,
+                                    p.target_rate,
+                                    duration,
+                                    p.channel_count as u16,
+                                ));
+                            }
 
-                        p.source_rate = rate;
+                            p.source_rate = rate;
+                        }
                     }
                 }
 
+                match &mut self.stretcher {
+                    Some(stretcher) => stretcher.process_ring_buffers(
+                        &mut p.stretch_input,
+                        &p.stretch_output,
+                        DEFAULT_BUFFER_FRAMES / 4,
+                    ),
+                    None => p
+                        .stretch_input
+                        .move_to(&p.stretch_output, DEFAULT_BUFFER_FRAMES),
+                };
+
                 if let Some(resampler) = &mut self.resampler {
                     let _processed = resampler.process_ring_buffers(
                         &mut p.resampler_input,
-                        &p.device_input_producers,
+                        &p.resampler_output,
                         DEFAULT_BUFFER_FRAMES,
                     );
                 }
 
+                Self::process_dsp(
+                    p,
+                    self.equalizer.as_mut(),
+                    &mut self.dsp_chain,
+                    self.mixer.as_mut(),
+                    self.visualizer.as_mut(),
+                    self.dither.as_mut().zip(dither_gain),
+                );
+
                 Ok(DecodeStepResult::Continue)
             }
         }
     }
 
+    /// Move the next block of the audio left over from the passthrough pipeline into the
+    /// conversion pipeline, as if it had just been decoded. Returns `None` once it's empty.
+    fn drain_backlog(
+        backlog: &mut ChannelConsumers<f32>,
+        output: &ChannelProducers<f64>,
+        rate: u32,
+    ) -> Option<DecodeResult> {
+        let frames = backlog.try_read_to_staging(DEFAULT_BUFFER_FRAMES / 4);
+        if frames == 0 {
+            return None;
+        }
+
+        let converted: Vec<Vec<f64>> = backlog
+            .staging()
+            .iter()
+            .map(|channel| channel.iter().map(|&sample| sample as f64).collect())
+            .collect();
+        output.write_vecs(&converted);
+
+        Some(DecodeResult::Decoded { frames, rate })
+    }
+
+    /// Run resampled audio through the DSP stage, mix it to the device's channels, let the
+    /// visualizer see it, dither it with the gain the device applies and hand it to the device
+    /// buffers.
+    fn process_dsp(
+        p: &mut ConvertPipeline,
+        equalizer: Option<&mut Equalizer>,
+        dsp_chain: &mut DspChain,
+        mixer: Option<&mut ChannelMixer>,
+        visualizer: Option<&mut Visualizer>,
+        dither: Option<(&mut Dither, f64)>,
+    ) {
+        let frames = p.dsp_input.try_read_to_staging(DEFAULT_BUFFER_FRAMES);
+        if frames == 0 {
+            return;
+        }
+
+        if let Some(equalizer) = equalizer {
+            equalizer.process(p.dsp_input.staging_mut(), frames);
+        }
+
+        if !dsp_chain.is_empty() {
+            dsp_chain.process(
+                p.dsp_input.staging_mut(),
+                frames,
+                p.target_rate,
+                p.channel_count,
+            );
+        }
+
+        let output = match mixer {
+            Some(mixer) => mixer.process(p.dsp_input.staging(), frames),
+            None => p.dsp_input.staging_mut(),
+        };
+
+        if let Some(visualizer) = visualizer {
+            visualizer.push(output, frames);
+        }
+
+        if let Some((dither, gain)) = dither {
+            dither.process(output, frames, gain);
+        }
+
+        p.device_input_producers.write_vecs(output);
+    }
+
     /// Handle decode errors uniformly
     fn handle_decode_error(
         e: crate::media::errors::PlaybackReadError,
@@ -0,0 +1,174 @@
+use std::time::Duration;
+
+use tracing::{info, warn};
+
+use crate::{
+    devices::util::EqualPowerFade,
+    media::{
+        errors::PlaybackReadError,
+        pipeline::{ChannelBuffers, ChannelConsumers, ChannelProducers, DecodeResult},
+        traits::MediaStream,
+    },
+};
+
+use super::media_controller::MediaController;
+
+/// An in-progress crossfade between the previous track and the current one.
+///
+/// Both streams are decoded into their own buffers and mixed together before being written to
+/// the pipeline, so the resampler and device only ever see a single stream. The incoming stream
+/// is owned by the [`MediaController`] as usual - only the outgoing stream lives here.
+pub struct Crossfade {
+    outgoing: Box<dyn MediaStream>,
+    outgoing_eof: bool,
+    outgoing_producers: ChannelProducers<f64>,
+    outgoing_consumers: ChannelConsumers<f64>,
+    outgoing_buffered: usize,
+    incoming_producers: ChannelProducers<f64>,
+    incoming_consumers: ChannelConsumers<f64>,
+    incoming_buffered: usize,
+    fade: EqualPowerFade,
+    sample_rate: u32,
+    mixed: Vec<Vec<f64>>,
+}
+
+impl Crossfade {
+    pub fn new(
+        outgoing: Box<dyn MediaStream>,
+        channel_count: usize,
+        sample_rate: u32,
+        length: Duration,
+        buffer_frames: usize,
+    ) -> Self {
+        info!("Starting {:.1}s crossfade", length.as_secs_f64());
+
+        let (outgoing_producers, outgoing_consumers) =
+            ChannelBuffers::<f64>::new(channel_count, buffer_frames).split();
+        let (incoming_producers, incoming_consumers) =
+            ChannelBuffers::<f64>::new(channel_count, buffer_frames).split();
+
+        Self {
+            outgoing,
+            outgoing_eof: false,
+            outgoing_producers,
+            outgoing_consumers,
+            outgoing_buffered: 0,
+            incoming_producers,
+            incoming_consumers,
+            incoming_buffered: 0,
+            fade: EqualPowerFade::new(sample_rate, length),
+            sample_rate,
+            mixed: vec![Vec::with_capacity(buffer_frames); channel_count],
+        }
+    }
+
+    pub fn is_finished(&self) -> bool {
+        self.fade.is_finished()
+    }
+
+    /// Decode from whichever stream has the least audio buffered, then mix as much as possible
+    /// into `output`.
+    ///
+    /// Returns the number of frames written. Errors and EOF of the incoming stream are passed
+    /// through, while the outgoing stream simply fades into silence if it ends or fails early.
+    pub fn decode_into(
+        &mut self,
+        media: &mut MediaController,
+        output: &ChannelProducers<f64>,
+    ) -> Result<DecodeResult, PlaybackReadError> {
+        // Only decoding one stream per cycle keeps both buffers from growing past a packet
+        if !self.outgoing_eof && self.outgoing_buffered <= self.incoming_buffered {
+            self.decode_outgoing();
+        } else if let DecodeResult::Decoded { frames, .. } =
+            media.decode_into(&self.incoming_producers)?
+        {
+            self.incoming_buffered += frames;
+        } else {
+            return Ok(DecodeResult::Eof);
+        }
+
+        let frames = self.mix_into(output);
+
+        Ok(DecodeResult::Decoded {
+            frames,
+            rate: self.sample_rate,
+        })
+    }
+
+    /// Write any incoming audio that hasn't been mixed yet to `output` and close the outgoing
+    /// stream. Called once the fade has completed.
+    pub fn finish(mut self, output: &ChannelProducers<f64>) {
+        let read = self
+            .incoming_consumers
+            .try_read_to_staging(self.incoming_buffered);
+        if read > 0 {
+            output.write_vecs(self.incoming_consumers.staging());
+        }
+
+        self.close();
+    }
+
+    /// Close the outgoing stream without writing anything else.
+    pub fn close(mut self) {
+        self.outgoing.stop_playback().ok();
+        self.outgoing.close().ok();
+    }
+
+    fn decode_outgoing(&mut self) {
+        match self.outgoing.decode_into(&self.outgoing_producers) {
+            Ok(DecodeResult::Decoded { frames, .. }) => self.outgoing_buffered += frames,
+            Ok(DecodeResult::Eof) | Err(PlaybackReadError::Eof) => self.outgoing_eof = true,
+            Err(e) => {
+                warn!("Outgoing track failed during crossfade, ending it early: {e}");
+                self.outgoing_eof = true;
+            }
+        }
+    }
+
+    fn mix_into(&mut self, output: &ChannelProducers<f64>) -> usize {
+        // Once the outgoing track has run out, the incoming track is mixed against silence
+        let frames = if self.outgoing_eof && self.outgoing_buffered == 0 {
+            self.incoming_buffered
+        } else {
+            self.incoming_buffered.min(self.outgoing_buffered)
+        };
+
+        if frames == 0 {
+            return 0;
+        }
+
+        let incoming_read = self.incoming_consumers.try_read_to_staging(frames);
+        let outgoing_read = if self.outgoing_buffered > 0 {
+            self.outgoing_consumers.try_read_to_staging(frames)
+        } else {
+            0
+        };
+        self.incoming_buffered -= incoming_read;
+        self.outgoing_buffered -= outgoing_read;
+
+        let incoming = self.incoming_consumers.staging();
+        let outgoing = self.outgoing_consumers.staging();
+
+        for channel in &mut self.mixed {
+            channel.clear();
+        }
+
+        for frame in 0..incoming_read {
+            let (outgoing_gain, incoming_gain) = self.fade.next_gains();
+
+            for (ch, mixed) in self.mixed.iter_mut().enumerate() {
+                let outgoing_sample = if frame < outgoing_read {
+                    outgoing[ch][frame]
+                } else {
+                    0.0
+                };
+
+                mixed.push(incoming[ch][frame] * incoming_gain + outgoing_sample * outgoing_gain);
+            }
+        }
+
+        output.write_vecs(&self.mixed);
+
+        incoming_read
+    }
+}
@@ -4,14 +4,22 @@ use tracing::{info, warn};
 
 use crate::{
     devices::{
-        builtin::{cpal::CpalProvider, dummy::DummyDeviceProvider},
-        errors::{FindError, OpenError, ResetError, StateError, SubmissionError},
-        format::{ChannelSpec, FormatInfo},
-        traits::{Device, DeviceProvider, OutputStream},
+        builtin::{
+            cpal::CpalProvider, dummy::DummyDeviceProvider, file::FileProvider,
+            network::NetworkProvider,
+        },
+        errors::{FindError, ListError, OpenError, ResetError, StateError, SubmissionError},
+        format::{BufferSize, ChannelSpec, FormatInfo, SampleFormat},
+        traits::{Device, DeviceChange, DeviceProvider, OutputStream},
     },
     media::pipeline::ChannelConsumers,
+    playback::events::OutputDevice,
 };
 
+#[cfg(target_os = "linux")]
+use crate::devices::builtin::alsa::AlsaProvider;
+#[cfg(all(target_os = "linux", feature = "pipewire"))]
+use crate::devices::builtin::pipewire::PipeWireProvider;
 #[cfg(target_os = "windows")]
 use crate::devices::builtin::win_audiograph::AudioGraphProvider;
 
@@ -27,6 +35,7 @@ pub enum DeviceError {
     NoStream,
     OpenError(OpenError),
     FindError(FindError),
+    ListError(ListError),
     StateError(StateError),
     ResetError(ResetError),
     SubmissionError(SubmissionError),
@@ -44,6 +53,12 @@ impl From<FindError> for DeviceError {
     }
 }
 
+impl From<ListError> for DeviceError {
+    fn from(e: ListError) -> Self {
+        DeviceError::ListError(e)
+    }
+}
+
 impl From<StateError> for DeviceError {
     fn from(e: StateError) -> Self {
         DeviceError::StateError(e)
@@ -70,6 +85,7 @@ impl std::fmt::Display for DeviceError {
             DeviceError::NoStream => write!(f, "No stream available"),
             DeviceError::OpenError(e) => write!(f, "Open error: {:?}", e),
             DeviceError::FindError(e) => write!(f, "Find error: {:?}", e),
+            DeviceError::ListError(e) => write!(f, "List error: {:?}", e),
             DeviceError::StateError(e) => write!(f, "State error: {:?}", e),
             DeviceError::ResetError(e) => write!(f, "Reset error: {:?}", e),
             DeviceError::SubmissionError(e) => write!(f, "Submission error: {:?}", e),
@@ -79,6 +95,48 @@ impl std::fmt::Display for DeviceError {
 
 impl std::error::Error for DeviceError {}
 
+/// How the stream should react to changes in the available devices.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum DeviceChangeAction {
+    /// The stream is already on the device it should be on.
+    Stay,
+    /// The stream should move to another device, because the default device changed or the
+    /// selected device was reconnected.
+    Move,
+    /// The device the stream was playing to was disconnected.
+    Lost,
+}
+
+/// Decide how the stream should react to the given device changes.
+fn action_for_changes(
+    changes: &[DeviceChange],
+    current_uid: Option<&str>,
+    selected_uid: Option<&str>,
+    selected_missing: bool,
+) -> DeviceChangeAction {
+    let following_default = selected_uid.is_none() || selected_missing;
+    let mut action = DeviceChangeAction::Stay;
+
+    for change in changes {
+        match change {
+            DeviceChange::Removed(uid) if current_uid == Some(uid.as_str()) => {
+                return DeviceChangeAction::Lost;
+            }
+            DeviceChange::Added(uid) if selected_missing && selected_uid == Some(uid.as_str()) => {
+                action = DeviceChangeAction::Move;
+            }
+            DeviceChange::DefaultChanged(uid)
+                if following_default && current_uid != Some(uid.as_str()) =>
+            {
+                action = DeviceChangeAction::Move;
+            }
+            _ => {}
+        }
+    }
+
+    action
+}
+
 /// Controller for audio device and stream management.
 ///
 /// This component handles all interactions with device providers, devices,
@@ -86,71 +144,274 @@ impl std::error::Error for DeviceError {}
 /// playback control, and volume management.
 pub struct DeviceController {
     device_provider: Option<Box<dyn DeviceProvider>>,
+    /// The name of the initialized device provider.
+    provider_name: Option<&'static str>,
+    /// The UID of the device selected by the user. `None` follows the provider's default device.
+    selected_uid: Option<String>,
+    /// Whether the selected device couldn't be found the last time a stream was opened, so the
+    /// default device was used instead.
+    selected_missing: bool,
     device: Option<Box<dyn Device>>,
     stream: Option<Box<dyn OutputStream>>,
     current_format: Option<FormatInfo>,
     last_volume: f64,
     last_replaygain: f64,
+    last_media_name: Option<String>,
+    /// The sample format and rate to open streams with if the device supports them, for
+    /// bit-perfect playback.
+    native_format: Option<(SampleFormat, u32)>,
+    /// Whether volume and ReplayGain are bypassed, so samples reach the device unchanged.
+    bypass_gain: bool,
+    /// The buffer size to open streams with, if the user chose one.
+    buffer_size: Option<BufferSize>,
 }
 
 impl DeviceController {
     pub fn new() -> Self {
         Self {
             device_provider: None,
+            provider_name: None,
+            selected_uid: None,
+            selected_missing: false,
             device: None,
             stream: None,
             current_format: None,
             last_volume: 1.0,
             last_replaygain: 1.0,
+            last_media_name: None,
+            native_format: None,
+            bypass_gain: false,
+            buffer_size: None,
         }
     }
 
-    /// Initialize the device provider based on the environment or platform defaults.
-    pub fn initialize_provider(&mut self) {
+    /// Initialize the device provider based on the environment, the given setting, or platform
+    /// defaults, in that order.
+    pub fn initialize_provider(&mut self, requested: Option<&str>) {
+        let provider_name = Self::resolve_provider(requested);
+        self.initialize_provider_by_name(&provider_name);
+    }
+
+    /// Switch to a different device provider, if the given setting resolves to a provider other
+    /// than the current one.
+    pub fn switch_provider(&mut self, requested: Option<&str>) {
+        let provider_name = Self::resolve_provider(requested);
+        if self.provider_name != Some(provider_name.as_str()) {
+            self.initialize_provider_by_name(&provider_name);
+        }
+    }
+
+    fn resolve_provider(requested: Option<&str>) -> String {
         let default_device_provider = match OS {
-            "linux" => "cpal", // TODO: use pulseaudio
+            "linux" if cfg!(feature = "pipewire") => "pipewire",
             "windows" => "win_audiograph",
             _ => "cpal",
         };
 
-        let requested_device_provider = std::env::var("DEVICE_PROVIDER")
-            .unwrap_or_else(|_| default_device_provider.to_string());
-
-        self.initialize_provider_by_name(&requested_device_provider);
+        std::env::var("DEVICE_PROVIDER")
+            .ok()
+            .or_else(|| requested.map(str::to_string))
+            .unwrap_or_else(|| default_device_provider.to_string())
     }
 
     /// Initialize a specific device provider by name.
     pub fn initialize_provider_by_name(&mut self, provider_name: &str) {
+        self.close_stream();
+        self.device = None;
+
         match provider_name {
             "pulse" => {
                 warn!("pulseaudio support was removed");
-                warn!("Falling back to CPAL");
-                self.device_provider = Some(Box::new(CpalProvider::default()));
+                if cfg!(all(target_os = "linux", feature = "pipewire")) {
+                    warn!("Using PipeWire instead");
+                    self.initialize_provider_by_name("pipewire");
+                } else {
+                    warn!("Falling back to CPAL");
+                    self.device_provider = Some(Box::new(CpalProvider::default()));
+                    self.provider_name = Some("cpal");
+                }
+            }
+            "pipewire" => {
+                #[cfg(all(target_os = "linux", feature = "pipewire"))]
+                match PipeWireProvider::connect() {
+                    Ok(provider) => {
+                        self.device_provider = Some(Box::new(provider));
+                        self.provider_name = Some("pipewire");
+                    }
+                    Err(e) => {
+                        warn!("Failed to connect to PipeWire: {}", e);
+                        warn!("Falling back to CPAL");
+                        self.device_provider = Some(Box::new(CpalProvider::default()));
+                        self.provider_name = Some("cpal");
+                    }
+                }
+                #[cfg(not(all(target_os = "linux", feature = "pipewire")))]
+                {
+                    warn!("pipewire support is not enabled in this build");
+                    warn!("Falling back to CPAL");
+                    self.device_provider = Some(Box::new(CpalProvider::default()));
+                    self.provider_name = Some("cpal");
+                }
             }
             "win_audiograph" => {
                 #[cfg(target_os = "windows")]
                 {
                     self.device_provider = Some(Box::new(AudioGraphProvider::default()));
+                    self.provider_name = Some("win_audiograph");
                 }
                 #[cfg(not(target_os = "windows"))]
                 {
                     warn!("win_audiograph is not supported on this platform");
                     warn!("Falling back to CPAL");
                     self.device_provider = Some(Box::new(CpalProvider::default()));
+                    self.provider_name = Some("cpal");
+                }
+            }
+            "alsa" => {
+                #[cfg(target_os = "linux")]
+                {
+                    self.device_provider = Some(Box::new(AlsaProvider::default()));
+                    self.provider_name = Some("alsa");
+                }
+                #[cfg(not(target_os = "linux"))]
+                {
+                    warn!("ALSA is only available on Linux");
+                    warn!("Falling back to CPAL");
+                    self.device_provider = Some(Box::new(CpalProvider::default()));
+                    self.provider_name = Some("cpal");
                 }
             }
             "cpal" => {
                 self.device_provider = Some(Box::new(CpalProvider::default()));
+                self.provider_name = Some("cpal");
             }
             "dummy" => {
                 self.device_provider = Some(Box::new(DummyDeviceProvider::new()));
+                self.provider_name = Some("dummy");
+            }
+            "file" => {
+                self.device_provider = Some(Box::new(FileProvider::new()));
+                self.provider_name = Some("file");
+            }
+            "network" => {
+                self.device_provider = Some(Box::new(NetworkProvider::new()));
+                self.provider_name = Some("network");
             }
             _ => {
                 warn!("Unknown device provider: {}", provider_name);
                 warn!("Falling back to CPAL");
                 self.device_provider = Some(Box::new(CpalProvider::default()));
+                self.provider_name = Some("cpal");
+            }
+        }
+
+        self.initialize_current_provider();
+    }
+
+    /// Use the given device provider instead of one chosen by name.
+    #[cfg(test)]
+    pub fn use_provider(&mut self, provider_name: &'static str, provider: Box<dyn DeviceProvider>) {
+        self.close_stream();
+        self.device = None;
+
+        self.device_provider = Some(provider);
+        self.provider_name = Some(provider_name);
+        self.initialize_current_provider();
+    }
+
+    fn initialize_current_provider(&mut self) {
+        if let Some(provider) = &mut self.device_provider
+            && let Err(e) = provider.initialize()
+        {
+            warn!("Failed to initialize device provider: {}", e);
+        }
+    }
+
+    /// Select the buffer size to open streams with. `None` uses the device's default. This takes
+    /// effect the next time a stream is created.
+    pub fn select_buffer_size(&mut self, buffer_size: Option<BufferSize>) {
+        self.buffer_size = buffer_size;
+    }
+
+    /// Select the device to open streams on. `None` follows the provider's default device. This
+    /// takes effect the next time a stream is created.
+    pub fn select_device(&mut self, uid: Option<String>) {
+        self.selected_uid = uid;
+        self.selected_missing = false;
+    }
+
+    /// Whether the selected device couldn't be found when the current stream was opened, so the
+    /// provider's default device is being used instead.
+    pub fn selected_device_missing(&self) -> bool {
+        self.selected_missing
+    }
+
+    /// Check for devices being connected or disconnected, and decide whether the stream has to
+    /// react. Returns `None` if no devices changed.
+    pub fn poll_device_changes(&mut self) -> Option<DeviceChangeAction> {
+        let changes = self.device_provider.as_mut()?.poll_device_changes();
+        if changes.is_empty() {
+            return None;
+        }
+
+        let current_uid = self.device.as_ref().and_then(|d| d.get_uid().ok());
+
+        Some(action_for_changes(
+            &changes,
+            current_uid.as_deref(),
+            self.selected_uid.as_deref(),
+            self.selected_missing,
+        ))
+    }
+
+    /// List the output devices offered by the device provider.
+    pub fn list_devices(&mut self) -> Result<Vec<OutputDevice>, DeviceError> {
+        let device_provider = self
+            .device_provider
+            .as_mut()
+            .ok_or(DeviceError::NoProvider)?;
+
+        let devices = device_provider
+            .get_devices()?
+            .into_iter()
+            .filter_map(|device| {
+                let name = device.get_name().ok()?;
+                let uid = device.get_uid().unwrap_or_else(|_| name.clone());
+                Some(OutputDevice { uid, name })
+            })
+            .collect();
+
+        Ok(devices)
+    }
+
+    /// Find the selected device, falling back to the provider's default device if it isn't
+    /// available.
+    fn find_device(&mut self) -> Result<Box<dyn Device>, DeviceError> {
+        let device_provider = self
+            .device_provider
+            .as_mut()
+            .ok_or(DeviceError::NoProvider)?;
+
+        if let Some(uid) = &self.selected_uid {
+            match device_provider.get_device_by_uid(uid) {
+                Ok(device) => {
+                    self.selected_missing = false;
+                    return Ok(device);
+                }
+                Err(FindError::DeviceDoesNotExist) => {
+                    if !self.selected_missing {
+                        warn!(
+                            "Output device '{}' not found, using the default device",
+                            uid
+                        );
+                    }
+                    self.selected_missing = true;
+                }
+                Err(e) => return Err(e.into()),
             }
         }
+
+        Ok(device_provider.get_default_device()?)
     }
 
     /// Check if a stream is currently open.
@@ -168,24 +429,45 @@ impl DeviceController {
     ) -> Result<FormatInfo, DeviceError> {
         self.close_stream();
 
-        let device_provider = self
-            .device_provider
-            .as_mut()
-            .ok_or(DeviceError::NoProvider)?;
-
-        let mut device = device_provider.get_default_device()?;
+        let mut device = self.find_device()?;
 
         let mut format = device
             .get_default_format()
             .map_err(|_| DeviceError::NoDevice)?;
 
+        if let Some(buffer_size) = self.buffer_size {
+            format.buffer_size = buffer_size;
+        }
+
         let requested = channels.map(|ch| FormatInfo {
             channels: ch,
             sample_rate: format.sample_rate,
             ..format
         });
 
-        let stream = if let Some(req) = requested {
+        let native = self.native_format.and_then(|(sample_type, sample_rate)| {
+            let native = FormatInfo {
+                sample_type,
+                sample_rate,
+                channels: channels.unwrap_or(format.channels),
+                ..format
+            };
+
+            Self::device_supports(device.as_ref(), &native).then_some(native)
+        });
+
+        let native_stream = native.and_then(|native| match device.open_device(native) {
+            Ok(stream) => Some((stream, native)),
+            Err(e) => {
+                warn!(?native, "Failed to open device with native format: {:?}", e);
+                None
+            }
+        });
+
+        let stream = if let Some((stream, native)) = native_stream {
+            format = native;
+            stream
+        } else if let Some(req) = requested {
             match device.open_device(req) {
                 Ok(stream) => {
                     format = req;
@@ -208,9 +490,13 @@ impl DeviceController {
         self.current_format = Some(format);
         self.device = Some(device);
 
+        let (volume, replaygain) = self.effective_gain();
         if let Some(stream) = &mut self.stream {
-            stream.set_volume(self.last_volume).ok();
-            stream.set_replaygain(self.last_replaygain).ok();
+            stream.set_volume(volume).ok();
+            stream.set_replaygain(replaygain).ok();
+            if let Some(name) = &self.last_media_name {
+                stream.set_media_name(name).ok();
+            }
         }
 
         info!(
@@ -232,12 +518,7 @@ impl DeviceController {
         force: bool,
         channels: Option<ChannelSpec>,
     ) -> Result<FormatInfo, DeviceError> {
-        let device_provider = self
-            .device_provider
-            .as_mut()
-            .ok_or(DeviceError::NoProvider)?;
-
-        let new_device = device_provider.get_default_device()?;
+        let new_device = self.find_device()?;
         let new_uid = new_device.get_uid().ok();
         let current_uid = self.device.as_ref().and_then(|d| d.get_uid().ok());
 
@@ -250,7 +531,7 @@ impl DeviceController {
         }
 
         // Need to drop the new_device before calling create_stream since it will
-        // try to get the default device again
+        // try to find the device again
         drop(new_device);
 
         self.create_stream(channels)
@@ -280,6 +561,13 @@ impl DeviceController {
         Ok(())
     }
 
+    /// The number of frames submitted to the current stream that haven't been played yet.
+    pub fn buffered_frames(&self) -> usize {
+        self.stream
+            .as_ref()
+            .map_or(0, |stream| stream.buffered_frames())
+    }
+
     /// Reset the stream buffer.
     pub fn reset(&mut self) -> Result<(), DeviceError> {
         let stream = self.stream.as_mut().ok_or(DeviceError::NoStream)?;
@@ -320,22 +608,95 @@ impl DeviceController {
         };
 
         self.last_volume = volume_scaled;
+        self.apply_gain()
+    }
+
+    /// Set the ReplayGain multiplier (linear).
+    pub fn set_replaygain(&mut self, gain: f64) -> Result<(), DeviceError> {
+        self.last_replaygain = gain;
+        self.apply_gain()
+    }
+
+    /// Bypass volume and ReplayGain, passing samples to the device unchanged. The last values are
+    /// restored when the bypass is turned off.
+    pub fn set_gain_bypass(&mut self, bypass: bool) -> Result<(), DeviceError> {
+        self.bypass_gain = bypass;
+        self.apply_gain()
+    }
+
+    /// The volume and ReplayGain multiplier that should be applied to the stream.
+    fn effective_gain(&self) -> (f64, f64) {
+        if self.bypass_gain {
+            (1.0, 1.0)
+        } else {
+            (self.last_volume, self.last_replaygain)
+        }
+    }
+
+    /// The total gain the stream applies to samples before quantizing them, once any volume
+    /// change has finished ramping.
+    pub fn output_gain(&self) -> f64 {
+        let (volume, replaygain) = self.effective_gain();
+        volume * replaygain
+    }
+
+    fn apply_gain(&mut self) -> Result<(), DeviceError> {
+        let (volume, replaygain) = self.effective_gain();
 
         if let Some(stream) = &mut self.stream {
-            stream.set_volume(volume_scaled)?;
+            stream.set_volume(volume)?;
+            stream.set_replaygain(replaygain)?;
         }
 
         Ok(())
     }
 
-    /// Set the ReplayGain multiplier (linear).
-    pub fn set_replaygain(&mut self, gain: f64) -> Result<(), DeviceError> {
-        self.last_replaygain = gain;
+    /// Set the sample format and rate that streams should be opened with when the device
+    /// supports them. This takes effect the next time a stream is created.
+    pub fn set_native_format(&mut self, native: Option<(SampleFormat, u32)>) {
+        self.native_format = native;
+    }
 
-        if let Some(stream) = &mut self.stream {
-            stream.set_replaygain(gain)?;
+    /// Whether the stream would have to be reopened to play the given format natively. This is
+    /// false if the stream already uses the format, or if the device doesn't support it.
+    pub fn needs_native_format_change(&self, sample_type: SampleFormat, sample_rate: u32) -> bool {
+        let (Some(device), Some(current)) = (&self.device, &self.current_format) else {
+            return false;
+        };
+
+        if current.sample_type == sample_type && current.sample_rate == sample_rate {
+            return false;
         }
 
+        Self::device_supports(
+            device.as_ref(),
+            &FormatInfo {
+                sample_type,
+                sample_rate,
+                ..*current
+            },
+        )
+    }
+
+    fn device_supports(device: &dyn Device, format: &FormatInfo) -> bool {
+        device.get_supported_formats().is_ok_and(|formats| {
+            formats.iter().any(|supported| {
+                supported.originating_provider == format.originating_provider
+                    && supported.supports(format.sample_type, format.sample_rate, format.channels)
+            })
+        })
+    }
+
+    /// Set the name of the media being played, shown by providers that publish stream
+    /// properties to the system.
+    pub fn set_media_name(&mut self, name: Option<String>) -> Result<(), DeviceError> {
+        if let Some(stream) = &mut self.stream
+            && let Some(name) = &name
+        {
+            stream.set_media_name(name)?;
+        }
+
+        self.last_media_name = name;
         Ok(())
     }
 
@@ -358,3 +719,60 @@ impl Default for DeviceController {
         Self::new()
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn removed(uid: &str) -> DeviceChange {
+        DeviceChange::Removed(uid.to_string())
+    }
+
+    fn added(uid: &str) -> DeviceChange {
+        DeviceChange::Added(uid.to_string())
+    }
+
+    fn default_changed(uid: &str) -> DeviceChange {
+        DeviceChange::DefaultChanged(uid.to_string())
+    }
+
+    #[test]
+    fn follows_default_device() {
+        let changes = [added("Headphones"), default_changed("Headphones")];
+        assert_eq!(
+            action_for_changes(&changes, Some("Speakers"), None, false),
+            DeviceChangeAction::Move
+        );
+
+        // a selected device isn't left for the new default
+        assert_eq!(
+            action_for_changes(&changes, Some("DAC"), Some("DAC"), false),
+            DeviceChangeAction::Stay
+        );
+    }
+
+    #[test]
+    fn reacts_to_current_device_loss() {
+        let changes = [removed("DAC"), default_changed("Speakers")];
+        assert_eq!(
+            action_for_changes(&changes, Some("DAC"), Some("DAC"), false),
+            DeviceChangeAction::Lost
+        );
+        assert_eq!(
+            action_for_changes(&[removed("Headphones")], Some("DAC"), None, false),
+            DeviceChangeAction::Stay
+        );
+    }
+
+    #[test]
+    fn returns_to_reconnected_device() {
+        assert_eq!(
+            action_for_changes(&[added("DAC")], Some("Speakers"), Some("DAC"), true),
+            DeviceChangeAction::Move
+        );
+        assert_eq!(
+            action_for_changes(&[added("DAC")], Some("Speakers"), None, false),
+            DeviceChangeAction::Stay
+        );
+    }
+}
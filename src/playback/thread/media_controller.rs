@@ -1,5 +1,3 @@
-use std::path::{Path, PathBuf};
-
 use tracing::info;
 
 use crate::{
@@ -12,13 +10,25 @@ use crate::{
         lookup_table::try_open_media,
         metadata::Metadata,
         pipeline::{ChannelProducers, DecodeResult},
+        ranged::RangedStream,
         traits::{F32DecodeResult, MediaProviderFeatures, MediaStream},
     },
+    playback::queue::TrackSource,
 };
 
+#[derive(Debug, Clone, Copy)]
 pub struct MediaInfo {
     pub channels: ChannelSpec,
     pub duration_secs: Option<u64>,
+    pub sample_rate: Option<u32>,
+    pub sample_format: Option<SampleFormat>,
+}
+
+/// A stream that has been opened and started ahead of time, waiting to replace the current one.
+struct PreparedStream {
+    stream: Box<dyn MediaStream>,
+    source: TrackSource,
+    info: MediaInfo,
 }
 
 pub struct CompleteMetadata {
@@ -32,14 +42,18 @@ pub struct CompleteMetadata {
 /// including opening/closing files, decoding audio, and retrieving metadata.
 pub struct MediaController {
     media_stream: Option<Box<dyn MediaStream>>,
-    current_path: Option<PathBuf>,
+    current_source: Option<TrackSource>,
+    /// The upcoming track, opened in advance so that it can be spliced onto the end of the
+    /// current one without waiting for the file to be probed.
+    prepared: Option<PreparedStream>,
 }
 
 impl MediaController {
     pub fn new() -> Self {
         Self {
             media_stream: None,
-            current_path: None,
+            current_source: None,
+            prepared: None,
         }
     }
 
@@ -51,14 +65,37 @@ impl MediaController {
     /// Open a media file and prepare it for playback.
     ///
     /// Returns information about the opened media file that can be used
-    /// to configure the audio pipeline and device.
-    pub fn open(&mut self, path: &Path) -> Result<MediaInfo, PlaybackStartError> {
-        info!("Opening track '{}'", path.display());
+    /// to configure the audio pipeline and device. If the file was already prepared with
+    /// [`MediaController::prepare`], the prepared stream is used instead of opening the file again.
+    pub fn open(&mut self, source: &TrackSource) -> Result<MediaInfo, PlaybackStartError> {
+        info!("Opening track '{}'", source);
+
+        if self.prepared_source() == Some(source)
+            && let Some(info) = self.promote_prepared()
+        {
+            return Ok(info);
+        }
 
         // Close any existing stream
         self.close();
+        self.discard_prepared();
+
+        let (media_stream, info) = Self::open_stream(source)?;
+
+        self.media_stream = Some(media_stream);
+        self.current_source = Some(source.clone());
+
+        Ok(info)
+    }
 
-        let src = try_open_media(path, MediaProviderFeatures::PROVIDES_DECODER);
+    /// Open and start a stream for the given track, without touching the current stream.
+    ///
+    /// For a track split from a file by a CUE sheet, the file is opened and only the track's
+    /// section of it is played.
+    fn open_stream(
+        source: &TrackSource,
+    ) -> Result<(Box<dyn MediaStream>, MediaInfo), PlaybackStartError> {
+        let src = try_open_media(&source.path, MediaProviderFeatures::PROVIDES_DECODER);
 
         if let Err(e) = src {
             return Err(PlaybackStartError::MediaError(format!(
@@ -77,19 +114,85 @@ impl MediaController {
             PlaybackStartError::MediaError(format!("Unable to start playback: {}", e))
         })?;
 
+        if let Some(range) = source.range {
+            let ranged = RangedStream::new(media_stream, &source.path, range).map_err(|e| {
+                PlaybackStartError::MediaError(format!("Unable to seek to track start: {}", e))
+            })?;
+            media_stream = Box::new(ranged);
+        }
+
         let channels = media_stream.channels().map_err(|e| {
             PlaybackStartError::MediaError(format!("Unable to get channels: {}", e))
         })?;
 
-        let duration_secs = media_stream.duration_secs().ok();
+        let info = MediaInfo {
+            channels,
+            duration_secs: media_stream.duration_secs().ok(),
+            sample_rate: media_stream.sample_rate().ok(),
+            sample_format: media_stream.sample_format().ok(),
+        };
 
-        self.media_stream = Some(media_stream);
-        self.current_path = Some(path.to_path_buf());
+        Ok((media_stream, info))
+    }
 
-        Ok(MediaInfo {
-            channels,
-            duration_secs,
-        })
+    /// Open the given track in advance, so that it is ready to be played as soon as the current
+    /// stream ends. Any previously prepared stream is discarded.
+    pub fn prepare(&mut self, source: &TrackSource) -> Result<MediaInfo, PlaybackStartError> {
+        self.discard_prepared();
+
+        let (stream, info) = Self::open_stream(source)?;
+        self.prepared = Some(PreparedStream {
+            stream,
+            source: source.clone(),
+            info,
+        });
+
+        Ok(info)
+    }
+
+    /// Returns the track of the prepared stream, if there is one.
+    pub fn prepared_source(&self) -> Option<&TrackSource> {
+        self.prepared.as_ref().map(|p| &p.source)
+    }
+
+    /// Returns information about the prepared stream, if there is one.
+    pub fn prepared_info(&self) -> Option<&MediaInfo> {
+        self.prepared.as_ref().map(|p| &p.info)
+    }
+
+    /// Close the prepared stream, if any.
+    pub fn discard_prepared(&mut self) {
+        if let Some(mut prepared) = self.prepared.take() {
+            prepared.stream.stop_playback().ok();
+            prepared.stream.close().ok();
+        }
+    }
+
+    /// Close the current stream and replace it with the prepared stream.
+    ///
+    /// Returns `None` (leaving the current stream untouched) if nothing was prepared.
+    pub fn promote_prepared(&mut self) -> Option<MediaInfo> {
+        let (info, previous) = self.swap_prepared()?;
+
+        if let Some(mut stream) = previous {
+            stream.stop_playback().ok();
+            stream.close().ok();
+        }
+
+        Some(info)
+    }
+
+    /// Replace the current stream with the prepared stream, handing the previous stream back to
+    /// the caller instead of closing it.
+    ///
+    /// Returns `None` (leaving the current stream untouched) if nothing was prepared.
+    pub fn swap_prepared(&mut self) -> Option<(MediaInfo, Option<Box<dyn MediaStream>>)> {
+        let prepared = self.prepared.take()?;
+
+        let previous = self.media_stream.replace(prepared.stream);
+        self.current_source = Some(prepared.source);
+
+        Some((prepared.info, previous))
     }
 
     /// Close the current media stream, if any.
@@ -99,11 +202,11 @@ impl MediaController {
             stream.close().ok();
         }
 
-        self.current_path = None;
+        self.current_source = None;
     }
 
-    pub fn current_path(&self) -> Option<&Path> {
-        self.current_path.as_deref()
+    pub fn current_source(&self) -> Option<&TrackSource> {
+        self.current_source.as_ref()
     }
 
     /// Seek to the specified time in seconds.
@@ -162,6 +265,13 @@ impl MediaController {
         })
     }
 
+    pub fn duration_secs(&self) -> Result<u64, TrackDurationError> {
+        self.media_stream
+            .as_ref()
+            .ok_or(TrackDurationError::NeverStarted)?
+            .duration_secs()
+    }
+
     pub fn position_ms(&self) -> Result<u64, TrackDurationError> {
         self.media_stream
             .as_ref()
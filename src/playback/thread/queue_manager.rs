@@ -1,7 +1,6 @@
 use std::{
     collections::VecDeque,
     mem::take,
-    path::PathBuf,
     sync::{Arc, RwLock},
 };
 
@@ -9,7 +8,11 @@ use rand::{rng, seq::SliceRandom};
 use smallvec::{SmallVec, smallvec};
 
 use crate::{
-    playback::{events::RepeatState, queue::QueueItemData, session_storage::PlaybackSessionData},
+    playback::{
+        events::RepeatState,
+        queue::{QueueItemData, TrackSource},
+        session_storage::PlaybackSessionData,
+    },
     settings::playback::PlaybackSettings,
 };
 
@@ -26,11 +29,11 @@ pub enum QueueNavigationResult {
     /// The queue position changed.
     Changed {
         index: usize,
-        path: PathBuf,
+        source: TrackSource,
         reshuffled: Reshuffled,
     },
     /// The current track should repeat (RepeatOne mode).
-    Unchanged { path: PathBuf },
+    Unchanged { source: TrackSource },
     /// End of queue reached.
     EndOfQueue,
 }
@@ -41,8 +44,8 @@ pub enum DequeueResult {
     Removed { new_position: usize },
     /// The currently playing item was removed.
     RemovedCurrent {
-        /// The path of the next track to play, if any.
-        new_path: Option<PathBuf>,
+        /// The next track to play, if any.
+        new_source: Option<TrackSource>,
     },
     /// Nothing changed (index out of bounds).
     Unchanged,
@@ -53,7 +56,7 @@ pub enum DequeueManyResult {
     /// Items were removed, queue position adjusted.
     Removed { new_position: usize },
     /// The currently playing item was removed.
-    RemovedCurrent { new_path: Option<PathBuf> },
+    RemovedCurrent { new_source: Option<TrackSource> },
     /// Nothing changed (indices empty or all out of bounds).
     Unchanged,
 }
@@ -107,7 +110,7 @@ pub enum ReplaceResult {
 
 #[derive(Debug, Clone)]
 pub enum JumpResult {
-    Jumped { path: PathBuf },
+    Jumped { source: TrackSource },
     OutOfBounds,
 }
 
@@ -161,10 +164,10 @@ pub enum UndoAction {
 
 #[derive(Debug, Clone)]
 pub enum UndoResult {
-    /// The last action was undone successfully. Contains the current index and path.
+    /// The last action was undone successfully. Contains the current index and track.
     Ok {
         current_idx: usize,
-        current_path: PathBuf,
+        current_source: TrackSource,
         shuffle: bool,
     },
     /// The last action was undone successfully, but no current track is selected.
@@ -208,7 +211,7 @@ impl QueueManager {
         {
             UndoResult::Ok {
                 current_idx,
-                current_path: queue[current_idx].get_path().clone(),
+                current_source: queue[current_idx].source(),
                 shuffle,
             }
         } else {
@@ -516,11 +519,11 @@ impl QueueManager {
 
             if self.repeat == RepeatState::RepeatingOne
                 && !user_initiated
-                && let Some(path) = queue.get(self.queue_next.saturating_sub(1))
-                && Self::item_is_playable(path)
+                && let Some(item) = queue.get(self.queue_next.saturating_sub(1))
+                && Self::item_is_playable(item)
             {
                 return QueueNavigationResult::Unchanged {
-                    path: path.get_path().clone(),
+                    source: item.source(),
                 };
             }
 
@@ -528,7 +531,7 @@ impl QueueManager {
                 self.queue_next = index + 1;
                 QueueNavigationResult::Changed {
                     index,
-                    path: queue[index].get_path().clone(),
+                    source: queue[index].source(),
                     reshuffled: Reshuffled::NotReshuffled,
                 }
             } else if self.repeat == RepeatState::Repeating {
@@ -539,7 +542,7 @@ impl QueueManager {
                     self.queue_next = index + 1;
                     QueueNavigationResult::Changed {
                         index,
-                        path: queue[index].get_path().clone(),
+                        source: queue[index].source(),
                         reshuffled: if self.shuffle {
                             Reshuffled::Reshuffled
                         } else {
@@ -565,6 +568,52 @@ impl QueueManager {
         result
     }
 
+    /// Get the track that `next(false)` would move to, without advancing.
+    ///
+    /// Returns `None` if playback would stop, or if the next track can't be known in advance
+    /// because the queue is reshuffled when it wraps around.
+    pub fn peek_next(&self) -> Option<TrackSource> {
+        let queue = self.queue.read().expect("poisoned queue lock");
+        self.peek_next_index(&queue)
+            .map(|index| queue[index].source())
+    }
+
+    /// Returns true if the track that `next(false)` would move to is from the same known album
+    /// as the current track.
+    pub fn next_shares_album(&self) -> bool {
+        let queue = self.queue.read().expect("poisoned queue lock");
+        let Some(current) = self
+            .queue_next
+            .checked_sub(1)
+            .and_then(|index| queue.get(index))
+            .and_then(QueueItemData::get_db_album_id)
+        else {
+            return false;
+        };
+
+        self.peek_next_index(&queue)
+            .and_then(|index| queue[index].get_db_album_id())
+            == Some(current)
+    }
+
+    fn peek_next_index(&self, queue: &[QueueItemData]) -> Option<usize> {
+        if self.repeat == RepeatState::RepeatingOne {
+            let index = self.queue_next.checked_sub(1)?;
+            return queue
+                .get(index)
+                .is_some_and(Self::item_is_playable)
+                .then_some(index);
+        }
+
+        if let Some(index) = Self::next_playable_from(queue, self.queue_next) {
+            Some(index)
+        } else if self.repeat == RepeatState::Repeating && !self.shuffle {
+            Self::first_playable_index(queue)
+        } else {
+            None
+        }
+    }
+
     /// Go to the previous track in the queue.
     pub fn previous(&mut self) -> QueueNavigationResult {
         let result = {
@@ -576,7 +625,7 @@ impl QueueManager {
                 self.queue_next = index + 1;
                 QueueNavigationResult::Changed {
                     index,
-                    path: queue[index].get_path().clone(),
+                    source: queue[index].source(),
                     reshuffled: Reshuffled::NotReshuffled,
                 }
             } else if self.repeat == RepeatState::Repeating
@@ -591,7 +640,7 @@ impl QueueManager {
                 self.queue_next = index + 1;
                 QueueNavigationResult::Changed {
                     index,
-                    path: queue[index].get_path().clone(),
+                    source: queue[index].source(),
                     reshuffled: if self.shuffle {
                         Reshuffled::Reshuffled
                     } else {
@@ -619,11 +668,11 @@ impl QueueManager {
         let queue = self.queue.read().expect("poisoned queue lock");
 
         if index < queue.len() && Self::item_is_playable(&queue[index]) {
-            let path = queue[index].get_path().clone();
+            let source = queue[index].source();
             drop(queue);
             self.queue_next = index + 1;
             self.persist_session_state();
-            JumpResult::Jumped { path }
+            JumpResult::Jumped { source }
         } else {
             JumpResult::OutOfBounds
         }
@@ -840,10 +889,10 @@ impl QueueManager {
         let current = self.queue_next.saturating_sub(1);
 
         let res = if index == current {
-            let new_path = Self::next_playable_from(&queue, current)
+            let new_source = Self::next_playable_from(&queue, current)
                 .and_then(|idx| queue.get(idx))
-                .map(|v| v.get_path().clone());
-            DequeueResult::RemovedCurrent { new_path }
+                .map(QueueItemData::source);
+            DequeueResult::RemovedCurrent { new_source }
         } else if index < current {
             self.queue_next -= 1;
             DequeueResult::Removed {
@@ -910,10 +959,10 @@ impl QueueManager {
 
         let res = if removed_current {
             let current = current.expect("removed_current implies current is Some");
-            let new_path = Self::next_playable_from(&queue, current - items_before_current)
+            let new_source = Self::next_playable_from(&queue, current - items_before_current)
                 .and_then(|idx| queue.get(idx))
-                .map(|v| v.get_path().clone());
-            DequeueManyResult::RemovedCurrent { new_path }
+                .map(QueueItemData::source);
+            DequeueManyResult::RemovedCurrent { new_source }
         } else if self.queue_next > 0 {
             self.queue_next -= items_before_current;
             DequeueManyResult::Removed {
@@ -1250,6 +1299,15 @@ impl QueueManager {
         });
     }
 
+    /// Persist the position inside the current track, so that it can be restored on startup.
+    pub fn persist_track_position(&self, position_ms: Option<u64>) {
+        self.storage_tx.send_if_modified(|session| {
+            let changed = session.position_ms != position_ms;
+            session.position_ms = position_ms;
+            changed
+        });
+    }
+
     /// Persist the queue session when queue contents or ordering changed.
     ///
     /// This refreshes the stored queue alongside the current position,
@@ -1284,10 +1342,15 @@ mod tests {
     use serde_json::json;
     use tokio::sync::watch;
 
-    use super::{DequeueManyResult, MoveItemsResult, QueueManager, UndoResult};
+    use super::{
+        DequeueManyResult, MoveItemsResult, QueueManager, QueueNavigationResult, UndoResult,
+    };
     use crate::{
-        playback::{queue::QueueItemData, session_storage::PlaybackSessionData},
+        playback::{
+            events::RepeatState, queue::QueueItemData, session_storage::PlaybackSessionData,
+        },
         settings::playback::PlaybackSettings,
+        test_support::TestDir,
     };
 
     #[derive(Debug, Clone, PartialEq)]
@@ -1307,6 +1370,18 @@ mod tests {
         .expect("valid queue item")
     }
 
+    fn playable_item(dir: &TestDir, id: i64) -> QueueItemData {
+        let path = dir.join(&format!("{id}.flac"));
+        std::fs::write(&path, b"").expect("can create test file");
+
+        serde_json::from_value(json!({
+            "db_id": id,
+            "db_album_id": id / 10,
+            "path": path,
+        }))
+        .expect("valid queue item")
+    }
+
     fn manager_with_queue(items: Vec<QueueItemData>) -> QueueManager {
         let queue = Arc::new(RwLock::new(items));
         let (storage_tx, _storage_rx) = watch::channel(PlaybackSessionData::default());
@@ -1571,7 +1646,7 @@ mod tests {
 
         let res = manager.dequeue_many(vec![1, 3]);
 
-        // new_path depends on files existing on disk; just assert the variant.
+        // new_source depends on files existing on disk; just assert the variant.
         assert!(matches!(res, DequeueManyResult::RemovedCurrent { .. }));
         assert_eq!(manager.len(), 2);
     }
@@ -1761,4 +1836,65 @@ mod tests {
         assert!(!matches!(manager.undo_last_action(), UndoResult::None));
         assert_eq!(snapshot(&manager), before);
     }
+
+    #[test]
+    fn peek_next_matches_next_without_advancing() {
+        let dir = TestDir::new("hummingbird-queue-peek-test");
+        let mut manager = manager_with_queue(vec![
+            playable_item(&dir, 1),
+            item(2),
+            playable_item(&dir, 3),
+        ]);
+        manager.set_position(0);
+
+        let peeked = manager.peek_next();
+        assert_eq!(manager.current_position(), Some(0));
+
+        let QueueNavigationResult::Changed { index, source, .. } = manager.next(false) else {
+            panic!("expected the queue to advance");
+        };
+        assert_eq!(index, 2);
+        assert_eq!(peeked, Some(source));
+
+        assert_eq!(manager.peek_next(), None);
+    }
+
+    #[test]
+    fn peek_next_follows_repeat_state() {
+        let dir = TestDir::new("hummingbird-queue-peek-test");
+        let first = playable_item(&dir, 1);
+        let second = playable_item(&dir, 2);
+        let mut manager = manager_with_queue(vec![first.clone(), second.clone()]);
+        manager.set_position(1);
+
+        manager.set_repeat(RepeatState::Repeating);
+        assert_eq!(manager.peek_next(), Some(first.source()));
+
+        manager.set_repeat(RepeatState::RepeatingOne);
+        assert_eq!(manager.peek_next(), Some(second.source()));
+
+        manager.set_repeat(RepeatState::Repeating);
+        manager.toggle_shuffle();
+        manager.set_position(manager.len() - 1);
+        assert_eq!(manager.peek_next(), None);
+    }
+
+    #[test]
+    fn next_shares_album_compares_current_and_next_items() {
+        let dir = TestDir::new("hummingbird-queue-peek-test");
+        let mut manager = manager_with_queue(vec![
+            playable_item(&dir, 11),
+            playable_item(&dir, 12),
+            playable_item(&dir, 21),
+        ]);
+
+        manager.set_position(0);
+        assert!(manager.next_shares_album());
+
+        manager.set_position(1);
+        assert!(!manager.next_shares_album());
+
+        manager.set_position(2);
+        assert!(!manager.next_shares_album());
+    }
 }
@@ -0,0 +1,101 @@
+use std::time::{Duration, Instant};
+
+use crate::playback::events::{SleepTimer, SleepTimerState};
+
+/// How long before a timed sleep timer fires the volume starts fading out.
+const FADE_OUT_LENGTH: Duration = Duration::from_secs(20);
+
+/// A running sleep timer.
+pub struct ActiveSleepTimer {
+    timer: SleepTimer,
+    deadline: Option<Instant>,
+}
+
+impl ActiveSleepTimer {
+    pub fn new(timer: SleepTimer, now: Instant) -> Self {
+        let deadline = match timer {
+            SleepTimer::Minutes(minutes) => Some(now + Duration::from_secs(minutes as u64 * 60)),
+            SleepTimer::EndOfTrack | SleepTimer::EndOfAlbum => None,
+        };
+
+        Self { timer, deadline }
+    }
+
+    pub fn state(&self, now: Instant) -> SleepTimerState {
+        match (self.timer, self.deadline) {
+            (SleepTimer::EndOfTrack, _) => SleepTimerState::EndOfTrack,
+            (SleepTimer::EndOfAlbum, _) => SleepTimerState::EndOfAlbum,
+            (SleepTimer::Minutes(_), deadline) => {
+                let remaining = deadline.map_or(Duration::ZERO, |deadline| {
+                    deadline.saturating_duration_since(now)
+                });
+                // round up, so that the timer never shows zero while it's still running
+                SleepTimerState::Remaining(remaining.as_millis().div_ceil(1000) as u64)
+            }
+        }
+    }
+
+    /// Whether the timer has run out and playback should stop now.
+    pub fn is_due(&self, now: Instant) -> bool {
+        self.deadline.is_some_and(|deadline| now >= deadline)
+    }
+
+    /// The factor the volume should be scaled by, fading linearly to silence over the last
+    /// [`FADE_OUT_LENGTH`] of a timed sleep timer.
+    pub fn fade_gain(&self, now: Instant) -> f64 {
+        let Some(deadline) = self.deadline else {
+            return 1.0;
+        };
+
+        let remaining = deadline.saturating_duration_since(now);
+        (remaining.as_secs_f64() / FADE_OUT_LENGTH.as_secs_f64()).min(1.0)
+    }
+
+    /// Whether playback should stop once the current track ends, instead of moving on to the
+    /// next one.
+    pub fn stops_after_current(&self, next_shares_album: bool) -> bool {
+        match self.timer {
+            SleepTimer::Minutes(_) => false,
+            SleepTimer::EndOfTrack => true,
+            SleepTimer::EndOfAlbum => !next_shares_album,
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn timed_sleep_timer_fades_out_before_firing() {
+        let start = Instant::now();
+        let timer = ActiveSleepTimer::new(SleepTimer::Minutes(1), start);
+
+        assert_eq!(timer.state(start), SleepTimerState::Remaining(60));
+        assert_eq!(timer.fade_gain(start), 1.0);
+        assert!(!timer.is_due(start));
+
+        let fading = start + Duration::from_secs(50);
+        assert!((timer.fade_gain(fading) - 0.5).abs() < 1e-9);
+        assert_eq!(timer.state(fading), SleepTimerState::Remaining(10));
+
+        let end = start + Duration::from_secs(60);
+        assert_eq!(timer.fade_gain(end), 0.0);
+        assert!(timer.is_due(end));
+        assert!(!timer.stops_after_current(false));
+    }
+
+    #[test]
+    fn album_sleep_timer_stops_at_album_boundary() {
+        let now = Instant::now();
+        let timer = ActiveSleepTimer::new(SleepTimer::EndOfAlbum, now);
+
+        assert!(!timer.stops_after_current(true));
+        assert!(timer.stops_after_current(false));
+        assert!(!timer.is_due(now + Duration::from_secs(3600)));
+        assert_eq!(timer.fade_gain(now), 1.0);
+
+        let track = ActiveSleepTimer::new(SleepTimer::EndOfTrack, now);
+        assert!(track.stops_after_current(true));
+    }
+}
@@ -19,7 +19,7 @@ use tracing::{Instrument as _, debug_span, error, trace_span, warn};
 use crate::{
     media::metadata::Metadata,
     playback::{
-        events::{PlaybackCommand, RepeatState},
+        events::{PlaybackCommand, RepeatState, SleepTimerState},
         interface::PlaybackInterface,
         thread::PlaybackState,
     },
@@ -68,6 +68,17 @@ pub trait PlaybackController: Send {
     /// Indicates that the playback volume has changed.
     async fn volume_changed(&mut self, new_volume: f64) -> anyhow::Result<()>;
 
+    /// Indicates that the playback rate has changed. Positions are always reported in the
+    /// track's own timeline, so at a rate of 2.0 the position advances two seconds per second.
+    async fn rate_changed(&mut self, new_rate: f64) -> anyhow::Result<()>;
+
+    /// Indicates that the sleep timer has been started, cancelled, or has fired, or that the
+    /// remaining time has changed. This occurs every second while a timed sleep timer is running.
+    async fn sleep_timer_changed(
+        &mut self,
+        sleep_timer: Option<SleepTimerState>,
+    ) -> anyhow::Result<()>;
+
     /// Indicates that new metadata has been recieved from the decoder. This may occur more than
     /// once per track.
     async fn metadata_changed(&mut self, metadata: &Metadata) -> anyhow::Result<()>;
@@ -146,6 +157,13 @@ impl ControllerBridge {
             .unwrap();
     }
 
+    #[cfg(target_os = "linux")]
+    pub fn set_rate(&self, rate: f64) {
+        self.playback_thread
+            .send(PlaybackCommand::SetSpeed(rate))
+            .unwrap();
+    }
+
     #[cfg(target_os = "linux")]
     pub fn toggle_shuffle(&self) {
         self.playback_thread
@@ -177,6 +195,8 @@ enum PbcEvent {
     DurationChanged(u64),
     NewFile(PathBuf),
     VolumeChanged(f64),
+    RateChanged(f64),
+    SleepTimerChanged(Option<SleepTimerState>),
     RepeatStateChanged(RepeatState),
     PlaybackStateChanged(PlaybackState),
     ShuffleStateChanged(bool),
@@ -191,6 +211,8 @@ impl PbcEvent {
             Self::DurationChanged(dur) => pbc.duration_changed(*dur).await,
             Self::NewFile(path) => pbc.new_file(path).await,
             Self::VolumeChanged(vol) => pbc.volume_changed(*vol).await,
+            Self::RateChanged(rate) => pbc.rate_changed(*rate).await,
+            Self::SleepTimerChanged(timer) => pbc.sleep_timer_changed(*timer).await,
             Self::RepeatStateChanged(state) => pbc.repeat_state_changed(*state).await,
             Self::PlaybackStateChanged(state) => pbc.playback_state_changed(*state).await,
             Self::ShuffleStateChanged(shuffle) => pbc.shuffle_state_changed(*shuffle).await,
@@ -208,6 +230,8 @@ pub fn register_pbc_event_handlers(cx: &mut App) {
     let duration = playback_info.duration.clone();
     let track = playback_info.current_track.clone();
     let volume = playback_info.volume.clone();
+    let speed = playback_info.speed.clone();
+    let sleep_timer = playback_info.sleep_timer.clone();
     let repeat = playback_info.repeating.clone();
     let state = playback_info.playback_state.clone();
     let shuffle = playback_info.shuffling.clone();
@@ -268,6 +292,24 @@ pub fn register_pbc_event_handlers(cx: &mut App) {
     })
     .detach();
 
+    cx.observe(&speed, |e, cx| {
+        let &rate = e.read(cx);
+        let PbcHandle(tx, _) = cx.global();
+        if let Err(err) = tx.send(PbcEvent::RateChanged(rate)) {
+            error!(msg = ?err.0, "failed to send pbc event: {err}");
+        }
+    })
+    .detach();
+
+    cx.observe(&sleep_timer, |e, cx| {
+        let &timer = e.read(cx);
+        let PbcHandle(tx, _) = cx.global();
+        if let Err(err) = tx.send(PbcEvent::SleepTimerChanged(timer)) {
+            error!(msg = ?err.0, "failed to send pbc event: {err}");
+        }
+    })
+    .detach();
+
     cx.observe(&repeat, |e, cx| {
         let &repeat = e.read(cx);
         let PbcHandle(tx, _) = cx.global();
@@ -18,7 +18,10 @@ use tracing::{debug, error};
 
 use crate::{
     media::metadata::Metadata,
-    playback::{events::RepeatState, thread::PlaybackState},
+    playback::{
+        events::{RepeatState, SleepTimerState},
+        thread::PlaybackState,
+    },
 };
 
 use super::{ControllerBridge, InitPlaybackController, PlaybackController};
@@ -275,6 +278,15 @@ impl PlaybackController for MacMediaPlayerController {
     async fn volume_changed(&mut self, _new_volume: f64) -> anyhow::Result<()> {
         Ok(())
     }
+    async fn rate_changed(&mut self, _new_rate: f64) -> anyhow::Result<()> {
+        Ok(())
+    }
+    async fn sleep_timer_changed(
+        &mut self,
+        _sleep_timer: Option<SleepTimerState>,
+    ) -> anyhow::Result<()> {
+        Ok(())
+    }
     async fn metadata_changed(&mut self, metadata: &Metadata) -> anyhow::Result<()> {
         unsafe {
             self.new_metadata(metadata);
@@ -14,8 +14,12 @@ use tokio::sync::RwLock;
 use zbus::fdo;
 
 use crate::{
+    devices::stretch::{MAX_SPEED, MIN_SPEED},
     media::metadata::Metadata,
-    playback::{events::RepeatState, thread::PlaybackState},
+    playback::{
+        events::{RepeatState, SleepTimerState},
+        thread::PlaybackState,
+    },
     services::controllers::{ControllerBridge, InitPlaybackController, PlaybackController},
 };
 
@@ -29,7 +33,44 @@ pub struct MprisControllerData {
     last_position: Option<u64>,
     last_duration: Option<u64>,
     last_volume: Option<f64>,
+    last_rate: f64,
     last_shuffle: bool,
+    last_sleep_timer: Option<SleepTimerState>,
+}
+
+/// The sleep timer isn't part of the MPRIS specification, so it's exposed through
+/// Hummingbird-specific metadata keys: `hummingbird:sleepTimer` holds the kind of timer
+/// (`time`, `end-of-track` or `end-of-album`), and `hummingbird:sleepTimerRemaining` the
+/// remaining time in microseconds for timed sleep timers.
+fn set_sleep_timer_metadata(metadata: &mut mpris_server::Metadata, timer: Option<SleepTimerState>) {
+    let (kind, remaining) = match timer {
+        Some(SleepTimerState::Remaining(secs)) => {
+            (Some("time"), Some(Time::from_secs(secs as i64)))
+        }
+        Some(SleepTimerState::EndOfTrack) => (Some("end-of-track"), None),
+        Some(SleepTimerState::EndOfAlbum) => (Some("end-of-album"), None),
+        None => (None, None),
+    };
+
+    metadata.set("hummingbird:sleepTimer", kind);
+    metadata.set(
+        "hummingbird:sleepTimerRemaining",
+        remaining.map(|time| time.as_micros()),
+    );
+}
+
+/// Whether a change in the sleep timer is worth a metadata update. Timed sleep timers change
+/// every second, so clients are only told about whole minutes.
+fn sleep_timer_changed_significantly(
+    old: Option<SleepTimerState>,
+    new: Option<SleepTimerState>,
+) -> bool {
+    match (old, new) {
+        (Some(SleepTimerState::Remaining(old)), Some(SleepTimerState::Remaining(new))) => {
+            old.div_ceil(60) != new.div_ceil(60)
+        }
+        (old, new) => old != new,
+    }
 }
 
 pub struct MprisControllerServer {
@@ -69,10 +110,14 @@ impl MprisControllerServer {
             mpris_data.set_disc_number(metadata.disc_current.map(|v| v as i32));
             mpris_data.set_length(data.last_duration.map(|v| Time::from_secs(v as i64)));
             mpris_data.set_art_url(data.last_album_art.clone());
+            set_sleep_timer_metadata(&mut mpris_data, data.last_sleep_timer);
 
             Ok(mpris_data)
         } else {
-            Ok(mpris_server::Metadata::new())
+            let mut mpris_data = mpris_server::Metadata::new();
+            set_sleep_timer_metadata(&mut mpris_data, data.last_sleep_timer);
+
+            Ok(mpris_data)
         }
     }
 
@@ -253,7 +298,11 @@ impl PlayerInterface for MprisControllerServer {
         Ok(())
     }
 
-    async fn set_rate(&self, _rate: PlaybackRate) -> zbus::Result<()> {
+    async fn set_rate(&self, rate: PlaybackRate) -> zbus::Result<()> {
+        // the spec says a rate of 0.0 should be ignored rather than pausing playback
+        if rate > 0.0 {
+            self.bridge.set_rate(rate.clamp(MIN_SPEED, MAX_SPEED));
+        }
         Ok(())
     }
 
@@ -279,7 +328,8 @@ impl PlayerInterface for MprisControllerServer {
     }
 
     async fn rate(&self) -> fdo::Result<PlaybackRate> {
-        Ok(1_f64)
+        let data = self.data.read().await;
+        Ok(data.last_rate)
     }
 
     async fn set_shuffle(&self, _shuffle: bool) -> zbus::Result<()> {
@@ -308,12 +358,11 @@ impl PlayerInterface for MprisControllerServer {
     }
 
     async fn maximum_rate(&self) -> fdo::Result<PlaybackRate> {
-        // NB: if we add rate changing this should change
-        Ok(1.0_f64)
+        Ok(MAX_SPEED)
     }
 
     async fn minimum_rate(&self) -> fdo::Result<PlaybackRate> {
-        Ok(1.0_f64)
+        Ok(MIN_SPEED)
     }
 }
 
@@ -335,8 +384,10 @@ impl InitPlaybackController for MprisController {
             last_position: None,
             last_duration: None,
             last_volume: None,
+            last_rate: 1.0,
             last_shuffle: false,
             last_album_art: None,
+            last_sleep_timer: None,
         }));
 
         let server_data = data.clone();
@@ -360,8 +411,10 @@ impl PlaybackController for MprisController {
         data.last_position = Some(new_position);
 
         if let Some(original_position) = original_position {
+            // faster playback moves more than one second between updates
+            let max_step = (data.last_rate.ceil() as i64).max(1);
             let position_diff = new_position as i64 - original_position as i64;
-            if position_diff != 1 {
+            if !(1..=max_step).contains(&position_diff) {
                 self.server
                     .emit(Signal::Seeked {
                         position: Time::from_secs(new_position as i64),
@@ -430,6 +483,37 @@ impl PlaybackController for MprisController {
         Ok(())
     }
 
+    async fn rate_changed(&mut self, new_rate: f64) -> anyhow::Result<()> {
+        let mut data = self.data.write().await;
+        data.last_rate = new_rate;
+
+        self.server
+            .properties_changed([Property::Rate(new_rate)])
+            .await?;
+
+        Ok(())
+    }
+
+    async fn sleep_timer_changed(
+        &mut self,
+        sleep_timer: Option<SleepTimerState>,
+    ) -> anyhow::Result<()> {
+        let mut data = self.data.write().await;
+        let significant = sleep_timer_changed_significantly(data.last_sleep_timer, sleep_timer);
+        data.last_sleep_timer = sleep_timer;
+        drop(data);
+
+        if significant {
+            self.server
+                .properties_changed([Property::Metadata(
+                    self.server.imp().metadata_int().await.unwrap(),
+                )])
+                .await?;
+        }
+
+        Ok(())
+    }
+
     async fn repeat_state_changed(&mut self, repeat_state: RepeatState) -> anyhow::Result<()> {
         let mut data = self.data.write().await;
         data.last_repeat_state = Some(repeat_state);
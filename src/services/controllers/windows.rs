@@ -17,7 +17,10 @@ use windows::{
 
 use crate::{
     media::metadata::Metadata,
-    playback::{events::RepeatState, thread::PlaybackState},
+    playback::{
+        events::{RepeatState, SleepTimerState},
+        thread::PlaybackState,
+    },
     services::controllers::InitPlaybackController,
 };
 
@@ -123,6 +126,17 @@ impl PlaybackController for WindowsController {
         Ok(())
     }
 
+    async fn rate_changed(&mut self, _new_rate: f64) -> anyhow::Result<()> {
+        Ok(())
+    }
+
+    async fn sleep_timer_changed(
+        &mut self,
+        _sleep_timer: Option<SleepTimerState>,
+    ) -> anyhow::Result<()> {
+        Ok(())
+    }
+
     async fn metadata_changed(&mut self, metadata: &Metadata) -> anyhow::Result<()> {
         if let Some(title) = metadata.name.clone() {
             let string = HSTRING::from(title);
@@ -1,6 +1,13 @@
+pub mod channels;
+pub mod crossfade;
+pub mod dsp;
+pub mod equalizer;
 pub mod interface;
+pub mod output;
 pub mod playback;
 pub mod replaygain;
+pub mod resampler;
+pub mod resume;
 pub mod scan;
 pub mod services;
 pub mod storage;
@@ -0,0 +1,63 @@
+use serde::{Deserialize, Serialize};
+
+/// How tracks with a different channel layout than the output device are played.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize, Default)]
+pub enum ChannelMode {
+    /// Reopen the device with each track's own layout. Tracks are only mixed if the device
+    /// doesn't support their layout.
+    #[default]
+    Native,
+    /// Keep the device at its own layout, and mix every track to it.
+    Downmix,
+}
+
+#[derive(Debug, Clone, Copy, Serialize, Deserialize, PartialEq)]
+#[serde(default)]
+pub struct ChannelSettings {
+    pub mode: ChannelMode,
+    /// Level of the center channel when it is mixed into the front channels, in dB.
+    /// Range: -12.0 to 0.0
+    pub center_mix_db: f64,
+    /// Level of surround channels when they are mixed into the front channels, in dB.
+    /// Range: -12.0 to 0.0
+    pub surround_mix_db: f64,
+    /// Level of the LFE channel when it is mixed into other channels, in dB. The LFE channel is
+    /// dropped if this is `None`. Range: -12.0 to 0.0
+    pub lfe_mix_db: Option<f64>,
+    /// Whether mixed channels should be scaled down so that they can't clip.
+    pub normalize: bool,
+}
+
+impl Default for ChannelSettings {
+    fn default() -> Self {
+        Self {
+            mode: ChannelMode::Native,
+            center_mix_db: -3.0,
+            surround_mix_db: -3.0,
+            lfe_mix_db: None,
+            normalize: true,
+        }
+    }
+}
+
+fn db_to_gain(db: f64) -> f64 {
+    10.0_f64.powf(db.clamp(-12.0, 0.0) / 20.0)
+}
+
+impl ChannelSettings {
+    /// The linear gain of the center channel when it is mixed into the front channels.
+    pub fn center_gain(&self) -> f64 {
+        db_to_gain(self.center_mix_db)
+    }
+
+    /// The linear gain of surround channels when they are mixed into the front channels.
+    pub fn surround_gain(&self) -> f64 {
+        db_to_gain(self.surround_mix_db)
+    }
+
+    /// The linear gain of the LFE channel when it is mixed into other channels, if it is mixed
+    /// at all.
+    pub fn lfe_gain(&self) -> Option<f64> {
+        self.lfe_mix_db.map(db_to_gain)
+    }
+}
@@ -0,0 +1,35 @@
+use std::time::Duration;
+
+use serde::{Deserialize, Serialize};
+
+#[derive(Debug, Clone, Copy, Serialize, Deserialize, PartialEq)]
+#[serde(default)]
+pub struct CrossfadeSettings {
+    /// Whether the end of a track should overlap with the start of the next one.
+    pub enabled: bool,
+    /// Length of the fade in seconds. Range: 1.0 to 12.0
+    pub duration_secs: f64,
+    /// Play consecutive tracks from the same album gaplessly instead of fading between them.
+    pub skip_same_album: bool,
+}
+
+impl Default for CrossfadeSettings {
+    fn default() -> Self {
+        Self {
+            enabled: false,
+            duration_secs: 5.0,
+            skip_same_album: true,
+        }
+    }
+}
+
+impl CrossfadeSettings {
+    /// The length of the fade between two tracks, or `None` if they shouldn't be crossfaded.
+    pub fn fade_length(&self, same_album: bool) -> Option<Duration> {
+        if !self.enabled || (self.skip_same_album && same_album) {
+            return None;
+        }
+
+        Some(Duration::from_secs_f64(self.duration_secs.clamp(1.0, 12.0)))
+    }
+}
@@ -0,0 +1,60 @@
+use serde::{Deserialize, Serialize};
+
+/// Configuration for a single built-in effect.
+#[derive(Debug, Clone, Copy, PartialEq, Serialize, Deserialize)]
+#[serde(tag = "kind", rename_all = "snake_case")]
+pub enum EffectConfig {
+    /// Stereo balance. Range: -1.0 (left only) to 1.0 (right only)
+    Balance {
+        balance: f64,
+    },
+    MonoDownmix,
+    ChannelSwap,
+    /// Brick-wall limiter. The threshold is in dBFS, and the release time in milliseconds.
+    Limiter {
+        threshold_db: f64,
+        release_ms: f64,
+    },
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Serialize, Deserialize)]
+pub struct EffectEntry {
+    pub enabled: bool,
+    #[serde(flatten)]
+    pub effect: EffectConfig,
+}
+
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+#[serde(default)]
+pub struct DspSettings {
+    /// The effect chain, applied in order after the equalizer.
+    pub effects: Vec<EffectEntry>,
+}
+
+impl Default for DspSettings {
+    fn default() -> Self {
+        let disabled = |effect| EffectEntry {
+            enabled: false,
+            effect,
+        };
+
+        Self {
+            effects: vec![
+                disabled(EffectConfig::Balance { balance: 0.0 }),
+                disabled(EffectConfig::MonoDownmix),
+                disabled(EffectConfig::ChannelSwap),
+                disabled(EffectConfig::Limiter {
+                    threshold_db: -0.3,
+                    release_ms: 50.0,
+                }),
+            ],
+        }
+    }
+}
+
+impl DspSettings {
+    /// Returns true if at least one effect in the chain is enabled.
+    pub fn any_enabled(&self) -> bool {
+        self.effects.iter().any(|entry| entry.enabled)
+    }
+}
@@ -0,0 +1,149 @@
+use serde::{Deserialize, Serialize};
+
+/// Center frequencies of the default 10-band layout, in Hz.
+pub const DEFAULT_BAND_FREQUENCIES: [f64; 10] = [
+    31.0, 62.0, 125.0, 250.0, 500.0, 1000.0, 2000.0, 4000.0, 8000.0, 16000.0,
+];
+
+/// Gain range of a single band, in dB.
+pub const BAND_GAIN_RANGE_DB: (f64, f64) = (-12.0, 12.0);
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum FilterKind {
+    Peaking,
+    LowShelf,
+    HighShelf,
+    LowPass,
+    HighPass,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Serialize, Deserialize)]
+pub struct EqualizerBand {
+    pub kind: FilterKind,
+    /// Center (or corner) frequency in Hz.
+    pub frequency_hz: f64,
+    /// Gain in dB. Ignored by low and high pass filters.
+    pub gain_db: f64,
+    /// Quality factor. For shelves this controls the slope.
+    pub q: f64,
+}
+
+impl EqualizerBand {
+    pub fn new(kind: FilterKind, frequency_hz: f64, gain_db: f64) -> Self {
+        let q = match kind {
+            FilterKind::Peaking => 1.41,
+            _ => std::f64::consts::FRAC_1_SQRT_2,
+        };
+
+        Self {
+            kind,
+            frequency_hz,
+            gain_db,
+            q,
+        }
+    }
+}
+
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct EqualizerPreset {
+    pub name: String,
+    pub preamp_db: f64,
+    pub bands: Vec<EqualizerBand>,
+}
+
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+#[serde(default)]
+pub struct EqualizerSettings {
+    pub enabled: bool,
+    /// Pre-amp in dB, applied before the bands. Range: -12.0 to +12.0
+    pub preamp_db: f64,
+    /// The active bands, applied in order.
+    pub bands: Vec<EqualizerBand>,
+    /// Named presets. Selecting a preset copies its pre-amp and bands into the active
+    /// configuration, so the preset itself is never modified by editing the bands.
+    pub presets: Vec<EqualizerPreset>,
+}
+
+impl Default for EqualizerSettings {
+    fn default() -> Self {
+        Self {
+            enabled: false,
+            preamp_db: 0.0,
+            bands: default_bands(&[0.0; 10]),
+            presets: default_presets(),
+        }
+    }
+}
+
+impl EqualizerSettings {
+    /// Returns the name of the preset matching the active configuration, if any.
+    pub fn active_preset(&self) -> Option<&str> {
+        self.presets
+            .iter()
+            .find(|preset| preset.preamp_db == self.preamp_db && preset.bands == self.bands)
+            .map(|preset| preset.name.as_str())
+    }
+
+    /// Copy the named preset into the active configuration. Returns false if there is no preset
+    /// with that name.
+    pub fn apply_preset(&mut self, name: &str) -> bool {
+        let Some(preset) = self.presets.iter().find(|preset| preset.name == name) else {
+            return false;
+        };
+
+        self.preamp_db = preset.preamp_db;
+        self.bands = preset.bands.clone();
+        true
+    }
+}
+
+/// Build the default 10-band layout with the given gains: a low shelf, eight peaking filters
+/// and a high shelf.
+fn default_bands(gains_db: &[f64; 10]) -> Vec<EqualizerBand> {
+    DEFAULT_BAND_FREQUENCIES
+        .iter()
+        .zip(gains_db)
+        .enumerate()
+        .map(|(idx, (&frequency, &gain))| {
+            let kind = match idx {
+                0 => FilterKind::LowShelf,
+                9 => FilterKind::HighShelf,
+                _ => FilterKind::Peaking,
+            };
+            EqualizerBand::new(kind, frequency, gain)
+        })
+        .collect()
+}
+
+fn default_presets() -> Vec<EqualizerPreset> {
+    let preset = |name: &str, preamp_db: f64, gains_db: [f64; 10]| EqualizerPreset {
+        name: name.to_string(),
+        preamp_db,
+        bands: default_bands(&gains_db),
+    };
+
+    vec![
+        preset("Flat", 0.0, [0.0; 10]),
+        preset(
+            "Bass Boost",
+            -4.0,
+            [6.0, 5.0, 4.0, 2.0, 0.0, 0.0, 0.0, 0.0, 0.0, 0.0],
+        ),
+        preset(
+            "Treble Boost",
+            -4.0,
+            [0.0, 0.0, 0.0, 0.0, 0.0, 1.0, 2.0, 4.0, 5.0, 6.0],
+        ),
+        preset(
+            "Vocal",
+            -3.0,
+            [-2.0, -2.0, -1.0, 1.0, 3.0, 3.0, 2.0, 1.0, 0.0, -1.0],
+        ),
+        preset(
+            "Loudness",
+            -4.0,
+            [5.0, 4.0, 2.0, 0.0, -1.0, 0.0, 0.0, 1.0, 3.0, 4.0],
+        ),
+    ]
+}
@@ -0,0 +1,79 @@
+use serde::{Deserialize, Serialize};
+
+use crate::devices::format::BufferSize;
+
+/// The device providers that can be selected on this platform, with their display names.
+#[cfg(target_os = "windows")]
+pub const PROVIDERS: &[(&str, &str)] = &[
+    ("win_audiograph", "AudioGraph"),
+    ("cpal", "WASAPI"),
+    ("network", "Network"),
+];
+#[cfg(all(target_os = "linux", feature = "pipewire"))]
+pub const PROVIDERS: &[(&str, &str)] = &[
+    ("pipewire", "PipeWire"),
+    ("cpal", "ALSA"),
+    ("alsa", "ALSA (hardware)"),
+    ("network", "Network"),
+];
+#[cfg(all(target_os = "linux", not(feature = "pipewire")))]
+pub const PROVIDERS: &[(&str, &str)] = &[
+    ("cpal", "ALSA"),
+    ("alsa", "ALSA (hardware)"),
+    ("network", "Network"),
+];
+#[cfg(not(any(target_os = "windows", target_os = "linux")))]
+pub const PROVIDERS: &[(&str, &str)] = &[("cpal", "CPAL"), ("network", "Network")];
+
+/// The device providers that let the period and buffer size be chosen.
+pub const BUFFER_SIZE_PROVIDERS: &[&str] = &["alsa"];
+
+#[derive(Debug, Clone, Serialize, Deserialize, PartialEq)]
+#[serde(default)]
+pub struct OutputDeviceSettings {
+    /// The device provider to play through. `None` uses the platform's default provider. The
+    /// `DEVICE_PROVIDER` environment variable takes precedence over this setting.
+    pub provider: Option<String>,
+    /// The UID of the device to play to. `None` follows the provider's default device.
+    pub device_uid: Option<String>,
+    /// The name of the device when it was selected, shown while it isn't connected.
+    pub device_name: Option<String>,
+    /// Whether to pause when the device being played to is disconnected, instead of continuing
+    /// on the default device.
+    pub pause_on_disconnect: bool,
+    /// The size of the device's buffer in frames. `None` uses the provider's default. Only used
+    /// by providers in `BUFFER_SIZE_PROVIDERS`.
+    pub buffer_frames: Option<u32>,
+    /// The size of each period of the device's buffer in frames. `None` uses a quarter of the
+    /// buffer. Only used if `buffer_frames` is set.
+    pub period_frames: Option<u32>,
+}
+
+impl Default for OutputDeviceSettings {
+    fn default() -> Self {
+        Self {
+            provider: None,
+            device_uid: None,
+            device_name: None,
+            pause_on_disconnect: true,
+            buffer_frames: None,
+            period_frames: None,
+        }
+    }
+}
+
+impl OutputDeviceSettings {
+    /// The name to show for the selected device, if one is selected.
+    pub fn display_name(&self) -> Option<&str> {
+        self.device_name.as_deref().or(self.device_uid.as_deref())
+    }
+
+    /// The buffer size to open streams with, if one was chosen.
+    pub fn buffer_size(&self) -> Option<BufferSize> {
+        match (self.period_frames, self.buffer_frames) {
+            (Some(period), Some(buffer)) => Some(BufferSize::Range(period, buffer)),
+            (None, Some(buffer)) => Some(BufferSize::Fixed(buffer)),
+            (_, None) => None,
+        }
+    }
+}
@@ -1,11 +1,19 @@
 use serde::{Deserialize, Serialize};
 
-use super::replaygain::ReplayGainSettings;
+use super::{
+    channels::ChannelSettings, crossfade::CrossfadeSettings, dsp::DspSettings,
+    equalizer::EqualizerSettings, output::OutputDeviceSettings, replaygain::ReplayGainSettings,
+    resampler::ResamplerProfile, resume::ResumeSettings,
+};
 
 fn default_keep_current_on_queue_clear() -> bool {
     true
 }
 
+fn default_preserve_pitch() -> bool {
+    true
+}
+
 /// User-set playback settings, to be passed to the playback thread.
 #[derive(Debug, Clone, Serialize, Deserialize, PartialEq)]
 pub struct PlaybackSettings {
@@ -51,6 +59,80 @@ pub struct PlaybackSettings {
     /// Whether to prevent the system screensaver and sleep while playing.
     #[serde(default)]
     pub prevent_idle: bool,
+
+    /// Crossfade settings.
+    ///
+    /// Crossfading only applies to automatic track changes. Skipping or jumping to a track always
+    /// starts it immediately.
+    #[serde(default)]
+    pub crossfade: CrossfadeSettings,
+
+    /// Parametric equalizer settings.
+    ///
+    /// While the equalizer is enabled, all audio goes through the f64 conversion pipeline, even
+    /// if the source and device formats would otherwise allow for f32 passthrough.
+    #[serde(default)]
+    pub equalizer: EqualizerSettings,
+
+    /// The effect chain applied after the equalizer.
+    ///
+    /// Like the equalizer, enabling any effect disables f32 passthrough.
+    #[serde(default)]
+    pub dsp: DspSettings,
+
+    /// Determines whether or not changing the playback speed should keep the original pitch.
+    ///
+    /// If the option is true, audio is time-stretched before resampling. If the option is false,
+    /// speed changes shift the pitch as well, like changing the speed of a record player.
+    ///
+    /// Defaults to true.
+    #[serde(default = "default_preserve_pitch")]
+    pub preserve_pitch: bool,
+
+    /// Resume point settings.
+    ///
+    /// Resumable tracks, like audiobooks and podcasts, continue from where they were left off
+    /// when they are played again.
+    #[serde(default)]
+    pub resume: ResumeSettings,
+
+    /// The output device to play to.
+    #[serde(default)]
+    pub output: OutputDeviceSettings,
+
+    /// Determines whether or not tracks should be sent to the device without any changes.
+    ///
+    /// If the option is true, the device is opened at each track's own sample rate and format
+    /// when it supports them, and volume and ReplayGain are not applied. The equalizer, effects,
+    /// speed changes and crossfades still work, but the output is no longer bit-perfect while
+    /// they are in use.
+    ///
+    /// Defaults to false.
+    #[serde(default)]
+    pub bit_perfect: bool,
+
+    /// How tracks with a different channel layout than the output device are played, and how
+    /// channels are mixed when they have to be.
+    #[serde(default)]
+    pub channels: ChannelSettings,
+
+    /// The quality of the resampler used when the device runs at a different sample rate than
+    /// the track.
+    ///
+    /// Defaults to balanced.
+    #[serde(default)]
+    pub resampler: ResamplerProfile,
+
+    /// Determines whether or not dither added before quantizing to 16 or 24 bit integers should
+    /// be noise shaped.
+    ///
+    /// TPDF dither is always added when audio is quantized, unless playback is bit-perfect. If
+    /// the option is true, the dither noise is shaped to be less audible, at the cost of a higher
+    /// total noise level.
+    ///
+    /// Defaults to false.
+    #[serde(default)]
+    pub noise_shaping: bool,
 }
 
 #[allow(clippy::derivable_impls)]
@@ -62,6 +144,16 @@ impl Default for PlaybackSettings {
             keep_current_on_queue_clear: true,
             replaygain: ReplayGainSettings::default(),
             prevent_idle: false,
+            crossfade: CrossfadeSettings::default(),
+            equalizer: EqualizerSettings::default(),
+            dsp: DspSettings::default(),
+            preserve_pitch: true,
+            resume: ResumeSettings::default(),
+            output: OutputDeviceSettings::default(),
+            bit_perfect: false,
+            channels: ChannelSettings::default(),
+            resampler: ResamplerProfile::default(),
+            noise_shaping: false,
         }
     }
 }
@@ -23,6 +23,9 @@ pub struct ReplayGainSettings {
     pub preamp_db: f64,
     /// Fallback pre-amp in dB, applied when track has no RG data. Range: -6.0 to +6.0
     pub fallback_preamp_db: f64,
+    /// Whether loudness analysis results are also written to the tags of the analyzed files.
+    #[serde(default)]
+    pub write_analysis_tags: bool,
 }
 
 /// Calculate the linear gain multiplier for a track. Returns the multiplier to apply to audio samples.
@@ -0,0 +1,20 @@
+use serde::{Deserialize, Serialize};
+
+/// Resampler quality profiles, from cheapest to most accurate.
+///
+/// Higher quality profiles use longer filters, which give a flatter passband and a deeper
+/// stopband at the cost of more CPU time and latency.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize, Default)]
+pub enum ResamplerProfile {
+    /// A short sinc filter with linear interpolation between filter points.
+    Fast,
+    /// An FFT-based synchronous resampler.
+    #[default]
+    Balanced,
+    /// A long sinc filter with cubic interpolation between filter points.
+    VeryHigh,
+    /// When the target rate is an integer multiple of the source rate, every source sample is
+    /// kept unchanged and only the samples between them are interpolated. Other ratios use the
+    /// very high profile.
+    BitExact,
+}
@@ -0,0 +1,43 @@
+use serde::{Deserialize, Serialize};
+
+#[derive(Debug, Clone, Serialize, Deserialize, PartialEq)]
+#[serde(default)]
+pub struct ResumeSettings {
+    /// Whether long tracks should remember where they were left off.
+    pub enabled: bool,
+    /// Tracks at least this long are resumable. Range: 5 to 120
+    pub min_length_minutes: u32,
+    /// Tracks tagged with any of these genres are resumable regardless of their length. Matched
+    /// case-insensitively against each genre in the tag.
+    pub genres: Vec<String>,
+}
+
+impl Default for ResumeSettings {
+    fn default() -> Self {
+        Self {
+            enabled: true,
+            min_length_minutes: 30,
+            genres: vec!["Audiobook".to_string(), "Podcast".to_string()],
+        }
+    }
+}
+
+impl ResumeSettings {
+    /// Whether a track with the given length and genre tag should remember its position.
+    pub fn applies_to(&self, duration_secs: u64, genre: Option<&str>) -> bool {
+        if !self.enabled {
+            return false;
+        }
+
+        if duration_secs >= self.min_length_minutes.clamp(5, 120) as u64 * 60 {
+            return true;
+        }
+
+        genre.is_some_and(|genre| {
+            genre
+                .split([';', ',', '/'])
+                .map(str::trim)
+                .any(|genre| self.genres.iter().any(|g| g.eq_ignore_ascii_case(genre)))
+        })
+    }
+}
@@ -21,6 +21,10 @@ pub struct ScanSettings {
     pub paths: Vec<Utf8PathBuf>,
     #[serde(default)]
     pub missing_folder_policy: MissingFolderPolicy,
+    /// Whether to look at the contents of files without an extension to find out if they can be
+    /// played.
+    #[serde(default)]
+    pub probe_extensionless: bool,
 }
 
 impl Default for ScanSettings {
@@ -28,6 +32,7 @@ impl Default for ScanSettings {
         Self {
             paths: retrieve_default_paths(),
             missing_folder_policy: MissingFolderPolicy::default(),
+            probe_extensionless: false,
         }
     }
 }
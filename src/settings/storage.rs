@@ -331,7 +331,7 @@ mod tests {
         );
 
         let expected = StorageData {
-            current_track: Some(CurrentTrack::new(track_path.clone())),
+            current_track: Some(CurrentTrack::new(track_path.clone().into())),
             volume: 0.42,
             sidebar_width: 300.0,
             queue_width: 410.0,
@@ -406,7 +406,7 @@ mod tests {
         );
 
         let stored = StorageData {
-            current_track: Some(CurrentTrack::new(missing_track)),
+            current_track: Some(CurrentTrack::new(missing_track.into())),
             volume: 0.33,
             sidebar_width: 280.0,
             queue_width: 350.0,
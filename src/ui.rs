@@ -3,6 +3,7 @@ pub mod app;
 mod arguments;
 mod assets;
 pub mod availability;
+mod bookmarks;
 mod caching;
 mod command_palette;
 pub mod components;
@@ -15,6 +16,7 @@ pub mod library;
 mod lyrics;
 pub mod models;
 mod queue;
+mod resume;
 mod right_sidebar;
 mod scroll_follow;
 mod search;
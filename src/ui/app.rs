@@ -15,6 +15,7 @@ use tracing::{debug, warn};
 
 use crate::{
     library::{
+        analysis::{AnalysisInterface, start_analyzer},
         db::create_pool,
         scan::{ScanEvent, ScanInterface, start_scanner},
     },
@@ -37,15 +38,19 @@ use crate::{
         caching::HummingbirdImageCache,
         command_palette::{CommandPalette, CommandPaletteHolder},
         components::dropdown,
-        library::{self, missing_folder_dialog::MissingFolderDialog},
+        library::{self, missing_folder_dialog::MissingFolderDialog, tag_editor::TagEditor},
         models::WindowInformation,
-        settings::corrupt_settings_dialog::CorruptSettingsDialog,
+        settings::{
+            corrupt_settings_dialog::CorruptSettingsDialog,
+            output_device_dialog::OutputDeviceDialog,
+        },
     },
 };
 
 use super::{
     about::about_dialog,
     arguments::parse_args_and_prepare,
+    bookmarks::Bookmarks,
     components::{
         context, input,
         modal::{self, ModalActive},
@@ -57,6 +62,7 @@ use super::{
     header::Header,
     library::Library,
     models::{self, CurrentTrack, Models, PlaybackInfo, build_models},
+    resume::setup_resume,
     right_sidebar::RightSidebar,
     search::SearchView,
     settings::close_orphaned_settings_windows,
@@ -76,7 +82,10 @@ struct MainWindow {
     pub about_focus: FocusHandle,
     pub missing_folder_dialog: Entity<MissingFolderDialog>,
     pub corrupt_settings_dialog: Entity<CorruptSettingsDialog>,
+    pub output_device_dialog: Entity<OutputDeviceDialog>,
+    pub tag_editor: Entity<TagEditor>,
     pub palette: Entity<CommandPalette>,
+    pub bookmarks: Entity<Bookmarks>,
     pub image_cache: Entity<HummingbirdImageCache>,
 }
 
@@ -96,6 +105,13 @@ impl Render for MainWindow {
                 scan_state,
                 ScanEvent::WaitingForMissingFolderDecision { .. }
             );
+        let show_output_device_dialog = !show_corrupt_settings_dialog
+            && !show_missing_folder_dialog
+            && cx
+                .global::<PlaybackInfo>()
+                .unavailable_output_device
+                .read(cx)
+                .is_some();
         let show_sidebar = *self.show_queue.read(cx) || *self.show_lyrics.read(cx);
 
         div()
@@ -109,7 +125,7 @@ impl Render for MainWindow {
                         let items = ev
                             .paths()
                             .iter()
-                            .map(|path| QueueItemData::new(cx, path.clone(), None, None))
+                            .map(|path| QueueItemData::new(cx, path.clone(), None, None, None))
                             .collect();
 
                         let playback_interface = cx.global::<PlaybackInterface>();
@@ -138,6 +154,8 @@ impl Render for MainWindow {
                     .child(self.header.clone())
                     .child(self.search.clone())
                     .child(self.palette.clone())
+                    .child(self.bookmarks.clone())
+                    .child(self.tag_editor.clone())
                     .when(show_about, |this| {
                         this.child(about_dialog(self.about_focus.clone(), &|_, cx| {
                             let show_about = cx.global::<Models>().show_about.clone();
@@ -149,6 +167,9 @@ impl Render for MainWindow {
                     })
                     .when(show_corrupt_settings_dialog, |this| {
                         this.child(self.corrupt_settings_dialog.clone())
+                    })
+                    .when(show_output_device_dialog, |this| {
+                        this.child(self.output_device_dialog.clone())
                     }),
             ))
     }
@@ -276,6 +297,15 @@ fn build_main_window(window: &mut Window, cx: &mut App) -> Entity<MainWindow> {
         })
         .detach();
 
+        let unavailable_output_device = cx
+            .global::<PlaybackInfo>()
+            .unavailable_output_device
+            .clone();
+        cx.observe(&unavailable_output_device, |_, _, cx| {
+            cx.notify();
+        })
+        .detach();
+
         MainWindow {
             controls: Controls::new(cx, show_queue.clone(), show_lyrics.clone()),
             right_sidebar: RightSidebar::new(cx, show_queue.clone(), show_lyrics.clone()),
@@ -288,7 +318,10 @@ fn build_main_window(window: &mut Window, cx: &mut App) -> Entity<MainWindow> {
             about_focus,
             missing_folder_dialog: MissingFolderDialog::new(cx),
             corrupt_settings_dialog: CorruptSettingsDialog::new(cx),
+            output_device_dialog: OutputDeviceDialog::new(cx),
+            tag_editor: TagEditor::new(cx),
             palette,
+            bookmarks: Bookmarks::new(cx),
             // use a really small global image cache
             // this is literally just to ensure that images are *always* removed
             // from memory *at some point*
@@ -362,7 +395,8 @@ pub fn run() -> anyhow::Result<()> {
             .filter(|position| *position < playback_session.queue.len());
         let initial_track = initial_position
             .and_then(|position| playback_session.queue.get(position))
-            .map(|item| CurrentTrack::new(item.get_path().clone()));
+            .map(|item| CurrentTrack::new(item.source()));
+        let initial_track_position = playback_session.position_ms;
 
         let queue: Arc<RwLock<Vec<QueueItemData>>> =
             Arc::new(RwLock::new(playback_session.queue.clone()));
@@ -425,8 +459,14 @@ pub fn run() -> anyhow::Result<()> {
         }
         scan_interface.start_broadcast(cx);
 
+        let record_updater = scan_interface.record_updater();
         cx.set_global(scan_interface);
 
+        let mut analysis_interface: AnalysisInterface =
+            start_analyzer(pool.clone(), record_updater);
+        analysis_interface.start_broadcast(cx);
+        cx.set_global(analysis_interface);
+
         let settings_health = cx.global::<Models>().settings_health.clone();
         cx.observe(&settings_health, |health, cx| {
             if matches!(health.read(cx), models::SettingsHealth::Ok) {
@@ -472,9 +512,13 @@ pub fn run() -> anyhow::Result<()> {
             && let Some(pos) = initial_position
         {
             playback_interface.jump(pos);
+            if let Some(position_ms) = initial_track_position {
+                playback_interface.seek(position_ms as f64 / 1000.0);
+            }
             playback_interface.pause();
         }
         cx.set_global(playback_interface);
+        setup_resume(cx);
 
         // Update `StorageData` and save it to file system while quitting the app.
         cx.on_app_quit({
@@ -24,7 +24,7 @@ pub fn parse_args_and_prepare(cx: &mut App, interface: &PlaybackInterface) -> bo
         interface.queue_list(
             files
                 .iter()
-                .map(|path| QueueItemData::new(cx, path.clone(), None, None))
+                .map(|path| QueueItemData::new(cx, path.clone(), None, None, None))
                 .collect(),
         );
     }
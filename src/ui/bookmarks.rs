@@ -0,0 +1,227 @@
+use std::sync::{Arc, atomic::Ordering};
+
+use cntp_i18n::tr;
+use gpui::{
+    App, AppContext, Context, Entity, IntoElement, ParentElement, Render, SharedString, Styled,
+    Window, actions, div, px,
+};
+use nucleo::Utf32String;
+use tracing::error;
+
+use crate::{
+    library::{db::LibraryAccess, types::Bookmark},
+    playback::{interface::PlaybackInterface, thread::PlaybackState},
+    ui::{
+        components::{
+            icons::{BOOKMARK, BOOKMARK_PLUS},
+            modal::{ModalActive, modal},
+            palette::{ExtraItem, ExtraItemProvider, FinderItemLeft, Palette, PaletteItem},
+        },
+        models::{Models, PlaybackInfo},
+        util::format_duration,
+    },
+};
+
+actions!(bookmarks, [OpenBookmarks]);
+
+impl PaletteItem for Bookmark {
+    fn left_content(&self, _: &mut App) -> Option<FinderItemLeft> {
+        Some(FinderItemLeft::Icon(BOOKMARK.into()))
+    }
+
+    fn middle_content(&self, _: &mut App) -> SharedString {
+        self.name.0.clone()
+    }
+
+    fn right_content(&self, _: &mut App) -> Option<SharedString> {
+        Some(format_duration(self.position_ms / 1000, false).into())
+    }
+}
+
+/// Get the library ID of the track that is currently playing, if it is in the library.
+fn current_track_id(cx: &App) -> Option<i64> {
+    let source = cx
+        .global::<PlaybackInfo>()
+        .current_track
+        .read(cx)
+        .as_ref()?
+        .source()
+        .clone();
+
+    cx.get_track_by_path(&source.path, source.range)
+        .ok()
+        .flatten()
+        .map(|track| track.id)
+}
+
+/// Reload the bookmarks of the current track from the database.
+pub fn reload_bookmarks(cx: &mut App) {
+    let bookmarks = match current_track_id(cx).map(|id| cx.bookmarks_for_track(id)) {
+        Some(Ok(bookmarks)) => bookmarks,
+        Some(Err(err)) => {
+            error!(?err, "Failed to load bookmarks: {err}");
+            Arc::new(Vec::new())
+        }
+        None => Arc::new(Vec::new()),
+    };
+
+    let model = cx.global::<Models>().bookmarks.clone();
+    model.write(cx, bookmarks);
+}
+
+/// Save the current playback position as a bookmark with the given name.
+fn add_bookmark(name: &str, cx: &mut App) {
+    let Some(track_id) = current_track_id(cx) else {
+        return;
+    };
+
+    let position_ms = *cx.global::<PlaybackInfo>().position.read(cx);
+
+    if let Err(err) = cx.create_bookmark(track_id, name, position_ms as i64) {
+        error!(?err, "Failed to create bookmark: {err}");
+    }
+
+    reload_bookmarks(cx);
+}
+
+pub fn jump_to_bookmark(bookmark: &Bookmark, cx: &mut App) {
+    if *cx.global::<PlaybackInfo>().playback_state.read(cx) == PlaybackState::Stopped {
+        return;
+    }
+
+    cx.global::<PlaybackInterface>()
+        .seek(bookmark.position_ms as f64 / 1000.0);
+}
+
+pub fn delete_bookmark(bookmark: &Bookmark, cx: &mut App) {
+    if let Err(err) = cx.delete_bookmark(bookmark.id) {
+        error!(?err, "Failed to delete bookmark: {err}");
+    }
+
+    reload_bookmarks(cx);
+}
+
+type MatcherFunc = Box<dyn Fn(&Arc<Bookmark>, &mut App) -> Utf32String + 'static>;
+type OnAccept = Box<dyn Fn(&Arc<Bookmark>, &mut App) + 'static>;
+
+/// Lists the bookmarks of the current track, and saves new ones.
+pub struct Bookmarks {
+    show: Entity<bool>,
+    palette: Entity<Palette<Bookmark, MatcherFunc, OnAccept>>,
+}
+
+impl Bookmarks {
+    pub fn new(cx: &mut App) -> Entity<Self> {
+        cx.new(|cx| {
+            let show = cx.new(|_| false);
+            let current_track = cx.global::<PlaybackInfo>().current_track.clone();
+            let bookmarks = cx.global::<Models>().bookmarks.clone();
+
+            cx.observe(&current_track, |_, _, cx| {
+                reload_bookmarks(cx);
+            })
+            .detach();
+
+            cx.observe(&bookmarks, |this: &mut Self, bookmarks, cx| {
+                let items = bookmarks
+                    .read(cx)
+                    .iter()
+                    .cloned()
+                    .map(Arc::new)
+                    .collect::<Vec<_>>();
+
+                this.palette.update(cx, |_, cx| {
+                    cx.emit(items);
+                });
+            })
+            .detach();
+
+            cx.observe(&show, |_, _, cx| cx.notify()).detach();
+
+            reload_bookmarks(cx);
+
+            let matcher: MatcherFunc = Box::new(|bookmark, _| bookmark.name.0.to_string().into());
+
+            let show_clone = show.clone();
+            let on_accept: OnAccept = Box::new(move |bookmark, cx| {
+                jump_to_bookmark(bookmark, cx);
+                show_clone.write(cx, false);
+            });
+
+            let items = bookmarks.read(cx).iter().cloned().map(Arc::new).collect();
+
+            let palette = Palette::new(cx, items, matcher, on_accept, &show);
+
+            let show_for_create = show.clone();
+            let provider: ExtraItemProvider = Arc::new(move |query: &str| {
+                let name = query.trim();
+                if name.is_empty() {
+                    return Vec::new();
+                }
+
+                let name_string = name.to_string();
+                let show_clone = show_for_create.clone();
+
+                vec![ExtraItem {
+                    left: Some(FinderItemLeft::Icon(BOOKMARK_PLUS.into())),
+                    middle: tr!(
+                        "CREATE_BOOKMARK",
+                        "Add bookmark '{{name}}' at the current position",
+                        name = name
+                    )
+                    .into(),
+                    right: None,
+                    on_accept: Arc::new(move |cx| {
+                        add_bookmark(&name_string, cx);
+                        show_clone.write(cx, false);
+                    }),
+                }]
+            });
+
+            cx.update_entity(&palette, |palette, cx| {
+                palette.register_extra_provider(provider, cx);
+            });
+
+            let show_clone = show.clone();
+            let weak_palette = palette.downgrade();
+            App::on_action(cx, move |_: &OpenBookmarks, cx: &mut App| {
+                if cx.global::<ModalActive>().0.load(Ordering::Relaxed)
+                    || cx.global::<PlaybackInfo>().current_track.read(cx).is_none()
+                {
+                    return;
+                }
+
+                show_clone.write(cx, true);
+                weak_palette
+                    .update(cx, |palette, cx| {
+                        palette.reset(cx);
+                    })
+                    .ok();
+            });
+
+            Self { show, palette }
+        })
+    }
+}
+
+impl Render for Bookmarks {
+    fn render(&mut self, window: &mut Window, cx: &mut Context<Self>) -> impl IntoElement {
+        if *self.show.read(cx) {
+            let palette = self.palette.clone();
+            let show = self.show.clone();
+
+            palette.update(cx, |palette, cx| {
+                palette.focus(window, cx);
+            });
+
+            modal()
+                .child(div().w(px(550.0)).h(px(300.0)).child(palette.clone()))
+                .on_exit(move |_, cx| {
+                    show.write(cx, false);
+                })
+                .into_any_element()
+        } else {
+            div().into_any_element()
+        }
+    }
+}
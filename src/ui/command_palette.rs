@@ -13,15 +13,23 @@ use tracing::error;
 #[cfg(feature = "update")]
 use crate::ui::global_actions::CheckForUpdates;
 use crate::ui::{components::modal::ModalActive, global_actions::Undo};
-use crate::ui::{
-    components::{
-        modal::modal,
-        palette::{FinderItemLeft, Palette, PaletteItem},
+use crate::{
+    playback::events::SleepTimerState,
+    ui::{
+        bookmarks::OpenBookmarks,
+        components::{
+            modal::modal,
+            palette::{FinderItemLeft, Palette, PaletteItem},
+        },
+        global_actions::{
+            About, AnalyzeLoudness, CancelSleepTimer, ClearLoop, ForceScan, Next, PlayPause,
+            Previous, Quit, ReanalyzeLoudness, Search, SetLoopEnd, SetLoopStart, Settings,
+            ShuffleAll, SleepAfter15Minutes, SleepAfter30Minutes, SleepAfter60Minutes,
+            SleepAfter90Minutes, SleepAfterAlbum, SleepAfterTrack, StopLoudnessAnalysis,
+        },
+        models::PlaybackInfo,
+        troubleshooting::{CopyTroubleshootingInfo, OpenLog},
     },
-    global_actions::{
-        About, ForceScan, Next, PlayPause, Previous, Quit, Search, Settings, ShuffleAll,
-    },
-    troubleshooting::{CopyTroubleshootingInfo, OpenLog},
 };
 
 actions!(hummingbird, [OpenPalette]);
@@ -246,6 +254,157 @@ impl CommandPalette {
                 ),
             );
 
+            items.insert(
+                ("sleep_timer::15", 0),
+                Command::new(
+                    Some(tr!("ACTION_GROUP_PLAYBACK")),
+                    tr!("ACTION_SLEEP_15_MINUTES", "Sleep Timer: 15 Minutes"),
+                    SleepAfter15Minutes,
+                    None,
+                ),
+            );
+            items.insert(
+                ("sleep_timer::30", 0),
+                Command::new(
+                    Some(tr!("ACTION_GROUP_PLAYBACK")),
+                    tr!("ACTION_SLEEP_30_MINUTES", "Sleep Timer: 30 Minutes"),
+                    SleepAfter30Minutes,
+                    None,
+                ),
+            );
+            items.insert(
+                ("sleep_timer::60", 0),
+                Command::new(
+                    Some(tr!("ACTION_GROUP_PLAYBACK")),
+                    tr!("ACTION_SLEEP_60_MINUTES", "Sleep Timer: 1 Hour"),
+                    SleepAfter60Minutes,
+                    None,
+                ),
+            );
+            items.insert(
+                ("sleep_timer::90", 0),
+                Command::new(
+                    Some(tr!("ACTION_GROUP_PLAYBACK")),
+                    tr!("ACTION_SLEEP_90_MINUTES", "Sleep Timer: 1.5 Hours"),
+                    SleepAfter90Minutes,
+                    None,
+                ),
+            );
+            items.insert(
+                ("sleep_timer::track", 0),
+                Command::new(
+                    Some(tr!("ACTION_GROUP_PLAYBACK")),
+                    tr!(
+                        "ACTION_SLEEP_END_OF_TRACK",
+                        "Sleep Timer: End of Current Track"
+                    ),
+                    SleepAfterTrack,
+                    None,
+                ),
+            );
+            items.insert(
+                ("sleep_timer::album", 0),
+                Command::new(
+                    Some(tr!("ACTION_GROUP_PLAYBACK")),
+                    tr!(
+                        "ACTION_SLEEP_END_OF_ALBUM",
+                        "Sleep Timer: End of Current Album"
+                    ),
+                    SleepAfterAlbum,
+                    None,
+                ),
+            );
+
+            // the cancel command shows the remaining time, so it's only listed while a timer
+            // is running
+            let sleep_timer = cx.global::<PlaybackInfo>().sleep_timer.clone();
+            cx.observe(&sleep_timer, |this: &mut Self, sleep_timer, cx| {
+                let id = ("sleep_timer::cancel", 0);
+
+                let Some(state) = *sleep_timer.read(cx) else {
+                    if this.items.contains_key(&id) {
+                        cx.emit(CommandEvent::RemoveCommand(id));
+                    }
+                    return;
+                };
+
+                let name: SharedString = match state {
+                    SleepTimerState::Remaining(secs) => tr!(
+                        "ACTION_CANCEL_SLEEP_TIMER_REMAINING",
+                        "Cancel Sleep Timer ({{minutes}} min left)",
+                        minutes = secs.div_ceil(60)
+                    )
+                    .into(),
+                    SleepTimerState::EndOfTrack => tr!(
+                        "ACTION_CANCEL_SLEEP_TIMER_TRACK",
+                        "Cancel Sleep Timer (at end of track)"
+                    )
+                    .into(),
+                    SleepTimerState::EndOfAlbum => tr!(
+                        "ACTION_CANCEL_SLEEP_TIMER_ALBUM",
+                        "Cancel Sleep Timer (at end of album)"
+                    )
+                    .into(),
+                };
+
+                if this
+                    .items
+                    .get(&id)
+                    .is_none_or(|command| command.name != name)
+                {
+                    cx.emit(CommandEvent::NewCommand(
+                        id,
+                        Command::new(
+                            Some(tr!("ACTION_GROUP_PLAYBACK")),
+                            name,
+                            CancelSleepTimer,
+                            None,
+                        ),
+                    ));
+                }
+            })
+            .detach();
+
+            items.insert(
+                ("ab_loop::start", 0),
+                Command::new(
+                    Some(tr!("ACTION_GROUP_PLAYBACK")),
+                    tr!("ACTION_SET_LOOP_START", "Set Loop Start (A)"),
+                    SetLoopStart,
+                    None,
+                ),
+            );
+            items.insert(
+                ("ab_loop::end", 0),
+                Command::new(
+                    Some(tr!("ACTION_GROUP_PLAYBACK")),
+                    tr!("ACTION_SET_LOOP_END", "Set Loop End (B)"),
+                    SetLoopEnd,
+                    None,
+                ),
+            );
+            items.insert(
+                ("ab_loop::clear", 0),
+                Command::new(
+                    Some(tr!("ACTION_GROUP_PLAYBACK")),
+                    tr!("ACTION_CLEAR_LOOP", "Clear A-B Loop"),
+                    ClearLoop,
+                    None,
+                ),
+            );
+            items.insert(
+                ("bookmarks::open", 0),
+                Command::new(
+                    Some(tr!("ACTION_GROUP_PLAYBACK")),
+                    tr!(
+                        "ACTION_OPEN_BOOKMARKS",
+                        "Bookmarks: Jump to or Add Bookmark"
+                    ),
+                    OpenBookmarks,
+                    None,
+                ),
+            );
+
             items.insert(
                 ("scan::forcescan", 0),
                 Command::new(
@@ -256,6 +415,40 @@ impl CommandPalette {
                 ),
             );
 
+            items.insert(
+                ("analysis::analyzeloudness", 0),
+                Command::new(
+                    Some(tr!("ACTION_GROUP_SCAN")),
+                    tr!(
+                        "ACTION_ANALYZE_LOUDNESS",
+                        "Analyze Tracks Missing ReplayGain"
+                    ),
+                    AnalyzeLoudness,
+                    None,
+                ),
+            );
+            items.insert(
+                ("analysis::reanalyzeloudness", 0),
+                Command::new(
+                    Some(tr!("ACTION_GROUP_SCAN")),
+                    tr!(
+                        "ACTION_REANALYZE_LOUDNESS",
+                        "Reanalyze ReplayGain for Entire Library"
+                    ),
+                    ReanalyzeLoudness,
+                    None,
+                ),
+            );
+            items.insert(
+                ("analysis::stoploudnessanalysis", 0),
+                Command::new(
+                    Some(tr!("ACTION_GROUP_SCAN")),
+                    tr!("ACTION_STOP_LOUDNESS_ANALYSIS", "Stop Loudness Analysis"),
+                    StopLoudnessAnalysis,
+                    None,
+                ),
+            );
+
             items.insert(
                 ("shuffle::all", 0),
                 Command::new(
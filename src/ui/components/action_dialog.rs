@@ -258,13 +258,12 @@ impl ActionDialog {
 
     /// Allows the dialog to be closed via the Escape key or by clicking the backdrop. Omit for
     /// force-a-decision dialogs.
-    // TODO: remove dead_code for on_dismiss and width when they're used
-    #[allow(dead_code)]
     pub fn on_dismiss(mut self, handler: impl Fn(&mut Window, &mut App) + 'static) -> Self {
         self.on_dismiss = Some(Box::new(handler));
         self
     }
 
+    // TODO: remove dead_code when this is used
     #[allow(dead_code)]
     pub fn width(mut self, width: Pixels) -> Self {
         self.width = width;
@@ -6,6 +6,8 @@ use gpui::{
     anchored, div, point, prelude::FluentBuilder, px, size,
 };
 
+use crate::media::cue::TrackRange;
+
 use super::scrollbar::ScrollableHandle;
 
 #[derive(Clone, Copy, PartialEq, Eq, Debug)]
@@ -51,6 +53,7 @@ pub struct TrackDragData {
     pub track_id: Option<i64>,
     pub album_id: Option<i64>,
     pub path: PathBuf,
+    pub range: Option<TrackRange>,
     pub display_name: SharedString,
     /// Source list ID, if dragged from a reorderable list (e.g. a playlist).
     pub source_list_id: Option<ElementId>,
@@ -62,12 +65,14 @@ impl TrackDragData {
         track_id: i64,
         album_id: Option<i64>,
         path: impl Into<PathBuf>,
+        range: Option<TrackRange>,
         display_name: impl Into<SharedString>,
     ) -> Self {
         Self {
             track_id: Some(track_id),
             album_id,
             path: path.into(),
+            range,
             display_name: display_name.into(),
             source_list_id: None,
             source_index: None,
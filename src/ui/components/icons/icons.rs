@@ -52,9 +52,14 @@ pub const SORT_DESCENDING: &str = "!bundled:icons/sort-descending.svg";
 pub const SORT_ASCENDING: &str = "!bundled:icons/sort-ascending.svg";
 pub const FOLDER_X: &str = "!bundled:icons/folder-x.svg";
 pub const MICROPHONE: &str = "!bundled:icons/microphone-2.svg";
+pub const MOON: &str = "!bundled:icons/moon.svg";
+pub const BOOKMARK: &str = "!bundled:icons/bookmark.svg";
+pub const BOOKMARK_PLUS: &str = "!bundled:icons/bookmark-plus.svg";
 pub const PENCIL: &str = "!bundled:icons/pencil.svg";
+pub const PROGRESS: &str = "!bundled:icons/progress.svg";
 #[cfg(feature = "update")]
 pub const UPDATE: &str = "!bundled:icons/arrow-big-down-lines.svg";
 pub const FILE_EXPORT: &str = "!bundled:icons/file-export.svg";
 pub const MUSIC: &str = "!bundled:icons/music.svg";
 pub const POWER: &str = "!bundled:icons/power.svg";
+pub const CHART_BAR: &str = "!bundled:icons/chart-bar.svg";
@@ -339,6 +339,10 @@ impl TextInput {
         self.content = value;
         self.move_to(self.content.len(), cx);
     }
+
+    pub fn set_placeholder(&mut self, placeholder: SharedString) {
+        self.placeholder = placeholder;
+    }
 }
 
 impl EntityInputHandler for TextInput {
@@ -60,6 +60,13 @@ impl Textbox {
             cx.notify();
         });
     }
+
+    pub fn set_placeholder(&self, cx: &mut App, placeholder: SharedString) {
+        self.input.update(cx, |input, cx| {
+            input.set_placeholder(placeholder);
+            cx.notify();
+        });
+    }
 }
 
 impl Render for Textbox {
@@ -1,10 +1,18 @@
 mod replaygain;
+mod sleep_timer;
+mod speed;
+mod visualizer;
 
 use crate::{
     library::db::LibraryAccess,
-    playback::{events::RepeatState, interface::PlaybackInterface, thread::PlaybackState},
+    playback::{
+        events::{AbLoop, RepeatState},
+        interface::PlaybackInterface,
+        thread::PlaybackState,
+    },
     settings::SettingsGlobal,
     ui::{
+        bookmarks::{delete_bookmark, jump_to_bookmark},
         caching::hummingbird_cache,
         components::{
             context::context,
@@ -30,9 +38,12 @@ use crate::{
 use cntp_i18n::tr;
 use gpui::{Corner, InteractiveElement, *};
 use prelude::FluentBuilder;
-use std::{path::PathBuf, rc::Rc};
+use std::{path::PathBuf, rc::Rc, sync::Arc};
 
-use self::replaygain::ReplayGainButton;
+use self::{
+    replaygain::ReplayGainButton, sleep_timer::SleepTimerButton, speed::SpeedButton,
+    visualizer::VisualizerButton,
+};
 use super::{
     components::{
         resizable::{ResizeEdge, resizable},
@@ -44,7 +55,7 @@ use super::{
     theme::Theme,
 };
 
-use crate::library::types::Track;
+use crate::library::types::{Bookmark, Track};
 use crate::settings::storage::{DEFAULT_CONTROLS_LEFT_WIDTH, DEFAULT_CONTROLS_RIGHT_WIDTH};
 use crate::ui::util::format_duration;
 
@@ -186,9 +197,9 @@ impl InfoSection {
             let current_track_path = initial_current_track
                 .as_ref()
                 .map(|track| track.get_path().clone());
-            let current_library_track = initial_current_track
-                .as_ref()
-                .and_then(|track| resolve_library_track_by_path(cx, track.get_path()));
+            let current_library_track = initial_current_track.as_ref().and_then(|track| {
+                resolve_library_track_by_path(cx, track.get_path(), track.source().range)
+            });
             let can_navigate_to_album = current_library_track
                 .as_ref()
                 .is_some_and(|track| track.album_id.is_some());
@@ -452,8 +463,9 @@ fn update_current_track_state(
     cx: &App,
 ) {
     this.current_track_path = current_track.map(|track| track.get_path().clone());
-    this.current_library_track =
-        current_track.and_then(|track| resolve_library_track_by_path(cx, track.get_path()));
+    this.current_library_track = current_track.and_then(|track| {
+        resolve_library_track_by_path(cx, track.get_path(), track.source().range)
+    });
     this.can_navigate_to_album = this
         .current_library_track
         .as_ref()
@@ -733,6 +745,9 @@ impl Render for PlaybackSection {
 pub struct Scrubber {
     position: Entity<u64>,
     duration: Entity<u64>,
+    ab_loop: Entity<AbLoop>,
+    bookmarks: Entity<Arc<Vec<Bookmark>>>,
+    bit_perfect: Entity<bool>,
     playback_section: Entity<PlaybackSection>,
 }
 
@@ -752,9 +767,32 @@ impl Scrubber {
             })
             .detach();
 
+            let ab_loop = cx.global::<PlaybackInfo>().ab_loop.clone();
+            let bookmarks = cx.global::<Models>().bookmarks.clone();
+
+            cx.observe(&ab_loop, |_, _, cx| {
+                cx.notify();
+            })
+            .detach();
+
+            cx.observe(&bookmarks, |_, _, cx| {
+                cx.notify();
+            })
+            .detach();
+
+            let bit_perfect = cx.global::<PlaybackInfo>().bit_perfect.clone();
+
+            cx.observe(&bit_perfect, |_, _, cx| {
+                cx.notify();
+            })
+            .detach();
+
             Self {
                 position: position_model,
                 duration: duration_model,
+                ab_loop,
+                bookmarks,
+                bit_perfect,
                 playback_section: PlaybackSection::new(cx),
             }
         })
@@ -769,9 +807,26 @@ impl Render for Scrubber {
         let position_secs = position_ms / 1_000;
         let duration_ms = duration_secs.saturating_mul(1_000);
         let remaining_secs = duration_secs.saturating_sub(position_secs);
+        let bit_perfect = *self.bit_perfect.read(cx);
 
         let window_width = window.viewport_size().width;
 
+        let fraction = |ms: u64| (ms as f32 / duration_ms as f32).min(1.0);
+
+        let ab_loop = *self.ab_loop.read(cx);
+        let loop_range = ab_loop
+            .end_ms
+            .filter(|_| duration_ms > 0)
+            .map(|end| (fraction(ab_loop.start_ms.unwrap_or(0)), fraction(end)));
+
+        let markers = self
+            .bookmarks
+            .read(cx)
+            .iter()
+            .filter(|_| duration_ms > 0)
+            .map(|bookmark| bookmark_marker(bookmark, fraction(bookmark.position_ms as u64), theme))
+            .collect::<Vec<_>>();
+
         div()
             .pl(px(13.0))
             .pr(px(13.0))
@@ -810,38 +865,112 @@ impl Render for Scrubber {
                     })
                     .child(self.playback_section.clone())
                     .child(div().h(px(30.0)))
+                    .when(bit_perfect, |this| {
+                        this.child(
+                            div()
+                                .id("bit-perfect")
+                                .ml(auto())
+                                .mr(px(8.0))
+                                .px(px(5.0))
+                                .py(px(1.0))
+                                .rounded(px(3.0))
+                                .border_1()
+                                .border_color(theme.border_color)
+                                .text_size(px(11.0))
+                                .line_height(rems(1.0))
+                                .text_color(theme.text_secondary)
+                                .child(tr!("BIT_PERFECT", "Bit-perfect"))
+                                .tooltip(build_tooltip(tr!(
+                                    "BIT_PERFECT_TOOLTIP",
+                                    "Playing at the track's own sample rate and format, without \
+                                    any processing"
+                                ))),
+                        )
+                    })
                     .child(
                         div()
-                            .ml(auto())
+                            .when(!bit_perfect, |this| this.ml(auto()))
                             .line_height(rems(1.0))
                             .child(format!("-{}", format_duration(remaining_secs as i64, true))),
                     ),
             )
             .child(
-                slider()
+                div()
                     .w_full()
-                    .h(px(6.0))
-                    .rounded(px(3.0))
-                    .id("scrubber-back")
-                    .value(if duration_ms > 0 {
-                        position_ms as f32 / duration_ms as f32
-                    } else {
-                        0.0
+                    .relative()
+                    .child(
+                        slider()
+                            .w_full()
+                            .h(px(6.0))
+                            .rounded(px(3.0))
+                            .id("scrubber-back")
+                            .value(if duration_ms > 0 {
+                                position_ms as f32 / duration_ms as f32
+                            } else {
+                                0.0
+                            })
+                            .on_change(move |v, _, cx| {
+                                let info = cx.global::<PlaybackInfo>().clone();
+
+                                if duration_secs > 0
+                                    && *info.playback_state.read(cx) != PlaybackState::Stopped
+                                {
+                                    cx.global::<PlaybackInterface>()
+                                        .seek(v as f64 * duration_secs as f64);
+                                }
+                            }),
+                    )
+                    .when_some(loop_range, |this, (start, end)| {
+                        this.child(
+                            div()
+                                .absolute()
+                                .top(px(-2.0))
+                                .h(px(10.0))
+                                .left(relative(start))
+                                .w(relative(end - start))
+                                .rounded(px(2.0))
+                                .border_x(px(2.0))
+                                .border_color(theme.playback_button_toggled)
+                                .bg(Rgba {
+                                    a: 0.3,
+                                    ..theme.playback_button_toggled
+                                }),
+                        )
                     })
-                    .on_change(move |v, _, cx| {
-                        let info = cx.global::<PlaybackInfo>().clone();
-
-                        if duration_secs > 0
-                            && *info.playback_state.read(cx) != PlaybackState::Stopped
-                        {
-                            cx.global::<PlaybackInterface>()
-                                .seek(v as f64 * duration_secs as f64);
-                        }
-                    }),
+                    .children(markers),
             )
     }
 }
 
+/// A clickable marker on the seek bar for a saved bookmark. Clicking it jumps to the bookmark,
+/// right-clicking removes it.
+fn bookmark_marker(bookmark: &Bookmark, fraction: f32, theme: &Theme) -> impl IntoElement {
+    let jump = bookmark.clone();
+    let remove = bookmark.clone();
+
+    div()
+        .id(("bookmark-marker", bookmark.id as usize))
+        .absolute()
+        .top(px(-4.0))
+        .h(px(14.0))
+        .w(px(4.0))
+        .ml(px(-2.0))
+        .left(relative(fraction))
+        .rounded(px(2.0))
+        .bg(theme.text)
+        .cursor_pointer()
+        .tooltip(build_tooltip(bookmark.name.0.clone()))
+        .on_mouse_down(MouseButton::Left, |_, window, cx| {
+            cx.stop_propagation();
+            window.prevent_default();
+        })
+        .on_click(move |_, _, cx| jump_to_bookmark(&jump, cx))
+        .on_mouse_down(MouseButton::Right, move |_, _, cx| {
+            cx.stop_propagation();
+            delete_bookmark(&remove, cx);
+        })
+}
+
 #[derive(IntoElement)]
 struct SidebarToggleButton {
     div: Stateful<Div>,
@@ -906,6 +1035,9 @@ pub struct SecondaryControls {
     show_queue: Entity<bool>,
     show_lyrics: Entity<bool>,
     replaygain_button: Entity<ReplayGainButton>,
+    speed_button: Entity<SpeedButton>,
+    sleep_timer_button: Entity<SleepTimerButton>,
+    visualizer_button: Entity<VisualizerButton>,
 }
 
 impl SecondaryControls {
@@ -924,6 +1056,9 @@ impl SecondaryControls {
                 show_queue,
                 show_lyrics,
                 replaygain_button: ReplayGainButton::new(cx),
+                speed_button: SpeedButton::new(cx),
+                sleep_timer_button: SleepTimerButton::new(cx),
+                visualizer_button: VisualizerButton::new(cx),
             }
         })
     }
@@ -1010,7 +1145,10 @@ impl Render for SecondaryControls {
                             ));
                         }),
                 )
+                .child(self.speed_button.clone())
+                .child(self.sleep_timer_button.clone())
                 .child(self.replaygain_button.clone())
+                .child(self.visualizer_button.clone())
                 .child(
                     div()
                         .h(px(24.0))
@@ -0,0 +1,222 @@
+use crate::{
+    playback::{
+        events::{SleepTimer, SleepTimerState},
+        interface::PlaybackInterface,
+    },
+    ui::{
+        components::{
+            icons::{CROSS, MOON, icon},
+            menu::{menu, menu_item, menu_separator},
+            popover::{PopoverPosition, popover},
+            tooltip::build_tooltip,
+        },
+        models::PlaybackInfo,
+    },
+};
+use cntp_i18n::tr;
+use gpui::{prelude::FluentBuilder, *};
+
+use crate::ui::theme::Theme;
+
+const TIMER_MINUTES: [u32; 4] = [15, 30, 60, 90];
+
+pub struct SleepTimerButton {
+    sleep_timer: Entity<Option<SleepTimerState>>,
+    show_popover: bool,
+}
+
+impl SleepTimerButton {
+    pub fn new(cx: &mut App) -> Entity<Self> {
+        cx.new(|cx| {
+            let sleep_timer = cx.global::<PlaybackInfo>().sleep_timer.clone();
+
+            cx.observe(&sleep_timer, |_, _, cx| {
+                cx.notify();
+            })
+            .detach();
+
+            Self {
+                sleep_timer,
+                show_popover: false,
+            }
+        })
+    }
+
+    fn close_popover(&mut self, cx: &mut Context<Self>) {
+        self.show_popover = false;
+        cx.notify();
+    }
+}
+
+fn format_remaining(secs: u64) -> String {
+    if secs >= 3600 {
+        format!("{}:{:02}:{:02}", secs / 3600, secs / 60 % 60, secs % 60)
+    } else {
+        format!("{}:{:02}", secs / 60, secs % 60)
+    }
+}
+
+impl Render for SleepTimerButton {
+    fn render(&mut self, _: &mut Window, cx: &mut Context<Self>) -> impl IntoElement {
+        let theme = cx.global::<Theme>();
+        let sleep_timer = *self.sleep_timer.read(cx);
+        let show_popover = self.show_popover;
+
+        let status: SharedString = match sleep_timer {
+            Some(SleepTimerState::Remaining(secs)) => tr!(
+                "SLEEP_TIMER_REMAINING",
+                "Stopping in {{time}}",
+                time = format_remaining(secs)
+            )
+            .into(),
+            Some(SleepTimerState::EndOfTrack) => {
+                tr!("SLEEP_TIMER_END_OF_TRACK", "Stopping after this track").into()
+            }
+            Some(SleepTimerState::EndOfAlbum) => {
+                tr!("SLEEP_TIMER_END_OF_ALBUM", "Stopping after this album").into()
+            }
+            None => tr!("SLEEP_TIMER_OFF", "Sleep timer off").into(),
+        };
+
+        div()
+            .relative()
+            .child(
+                div()
+                    .rounded(px(3.0))
+                    .min_w(px(25.0))
+                    .h(px(25.0))
+                    .mt(px(2.0))
+                    .px(px(3.0))
+                    .gap(px(3.0))
+                    .flex()
+                    .items_center()
+                    .justify_center()
+                    .text_xs()
+                    .font_weight(FontWeight::SEMIBOLD)
+                    .border_color(theme.playback_button_border)
+                    .id("sleep-timer-button")
+                    .cursor_pointer()
+                    .tooltip(build_tooltip(status.clone()))
+                    .bg(theme.playback_button)
+                    .hover(|this| this.bg(theme.playback_button_hover))
+                    .active(|this| this.bg(theme.playback_button_active))
+                    .when(sleep_timer.is_some(), |this| {
+                        this.text_color(theme.playback_button_toggled)
+                    })
+                    .on_mouse_down(MouseButton::Left, |_, window, cx| {
+                        cx.stop_propagation();
+                        window.prevent_default();
+                    })
+                    .on_click(cx.listener(|this, _, _, cx| {
+                        this.show_popover = !this.show_popover;
+                        cx.notify();
+                    }))
+                    .child(icon(MOON).size(px(14.0)))
+                    .when_some(
+                        sleep_timer.and_then(|state| match state {
+                            SleepTimerState::Remaining(secs) => Some(secs.div_ceil(60)),
+                            _ => None,
+                        }),
+                        |this, minutes| this.child(format!("{}m", minutes)),
+                    ),
+            )
+            .when(show_popover, |this| {
+                let entity = cx.entity().downgrade();
+                let entity2 = entity.clone();
+
+                let mut timers = menu();
+                for minutes in TIMER_MINUTES {
+                    let entity = entity.clone();
+                    timers = timers.item(
+                        menu_item(
+                            ("sleep-timer-minutes", minutes as usize),
+                            None::<SharedString>,
+                            tr!(
+                                "SLEEP_TIMER_MINUTES",
+                                "{{minutes}} minutes",
+                                minutes = minutes
+                            ),
+                            move |_, _, cx| {
+                                cx.global::<PlaybackInterface>()
+                                    .set_sleep_timer(Some(SleepTimer::Minutes(minutes)));
+                                entity.update(cx, |this, cx| this.close_popover(cx)).ok();
+                            },
+                        )
+                        .never_icon(),
+                    );
+                }
+
+                let end_of_track = entity.clone();
+                let end_of_album = entity.clone();
+                let cancel = entity.clone();
+
+                this.child(
+                    popover()
+                        .position(PopoverPosition::TopRight)
+                        .edge_offset(px(8.0))
+                        .on_dismiss(move |_, cx| {
+                            entity.update(cx, |this, cx| this.close_popover(cx)).ok();
+                        })
+                        .on_mouse_down_out(move |_, _, cx| {
+                            entity2.update(cx, |this, cx| this.close_popover(cx)).ok();
+                        })
+                        .child(
+                            div()
+                                .text_xs()
+                                .font_weight(FontWeight::SEMIBOLD)
+                                .text_color(theme.text_secondary)
+                                .px(px(8.0))
+                                .pt(px(4.0))
+                                .child(status),
+                        )
+                        .child(
+                            timers
+                                .item(menu_separator())
+                                .item(
+                                    menu_item(
+                                        "sleep-timer-track",
+                                        None::<SharedString>,
+                                        tr!("SLEEP_TIMER_AFTER_TRACK", "After current track"),
+                                        move |_, _, cx| {
+                                            cx.global::<PlaybackInterface>()
+                                                .set_sleep_timer(Some(SleepTimer::EndOfTrack));
+                                            end_of_track
+                                                .update(cx, |this, cx| this.close_popover(cx))
+                                                .ok();
+                                        },
+                                    )
+                                    .never_icon(),
+                                )
+                                .item(
+                                    menu_item(
+                                        "sleep-timer-album",
+                                        None::<SharedString>,
+                                        tr!("SLEEP_TIMER_AFTER_ALBUM", "After current album"),
+                                        move |_, _, cx| {
+                                            cx.global::<PlaybackInterface>()
+                                                .set_sleep_timer(Some(SleepTimer::EndOfAlbum));
+                                            end_of_album
+                                                .update(cx, |this, cx| this.close_popover(cx))
+                                                .ok();
+                                        },
+                                    )
+                                    .never_icon(),
+                                )
+                                .when(sleep_timer.is_some(), |menu| {
+                                    menu.item(menu_separator()).item(menu_item(
+                                        "sleep-timer-cancel",
+                                        Some(CROSS),
+                                        tr!("SLEEP_TIMER_CANCEL", "Cancel sleep timer"),
+                                        move |_, _, cx| {
+                                            cx.global::<PlaybackInterface>().set_sleep_timer(None);
+                                            cancel
+                                                .update(cx, |this, cx| this.close_popover(cx))
+                                                .ok();
+                                        },
+                                    ))
+                                }),
+                        ),
+                )
+            })
+    }
+}
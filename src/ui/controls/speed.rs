@@ -0,0 +1,169 @@
+use crate::{
+    devices::stretch::{MAX_SPEED, MIN_SPEED},
+    playback::interface::PlaybackInterface,
+    settings::{Settings, SettingsGlobal, save_settings},
+    ui::{
+        components::{
+            checkbox::checkbox,
+            label::label,
+            labeled_slider::labeled_slider,
+            popover::{PopoverPosition, popover},
+            tooltip::build_tooltip,
+        },
+        models::PlaybackInfo,
+    },
+};
+use cntp_i18n::tr;
+use gpui::{prelude::FluentBuilder, *};
+
+use crate::ui::theme::Theme;
+
+pub struct SpeedButton {
+    settings: Entity<Settings>,
+    speed: Entity<f64>,
+    show_popover: bool,
+}
+
+impl SpeedButton {
+    pub fn new(cx: &mut App) -> Entity<Self> {
+        cx.new(|cx| {
+            let settings = cx.global::<SettingsGlobal>().model.clone();
+            let speed = cx.global::<PlaybackInfo>().speed.clone();
+
+            cx.observe(&settings, |_, _, cx| {
+                cx.notify();
+            })
+            .detach();
+
+            cx.observe(&speed, |_, _, cx| {
+                cx.notify();
+            })
+            .detach();
+
+            Self {
+                settings,
+                speed,
+                show_popover: false,
+            }
+        })
+    }
+
+    fn close_popover(&mut self, cx: &mut Context<Self>) {
+        self.show_popover = false;
+        cx.notify();
+    }
+}
+
+impl Render for SpeedButton {
+    fn render(&mut self, _: &mut Window, cx: &mut Context<Self>) -> impl IntoElement {
+        let theme = cx.global::<Theme>();
+        let speed = *self.speed.read(cx);
+        let preserve_pitch = self.settings.read(cx).playback.preserve_pitch;
+        let settings = self.settings.clone();
+        let show_popover = self.show_popover;
+
+        div()
+            .relative()
+            .child(
+                div()
+                    .rounded(px(3.0))
+                    .min_w(px(25.0))
+                    .h(px(25.0))
+                    .mt(px(2.0))
+                    .px(px(3.0))
+                    .flex()
+                    .items_center()
+                    .justify_center()
+                    .text_xs()
+                    .font_weight(FontWeight::SEMIBOLD)
+                    .border_color(theme.playback_button_border)
+                    .id("speed-button")
+                    .cursor_pointer()
+                    .tooltip(build_tooltip(tr!("PLAYBACK_SPEED", "Playback speed")))
+                    .bg(theme.playback_button)
+                    .hover(|this| this.bg(theme.playback_button_hover))
+                    .active(|this| this.bg(theme.playback_button_active))
+                    .when(speed != 1.0, |this| {
+                        this.text_color(theme.playback_button_toggled)
+                    })
+                    .on_mouse_down(MouseButton::Left, |_, window, cx| {
+                        cx.stop_propagation();
+                        window.prevent_default();
+                    })
+                    .on_click(cx.listener(|this, _, _, cx| {
+                        this.show_popover = !this.show_popover;
+                        cx.notify();
+                    }))
+                    .child(format!("{}×", speed)),
+            )
+            .when(show_popover, |this| {
+                let entity = cx.entity().downgrade();
+                let entity2 = entity.clone();
+                this.child(
+                    popover()
+                        .position(PopoverPosition::TopRight)
+                        .edge_offset(px(8.0))
+                        .on_dismiss(move |_, cx| {
+                            entity.update(cx, |this, cx| this.close_popover(cx)).ok();
+                        })
+                        .min_w(px(220.0))
+                        .on_mouse_down_out(move |_, _, cx| {
+                            entity2.update(cx, |this, cx| this.close_popover(cx)).ok();
+                        })
+                        .child(
+                            div()
+                                .flex()
+                                .flex_col()
+                                .gap(px(10.0))
+                                .p(px(4.0))
+                                .pb(px(8.0))
+                                .child(
+                                    div()
+                                        .flex()
+                                        .flex_col()
+                                        .child(
+                                            div()
+                                                .text_xs()
+                                                .font_weight(FontWeight::SEMIBOLD)
+                                                .text_color(theme.text_secondary)
+                                                .mb(px(1.0))
+                                                .child(tr!("PLAYBACK_SPEED")),
+                                        )
+                                        .child(
+                                            labeled_slider("speed")
+                                                .slider_id("speed-track")
+                                                .min(MIN_SPEED as f32)
+                                                .max(MAX_SPEED as f32)
+                                                .value(speed as f32)
+                                                .default_value(1.0)
+                                                .format_value(|v| format!("{:.2}×", v).into())
+                                                .on_change(move |v, _, cx| {
+                                                    // snap to steps of 0.05x
+                                                    let speed = (v as f64 * 20.0).round() / 20.0;
+                                                    cx.global::<PlaybackInterface>()
+                                                        .set_speed(speed);
+                                                }),
+                                        ),
+                                )
+                                .child(
+                                    label(
+                                        "speed-preserve-pitch",
+                                        tr!("PLAYBACK_PRESERVE_PITCH", "Preserve pitch"),
+                                    )
+                                    .cursor_pointer()
+                                    .w_full()
+                                    .on_click(move |_, _, cx| {
+                                        settings.update(cx, |settings, cx| {
+                                            settings.playback.preserve_pitch =
+                                                !settings.playback.preserve_pitch;
+                                            save_settings(cx, settings);
+                                            cx.notify();
+                                        });
+                                    })
+                                    .child(checkbox("speed-preserve-pitch-check", preserve_pitch)),
+                                ),
+                        ),
+                )
+            })
+    }
+}
@@ -0,0 +1,163 @@
+use std::sync::Arc;
+
+use crate::{
+    devices::visualizer::{ChannelLevel, SPECTRUM_FLOOR_DB, VisualizerFrame},
+    playback::interface::{PlaybackInterface, VisualizerSubscription},
+    ui::{
+        components::{
+            icons::{CHART_BAR, icon},
+            popover::{PopoverPosition, popover},
+            tooltip::build_tooltip,
+        },
+        models::PlaybackInfo,
+    },
+};
+use cntp_i18n::tr;
+use gpui::{prelude::FluentBuilder, *};
+
+use crate::ui::theme::Theme;
+
+const SPECTRUM_HEIGHT: f32 = 64.0;
+
+/// Where a level in dBFS falls between the spectrum floor and full scale, from 0.0 to 1.0.
+fn meter_position(db: f32) -> f32 {
+    (1.0 - db / SPECTRUM_FLOOR_DB).clamp(0.0, 1.0)
+}
+
+fn amplitude_position(amplitude: f32) -> f32 {
+    meter_position(20.0 * amplitude.log10())
+}
+
+pub struct VisualizerButton {
+    frame: Entity<Option<Arc<VisualizerFrame>>>,
+    /// Held while the popover is open, so that audio is only analyzed while it's shown.
+    subscription: Option<VisualizerSubscription>,
+}
+
+impl VisualizerButton {
+    pub fn new(cx: &mut App) -> Entity<Self> {
+        cx.new(|cx| {
+            let frame = cx.global::<PlaybackInfo>().visualizer.clone();
+
+            cx.observe(&frame, |this: &mut Self, _, cx| {
+                if this.subscription.is_some() {
+                    cx.notify();
+                }
+            })
+            .detach();
+
+            Self {
+                frame,
+                subscription: None,
+            }
+        })
+    }
+
+    fn set_open(&mut self, open: bool, cx: &mut Context<Self>) {
+        self.subscription = open.then(|| cx.global::<PlaybackInterface>().subscribe_visualizer());
+        cx.notify();
+    }
+}
+
+fn level_meter(level: &ChannelLevel, theme: &Theme) -> impl IntoElement {
+    div()
+        .relative()
+        .w_full()
+        .h(px(4.0))
+        .rounded(px(2.0))
+        .bg(theme.slider_background)
+        .child(
+            div()
+                .h_full()
+                .rounded(px(2.0))
+                .bg(theme.slider_foreground)
+                .w(relative(amplitude_position(level.rms))),
+        )
+        .child(
+            div()
+                .absolute()
+                .top_0()
+                .h_full()
+                .w(px(2.0))
+                .left(relative(amplitude_position(level.peak)))
+                .bg(theme.text_secondary),
+        )
+}
+
+impl Render for VisualizerButton {
+    fn render(&mut self, _: &mut Window, cx: &mut Context<Self>) -> impl IntoElement {
+        let theme = cx.global::<Theme>();
+        let show_popover = self.subscription.is_some();
+        let frame = self.frame.read(cx).clone().unwrap_or_default();
+
+        div()
+            .relative()
+            .child(
+                div()
+                    .rounded(px(3.0))
+                    .w(px(25.0))
+                    .h(px(25.0))
+                    .mt(px(2.0))
+                    .flex()
+                    .items_center()
+                    .justify_center()
+                    .border_color(theme.playback_button_border)
+                    .id("visualizer-button")
+                    .cursor_pointer()
+                    .tooltip(build_tooltip(tr!("VISUALIZER", "Spectrum analyzer")))
+                    .bg(theme.playback_button)
+                    .hover(|this| this.bg(theme.playback_button_hover))
+                    .active(|this| this.bg(theme.playback_button_active))
+                    .when(show_popover, |this| {
+                        this.text_color(theme.playback_button_toggled)
+                    })
+                    .on_mouse_down(MouseButton::Left, |_, window, cx| {
+                        cx.stop_propagation();
+                        window.prevent_default();
+                    })
+                    .on_click(cx.listener(|this, _, _, cx| {
+                        this.set_open(this.subscription.is_none(), cx);
+                    }))
+                    .child(icon(CHART_BAR).size(px(14.0))),
+            )
+            .when(show_popover, |this| {
+                let entity = cx.entity().downgrade();
+                let entity2 = entity.clone();
+                this.child(
+                    popover()
+                        .position(PopoverPosition::TopRight)
+                        .edge_offset(px(8.0))
+                        .on_dismiss(move |_, cx| {
+                            entity.update(cx, |this, cx| this.set_open(false, cx)).ok();
+                        })
+                        .min_w(px(260.0))
+                        .on_mouse_down_out(move |_, _, cx| {
+                            entity2.update(cx, |this, cx| this.set_open(false, cx)).ok();
+                        })
+                        .child(
+                            div()
+                                .flex()
+                                .flex_col()
+                                .gap(px(8.0))
+                                .p(px(4.0))
+                                .child(
+                                    div()
+                                        .flex()
+                                        .items_end()
+                                        .gap(px(1.0))
+                                        .h(px(SPECTRUM_HEIGHT))
+                                        .children(frame.spectrum.iter().map(|&db| {
+                                            div()
+                                                .flex_1()
+                                                .h(px(SPECTRUM_HEIGHT * meter_position(db)))
+                                                .bg(theme.slider_foreground)
+                                        })),
+                                )
+                                .child(div().flex().flex_col().gap(px(2.0)).children(
+                                    frame.levels.iter().map(|level| level_meter(level, theme)),
+                                )),
+                        ),
+                )
+            })
+    }
+}
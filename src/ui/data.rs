@@ -1,48 +1,66 @@
-use std::path::{Path, PathBuf};
-
 use futures::TryFutureExt as _;
 use gpui::{App, Entity, Task};
 use tracing::{error, trace_span};
 
 use crate::{
-    media::{builtin::symphonia::SymphoniaProvider, metadata::Metadata, traits::MediaProvider},
-    playback::queue::{DataSource, QueueItemUIData},
+    media::{
+        builtin::symphonia::SymphoniaProvider, cue, metadata::Metadata, traits::MediaProvider,
+    },
+    playback::queue::{DataSource, QueueItemUIData, TrackSource},
 };
 
 #[tracing::instrument(level = "trace")]
-fn read_metadata(path: &Path) -> anyhow::Result<QueueItemUIData> {
+fn read_metadata(source: &TrackSource) -> anyhow::Result<QueueItemUIData> {
+    let TrackSource { path, range } = source;
     let file = std::fs::File::open(path)?;
 
     // TODO: Switch to a different media provider based on the file
     let mut stream = SymphoniaProvider.open(file, None)?;
     stream.start_playback()?;
 
+    let mut metadata = stream.read_metadata()?.clone();
+    let mut duration = stream.duration_secs().ok();
+    if let Some(range) = *range {
+        if let Some(track) = cue::track_metadata(path, range, &metadata) {
+            metadata = track;
+        }
+        duration = duration.map(|secs| range.duration_ms(secs * 1000) / 1000);
+    }
+
     let Metadata {
         name,
         artist,
         album_artist,
         ..
-    } = stream.read_metadata()?;
+    } = metadata;
     let ui_data = QueueItemUIData {
-        name: name.as_ref().map(Into::into),
-        artist_name: artist.as_ref().or(album_artist.as_ref()).map(Into::into),
+        name: name.map(Into::into),
+        artist_name: artist.or(album_artist).map(Into::into),
         source: DataSource::Metadata,
         album_id: None,
-        duration: stream.duration_secs().ok().map(|s| s as i64),
+        duration: duration.map(|s| s as i64),
     };
 
     Ok(ui_data)
 }
 
 pub trait Decode {
-    fn read_metadata(&self, path: PathBuf, entity: Entity<Option<QueueItemUIData>>) -> Task<()>;
+    fn read_metadata(
+        &self,
+        source: TrackSource,
+        entity: Entity<Option<QueueItemUIData>>,
+    ) -> Task<()>;
 }
 
 impl Decode for App {
-    fn read_metadata(&self, path: PathBuf, entity: Entity<Option<QueueItemUIData>>) -> Task<()> {
+    fn read_metadata(
+        &self,
+        source: TrackSource,
+        entity: Entity<Option<QueueItemUIData>>,
+    ) -> Task<()> {
         self.spawn(async move |cx| {
-            let span = trace_span!("read_metadata_outer", path = %path.display());
-            let task = crate::RUNTIME.spawn_blocking(move || read_metadata(&path));
+            let span = trace_span!("read_metadata_outer", %source);
+            let task = crate::RUNTIME.spawn_blocking(move || read_metadata(&source));
             match task.err_into().await.flatten() {
                 Err(err) => error!(parent: span, ?err, "Failed to read metadata: {err}"),
                 Ok(metadata) => entity.update(cx, |m, cx| {
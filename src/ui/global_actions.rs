@@ -3,9 +3,14 @@ use gpui::{App, AppContext, KeyBinding, MenuItem, actions};
 use tracing::{debug, info, warn};
 
 use crate::{
-    library::{db::LibraryAccess, scan::ScanInterface},
-    playback::{interface::PlaybackInterface, queue::QueueItemData, thread::PlaybackState},
+    library::{analysis::AnalysisInterface, db::LibraryAccess, scan::ScanInterface},
+    playback::{
+        events::SleepTimer, interface::PlaybackInterface, queue::QueueItemData,
+        thread::PlaybackState,
+    },
+    settings::SettingsGlobal,
     ui::{
+        bookmarks::OpenBookmarks,
         command_palette::OpenPalette,
         components::menus_builder::{
             MenuBuilder, MenuPlatform, MenusBuilder, menu_item, menu_separator,
@@ -23,6 +28,23 @@ actions!(hummingbird, [Quit, About, CloseWindow, Search, Settings]);
 actions!(hummingbird, [CheckForUpdates]);
 actions!(player, [PlayPause, Next, Previous, ShuffleAll]);
 actions!(scan, [ForceScan, Scan]);
+actions!(
+    analysis,
+    [AnalyzeLoudness, ReanalyzeLoudness, StopLoudnessAnalysis]
+);
+actions!(
+    sleep_timer,
+    [
+        SleepAfter15Minutes,
+        SleepAfter30Minutes,
+        SleepAfter60Minutes,
+        SleepAfter90Minutes,
+        SleepAfterTrack,
+        SleepAfterAlbum,
+        CancelSleepTimer
+    ]
+);
+actions!(ab_loop, [SetLoopStart, SetLoopEnd, ClearLoop]);
 actions!(hummingbird, [HideSelf, HideOthers, ShowAll]);
 actions!(help, [Discord, Patreon, Issues]);
 actions!(queue, [Undo]);
@@ -48,6 +70,19 @@ pub fn register_actions(cx: &mut App) {
     cx.on_action(issues);
     cx.on_action(shuffle_all);
     cx.on_action(scan);
+    cx.on_action(analyze_loudness);
+    cx.on_action(reanalyze_loudness);
+    cx.on_action(stop_loudness_analysis);
+    cx.on_action(|_: &SleepAfter15Minutes, cx| set_sleep_timer(SleepTimer::Minutes(15), cx));
+    cx.on_action(|_: &SleepAfter30Minutes, cx| set_sleep_timer(SleepTimer::Minutes(30), cx));
+    cx.on_action(|_: &SleepAfter60Minutes, cx| set_sleep_timer(SleepTimer::Minutes(60), cx));
+    cx.on_action(|_: &SleepAfter90Minutes, cx| set_sleep_timer(SleepTimer::Minutes(90), cx));
+    cx.on_action(|_: &SleepAfterTrack, cx| set_sleep_timer(SleepTimer::EndOfTrack, cx));
+    cx.on_action(|_: &SleepAfterAlbum, cx| set_sleep_timer(SleepTimer::EndOfAlbum, cx));
+    cx.on_action(cancel_sleep_timer);
+    cx.on_action(set_loop_start);
+    cx.on_action(set_loop_end);
+    cx.on_action(clear_loop);
     cx.on_action(open_log);
     cx.on_action(copy_troubleshooting_info);
 
@@ -80,6 +115,10 @@ pub fn register_actions(cx: &mut App) {
     cx.bind_keys([KeyBinding::new("alt-shift-s", ForceScan, None)]);
     cx.bind_keys([KeyBinding::new("alt-s", Scan, None)]);
     cx.bind_keys([KeyBinding::new("space", PlayPause, None)]);
+    cx.bind_keys([KeyBinding::new("[", SetLoopStart, Some("!TextInput"))]);
+    cx.bind_keys([KeyBinding::new("]", SetLoopEnd, Some("!TextInput"))]);
+    cx.bind_keys([KeyBinding::new("\\", ClearLoop, Some("!TextInput"))]);
+    cx.bind_keys([KeyBinding::new("secondary-b", OpenBookmarks, None)]);
 
     let mut app_menu = MenuBuilder::new(tr!("APP_NAME"))
         .add_item(menu_item(
@@ -310,6 +349,53 @@ fn scan(_: &Scan, cx: &mut App) {
     scanner.scan();
 }
 
+fn write_analysis_tags(cx: &App) -> bool {
+    let settings = cx.global::<SettingsGlobal>().model.read(cx);
+    settings.playback.replaygain.write_analysis_tags
+}
+
+fn analyze_loudness(_: &AnalyzeLoudness, cx: &mut App) {
+    let write_tags = write_analysis_tags(cx);
+    let analyzer = cx.global::<AnalysisInterface>();
+    analyzer.analyze_missing(write_tags);
+}
+
+fn reanalyze_loudness(_: &ReanalyzeLoudness, cx: &mut App) {
+    let write_tags = write_analysis_tags(cx);
+    let analyzer = cx.global::<AnalysisInterface>();
+    analyzer.analyze_all(write_tags);
+}
+
+fn stop_loudness_analysis(_: &StopLoudnessAnalysis, cx: &mut App) {
+    let analyzer = cx.global::<AnalysisInterface>();
+    analyzer.stop();
+}
+
+fn set_sleep_timer(timer: SleepTimer, cx: &mut App) {
+    let interface = cx.global::<PlaybackInterface>();
+    interface.set_sleep_timer(Some(timer));
+}
+
+fn cancel_sleep_timer(_: &CancelSleepTimer, cx: &mut App) {
+    let interface = cx.global::<PlaybackInterface>();
+    interface.set_sleep_timer(None);
+}
+
+fn set_loop_start(_: &SetLoopStart, cx: &mut App) {
+    let interface = cx.global::<PlaybackInterface>();
+    interface.set_loop_start();
+}
+
+fn set_loop_end(_: &SetLoopEnd, cx: &mut App) {
+    let interface = cx.global::<PlaybackInterface>();
+    interface.set_loop_end();
+}
+
+fn clear_loop(_: &ClearLoop, cx: &mut App) {
+    let interface = cx.global::<PlaybackInterface>();
+    interface.clear_loop();
+}
+
 fn open_settings(_: &Settings, cx: &mut App) {
     open_settings_window(cx);
 }
@@ -335,7 +421,7 @@ fn shuffle_all(_: &ShuffleAll, cx: &mut App) {
     if let Ok(tracks) = cx.get_all_tracks() {
         let tracks = tracks
             .into_iter()
-            .map(|v| QueueItemData::new(cx, v.0.into(), Some(v.1), Some(v.2)))
+            .map(|v| QueueItemData::new(cx, v.0.into(), v.1, Some(v.2), Some(v.3)))
             .collect();
 
         let interface = cx.global::<PlaybackInterface>();
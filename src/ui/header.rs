@@ -5,11 +5,11 @@ mod update;
 
 use super::{models::Models, theme::Theme};
 use crate::{
-    library::scan::ScanEvent,
+    library::{analysis::AnalysisEvent, scan::ScanEvent},
     settings::{Settings, SettingsGlobal},
     ui::{
         components::{
-            icons::{FOLDER_SEARCH, icon},
+            icons::{FOLDER_SEARCH, VOLUME, icon},
             menu_bar::MenuBar,
             window_header::header,
         },
@@ -80,11 +80,13 @@ impl Render for Header {
 
 pub struct ScanStatus {
     scan_model: Entity<ScanEvent>,
+    analysis_model: Entity<AnalysisEvent>,
 }
 
 impl ScanStatus {
     pub fn new(cx: &mut App) -> Entity<Self> {
         let scan_model = cx.global::<Models>().scan_state.clone();
+        let analysis_model = cx.global::<Models>().analysis_state.clone();
 
         cx.new(|cx| {
             cx.observe(&scan_model, |_, _, cx| {
@@ -92,7 +94,15 @@ impl ScanStatus {
             })
             .detach();
 
-            Self { scan_model }
+            cx.observe(&analysis_model, |_, _, cx| {
+                cx.notify();
+            })
+            .detach();
+
+            Self {
+                scan_model,
+                analysis_model,
+            }
         })
     }
 }
@@ -102,6 +112,34 @@ impl Render for ScanStatus {
         let theme = cx.global::<Theme>();
         let status = self.scan_model.read(cx);
 
+        // scanning takes priority, analysis only shows while the scanner is idle
+        if matches!(
+            status,
+            ScanEvent::ScanCompleteIdle
+                | ScanEvent::ScanCompleteWatching
+                | ScanEvent::TargetedRescanComplete
+        ) && let AnalysisEvent::AnalysisProgress { current, total } =
+            self.analysis_model.read(cx)
+        {
+            return div()
+                .flex()
+                .text_sm()
+                .text_color(theme.text_secondary)
+                .child(
+                    div()
+                        .mr(px(8.0))
+                        .pt(px(4.5))
+                        .h_full()
+                        .child(icon(VOLUME).size(px(14.0))),
+                )
+                .child(tr!(
+                    "ANALYSIS_PROGRESS",
+                    "Analyzing loudness {{percentage}}%",
+                    percentage = (*current as f64 / (*total).max(1) as f64 * 100.0).round()
+                ))
+                .into_any_element();
+        }
+
         div()
             .flex()
             .text_sm()
@@ -162,5 +200,6 @@ impl Render for ScanStatus {
                     tr!("SCAN_COMPLETE_WATCHING", "Watching for updates").into()
                 }
             })
+            .into_any_element()
     }
 }
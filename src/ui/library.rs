@@ -45,6 +45,7 @@ pub mod playlist_view;
 mod release_view;
 mod sidebar;
 mod table_view_header;
+pub mod tag_editor;
 mod track_listing;
 mod track_view;
 mod update_playlist;
@@ -289,7 +289,7 @@ impl Render for ArtistDetailView {
             .is_some_and(|current_track| {
                 self.all_tracks
                     .iter()
-                    .any(|track| current_track == track.location && is_track_available(track))
+                    .any(|track| current_track == *track && is_track_available(track))
             });
         let has_available_artist_tracks = has_available_tracks(self.all_tracks.as_ref());
 
@@ -301,7 +301,7 @@ impl Render for ArtistDetailView {
             .is_some_and(|current_track| {
                 self.liked_tracks
                     .iter()
-                    .any(|track| current_track == track.location && is_track_available(track))
+                    .any(|track| current_track == *track && is_track_available(track))
             });
         let has_available_liked_tracks = has_available_tracks(self.liked_tracks.as_ref());
 
@@ -345,6 +345,7 @@ impl Render for ArtistDetailView {
                                                     QueueItemData::new(
                                                         cx,
                                                         track.location.clone(),
+                                                        track.range(),
                                                         Some(track.id),
                                                         track.album_id,
                                                     )
@@ -481,6 +482,7 @@ impl Render for ArtistDetailView {
                                                             QueueItemData::new(
                                                                 cx,
                                                                 track.location.clone(),
+                                                                track.range(),
                                                                 Some(track.id),
                                                                 track.album_id,
                                                             )
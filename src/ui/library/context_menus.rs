@@ -14,6 +14,7 @@ use crate::{
         scan::ScanInterface,
         types::{Album, Track},
     },
+    media::cue::TrackRange,
     playback::{
         interface::{PlaybackInterface, replace_queue},
         queue::QueueItemData,
@@ -24,6 +25,7 @@ use crate::{
             ViewSwitchMessage,
             add_to_playlist::AddToPlaylist,
             context_menus::{album::AlbumContextMenu, track::TrackContextMenu},
+            tag_editor::edit_tags,
         },
         models::{LIKED_SONGS_PLAYLIST_ID, Models, PlaybackInfo, PlaylistEvent},
     },
@@ -125,7 +127,7 @@ pub fn play_from_track(
     let playback_interface = cx.global::<PlaybackInterface>();
     if let Some(index) = queue_items
         .iter()
-        .position(|item| item.get_path() == &track.location)
+        .position(|item| item.get_path() == &track.location && item.get_range() == track.range())
     {
         playback_interface.replace_queue_with_index(queue_items, index);
     } else {
@@ -143,7 +145,15 @@ pub fn play_from_track_listing(
         tracks
             .iter()
             .filter(|item| is_track_available(item))
-            .map(|item| QueueItemData::new(cx, item.location.clone(), Some(item.id), item.album_id))
+            .map(|item| {
+                QueueItemData::new(
+                    cx,
+                    item.location.clone(),
+                    item.range(),
+                    Some(item.id),
+                    item.album_id,
+                )
+            })
             .collect()
     } else if let Some(playlist_id) = playlist_id {
         let ids = cx
@@ -155,9 +165,9 @@ pub fn play_from_track_listing(
 
         ids.iter()
             .zip(paths.iter())
-            .filter(|(_, path)| Path::new(path).exists())
-            .map(|((_, track_id, album_id), path)| {
-                QueueItemData::new(cx, path.into(), Some(*track_id), Some(*album_id))
+            .filter(|(_, (path, _))| Path::new(path).exists())
+            .map(|((_, track_id, album_id), (path, range))| {
+                QueueItemData::new(cx, path.into(), *range, Some(*track_id), Some(*album_id))
             })
             .collect()
     } else if let Some(album_id) = track.album_id {
@@ -165,12 +175,21 @@ pub fn play_from_track_listing(
             .expect("Failed to retrieve tracks")
             .iter()
             .filter(|item| is_track_available(item))
-            .map(|item| QueueItemData::new(cx, item.location.clone(), Some(item.id), item.album_id))
+            .map(|item| {
+                QueueItemData::new(
+                    cx,
+                    item.location.clone(),
+                    item.range(),
+                    Some(item.id),
+                    item.album_id,
+                )
+            })
             .collect()
     } else {
         vec![QueueItemData::new(
             cx,
             track.location.clone(),
+            track.range(),
             Some(track.id),
             track.album_id,
         )]
@@ -189,8 +208,12 @@ pub fn track_show_in_file_manager_label() -> SharedString {
     }
 }
 
-pub fn resolve_library_track_by_path(cx: &App, path: &Path) -> Option<Rc<Track>> {
-    cx.get_track_by_path(path)
+pub fn resolve_library_track_by_path(
+    cx: &App,
+    path: &Path,
+    range: Option<TrackRange>,
+) -> Option<Rc<Track>> {
+    cx.get_track_by_path(path, range)
         .ok()
         .flatten()
         .map(|track| Rc::new((*track).clone()))
@@ -227,7 +250,13 @@ pub fn remove_from_playlist(
 }
 
 fn play_track_now(cx: &mut App, track: &Track) {
-    let data = QueueItemData::new(cx, track.location.clone(), Some(track.id), track.album_id);
+    let data = QueueItemData::new(
+        cx,
+        track.location.clone(),
+        track.range(),
+        Some(track.id),
+        track.album_id,
+    );
     let playback_interface = cx.global::<PlaybackInterface>();
     let queue_length = cx
         .global::<Models>()
@@ -242,14 +271,26 @@ fn play_track_now(cx: &mut App, track: &Track) {
 }
 
 pub fn play_track_next(cx: &mut App, track: &Track) {
-    let data = QueueItemData::new(cx, track.location.clone(), Some(track.id), track.album_id);
+    let data = QueueItemData::new(
+        cx,
+        track.location.clone(),
+        track.range(),
+        Some(track.id),
+        track.album_id,
+    );
     let queue_position = cx.global::<Models>().queue.read(cx).position;
     cx.global::<PlaybackInterface>()
         .insert_at(data, queue_position + 1);
 }
 
 fn queue_track(cx: &mut App, track: &Track) {
-    let data = QueueItemData::new(cx, track.location.clone(), Some(track.id), track.album_id);
+    let data = QueueItemData::new(
+        cx,
+        track.location.clone(),
+        track.range(),
+        Some(track.id),
+        track.album_id,
+    );
     cx.global::<PlaybackInterface>().queue(data);
 }
 
@@ -296,7 +337,15 @@ fn available_album_queue_items(cx: &mut App, album: &Album) -> Vec<QueueItemData
         .unwrap_or_else(|_| Arc::new(Vec::new()))
         .iter()
         .filter(|track| is_track_available(track))
-        .map(|track| QueueItemData::new(cx, track.location.clone(), Some(track.id), track.album_id))
+        .map(|track| {
+            QueueItemData::new(
+                cx,
+                track.location.clone(),
+                track.range(),
+                Some(track.id),
+                track.album_id,
+            )
+        })
         .collect()
 }
 
@@ -352,6 +401,18 @@ pub(crate) fn rescan_album(cx: &App, album: &Album) {
     cx.global::<ScanInterface>().rescan_paths(utf8_paths);
 }
 
+fn edit_album_tags(cx: &mut App, album: &Album) {
+    let tracks = match cx.list_tracks_in_album(album.id) {
+        Ok(tracks) => tracks,
+        Err(err) => {
+            tracing::error!("could not list tracks for tag editing: {err:?}");
+            return;
+        }
+    };
+
+    edit_tags(cx, tracks.iter());
+}
+
 pub(crate) fn rescan_track(cx: &App, track: &Track) {
     let path = match Utf8PathBuf::from_path_buf(track.location.clone()) {
         Ok(path) => path,
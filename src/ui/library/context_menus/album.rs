@@ -8,15 +8,15 @@ use crate::{
     ui::{
         availability::album_has_available_tracks,
         components::{
-            icons::{PLAY, PLUS, SHUFFLE, USERS},
+            icons::{PENCIL, PLAY, PLUS, SHUFFLE, USERS},
             menu::{menu, menu_item, menu_separator},
         },
     },
 };
 
 use super::{
-    AlbumContextMenuContext, navigate_to_artist, play_album_next, play_album_now, queue_album,
-    rescan_album, shuffle_album,
+    AlbumContextMenuContext, edit_album_tags, navigate_to_artist, play_album_next, play_album_now,
+    queue_album, rescan_album, shuffle_album,
 };
 
 #[derive(IntoElement)]
@@ -39,6 +39,7 @@ impl RenderOnce for AlbumContextMenu {
         let album_for_queue = self.album.clone();
         let album_for_artist = self.album.clone();
         let album_for_rescan = self.album.clone();
+        let album_for_tags = self.album.clone();
         let show_go_to_artist = self.context.show_go_to_artist;
         let is_available = album_has_available_tracks(cx, album.id);
         let menu = menu()
@@ -89,7 +90,18 @@ impl RenderOnce for AlbumContextMenu {
                 move |_, _, cx| {
                     rescan_album(cx, &album_for_rescan);
                 },
-            ));
+            ))
+            .item(
+                menu_item(
+                    "album_edit_tags",
+                    Some(PENCIL),
+                    tr!("EDIT_TAGS", "Edit tags"),
+                    move |_, _, cx| {
+                        edit_album_tags(cx, &album_for_tags);
+                    },
+                )
+                .disabled(!is_available),
+            );
 
         if show_go_to_artist {
             menu.item(menu_separator()).item(menu_item(
@@ -10,11 +10,12 @@ use crate::{
         availability::is_track_path_available,
         components::{
             icons::{
-                DISC, FOLDER_SEARCH, PLAY, PLAYLIST_ADD, PLAYLIST_REMOVE, PLUS, STAR, STAR_FILLED,
-                USERS,
+                DISC, FOLDER_SEARCH, PENCIL, PLAY, PLAYLIST_ADD, PLAYLIST_REMOVE, PLUS, STAR,
+                STAR_FILLED, USERS,
             },
             menu::{menu, menu_item, menu_separator},
         },
+        library::tag_editor::{edit_tags, is_editable},
         models::{Models, toggle_like_by_id},
         util::reveal_path_for_file_manager,
     },
@@ -67,9 +68,11 @@ impl RenderOnce for TrackContextMenu {
         let track_for_album = self.track.clone();
         let track_for_reveal = self.track.clone();
         let track_for_rescan = self.track.clone();
+        let track_for_tags = self.track.clone();
         let can_go_to_artist = track_for_artist.album_id.is_some();
         let can_go_to_album = track_for_album.album_id.is_some();
         let can_reveal_track = is_track_path_available(track_for_reveal.location.as_path());
+        let can_edit_tags = can_reveal_track && is_editable(&track_for_tags);
         let show_add_to = self.show_add_to;
         let play_from_here = self.context.play_from_here.clone();
         let playlist_info = self.playlist_info;
@@ -167,6 +170,17 @@ impl RenderOnce for TrackContextMenu {
                     rescan_track(cx, &track_for_rescan);
                 },
             ))
+            .item(
+                menu_item(
+                    "track_edit_tags",
+                    Some(PENCIL),
+                    tr!("EDIT_TAGS", "Edit tags"),
+                    move |_, _, cx| {
+                        edit_tags(cx, [&*track_for_tags]);
+                    },
+                )
+                .disabled(!can_edit_tags),
+            )
             .item(menu_separator())
             .item(
                 menu_item(
@@ -16,6 +16,7 @@ use crate::{
         playlist::export_playlist,
         types::{Playlist, PlaylistType},
     },
+    media::cue::TrackRange,
     playback::queue::QueueItemData,
     ui::{
         caching::hummingbird_cache,
@@ -98,6 +99,7 @@ pub struct PlaylistTrackItem {
     track_id: i64,
     album_id: Option<i64>,
     track_path: std::path::PathBuf,
+    track_range: Option<TrackRange>,
     drag_enabled: bool,
 }
 
@@ -114,6 +116,7 @@ impl PlaylistTrackItem {
         track_id: i64,
         album_id: Option<i64>,
         track_path: std::path::PathBuf,
+        track_range: Option<TrackRange>,
         drag_enabled: bool,
     ) -> Entity<Self> {
         cx.new(|cx| {
@@ -132,6 +135,7 @@ impl PlaylistTrackItem {
                 track_id,
                 album_id,
                 track_path,
+                track_range,
                 drag_enabled,
             }
         })
@@ -158,6 +162,7 @@ impl Render for PlaylistTrackItem {
                 self.track_id,
                 self.album_id,
                 self.track_path.clone(),
+                self.track_range,
                 self.track_title.clone(),
             )
             .with_reorder_info(self.list_id.clone(), idx);
@@ -589,10 +594,11 @@ impl Render for PlaylistView {
                                                     playlist_track_ids
                                                         .iter()
                                                         .zip(track_files.iter())
-                                                        .map(|((_, track_id, album_id), path)| {
+                                                        .map(|((_, track_id, album_id), (path, range))| {
                                                             QueueItemData::new(
                                                                 cx,
                                                                 path.into(),
+                                                                *range,
                                                                 Some(*track_id),
                                                                 Some(*album_id),
                                                             )
@@ -774,6 +780,7 @@ impl Render for PlaylistView {
                                                         let track_title: SharedString =
                                                             track.title.clone().into();
                                                         let track_path = track.location.clone();
+                                                        let track_range = track.range();
                                                         let album_id = track.album_id;
 
                                                         let track_item = TrackItem::new(
@@ -804,6 +811,7 @@ impl Render for PlaylistView {
                                                             track_id,
                                                             album_id,
                                                             track_path,
+                                                            track_range,
                                                             is_custom_sort,
                                                         )
                                                     },
@@ -832,8 +840,8 @@ pub fn find_playlist_tracks(cx: &mut App, playlist_id: i64) -> Vec<QueueItemData
     playlist_track_ids
         .iter()
         .zip(track_files.iter())
-        .map(|((_, track_id, album_id), path)| {
-            QueueItemData::new(cx, path.into(), Some(*track_id), Some(*album_id))
+        .map(|((_, track_id, album_id), (path, range))| {
+            QueueItemData::new(cx, path.into(), *range, Some(*track_id), Some(*album_id))
         })
         .collect()
 }
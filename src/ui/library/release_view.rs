@@ -270,6 +270,7 @@ impl ReleaseView {
                                                     QueueItemData::new(
                                                         cx,
                                                         track.location.clone(),
+                                                        track.range(),
                                                         Some(track.id),
                                                         track.album_id,
                                                     )
@@ -559,7 +560,7 @@ impl Render for ReleaseView {
             .is_some_and(|current_track| {
                 self.tracks
                     .iter()
-                    .any(|track| current_track == track.location && is_track_available(track))
+                    .any(|track| current_track == *track && is_track_available(track))
             });
         let has_available_tracks = has_available_tracks(self.tracks.as_ref());
 
@@ -0,0 +1,754 @@
+use std::path::{Path, PathBuf};
+
+use anyhow::anyhow;
+use camino::Utf8PathBuf;
+use cntp_i18n::{tr, trn};
+use gpui::{
+    App, AppContext, Context, Entity, FontWeight, IntoElement, ParentElement, PathPromptOptions,
+    Render, SharedString, StyleRefinement, Styled, Task, Window, div, prelude::FluentBuilder, px,
+};
+use tracing::{error, warn};
+
+use crate::{
+    library::{scan::ScanInterface, types::Track},
+    media::{
+        lookup_table::{ContentProbe, can_be_read, try_open_media, try_write_tags},
+        metadata::{Metadata, TagField, TagUpdate},
+        traits::MediaProviderFeatures,
+    },
+    ui::{
+        components::{
+            button::{ButtonIntent, ButtonStyle, button},
+            modal::modal,
+            textbox::Textbox,
+        },
+        models::Models,
+        theme::Theme,
+    },
+};
+
+/// The fields edited with a text box, in the order they're shown.
+const TEXT_FIELDS: [TagField; 13] = [
+    TagField::Title,
+    TagField::Artist,
+    TagField::Album,
+    TagField::AlbumArtist,
+    TagField::TrackNumber,
+    TagField::TrackTotal,
+    TagField::DiscNumber,
+    TagField::DiscTotal,
+    TagField::Date,
+    TagField::Genre,
+    TagField::Label,
+    TagField::Catalog,
+    TagField::Isrc,
+];
+
+/// Opens the tag editor for the files of the given tracks. Tracks split from a file by a CUE sheet
+/// get their metadata from the sheet rather than the file's tags, so they're left out.
+pub fn edit_tags<'a>(cx: &mut App, tracks: impl IntoIterator<Item = &'a Track>) {
+    let mut paths: Vec<PathBuf> = Vec::new();
+    for track in tracks {
+        if is_editable(track) && !paths.contains(&track.location) {
+            paths.push(track.location.clone());
+        }
+    }
+
+    if paths.is_empty() {
+        return;
+    }
+
+    let targets = cx.global::<Models>().tag_editor_targets.clone();
+    targets.write(cx, paths);
+}
+
+/// Whether the tags of the file a track was read from can be edited.
+pub fn is_editable(track: &Track) -> bool {
+    track.range().is_none()
+}
+
+fn field_label(field: TagField) -> SharedString {
+    match field {
+        TagField::Title => tr!("TAG_EDITOR_TITLE", "Title"),
+        TagField::Artist => tr!("TAG_EDITOR_ARTIST", "Artist"),
+        TagField::Album => tr!("TAG_EDITOR_ALBUM", "Album"),
+        TagField::AlbumArtist => tr!("TAG_EDITOR_ALBUM_ARTIST", "Album artist"),
+        TagField::TrackNumber => tr!("TAG_EDITOR_TRACK_NUMBER", "Track number"),
+        TagField::TrackTotal => tr!("TAG_EDITOR_TRACK_TOTAL", "Total tracks"),
+        TagField::DiscNumber => tr!("TAG_EDITOR_DISC_NUMBER", "Disc number"),
+        TagField::DiscTotal => tr!("TAG_EDITOR_DISC_TOTAL", "Total discs"),
+        TagField::Date => tr!("TAG_EDITOR_DATE", "Date"),
+        TagField::Genre => tr!("TAG_EDITOR_GENRE", "Genre"),
+        TagField::Label => tr!("TAG_EDITOR_LABEL", "Label"),
+        TagField::Catalog => tr!("TAG_EDITOR_CATALOG", "Catalog number"),
+        TagField::Isrc => tr!("TAG_EDITOR_ISRC", "ISRC"),
+        TagField::Lyrics => tr!("TAG_EDITOR_LYRICS", "Lyrics"),
+    }
+    .into()
+}
+
+/// The value of a field as it's shown in the editor.
+fn field_value(metadata: &Metadata, field: TagField) -> Option<String> {
+    match field {
+        TagField::Title => metadata.name.clone(),
+        TagField::Artist => metadata.artist.clone(),
+        TagField::Album => metadata.album.clone(),
+        TagField::AlbumArtist => metadata.album_artist.clone(),
+        TagField::TrackNumber => metadata.track_current.map(|v| v.to_string()),
+        TagField::TrackTotal => metadata.track_max.map(|v| v.to_string()),
+        TagField::DiscNumber => metadata.disc_current.map(|v| v.to_string()),
+        TagField::DiscTotal => metadata.disc_max.map(|v| v.to_string()),
+        TagField::Date => metadata
+            .date
+            .map(|date| date.format("%Y-%m-%d").to_string())
+            .or_else(|| {
+                metadata
+                    .year_month
+                    .map(|(year, month)| format!("{year:04}-{month:02}"))
+            })
+            .or_else(|| metadata.year.map(|year| year.to_string())),
+        TagField::Genre => metadata.genre.clone(),
+        TagField::Label => metadata.label.clone(),
+        TagField::Catalog => metadata.catalog.clone(),
+        TagField::Isrc => metadata.isrc.clone(),
+        TagField::Lyrics => metadata.lyrics.clone(),
+    }
+}
+
+/// The value shared by all of the files, or `None` if they differ. Files without the tag count
+/// as having an empty value.
+fn common_value(values: impl IntoIterator<Item = Option<String>>) -> Option<String> {
+    let mut values = values.into_iter().map(Option::unwrap_or_default);
+    let first = values.next().unwrap_or_default();
+    values.all(|value| value == first).then_some(first)
+}
+
+/// The fields the user changed, from their value when the editor was opened and their current
+/// value.
+fn changed_fields<'a>(
+    fields: impl IntoIterator<Item = (TagField, &'a str, &'a str)>,
+) -> Vec<(TagField, String)> {
+    fields
+        .into_iter()
+        .filter_map(|(field, initial, current)| {
+            let current = current.trim();
+            (current != initial).then(|| (field, current.to_string()))
+        })
+        .collect()
+}
+
+fn read_tags(path: &Path) -> anyhow::Result<Metadata> {
+    let mut stream = try_open_media(path, MediaProviderFeatures::PROVIDES_METADATA)?
+        .ok_or_else(|| anyhow!("no provider can read the file"))?;
+    stream.start_playback()?;
+    let metadata = stream.read_metadata()?.clone();
+    stream.close()?;
+    Ok(metadata)
+}
+
+/// Reads the current tags of the files. Files that tags can't be written to are left out.
+fn read_files(paths: Vec<PathBuf>) -> Vec<(PathBuf, Metadata)> {
+    paths
+        .into_iter()
+        .filter(|path| {
+            can_be_read(
+                path,
+                MediaProviderFeatures::WRITES_TAGS,
+                ContentProbe::Never,
+            )
+            .unwrap_or_default()
+        })
+        .filter_map(|path| match read_tags(&path) {
+            Ok(metadata) => Some((path, metadata)),
+            Err(err) => {
+                warn!("Could not read tags from {:?}: {}", path, err);
+                None
+            }
+        })
+        .collect()
+}
+
+/// Writes the update to the files. Returns the files that were changed, and the number of files
+/// that couldn't be.
+fn write_files(files: Vec<PathBuf>, update: &TagUpdate) -> (Vec<Utf8PathBuf>, usize) {
+    let mut written = Vec::with_capacity(files.len());
+    let mut failed = 0;
+
+    for path in files {
+        match try_write_tags(&path, update) {
+            Ok(true) => {}
+            Ok(false) => {
+                warn!("Writing tags is not supported for {:?}", path);
+                failed += 1;
+                continue;
+            }
+            Err(e) => {
+                warn!("Could not write tags to {:?}: {}", path, e);
+                failed += 1;
+                continue;
+            }
+        }
+
+        match Utf8PathBuf::from_path_buf(path) {
+            Ok(path) => written.push(path),
+            Err(path) => warn!("cannot rescan track with non-UTF-8 path: {:?}", path),
+        }
+    }
+
+    (written, failed)
+}
+
+#[derive(Clone, Copy, PartialEq, Eq)]
+enum EditorState {
+    Closed,
+    Loading,
+    Ready,
+    Saving,
+}
+
+struct EditorField {
+    field: TagField,
+    /// The value shared by the files when the editor was opened, or an empty string if they
+    /// differ.
+    initial: SharedString,
+    input: Entity<Textbox>,
+}
+
+pub struct TagEditor {
+    targets: Entity<Vec<PathBuf>>,
+    state: EditorState,
+    /// The files being edited. Only files that tags can be written to are included.
+    files: Vec<PathBuf>,
+    /// The number of files that were requested but can't be edited.
+    skipped: usize,
+    /// The number of files that couldn't be written to by the last save.
+    failed: usize,
+    fields: Vec<EditorField>,
+    /// Replacement lyrics. An empty string removes the lyrics.
+    lyrics: Option<String>,
+    /// Replacement front cover, with the name of the file it was loaded from.
+    cover: Option<(SharedString, Vec<u8>)>,
+    task: Option<Task<()>>,
+}
+
+impl TagEditor {
+    pub fn new(cx: &mut App) -> Entity<Self> {
+        cx.new(|cx| {
+            let targets = cx.global::<Models>().tag_editor_targets.clone();
+            cx.observe(&targets, |this: &mut Self, _, cx| {
+                this.load(cx);
+            })
+            .detach();
+
+            let fields = TEXT_FIELDS
+                .iter()
+                .map(|field| {
+                    let weak_self = cx.entity().downgrade();
+                    let input =
+                        Textbox::new_with_submit(cx, StyleRefinement::default(), move |cx| {
+                            if let Some(entity) = weak_self.upgrade() {
+                                entity.update(cx, |this, cx| this.save(cx));
+                            }
+                        });
+
+                    EditorField {
+                        field: *field,
+                        initial: SharedString::default(),
+                        input,
+                    }
+                })
+                .collect();
+
+            Self {
+                targets,
+                state: EditorState::Closed,
+                files: Vec::new(),
+                skipped: 0,
+                failed: 0,
+                fields,
+                lyrics: None,
+                cover: None,
+                task: None,
+            }
+        })
+    }
+
+    fn load(&mut self, cx: &mut Context<Self>) {
+        let paths = self.targets.read(cx).clone();
+        let requested = paths.len();
+
+        self.files.clear();
+        self.skipped = 0;
+        self.failed = 0;
+        self.lyrics = None;
+        self.cover = None;
+        self.task = None;
+
+        if paths.is_empty() {
+            self.state = EditorState::Closed;
+            cx.notify();
+            return;
+        }
+
+        self.state = EditorState::Loading;
+
+        let task = crate::RUNTIME.spawn_blocking(move || read_files(paths));
+        self.task = Some(cx.spawn(async move |this, cx| {
+            let files = match task.await {
+                Ok(files) => files,
+                Err(err) => {
+                    error!("tag reading task panicked: {err:?}");
+                    Vec::new()
+                }
+            };
+
+            this.update(cx, |this: &mut Self, cx| {
+                this.populate(files, requested, cx);
+            })
+            .ok();
+        }));
+
+        cx.notify();
+    }
+
+    fn populate(
+        &mut self,
+        files: Vec<(PathBuf, Metadata)>,
+        requested: usize,
+        cx: &mut Context<Self>,
+    ) {
+        for field in &mut self.fields {
+            let common = common_value(files.iter().map(|(_, m)| field_value(m, field.field)));
+            let placeholder: SharedString = if common.is_none() {
+                tr!("TAG_EDITOR_MULTIPLE_VALUES", "Multiple values").into()
+            } else {
+                SharedString::default()
+            };
+
+            field.initial = common.unwrap_or_default().into();
+            let initial = field.initial.clone();
+            field.input.update(cx, |input, cx| {
+                input.set_value(cx, initial);
+                input.set_placeholder(cx, placeholder);
+            });
+        }
+
+        self.skipped = requested - files.len();
+        self.files = files.into_iter().map(|(path, _)| path).collect();
+        self.state = EditorState::Ready;
+        cx.notify();
+    }
+
+    fn close(&mut self, cx: &mut Context<Self>) {
+        self.targets.write(cx, Vec::new());
+    }
+
+    fn build_update(&self, cx: &App) -> TagUpdate {
+        let values: Vec<_> = self
+            .fields
+            .iter()
+            .map(|field| {
+                (
+                    field.field,
+                    field.initial.clone(),
+                    field.input.read(cx).value(cx),
+                )
+            })
+            .collect();
+
+        let mut fields = changed_fields(
+            values
+                .iter()
+                .map(|(field, initial, current)| (*field, initial.as_ref(), current.as_ref())),
+        );
+        if let Some(lyrics) = &self.lyrics {
+            fields.push((TagField::Lyrics, lyrics.clone()));
+        }
+
+        TagUpdate {
+            fields,
+            cover: self.cover.as_ref().map(|(_, data)| data.clone()),
+            ..Default::default()
+        }
+    }
+
+    fn save(&mut self, cx: &mut Context<Self>) {
+        if self.state != EditorState::Ready {
+            return;
+        }
+
+        let update = self.build_update(cx);
+        if update.fields.is_empty() && update.cover.is_none() {
+            self.close(cx);
+            return;
+        }
+
+        self.state = EditorState::Saving;
+        self.failed = 0;
+
+        let files = self.files.clone();
+        let count = files.len();
+        let task = crate::RUNTIME.spawn_blocking(move || write_files(files, &update));
+        self.task = Some(cx.spawn(async move |this, cx| {
+            let (written, failed) = match task.await {
+                Ok(result) => result,
+                Err(err) => {
+                    error!("tag writing task panicked: {err:?}");
+                    (Vec::new(), count)
+                }
+            };
+
+            this.update(cx, |this: &mut Self, cx| {
+                if !written.is_empty() {
+                    cx.global::<ScanInterface>().rescan_paths(written);
+                }
+
+                if failed == 0 {
+                    this.close(cx);
+                } else {
+                    this.failed = failed;
+                    this.state = EditorState::Ready;
+                    cx.notify();
+                }
+            })
+            .ok();
+        }));
+
+        cx.notify();
+    }
+
+    fn choose_cover(&mut self, cx: &mut Context<Self>) {
+        let path_future = cx.prompt_for_paths(PathPromptOptions {
+            files: true,
+            directories: false,
+            multiple: false,
+            prompt: Some(tr!("TAG_EDITOR_SELECT_COVER", "Select an image...").into()),
+        });
+
+        cx.spawn(async move |this, cx| {
+            let Ok(Ok(Some(path))) = path_future.await else {
+                return;
+            };
+            let Some(path) = path.into_iter().next() else {
+                return;
+            };
+
+            let read_path = path.clone();
+            let data = match crate::RUNTIME
+                .spawn_blocking(move || std::fs::read(read_path))
+                .await
+            {
+                Ok(Ok(data)) if infer::is_image(&data) => data,
+                Ok(Ok(_)) => {
+                    warn!("{:?} is not an image", path);
+                    return;
+                }
+                Ok(Err(err)) => {
+                    error!("Could not read cover image {:?}: {}", path, err);
+                    return;
+                }
+                Err(err) => {
+                    error!("cover reading task panicked: {err:?}");
+                    return;
+                }
+            };
+            let name = path
+                .file_name()
+                .map(|name| name.to_string_lossy().into_owned())
+                .unwrap_or_default();
+
+            this.update(cx, |this: &mut Self, cx| {
+                this.cover = Some((name.into(), data));
+                cx.notify();
+            })
+            .ok();
+        })
+        .detach();
+    }
+
+    fn choose_lyrics(&mut self, cx: &mut Context<Self>) {
+        let path_future = cx.prompt_for_paths(PathPromptOptions {
+            files: true,
+            directories: false,
+            multiple: false,
+            prompt: Some(tr!("TAG_EDITOR_SELECT_LYRICS", "Select a lyrics file...").into()),
+        });
+
+        cx.spawn(async move |this, cx| {
+            let Ok(Ok(Some(path))) = path_future.await else {
+                return;
+            };
+            let Some(path) = path.into_iter().next() else {
+                return;
+            };
+
+            let read_path = path.clone();
+            let lyrics = match crate::RUNTIME
+                .spawn_blocking(move || std::fs::read_to_string(read_path))
+                .await
+            {
+                Ok(Ok(lyrics)) => lyrics,
+                Ok(Err(err)) => {
+                    error!("Could not read lyrics file {:?}: {}", path, err);
+                    return;
+                }
+                Err(err) => {
+                    error!("lyrics reading task panicked: {err:?}");
+                    return;
+                }
+            };
+
+            this.update(cx, |this: &mut Self, cx| {
+                this.lyrics = Some(lyrics.trim().to_string());
+                cx.notify();
+            })
+            .ok();
+        })
+        .detach();
+    }
+}
+
+impl Render for TagEditor {
+    fn render(&mut self, _: &mut Window, cx: &mut Context<Self>) -> impl IntoElement {
+        if self.state == EditorState::Closed {
+            return div().into_any_element();
+        }
+
+        let text_secondary = cx.global::<Theme>().text_secondary;
+        let ready = self.state == EditorState::Ready;
+
+        let lyrics_status: SharedString = match self.lyrics.as_deref() {
+            None => tr!("TAG_EDITOR_UNCHANGED", "Unchanged").into(),
+            Some("") => tr!("TAG_EDITOR_LYRICS_REMOVED", "Will be removed").into(),
+            Some(_) => tr!("TAG_EDITOR_LYRICS_REPLACED", "Will be replaced").into(),
+        };
+        let cover_status: SharedString = match &self.cover {
+            None => tr!("TAG_EDITOR_UNCHANGED").into(),
+            Some((name, _)) => name.clone(),
+        };
+
+        let status: Option<SharedString> = match self.state {
+            EditorState::Loading => Some(tr!("TAG_EDITOR_LOADING", "Reading tags...").into()),
+            EditorState::Saving => Some(tr!("TAG_EDITOR_SAVING", "Saving...").into()),
+            _ if self.failed > 0 => Some(
+                trn!(
+                    "TAG_EDITOR_FAILED",
+                    "Could not save the tags of {{count}} file.",
+                    "Could not save the tags of {{count}} files.",
+                    count = self.failed as i64
+                )
+                .into(),
+            ),
+            _ if self.skipped > 0 => Some(
+                trn!(
+                    "TAG_EDITOR_SKIPPED",
+                    "{{count}} file can't be edited and will be left as it is.",
+                    "{{count}} files can't be edited and will be left as they are.",
+                    count = self.skipped as i64
+                )
+                .into(),
+            ),
+            _ => None,
+        };
+
+        let cell = |title: SharedString| {
+            div()
+                .w(px(248.0))
+                .flex()
+                .flex_col()
+                .gap(px(4.0))
+                .child(div().text_xs().text_color(text_secondary).child(title))
+        };
+
+        modal()
+            .on_exit(|_, cx| {
+                let targets = cx.global::<Models>().tag_editor_targets.clone();
+                targets.write(cx, Vec::new());
+            })
+            .child(
+                div()
+                    .w(px(544.0))
+                    .max_w_full()
+                    .p(px(24.0))
+                    .flex()
+                    .flex_col()
+                    .gap(px(16.0))
+                    .child(
+                        div()
+                            .flex()
+                            .flex_col()
+                            .child(
+                                div()
+                                    .text_size(px(18.0))
+                                    .font_weight(FontWeight::BOLD)
+                                    .line_height(px(24.0))
+                                    .child(tr!("TAG_EDITOR_HEADER", "Edit tags")),
+                            )
+                            .child(div().text_sm().text_color(text_secondary).child(trn!(
+                                "TAG_EDITOR_FILE_COUNT",
+                                "{{count}} file",
+                                "{{count}} files",
+                                count = self.files.len() as i64
+                            ))),
+                    )
+                    .child(
+                        div()
+                            .flex()
+                            .flex_wrap()
+                            .gap(px(12.0))
+                            .children(self.fields.iter().map(|field| {
+                                cell(field_label(field.field)).child(field.input.clone())
+                            }))
+                            .child(
+                                cell(field_label(TagField::Lyrics))
+                                    .child(div().text_sm().child(lyrics_status))
+                                    .child(
+                                        div()
+                                            .flex()
+                                            .gap(px(6.0))
+                                            .child(
+                                                button()
+                                                    .intent(ButtonIntent::Secondary)
+                                                    .child(tr!(
+                                                        "TAG_EDITOR_CHOOSE_FILE",
+                                                        "Choose file..."
+                                                    ))
+                                                    .id("tag-editor-lyrics-choose")
+                                                    .on_click(cx.listener(|this, _, _, cx| {
+                                                        this.choose_lyrics(cx);
+                                                    })),
+                                            )
+                                            .child(
+                                                button()
+                                                    .style(ButtonStyle::Minimal)
+                                                    .intent(ButtonIntent::Secondary)
+                                                    .child(tr!("TAG_EDITOR_REMOVE", "Remove"))
+                                                    .id("tag-editor-lyrics-remove")
+                                                    .on_click(cx.listener(|this, _, _, cx| {
+                                                        this.lyrics = Some(String::new());
+                                                        cx.notify();
+                                                    })),
+                                            ),
+                                    ),
+                            )
+                            .child(
+                                cell(tr!("TAG_EDITOR_COVER", "Cover art").into())
+                                    .child(
+                                        div()
+                                            .text_sm()
+                                            .overflow_hidden()
+                                            .text_ellipsis()
+                                            .child(cover_status),
+                                    )
+                                    .child(
+                                        div().flex().child(
+                                            button()
+                                                .intent(ButtonIntent::Secondary)
+                                                .child(tr!(
+                                                    "TAG_EDITOR_CHOOSE_IMAGE",
+                                                    "Choose image..."
+                                                ))
+                                                .id("tag-editor-cover-choose")
+                                                .on_click(cx.listener(|this, _, _, cx| {
+                                                    this.choose_cover(cx);
+                                                })),
+                                        ),
+                                    ),
+                            ),
+                    )
+                    .child(
+                        div()
+                            .flex()
+                            .items_center()
+                            .gap(px(8.0))
+                            .child(
+                                div()
+                                    .flex_grow()
+                                    .text_sm()
+                                    .text_color(text_secondary)
+                                    .when_some(status, |this, status| this.child(status)),
+                            )
+                            .child(
+                                button()
+                                    .intent(ButtonIntent::Secondary)
+                                    .child(tr!("TAG_EDITOR_CANCEL", "Cancel"))
+                                    .id("tag-editor-cancel")
+                                    .on_click(cx.listener(|this, _, _, cx| this.close(cx))),
+                            )
+                            .child(
+                                button()
+                                    .intent(ButtonIntent::Primary)
+                                    .when(!ready, |this| this.opacity(0.5))
+                                    .child(tr!("TAG_EDITOR_SAVE", "Save"))
+                                    .id("tag-editor-save")
+                                    .on_click(cx.listener(|this, _, _, cx| this.save(cx))),
+                            ),
+                    ),
+            )
+            .into_any_element()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn common_value_requires_every_file_to_agree() {
+        let same = vec![Some("Album".to_string()), Some("Album".to_string())];
+        assert_eq!(common_value(same), Some("Album".to_string()));
+
+        let mixed = vec![
+            Some("Album".to_string()),
+            Some("Album (Deluxe)".to_string()),
+        ];
+        assert_eq!(common_value(mixed), None);
+
+        // a missing tag only matches other missing or empty tags
+        assert_eq!(
+            common_value(vec![None, Some(String::new())]),
+            Some(String::new())
+        );
+        assert_eq!(common_value(vec![None, Some("Rock".to_string())]), None);
+    }
+
+    #[test]
+    fn only_changed_fields_are_written() {
+        let changed = changed_fields([
+            (TagField::Title, "Song", "Song"),
+            (TagField::Album, "Albm", " Album "),
+            (TagField::Genre, "Rock", ""),
+            // mixed values are shown empty, and stay untouched unless something is typed
+            (TagField::Artist, "", ""),
+        ]);
+
+        assert_eq!(
+            changed,
+            vec![
+                (TagField::Album, "Album".to_string()),
+                (TagField::Genre, String::new()),
+            ]
+        );
+    }
+
+    #[test]
+    fn partial_dates_keep_their_precision() {
+        let metadata = Metadata {
+            year_month: Some((1995, 6)),
+            ..Default::default()
+        };
+        assert_eq!(
+            field_value(&metadata, TagField::Date),
+            Some("1995-06".to_string())
+        );
+
+        let metadata = Metadata {
+            year: Some(1995),
+            ..Default::default()
+        };
+        assert_eq!(
+            field_value(&metadata, TagField::Date),
+            Some("1995".to_string())
+        );
+    }
+}
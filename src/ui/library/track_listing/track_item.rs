@@ -7,15 +7,18 @@ use gpui::{
 use std::{rc::Rc, sync::Arc};
 
 use crate::ui::components::drag_drop::{DragPreview, TrackDragData};
-use crate::ui::components::icons::{STAR, STAR_FILLED, icon};
+use crate::ui::components::icons::{CHECK, PROGRESS, STAR, STAR_FILLED, icon};
 use crate::ui::library::context_menus::play_track_next;
 use crate::ui::library::context_menus::track::TrackContextMenu;
 use crate::ui::models::{
-    HasLikedState, LIKED_SONGS_PLAYLIST_ID, subscribe_liked_updates, toggle_like,
+    HasLikedState, LIKED_SONGS_PLAYLIST_ID, Models, subscribe_liked_updates, toggle_like,
 };
 use crate::ui::util::format_duration;
 
-use crate::library::{db::LibraryAccess, types::Track};
+use crate::library::{
+    db::LibraryAccess,
+    types::{Track, TrackProgress},
+};
 use crate::ui::{
     availability::is_track_available,
     components::context::context,
@@ -43,6 +46,7 @@ pub struct TrackItem {
     queue_context: Option<Arc<Vec<Track>>>,
     show_go_to_album: bool,
     show_go_to_artist: bool,
+    progress: Option<TrackProgress>,
 }
 
 #[derive(Eq, PartialEq)]
@@ -91,6 +95,19 @@ impl TrackItem {
 
             subscribe_liked_updates(cx, move |_| Some(track_id));
 
+            let track_progress = cx.global::<Models>().track_progress.clone();
+            cx.observe(
+                &track_progress,
+                move |this: &mut Self, track_progress, cx| {
+                    let progress = track_progress.read(cx).get(&track_id).copied();
+                    if this.progress != progress {
+                        this.progress = progress;
+                        cx.notify();
+                    }
+                },
+            )
+            .detach();
+
             Self {
                 hover_group: format!("track-{}", track.id).into(),
                 is_liked: cx
@@ -110,6 +127,7 @@ impl TrackItem {
                 queue_context,
                 show_go_to_album,
                 show_go_to_artist,
+                progress: track_progress.read(cx).get(&track_id).copied(),
             }
         })
     }
@@ -136,6 +154,7 @@ impl Render for TrackItem {
         let is_available = self.is_available;
 
         let track_location_for_drag = self.track.location.clone();
+        let track_range_for_drag = self.track.range();
         let album_id = self.track.album_id;
         let track_title_for_drag: SharedString = self.track.title.clone().into();
 
@@ -256,6 +275,7 @@ impl Render for TrackItem {
                                                 track_id,
                                                 album_id,
                                                 track_location_for_drag,
+                                                track_range_for_drag,
                                                 track_title_for_drag.clone(),
                                             ),
                                             move |_, _, _, cx| {
@@ -264,7 +284,7 @@ impl Render for TrackItem {
                                         )
                                     })
                                     .when_some(current_track, |this, track| {
-                                        this.bg(if track == self.track.location {
+                                        this.bg(if track == self.track {
                                             theme.queue_item_current
                                         } else {
                                             theme.background_primary
@@ -372,7 +392,21 @@ impl Render for TrackItem {
                                             .border_l_1()
                                             .pl(px(10.0))
                                             .border_color(theme.border_color)
-                                            .text_align(TextAlign::Right)
+                                            .flex()
+                                            .items_center()
+                                            .justify_end()
+                                            .gap(px(6.0))
+                                            .when_some(self.progress, |this, progress| {
+                                                this.child(
+                                                    icon(if progress.finished {
+                                                        CHECK
+                                                    } else {
+                                                        PROGRESS
+                                                    })
+                                                    .size(px(14.0))
+                                                    .text_color(theme.text_secondary),
+                                                )
+                                            })
                                             .child(format_duration(self.track.duration, false)),
                                     ),
                             ),
@@ -11,6 +11,7 @@ use crate::{
         scan::ScanEvent,
         types::{Track, table::TrackColumn},
     },
+    media::cue::TrackRange,
     playback::{interface::PlaybackInterface, queue::QueueItemData},
     ui::{
         availability::is_track_path_available,
@@ -43,17 +44,20 @@ impl TrackView {
             let table_ref_clone = table_ref.clone();
 
             let handler = Rc::new(
-                move |cx: &mut App, id: &(i64, String, Option<i64>, String)| {
+                move |cx: &mut App, id: &(i64, String, Option<i64>, String, Option<TrackRange>)| {
                     if let Some(table) = table_ref_clone.borrow().as_ref() {
                         let items = table.read(cx).get_items();
                         if let Some(items) = items {
                             let queue_items: Vec<QueueItemData> = items
                                 .iter()
-                                .filter(|(_, _, _, path)| is_track_path_available(Path::new(path)))
-                                .map(|(id, _, album_id, path)| {
+                                .filter(|(_, _, _, path, _)| {
+                                    is_track_path_available(Path::new(path))
+                                })
+                                .map(|(id, _, album_id, path, range)| {
                                     QueueItemData::new(
                                         cx,
                                         PathBuf::from(path),
+                                        *range,
                                         Some(*id),
                                         *album_id,
                                     )
@@ -93,9 +97,15 @@ impl TrackView {
 
                         let queue_items = items
                             .iter()
-                            .filter(|(_, _, _, path)| is_track_path_available(Path::new(path)))
-                            .map(|(id, _, album_id, path)| {
-                                QueueItemData::new(cx, PathBuf::from(path), Some(*id), *album_id)
+                            .filter(|(_, _, _, path, _)| is_track_path_available(Path::new(path)))
+                            .map(|(id, _, album_id, path, range)| {
+                                QueueItemData::new(
+                                    cx,
+                                    PathBuf::from(path),
+                                    *range,
+                                    Some(*id),
+                                    *album_id,
+                                )
                             })
                             .collect::<Vec<_>>();
 
@@ -10,7 +10,8 @@ use crate::{
     ui::library::NavigationHistory,
 };
 use gpui::{
-    App, AppContext, AsyncApp, Context, Entity, EventEmitter, Global, Pixels, RenderImage, Size,
+    App, AppContext, AsyncApp, Context, Entity, EventEmitter, Global, Pixels, RenderImage,
+    SharedString, Size,
 };
 use rustc_hash::FxHashMap;
 use serde::{Deserialize, Serialize};
@@ -18,14 +19,17 @@ use tokio::sync::{Mutex, watch};
 use tracing::{debug, error, warn};
 
 use crate::{
+    devices::visualizer::VisualizerFrame,
     library::{
+        analysis::AnalysisEvent,
         db::{self, LibraryAccess, LikedTrackSortMethod, PlaylistTrackSortMethod},
         scan::ScanEvent,
+        types::{Bookmark, Track, TrackProgress},
     },
     media::metadata::Metadata,
     playback::{
-        events::RepeatState,
-        queue::{QueueItemData, QueueItemUIData},
+        events::{AbLoop, OutputDevice, RepeatState, SleepTimerState},
+        queue::{QueueItemData, QueueItemUIData, TrackSource},
         thread::PlaybackState,
     },
     services::mmb::{
@@ -71,12 +75,15 @@ pub struct Models {
     pub albumart_original: Entity<Option<Arc<RenderImage>>>,
     pub queue: Entity<Queue>,
     pub scan_state: Entity<ScanEvent>,
+    pub analysis_state: Entity<AnalysisEvent>,
     pub settings_health: Entity<SettingsHealth>,
     pub mmbs: Entity<MMBSList>,
     pub lastfm: Entity<LastFMState>,
     pub discord_rpc: Entity<DiscordRpcStatus>,
     pub switcher_model: Entity<NavigationHistory>,
     pub show_about: Entity<bool>,
+    /// Files open in the tag editor. The editor is hidden while this is empty.
+    pub tag_editor_targets: Entity<Vec<PathBuf>>,
     pub playlist_tracker: Entity<PlaylistInfoTransfer>,
     pub sidebar_width: Entity<Pixels>,
     pub queue_width: Entity<Pixels>,
@@ -88,6 +95,10 @@ pub struct Models {
     pub lyrics_height: Entity<Pixels>,
     pub controls_left_width: Entity<Pixels>,
     pub controls_right_width: Entity<Pixels>,
+    /// Bookmarks saved for the current track, ordered by position.
+    pub bookmarks: Entity<Arc<Vec<Bookmark>>>,
+    /// Saved positions of resumable tracks, by track ID.
+    pub track_progress: Entity<Arc<FxHashMap<i64, TrackProgress>>>,
     #[cfg(feature = "update")]
     pub pending_update: Entity<Option<PathBuf>>,
     pub window_information: Entity<Option<WindowInformation>>,
@@ -96,21 +107,43 @@ pub struct Models {
 impl Global for Models {}
 
 #[derive(Clone, PartialEq, Debug, Serialize, Deserialize)]
-pub struct CurrentTrack(PathBuf);
+#[serde(from = "StoredCurrentTrack")]
+pub struct CurrentTrack(TrackSource);
+
+/// Earlier versions stored the current track as a bare path.
+#[derive(Deserialize)]
+#[serde(untagged)]
+enum StoredCurrentTrack {
+    Path(PathBuf),
+    Source(TrackSource),
+}
+
+impl From<StoredCurrentTrack> for CurrentTrack {
+    fn from(value: StoredCurrentTrack) -> Self {
+        match value {
+            StoredCurrentTrack::Path(path) => CurrentTrack(path.into()),
+            StoredCurrentTrack::Source(source) => CurrentTrack(source),
+        }
+    }
+}
 
 impl CurrentTrack {
-    pub fn new(path: PathBuf) -> Self {
-        CurrentTrack(path)
+    pub fn new(source: TrackSource) -> Self {
+        CurrentTrack(source)
     }
 
     pub fn get_path(&self) -> &PathBuf {
+        &self.0.path
+    }
+
+    pub fn source(&self) -> &TrackSource {
         &self.0
     }
 }
 
-impl PartialEq<std::path::PathBuf> for CurrentTrack {
-    fn eq(&self, other: &std::path::PathBuf) -> bool {
-        &self.0 == other
+impl PartialEq<Track> for CurrentTrack {
+    fn eq(&self, other: &Track) -> bool {
+        self.0.path == other.location && self.0.range == other.range()
     }
 }
 
@@ -124,6 +157,20 @@ pub struct PlaybackInfo {
     pub repeating: Entity<RepeatState>,
     pub volume: Entity<f64>,
     pub prev_volume: Entity<f64>,
+    pub speed: Entity<f64>,
+    pub sleep_timer: Entity<Option<SleepTimerState>>,
+    pub ab_loop: Entity<AbLoop>,
+    /// The output devices offered by the current device provider, as last listed.
+    pub output_devices: Entity<Arc<Vec<OutputDevice>>>,
+    /// The name of the selected output device, if it couldn't be found and the default device is
+    /// being used instead.
+    pub unavailable_output_device: Entity<Option<SharedString>>,
+    /// Whether the current track is being played bit-perfectly.
+    pub bit_perfect: Entity<bool>,
+    /// The latest visualizer frame, while a view holds a
+    /// [`VisualizerSubscription`](crate::playback::interface::VisualizerSubscription) and audio
+    /// is playing.
+    pub visualizer: Entity<Option<Arc<VisualizerFrame>>>,
 }
 
 impl Global for PlaybackInfo {}
@@ -236,6 +283,7 @@ pub fn build_models(
     let albumart_original: Entity<Option<Arc<RenderImage>>> = cx.new(|_| None);
     let queue: Entity<Queue> = cx.new(move |_| queue);
     let scan_state: Entity<ScanEvent> = cx.new(|_| ScanEvent::ScanCompleteIdle);
+    let analysis_state: Entity<AnalysisEvent> = cx.new(|_| AnalysisEvent::AnalysisComplete);
     let initial_corrupt_path = cx.global::<SettingsGlobal>().initial_corrupt_path.clone();
     let settings_health: Entity<SettingsHealth> = cx.new(|_| match initial_corrupt_path {
         Some(path) => SettingsHealth::Corrupt { path },
@@ -243,6 +291,7 @@ pub fn build_models(
     });
     let mmbs: Entity<MMBSList> = cx.new(|_| MMBSList(FxHashMap::default()));
     let show_about: Entity<bool> = cx.new(|_| false);
+    let tag_editor_targets: Entity<Vec<PathBuf>> = cx.new(|_| Vec::new());
     let lastfm: Entity<LastFMState> = cx.new(|cx| {
         let directory = paths::data_dir();
         let path = directory.join("lastfm.json");
@@ -422,6 +471,9 @@ pub fn build_models(
         }
     });
 
+    let bookmarks = cx.new(|_| Arc::new(Vec::new()));
+    let track_progress = cx.new(|_| Arc::new(FxHashMap::default()));
+
     #[cfg(feature = "update")]
     let pending_update = cx.new(|_| None);
 
@@ -433,12 +485,14 @@ pub fn build_models(
         albumart_original,
         queue,
         scan_state,
+        analysis_state,
         settings_health,
         mmbs,
         lastfm,
         discord_rpc,
         switcher_model,
         show_about,
+        tag_editor_targets,
         playlist_tracker,
         sidebar_width,
         queue_width,
@@ -450,6 +504,8 @@ pub fn build_models(
         lyrics_height,
         controls_left_width,
         controls_right_width,
+        bookmarks,
+        track_progress,
         #[cfg(feature = "update")]
         pending_update,
         window_information,
@@ -463,6 +519,13 @@ pub fn build_models(
     let repeating: Entity<RepeatState> = cx.new(|_| initial_repeat);
     let volume: Entity<f64> = cx.new(|_| storage_data.volume);
     let prev_volume: Entity<f64> = cx.new(|_| storage_data.volume);
+    let speed: Entity<f64> = cx.new(|_| 1.0);
+    let sleep_timer: Entity<Option<SleepTimerState>> = cx.new(|_| None);
+    let ab_loop: Entity<AbLoop> = cx.new(|_| AbLoop::default());
+    let output_devices: Entity<Arc<Vec<OutputDevice>>> = cx.new(|_| Arc::new(Vec::new()));
+    let unavailable_output_device: Entity<Option<SharedString>> = cx.new(|_| None);
+    let bit_perfect: Entity<bool> = cx.new(|_| false);
+    let visualizer: Entity<Option<Arc<VisualizerFrame>>> = cx.new(|_| None);
 
     cx.set_global(PlaybackInfo {
         position,
@@ -473,6 +536,13 @@ pub fn build_models(
         repeating,
         volume,
         prev_volume,
+        speed,
+        sleep_timer,
+        ab_loop,
+        output_devices,
+        unavailable_output_device,
+        bit_perfect,
+        visualizer,
     });
 }
 
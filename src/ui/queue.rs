@@ -1061,6 +1061,7 @@ impl Render for Queue {
                             let queue_item = QueueItemData::new(
                                 cx,
                                 drag_data.path.clone(),
+                                drag_data.range,
                                 drag_data.track_id,
                                 drag_data.album_id,
                             );
@@ -1095,6 +1096,7 @@ impl Render for Queue {
                                         QueueItemData::new(
                                             cx,
                                             track.location.clone(),
+                                            track.range(),
                                             Some(track.id),
                                             Some(drag_data.album_id),
                                         )
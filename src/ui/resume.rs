@@ -0,0 +1,127 @@
+use std::sync::Arc;
+
+use gpui::{App, AppContext, Entity};
+use tracing::error;
+
+use crate::{
+    library::{db::LibraryAccess, types::TrackProgress},
+    playback::{
+        interface::PlaybackInterface,
+        resume::{ProgressUpdate, ResumeTracker},
+        thread::PlaybackState,
+    },
+    settings::SettingsGlobal,
+    ui::models::{Models, PlaybackInfo},
+};
+
+/// Write the position of a resumable track to the library.
+fn save_progress(update: Option<ProgressUpdate>, cx: &mut App) {
+    let Some(update) = update else {
+        return;
+    };
+
+    let progress = TrackProgress {
+        track_id: update.track_id,
+        position_ms: update.position_ms as i64,
+        finished: update.finished,
+    };
+
+    if let Err(err) =
+        cx.save_track_progress(progress.track_id, progress.position_ms, progress.finished)
+    {
+        error!(?err, "Failed to save track progress: {err}");
+        return;
+    }
+
+    let model = cx.global::<Models>().track_progress.clone();
+    model.update(cx, |track_progress, cx| {
+        Arc::make_mut(track_progress).insert(progress.track_id, progress);
+        cx.notify();
+    });
+}
+
+/// Start following the newly opened track if it's resumable, and continue it from where it was
+/// left off.
+fn track_changed(tracker: &Entity<ResumeTracker>, cx: &mut App) {
+    let previous = tracker.update(cx, |tracker, _| tracker.finish());
+    save_progress(previous, cx);
+
+    let Some(source) = cx
+        .global::<PlaybackInfo>()
+        .current_track
+        .read(cx)
+        .as_ref()
+        .map(|track| track.source().clone())
+    else {
+        return;
+    };
+
+    let info = match cx.resume_info_by_path(&source.path, source.range) {
+        Ok(Some(info)) => info,
+        Ok(None) => return,
+        Err(err) => {
+            error!(?err, "Failed to load resume point: {err}");
+            return;
+        }
+    };
+
+    let settings = &cx.global::<SettingsGlobal>().model.read(cx).playback.resume;
+    let genre = info.genres.as_ref().map(|genre| genre.0.as_str());
+    if !settings.applies_to(info.duration as u64, genre) {
+        return;
+    }
+
+    tracker.update(cx, |tracker, _| {
+        tracker.start(info.track_id, info.duration as u64 * 1000)
+    });
+
+    if info.finished != Some(true)
+        && let Some(position_ms) = info.position_ms.filter(|position| *position > 0)
+    {
+        cx.global::<PlaybackInterface>()
+            .resume_at(source, position_ms as u64);
+    }
+}
+
+/// Load the saved track positions, and keep them up to date as resumable tracks are played.
+pub fn setup_resume(cx: &mut App) {
+    match cx.all_track_progress() {
+        Ok(progress) => {
+            let progress = progress
+                .iter()
+                .map(|progress| (progress.track_id, *progress))
+                .collect();
+
+            let model = cx.global::<Models>().track_progress.clone();
+            model.write(cx, Arc::new(progress));
+        }
+        Err(err) => error!(?err, "Failed to load track progress: {err}"),
+    }
+
+    let tracker = cx.new(|_| ResumeTracker::default());
+    let playback_info = cx.global::<PlaybackInfo>().clone();
+
+    let tracker_clone = tracker.clone();
+    cx.observe(&playback_info.current_track, move |_, cx| {
+        track_changed(&tracker_clone, cx);
+    })
+    .detach();
+
+    let tracker_clone = tracker.clone();
+    cx.observe(&playback_info.position, move |position, cx| {
+        let position = *position.read(cx);
+        let update = tracker_clone.update(cx, |tracker, _| tracker.position_changed(position));
+        save_progress(update, cx);
+    })
+    .detach();
+
+    cx.observe(&playback_info.playback_state, move |state, cx| {
+        let update = match *state.read(cx) {
+            PlaybackState::Paused => tracker.update(cx, |tracker, _| tracker.checkpoint()),
+            PlaybackState::Stopped => tracker.update(cx, |tracker, _| tracker.finish()),
+            PlaybackState::Playing => None,
+        };
+        save_progress(update, cx);
+    })
+    .detach();
+}
@@ -31,6 +31,7 @@ use crate::{
         components::{
             button::{ButtonIntent, ButtonStyle, button},
             callout::callout,
+            checkbox::checkbox,
             dropdown::dropdown,
             icons::{ALERT_CIRCLE, CIRCLE_PLUS, FOLDER_SEARCH, TRASH, icon},
             label::label,
@@ -275,6 +276,35 @@ impl Render for LibrarySettings {
                         })
                 }),
             )
+            .child(
+                label(
+                    "probe-extensionless",
+                    tr!(
+                        "SCANNING_PROBE_EXTENSIONLESS",
+                        "Detect files without extensions"
+                    ),
+                )
+                .subtext(tr!(
+                    "SCANNING_PROBE_EXTENSIONLESS_SUBTEXT",
+                    "Read the start of files without an extension to find out whether they can \
+                    be played. This makes scans slower."
+                ))
+                .cursor_pointer()
+                .w_full()
+                .on_click(cx.listener(|this, _, _, cx| {
+                    this.settings.update(cx, |s, cx| {
+                        s.scanning.probe_extensionless = !s.scanning.probe_extensionless;
+                        save_settings(cx, s);
+                        cx.notify();
+                    });
+                    this.scanning_modified = true;
+                    cx.notify();
+                }))
+                .child(checkbox(
+                    "probe-extensionless-check",
+                    scanning.probe_extensionless,
+                )),
+            )
             .when(self.scanning_modified, |this| {
                 this.child(
                     callout(tr!(
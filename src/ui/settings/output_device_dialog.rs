@@ -0,0 +1,103 @@
+use cntp_i18n::tr;
+use gpui::{App, AppContext, Context, Entity, IntoElement, Render, Window, div};
+
+use crate::{
+    playback::interface::PlaybackInterface,
+    settings::{SettingsGlobal, output::OutputDeviceSettings, save_settings},
+    ui::{
+        components::{
+            action_dialog::{ActionDialog, ActionDialogAction},
+            button::ButtonIntent,
+            icons::{CROSS, VOLUME},
+        },
+        models::PlaybackInfo,
+    },
+};
+
+/// Shown when the saved output device couldn't be found at startup, and the default device is
+/// being used instead.
+pub struct OutputDeviceDialog;
+
+impl OutputDeviceDialog {
+    pub fn new(cx: &mut App) -> Entity<Self> {
+        cx.new(|_| Self)
+    }
+}
+
+fn dismiss(cx: &mut App) {
+    let unavailable = cx
+        .global::<PlaybackInfo>()
+        .unavailable_output_device
+        .clone();
+    unavailable.write(cx, None);
+}
+
+impl Render for OutputDeviceDialog {
+    fn render(&mut self, _window: &mut Window, cx: &mut Context<Self>) -> impl IntoElement {
+        let Some(name) = cx
+            .global::<PlaybackInfo>()
+            .unavailable_output_device
+            .read(cx)
+            .clone()
+        else {
+            return div().into_any_element();
+        };
+
+        ActionDialog::new(
+            tr!(
+                "OUTPUT_DEVICE_UNAVAILABLE_TITLE",
+                "Output device unavailable"
+            ),
+            tr!(
+                "OUTPUT_DEVICE_UNAVAILABLE_BODY",
+                "\"{{name}}\" isn't connected, so audio is playing through the default device \
+                instead.",
+                name = name
+            ),
+        )
+        .action(
+            ActionDialogAction::new(
+                "output-device-keep",
+                VOLUME,
+                tr!(
+                    "OUTPUT_DEVICE_UNAVAILABLE_KEEP",
+                    "Use the default device for now"
+                ),
+                ButtonIntent::Secondary,
+                |_, _, cx| dismiss(cx),
+            )
+            .subtitle(tr!(
+                "OUTPUT_DEVICE_UNAVAILABLE_KEEP_SUBTITLE",
+                "The saved device is tried again the next time the app starts."
+            )),
+        )
+        .action(
+            ActionDialogAction::new(
+                "output-device-forget",
+                CROSS,
+                tr!("OUTPUT_DEVICE_UNAVAILABLE_FORGET", "Forget this device"),
+                ButtonIntent::Secondary,
+                |_, _, cx| {
+                    let settings = cx.global::<SettingsGlobal>().model.clone();
+                    settings.update(cx, |settings, cx| {
+                        settings.playback.output = OutputDeviceSettings {
+                            provider: settings.playback.output.provider.clone(),
+                            ..Default::default()
+                        };
+                        cx.global::<PlaybackInterface>()
+                            .set_output_device(settings.playback.output.clone());
+                        save_settings(cx, settings);
+                        cx.notify();
+                    });
+                    dismiss(cx);
+                },
+            )
+            .subtitle(tr!(
+                "OUTPUT_DEVICE_UNAVAILABLE_FORGET_SUBTITLE",
+                "Always play through the system's default device."
+            )),
+        )
+        .on_dismiss(|_, cx| dismiss(cx))
+        .into_any_element()
+    }
+}
@@ -1,20 +1,37 @@
+use std::time::Duration;
+
 use cntp_i18n::tr;
 use gpui::{
-    App, AppContext, Context, Entity, IntoElement, ParentElement, Render, SharedString, Styled,
-    Window, div, px,
+    AnyElement, App, AppContext, Context, Entity, IntoElement, ParentElement, Render, SharedString,
+    Styled, Window, div, prelude::FluentBuilder, px,
 };
 
 use crate::{
+    devices::resample::Resampler,
+    playback::interface::PlaybackInterface,
     power::PowerManager,
-    settings::{Settings, SettingsGlobal, save_settings},
-    ui::components::{
-        checkbox::checkbox, label::label, labeled_slider::labeled_slider,
-        section_header::section_header,
+    settings::{
+        Settings, SettingsGlobal,
+        channels::ChannelMode,
+        dsp::EffectConfig,
+        equalizer::{BAND_GAIN_RANGE_DB, EqualizerSettings},
+        output::{BUFFER_SIZE_PROVIDERS, OutputDeviceSettings, PROVIDERS},
+        resampler::ResamplerProfile,
+        save_settings,
+    },
+    ui::{
+        components::{
+            checkbox::checkbox, dropdown::dropdown, label::label, labeled_slider::labeled_slider,
+            section_header::section_header,
+        },
+        models::PlaybackInfo,
     },
 };
 
 pub struct PlaybackSettings {
     settings: Entity<Settings>,
+    /// The latency of the selected resampler profile, from the last time it was measured.
+    resampler_latency: Option<(ResamplerProfile, Duration)>,
 }
 
 impl PlaybackSettings {
@@ -23,10 +40,35 @@ impl PlaybackSettings {
             let settings = cx.global::<SettingsGlobal>().model.clone();
             cx.observe(&settings, |_, _, cx| cx.notify()).detach();
 
-            Self { settings }
+            let playback_info = cx.global::<PlaybackInfo>();
+            let output_devices = playback_info.output_devices.clone();
+            let unavailable_output_device = playback_info.unavailable_output_device.clone();
+            cx.observe(&output_devices, |_, _, cx| cx.notify()).detach();
+            cx.observe(&unavailable_output_device, |_, _, cx| cx.notify())
+                .detach();
+
+            cx.global::<PlaybackInterface>().list_output_devices();
+
+            Self {
+                settings,
+                resampler_latency: None,
+            }
         })
     }
 
+    /// The latency the resampler profile adds when converting 44.1 kHz audio to 48 kHz. Measuring
+    /// it builds a whole resampler, so it's only measured again when the profile changes.
+    fn resampler_latency(&mut self, profile: ResamplerProfile) -> Duration {
+        match self.resampler_latency {
+            Some((measured, latency)) if measured == profile => latency,
+            _ => {
+                let latency = Resampler::latency(profile, 44100, 48000);
+                self.resampler_latency = Some((profile, latency));
+                latency
+            }
+        }
+    }
+
     fn update_playback(
         &self,
         cx: &mut App,
@@ -41,10 +83,388 @@ impl PlaybackSettings {
     }
 }
 
+/// The buffer sizes that can be chosen, in frames.
+const BUFFER_SIZES: &[u32] = &[1024, 2048, 4096, 8192, 16384];
+/// The period sizes that can be chosen, in frames.
+const PERIOD_SIZES: &[u32] = &[256, 512, 1024, 2048, 4096];
+
+/// Save the output device settings and switch to the device right away.
+fn update_output(
+    settings: &Entity<Settings>,
+    cx: &mut App,
+    update: impl FnOnce(&mut OutputDeviceSettings),
+) {
+    settings.update(cx, move |settings, cx| {
+        update(&mut settings.playback.output);
+
+        cx.global::<PlaybackInterface>()
+            .set_output_device(settings.playback.output.clone());
+        save_settings(cx, settings);
+        cx.notify();
+    });
+
+    let unavailable = cx
+        .global::<PlaybackInfo>()
+        .unavailable_output_device
+        .clone();
+    unavailable.write(cx, None);
+}
+
+/// Apply a change to the equalizer settings, sending it to the playback thread right away so that
+/// it can be heard while adjusting.
+fn update_equalizer(
+    settings: &Entity<Settings>,
+    cx: &mut App,
+    update: impl FnOnce(&mut EqualizerSettings),
+) {
+    settings.update(cx, move |settings, cx| {
+        update(&mut settings.playback.equalizer);
+
+        cx.global::<PlaybackInterface>()
+            .set_equalizer(settings.playback.equalizer.clone());
+        save_settings(cx, settings);
+        cx.notify();
+    });
+}
+
+/// Apply a change to the effect with the given index in the DSP chain.
+fn update_effect(settings: &Entity<Settings>, cx: &mut App, idx: usize, value: f64) {
+    settings.update(cx, move |settings, cx| {
+        if let Some(entry) = settings.playback.dsp.effects.get_mut(idx) {
+            match &mut entry.effect {
+                EffectConfig::Balance { balance } => *balance = value,
+                EffectConfig::Limiter { threshold_db, .. } => *threshold_db = value,
+                EffectConfig::MonoDownmix | EffectConfig::ChannelSwap => {}
+            }
+        }
+
+        save_settings(cx, settings);
+        cx.notify();
+    });
+}
+
+fn format_frequency(frequency_hz: f64) -> String {
+    if frequency_hz >= 1000.0 {
+        format!("{} kHz", frequency_hz / 1000.0)
+    } else {
+        format!("{} Hz", frequency_hz)
+    }
+}
+
 impl Render for PlaybackSettings {
     fn render(&mut self, _: &mut Window, cx: &mut Context<Self>) -> impl IntoElement {
         let playback = self.settings.read(cx).playback.clone();
 
+        let output_devices = cx.global::<PlaybackInfo>().output_devices.read(cx).clone();
+        let output_device_unavailable = playback.output.device_uid.as_ref().is_some_and(|uid| {
+            !output_devices.is_empty() && !output_devices.iter().any(|device| &device.uid == uid)
+        });
+
+        let provider_dropdown = (PROVIDERS.len() > 1).then(|| {
+            let settings = self.settings.clone();
+            let mut dd = dropdown::<Option<String>>("output-provider-dropdown")
+                .w(px(250.0))
+                .selected(playback.output.provider.clone())
+                .option(None, tr!("PLAYBACK_OUTPUT_PROVIDER_DEFAULT", "Default"))
+                .on_change(move |provider, _, cx| {
+                    let provider = provider.clone();
+                    update_output(&settings, cx, move |output| {
+                        // device UIDs are specific to each provider
+                        *output = OutputDeviceSettings {
+                            provider,
+                            pause_on_disconnect: output.pause_on_disconnect,
+                            ..Default::default()
+                        };
+                    });
+                    cx.global::<PlaybackInterface>().list_output_devices();
+                });
+            for (provider, name) in PROVIDERS {
+                dd = dd.option(Some(provider.to_string()), *name);
+            }
+            dd
+        });
+
+        let device_dropdown = {
+            let settings = self.settings.clone();
+            let devices = output_devices.clone();
+            let mut dd = dropdown::<Option<String>>("output-device-dropdown")
+                .w(px(250.0))
+                .selected(playback.output.device_uid.clone())
+                .option(
+                    None,
+                    tr!("PLAYBACK_OUTPUT_DEVICE_DEFAULT", "System default"),
+                )
+                .on_change(move |uid, _, cx| {
+                    let uid = uid.clone();
+                    let name = uid.as_ref().and_then(|uid| {
+                        devices
+                            .iter()
+                            .find(|device| &device.uid == uid)
+                            .map(|device| device.name.clone())
+                    });
+                    update_output(&settings, cx, move |output| {
+                        output.device_uid = uid;
+                        output.device_name = name;
+                    });
+                });
+            for device in output_devices.iter() {
+                dd = dd.option(Some(device.uid.clone()), device.name.clone());
+            }
+            // keep the saved device selectable while it's missing, or before the list arrives
+            if let Some(uid) = &playback.output.device_uid
+                && !output_devices.iter().any(|device| &device.uid == uid)
+            {
+                let name = playback.output.display_name().unwrap_or(uid);
+                let label: SharedString = if output_device_unavailable {
+                    tr!(
+                        "PLAYBACK_OUTPUT_DEVICE_MISSING",
+                        "{{name}} (not connected)",
+                        name = name
+                    )
+                    .into()
+                } else {
+                    name.to_string().into()
+                };
+                dd = dd.option(Some(uid.clone()), label);
+            }
+            dd
+        };
+
+        let buffer_size_dropdowns = playback
+            .output
+            .provider
+            .as_deref()
+            .is_some_and(|provider| BUFFER_SIZE_PROVIDERS.contains(&provider))
+            .then(|| {
+                let frames = |frames: u32| -> SharedString {
+                    tr!("PLAYBACK_FRAMES", "{{frames}} frames", frames = frames).into()
+                };
+
+                let settings = self.settings.clone();
+                let mut buffer = dropdown::<Option<u32>>("output-buffer-size-dropdown")
+                    .w(px(250.0))
+                    .selected(playback.output.buffer_frames)
+                    .option(None, tr!("PLAYBACK_OUTPUT_BUFFER_DEFAULT", "Default"))
+                    .on_change(move |buffer_frames, _, cx| {
+                        let buffer_frames = *buffer_frames;
+                        update_output(&settings, cx, move |output| {
+                            output.buffer_frames = buffer_frames;
+                        });
+                    });
+                for size in BUFFER_SIZES {
+                    buffer = buffer.option(Some(*size), frames(*size));
+                }
+
+                let settings = self.settings.clone();
+                let mut period = dropdown::<Option<u32>>("output-period-size-dropdown")
+                    .w(px(250.0))
+                    .selected(playback.output.period_frames)
+                    .option(
+                        None,
+                        tr!("PLAYBACK_OUTPUT_PERIOD_DEFAULT", "A quarter of the buffer"),
+                    )
+                    .on_change(move |period_frames, _, cx| {
+                        let period_frames = *period_frames;
+                        update_output(&settings, cx, move |output| {
+                            output.period_frames = period_frames;
+                        });
+                    });
+                for size in PERIOD_SIZES {
+                    period = period.option(Some(*size), frames(*size));
+                }
+
+                (buffer, period)
+            });
+
+        let channel_mode_dropdown = {
+            let settings = self.settings.clone();
+            dropdown::<ChannelMode>("channel-mode-dropdown")
+                .w(px(250.0))
+                .selected(playback.channels.mode)
+                .option(
+                    ChannelMode::Native,
+                    tr!(
+                        "PLAYBACK_CHANNEL_MODE_NATIVE",
+                        "Reopen device with native layout"
+                    ),
+                )
+                .option(
+                    ChannelMode::Downmix,
+                    tr!(
+                        "PLAYBACK_CHANNEL_MODE_DOWNMIX",
+                        "Always mix to device layout"
+                    ),
+                )
+                .on_change(move |mode, _, cx| {
+                    let mode = *mode;
+                    settings.update(cx, |settings, cx| {
+                        settings.playback.channels.mode = mode;
+                        save_settings(cx, settings);
+                        cx.notify();
+                    });
+                })
+        };
+
+        let resampler_dropdown = {
+            let settings = self.settings.clone();
+            dropdown::<ResamplerProfile>("resampler-dropdown")
+                .w(px(250.0))
+                .selected(playback.resampler)
+                .option(
+                    ResamplerProfile::Fast,
+                    tr!("PLAYBACK_RESAMPLER_FAST", "Fast"),
+                )
+                .option(
+                    ResamplerProfile::Balanced,
+                    tr!("PLAYBACK_RESAMPLER_BALANCED", "Balanced"),
+                )
+                .option(
+                    ResamplerProfile::VeryHigh,
+                    tr!("PLAYBACK_RESAMPLER_VERY_HIGH", "Very high"),
+                )
+                .option(
+                    ResamplerProfile::BitExact,
+                    tr!("PLAYBACK_RESAMPLER_BIT_EXACT", "Bit-exact (integer ratios)"),
+                )
+                .on_change(move |profile, _, cx| {
+                    let profile = *profile;
+                    settings.update(cx, |settings, cx| {
+                        settings.playback.resampler = profile;
+                        save_settings(cx, settings);
+                        cx.notify();
+                    });
+                })
+        };
+
+        let resampler_latency_ms = format!(
+            "{:.1}",
+            self.resampler_latency(playback.resampler).as_secs_f64() * 1000.0
+        );
+
+        let preset_dropdown = {
+            let settings = self.settings.clone();
+            let mut dd = dropdown::<Option<String>>("equalizer-preset-dropdown")
+                .w(px(250.0))
+                .selected(playback.equalizer.active_preset().map(str::to_string))
+                .option(None, tr!("PLAYBACK_EQUALIZER_CUSTOM", "Custom"))
+                .on_change(move |name, _, cx| {
+                    if let Some(name) = name {
+                        update_equalizer(&settings, cx, |equalizer| {
+                            equalizer.apply_preset(name);
+                        });
+                    }
+                });
+            for preset in &playback.equalizer.presets {
+                dd = dd.option(Some(preset.name.clone()), preset.name.clone());
+            }
+            dd
+        };
+
+        let (min_gain, max_gain) = BAND_GAIN_RANGE_DB;
+        let band_rows = playback
+            .equalizer
+            .bands
+            .iter()
+            .enumerate()
+            .map(|(idx, band)| {
+                let settings = self.settings.clone();
+                label(("equalizer-band", idx), format_frequency(band.frequency_hz))
+                    .w_full()
+                    .child(
+                        labeled_slider(("equalizer-band-slider", idx))
+                            .slider_id(("equalizer-band-track", idx))
+                            .w(px(250.0))
+                            .min(min_gain as f32)
+                            .max(max_gain as f32)
+                            .value(band.gain_db as f32)
+                            .default_value(0.0)
+                            .format_value(|v| -> SharedString { format!("{:+.1} dB", v).into() })
+                            .on_change(move |v, _, cx| {
+                                update_equalizer(&settings, cx, |equalizer| {
+                                    if let Some(band) = equalizer.bands.get_mut(idx) {
+                                        band.gain_db = v as f64;
+                                    }
+                                });
+                            }),
+                    )
+            })
+            .collect::<Vec<_>>();
+
+        let effect_rows = playback
+            .dsp
+            .effects
+            .iter()
+            .enumerate()
+            .flat_map(|(idx, entry)| {
+                let name = match entry.effect {
+                    EffectConfig::Balance { .. } => tr!("PLAYBACK_EFFECT_BALANCE", "Balance"),
+                    EffectConfig::MonoDownmix => tr!("PLAYBACK_EFFECT_MONO", "Mono downmix"),
+                    EffectConfig::ChannelSwap => {
+                        tr!(
+                            "PLAYBACK_EFFECT_CHANNEL_SWAP",
+                            "Swap left and right channels"
+                        )
+                    }
+                    EffectConfig::Limiter { .. } => tr!(
+                        "PLAYBACK_EFFECT_LIMITER",
+                        "Limiter (prevents clipping from ReplayGain pre-amp)"
+                    ),
+                };
+
+                let toggle = label(("playback-effect", idx), name)
+                    .cursor_pointer()
+                    .w_full()
+                    .on_click(cx.listener(move |this, _, _, cx| {
+                        this.update_playback(cx, |playback| {
+                            if let Some(entry) = playback.dsp.effects.get_mut(idx) {
+                                entry.enabled = !entry.enabled;
+                            }
+                        });
+                    }))
+                    .child(checkbox(("playback-effect-check", idx), entry.enabled))
+                    .into_any_element();
+
+                let slider = match entry.effect {
+                    EffectConfig::Balance { balance } => Some((
+                        tr!("PLAYBACK_EFFECT_BALANCE_POSITION", "Balance position"),
+                        labeled_slider(("effect-slider", idx))
+                            .min(-1.0)
+                            .max(1.0)
+                            .value(balance as f32)
+                            .default_value(0.0)
+                            .format_value(|v| -> SharedString { format!("{:+.2}", v).into() }),
+                    )),
+                    EffectConfig::Limiter { threshold_db, .. } => Some((
+                        tr!("PLAYBACK_EFFECT_LIMITER_THRESHOLD", "Limiter threshold"),
+                        labeled_slider(("effect-slider", idx))
+                            .min(-12.0)
+                            .max(0.0)
+                            .value(threshold_db as f32)
+                            .default_value(-0.3)
+                            .format_value(|v| -> SharedString { format!("{:.1} dBFS", v).into() }),
+                    )),
+                    EffectConfig::MonoDownmix | EffectConfig::ChannelSwap => None,
+                };
+
+                let slider = slider.map(|(name, slider)| {
+                    let settings = self.settings.clone();
+                    label(("playback-effect-value", idx), name)
+                        .w_full()
+                        .child(
+                            slider
+                                .slider_id(("effect-slider-track", idx))
+                                .w(px(250.0))
+                                .on_change(move |v, _, cx| {
+                                    update_effect(&settings, cx, idx, v as f64);
+                                }),
+                        )
+                        .into_any_element()
+                });
+
+                std::iter::once(toggle).chain(slider)
+            })
+            .collect::<Vec<AnyElement>>();
+
         div()
             .flex()
             .flex_col()
@@ -146,6 +566,32 @@ impl Render for PlaybackSettings {
                         }),
                 )
             })
+            .child(
+                label(
+                    "playback-rg-write-tags",
+                    tr!(
+                        "PLAYBACK_RG_WRITE_TAGS",
+                        "Write analyzed ReplayGain to files"
+                    ),
+                )
+                .subtext(tr!(
+                    "PLAYBACK_RG_WRITE_TAGS_SUBTEXT",
+                    "Loudness analysis saves its results as tags, so other players use the same \
+                    gains."
+                ))
+                .cursor_pointer()
+                .w_full()
+                .on_click(cx.listener(move |this, _, _, cx| {
+                    this.update_playback(cx, |playback| {
+                        playback.replaygain.write_analysis_tags =
+                            !playback.replaygain.write_analysis_tags;
+                    });
+                }))
+                .child(checkbox(
+                    "playback-rg-write-tags-check",
+                    playback.replaygain.write_analysis_tags,
+                )),
+            )
             .child(
                 label(
                     "playback-prevent-idle",
@@ -170,5 +616,429 @@ impl Render for PlaybackSettings {
                     playback.prevent_idle,
                 )),
             )
+            .child(
+                label(
+                    "playback-crossfade",
+                    tr!("PLAYBACK_CROSSFADE", "Crossfade between tracks"),
+                )
+                .subtext(tr!(
+                    "PLAYBACK_CROSSFADE_SUBTEXT",
+                    "Fades the next track in while the current one fades out."
+                ))
+                .cursor_pointer()
+                .w_full()
+                .on_click(cx.listener(move |this, _, _, cx| {
+                    this.update_playback(cx, |playback| {
+                        playback.crossfade.enabled = !playback.crossfade.enabled;
+                    });
+                }))
+                .child(checkbox(
+                    "playback-crossfade-check",
+                    playback.crossfade.enabled,
+                )),
+            )
+            .child({
+                let settings = self.settings.clone();
+                label(
+                    "playback-crossfade-length",
+                    tr!("PLAYBACK_CROSSFADE_LENGTH", "Crossfade length"),
+                )
+                .w_full()
+                .child(
+                    labeled_slider("crossfade-length")
+                        .slider_id("crossfade-length-track")
+                        .w(px(250.0))
+                        .min(1.0)
+                        .max(12.0)
+                        .value(playback.crossfade.duration_secs as f32)
+                        .default_value(5.0)
+                        .format_value(|v| -> SharedString { format!("{:.1} s", v).into() })
+                        .on_change(move |v, _, cx| {
+                            settings.update(cx, |settings, cx| {
+                                settings.playback.crossfade.duration_secs = v as f64;
+                                save_settings(cx, settings);
+                                cx.notify();
+                            });
+                        }),
+                )
+            })
+            .child(
+                label(
+                    "playback-crossfade-skip-same-album",
+                    tr!(
+                        "PLAYBACK_CROSSFADE_SKIP_SAME_ALBUM",
+                        "Don't crossfade between tracks from the same album"
+                    ),
+                )
+                .subtext(tr!(
+                    "PLAYBACK_CROSSFADE_SKIP_SAME_ALBUM_SUBTEXT",
+                    "Consecutive album tracks are played gaplessly instead."
+                ))
+                .cursor_pointer()
+                .w_full()
+                .on_click(cx.listener(move |this, _, _, cx| {
+                    this.update_playback(cx, |playback| {
+                        playback.crossfade.skip_same_album = !playback.crossfade.skip_same_album;
+                    });
+                }))
+                .child(checkbox(
+                    "playback-crossfade-skip-same-album-check",
+                    playback.crossfade.skip_same_album,
+                )),
+            )
+            .child(
+                label(
+                    "playback-resume",
+                    tr!("PLAYBACK_RESUME", "Remember position in long tracks"),
+                )
+                .subtext(tr!(
+                    "PLAYBACK_RESUME_SUBTEXT",
+                    "Audiobooks, podcasts and long tracks continue from where you left off."
+                ))
+                .cursor_pointer()
+                .w_full()
+                .on_click(cx.listener(move |this, _, _, cx| {
+                    this.update_playback(cx, |playback| {
+                        playback.resume.enabled = !playback.resume.enabled;
+                    });
+                }))
+                .child(checkbox("playback-resume-check", playback.resume.enabled)),
+            )
+            .child({
+                let settings = self.settings.clone();
+                label(
+                    "playback-resume-min-length",
+                    tr!(
+                        "PLAYBACK_RESUME_MIN_LENGTH",
+                        "Remember position in tracks longer than"
+                    ),
+                )
+                .w_full()
+                .child(
+                    labeled_slider("resume-min-length")
+                        .slider_id("resume-min-length-track")
+                        .w(px(250.0))
+                        .min(5.0)
+                        .max(120.0)
+                        .value(playback.resume.min_length_minutes as f32)
+                        .default_value(30.0)
+                        .format_value(|v| -> SharedString { format!("{:.0} min", v).into() })
+                        .on_change(move |v, _, cx| {
+                            settings.update(cx, |settings, cx| {
+                                settings.playback.resume.min_length_minutes = v.round() as u32;
+                                save_settings(cx, settings);
+                                cx.notify();
+                            });
+                        }),
+                )
+            })
+            .child(section_header(tr!("PLAYBACK_OUTPUT", "Output")))
+            .when_some(provider_dropdown, |this, provider_dropdown| {
+                this.child(
+                    label(
+                        "playback-output-provider",
+                        tr!("PLAYBACK_OUTPUT_PROVIDER", "Audio backend"),
+                    )
+                    .w_full()
+                    .child(provider_dropdown),
+                )
+            })
+            .child(
+                label(
+                    "playback-output-device",
+                    tr!("PLAYBACK_OUTPUT_DEVICE", "Output device"),
+                )
+                .when(output_device_unavailable, |this| {
+                    this.subtext(tr!(
+                        "PLAYBACK_OUTPUT_DEVICE_MISSING_SUBTEXT",
+                        "The selected device isn't connected. Playing through the default \
+                        device instead."
+                    ))
+                })
+                .w_full()
+                .child(device_dropdown),
+            )
+            .when_some(buffer_size_dropdowns, |this, (buffer, period)| {
+                this.child(
+                    label(
+                        "playback-output-buffer-size",
+                        tr!("PLAYBACK_OUTPUT_BUFFER_SIZE", "Buffer size"),
+                    )
+                    .subtext(tr!(
+                        "PLAYBACK_OUTPUT_BUFFER_SIZE_SUBTEXT",
+                        "Larger buffers help with dropouts, smaller ones respond faster."
+                    ))
+                    .w_full()
+                    .child(buffer),
+                )
+                .when(playback.output.buffer_frames.is_some(), |this| {
+                    this.child(
+                        label(
+                            "playback-output-period-size",
+                            tr!("PLAYBACK_OUTPUT_PERIOD_SIZE", "Period size"),
+                        )
+                        .subtext(tr!(
+                            "PLAYBACK_OUTPUT_PERIOD_SIZE_SUBTEXT",
+                            "How much audio the device plays between refills."
+                        ))
+                        .w_full()
+                        .child(period),
+                    )
+                })
+            })
+            .child(
+                label(
+                    "playback-pause-on-disconnect",
+                    tr!(
+                        "PLAYBACK_PAUSE_ON_DISCONNECT",
+                        "Pause when the output device is disconnected"
+                    ),
+                )
+                .subtext(tr!(
+                    "PLAYBACK_PAUSE_ON_DISCONNECT_SUBTEXT",
+                    "When disabled, playback continues on the default device."
+                ))
+                .cursor_pointer()
+                .w_full()
+                .on_click(cx.listener(move |this, _, _, cx| {
+                    this.update_playback(cx, |playback| {
+                        playback.output.pause_on_disconnect = !playback.output.pause_on_disconnect;
+                    });
+                }))
+                .child(checkbox(
+                    "playback-pause-on-disconnect-check",
+                    playback.output.pause_on_disconnect,
+                )),
+            )
+            .child(
+                label(
+                    "playback-bit-perfect",
+                    tr!("PLAYBACK_BIT_PERFECT", "Bit-perfect output"),
+                )
+                .subtext(tr!(
+                    "PLAYBACK_BIT_PERFECT_SUBTEXT",
+                    "Opens the device at each track's own sample rate and format when it's \
+                    supported, and bypasses volume and ReplayGain. The equalizer, effects, speed \
+                    changes and crossfades prevent bit-perfect playback while they are in use."
+                ))
+                .cursor_pointer()
+                .w_full()
+                .on_click(cx.listener(move |this, _, _, cx| {
+                    this.update_playback(cx, |playback| {
+                        playback.bit_perfect = !playback.bit_perfect;
+                    });
+                }))
+                .child(checkbox("playback-bit-perfect-check", playback.bit_perfect)),
+            )
+            .child(
+                label(
+                    "playback-channel-mode",
+                    tr!("PLAYBACK_CHANNEL_MODE", "Channel layout"),
+                )
+                .subtext(tr!(
+                    "PLAYBACK_CHANNEL_MODE_SUBTEXT",
+                    "How tracks with more or fewer channels than the device are played."
+                ))
+                .w_full()
+                .child(channel_mode_dropdown),
+            )
+            .child({
+                let settings = self.settings.clone();
+                label(
+                    "playback-center-mix",
+                    tr!("PLAYBACK_CENTER_MIX", "Center channel level when mixing"),
+                )
+                .w_full()
+                .child(
+                    labeled_slider("center-mix")
+                        .slider_id("center-mix-track")
+                        .w(px(250.0))
+                        .min(-12.0)
+                        .max(0.0)
+                        .value(playback.channels.center_mix_db as f32)
+                        .default_value(-3.0)
+                        .format_value(|v| -> SharedString { format!("{:+.1} dB", v).into() })
+                        .on_change(move |v, _, cx| {
+                            settings.update(cx, |settings, cx| {
+                                settings.playback.channels.center_mix_db = v as f64;
+                                save_settings(cx, settings);
+                                cx.notify();
+                            });
+                        }),
+                )
+            })
+            .child({
+                let settings = self.settings.clone();
+                label(
+                    "playback-surround-mix",
+                    tr!(
+                        "PLAYBACK_SURROUND_MIX",
+                        "Surround channel level when mixing"
+                    ),
+                )
+                .w_full()
+                .child(
+                    labeled_slider("surround-mix")
+                        .slider_id("surround-mix-track")
+                        .w(px(250.0))
+                        .min(-12.0)
+                        .max(0.0)
+                        .value(playback.channels.surround_mix_db as f32)
+                        .default_value(-3.0)
+                        .format_value(|v| -> SharedString { format!("{:+.1} dB", v).into() })
+                        .on_change(move |v, _, cx| {
+                            settings.update(cx, |settings, cx| {
+                                settings.playback.channels.surround_mix_db = v as f64;
+                                save_settings(cx, settings);
+                                cx.notify();
+                            });
+                        }),
+                )
+            })
+            .child(
+                label(
+                    "playback-lfe-mix",
+                    tr!(
+                        "PLAYBACK_LFE_MIX",
+                        "Mix the LFE channel into other channels"
+                    ),
+                )
+                .subtext(tr!(
+                    "PLAYBACK_LFE_MIX_SUBTEXT",
+                    "When disabled, the LFE channel is dropped if the device doesn't have one."
+                ))
+                .cursor_pointer()
+                .w_full()
+                .on_click(cx.listener(move |this, _, _, cx| {
+                    this.update_playback(cx, |playback| {
+                        playback.channels.lfe_mix_db = match playback.channels.lfe_mix_db {
+                            Some(_) => None,
+                            None => Some(0.0),
+                        };
+                    });
+                }))
+                .child(checkbox(
+                    "playback-lfe-mix-check",
+                    playback.channels.lfe_mix_db.is_some(),
+                )),
+            )
+            .child(
+                label(
+                    "playback-mix-normalize",
+                    tr!("PLAYBACK_MIX_NORMALIZE", "Prevent clipping when mixing"),
+                )
+                .subtext(tr!(
+                    "PLAYBACK_MIX_NORMALIZE_SUBTEXT",
+                    "Lowers the volume of mixed channels so that they can't clip."
+                ))
+                .cursor_pointer()
+                .w_full()
+                .on_click(cx.listener(move |this, _, _, cx| {
+                    this.update_playback(cx, |playback| {
+                        playback.channels.normalize = !playback.channels.normalize;
+                    });
+                }))
+                .child(checkbox(
+                    "playback-mix-normalize-check",
+                    playback.channels.normalize,
+                )),
+            )
+            .child(
+                label(
+                    "playback-resampler",
+                    tr!("PLAYBACK_RESAMPLER", "Resampling quality"),
+                )
+                .subtext(tr!(
+                    "PLAYBACK_RESAMPLER_SUBTEXT",
+                    "Higher quality uses more CPU time. Latency at 44.1 kHz to 48 kHz: about \
+                    {{latency}} ms.",
+                    latency = resampler_latency_ms.as_str()
+                ))
+                .w_full()
+                .child(resampler_dropdown),
+            )
+            .child(
+                label(
+                    "playback-noise-shaping",
+                    tr!("PLAYBACK_NOISE_SHAPING", "Noise shaped dither"),
+                )
+                .subtext(tr!(
+                    "PLAYBACK_NOISE_SHAPING_SUBTEXT",
+                    "Moves the dither added for 16 and 24 bit devices to frequencies where it is \
+                    harder to hear."
+                ))
+                .cursor_pointer()
+                .w_full()
+                .on_click(cx.listener(move |this, _, _, cx| {
+                    this.update_playback(cx, |playback| {
+                        playback.noise_shaping = !playback.noise_shaping;
+                    });
+                }))
+                .child(checkbox(
+                    "playback-noise-shaping-check",
+                    playback.noise_shaping,
+                )),
+            )
+            .child(section_header(tr!("PLAYBACK_EQUALIZER", "Equalizer")))
+            .child({
+                let settings = self.settings.clone();
+                label(
+                    "playback-equalizer-enabled",
+                    tr!("PLAYBACK_EQUALIZER_ENABLED", "Enable equalizer"),
+                )
+                .subtext(tr!(
+                    "PLAYBACK_EQUALIZER_ENABLED_SUBTEXT",
+                    "Audio is always converted to 64-bit float while the equalizer is enabled."
+                ))
+                .cursor_pointer()
+                .w_full()
+                .on_click(move |_, _, cx| {
+                    update_equalizer(&settings, cx, |equalizer| {
+                        equalizer.enabled = !equalizer.enabled;
+                    });
+                })
+                .child(checkbox(
+                    "playback-equalizer-enabled-check",
+                    playback.equalizer.enabled,
+                ))
+            })
+            .child(
+                label(
+                    "playback-equalizer-preset",
+                    tr!("PLAYBACK_EQUALIZER_PRESET", "Preset"),
+                )
+                .w_full()
+                .child(preset_dropdown),
+            )
+            .child({
+                let settings = self.settings.clone();
+                label(
+                    "playback-equalizer-preamp",
+                    tr!("PLAYBACK_EQUALIZER_PREAMP", "Equalizer pre-amp"),
+                )
+                .subtext(tr!(
+                    "PLAYBACK_EQUALIZER_PREAMP_SUBTEXT",
+                    "Lower this when boosting bands to avoid clipping."
+                ))
+                .w_full()
+                .child(
+                    labeled_slider("equalizer-preamp")
+                        .slider_id("equalizer-preamp-track")
+                        .w(px(250.0))
+                        .min(min_gain as f32)
+                        .max(max_gain as f32)
+                        .value(playback.equalizer.preamp_db as f32)
+                        .default_value(0.0)
+                        .format_value(|v| -> SharedString { format!("{:+.1} dB", v).into() })
+                        .on_change(move |v, _, cx| {
+                            update_equalizer(&settings, cx, |equalizer| {
+                                equalizer.preamp_db = v as f64;
+                            });
+                        }),
+                )
+            })
+            .children(band_rows)
+            .child(section_header(tr!("PLAYBACK_EFFECTS", "Effects")))
+            .children(effect_rows)
     }
 }